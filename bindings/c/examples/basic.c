@@ -0,0 +1,28 @@
+// cc examples/basic.c -Iinclude -Ltarget/release -lwattkit -o basic
+#include <stdio.h>
+#include <unistd.h>
+
+#include "wattkit.h"
+
+int main(void) {
+    WattkitSampler *sampler = wattkit_sampler_new();
+    if (wattkit_sampler_start(sampler, 100, 1) != WATTKIT_STATUS_OK) {
+        fprintf(stderr, "start failed: %s\n", wattkit_last_error());
+        return 1;
+    }
+
+    sleep(2); // Do intensive work here
+
+    WattkitProfile profile;
+    if (wattkit_sampler_stop(sampler) != WATTKIT_STATUS_OK ||
+        wattkit_sampler_profile(sampler, &profile) != WATTKIT_STATUS_OK) {
+        fprintf(stderr, "profiling failed: %s\n", wattkit_last_error());
+        return 1;
+    }
+    printf("Total Energy: %llu mJ\nAverage Power: %llu mW\n",
+           (unsigned long long)profile.total_energy,
+           (unsigned long long)profile.average_power);
+
+    wattkit_sampler_free(sampler);
+    return 0;
+}
@@ -0,0 +1,158 @@
+#ifndef WATTKIT_H
+#define WATTKIT_H
+
+/* Generated by cbindgen from src/lib.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum WattkitStatus {
+  WATTKIT_STATUS_OK = 0,
+  WATTKIT_STATUS_NULL_POINTER,
+  WATTKIT_STATUS_IO_REPORT,
+  WATTKIT_STATUS_HWMON,
+  WATTKIT_STATUS_POWER_GADGET,
+  WATTKIT_STATUS_SAMPLES_NOT_AVAILABLE,
+  WATTKIT_STATUS_SAMPLING_IN_PROGRESS,
+  WATTKIT_STATUS_NO_SAMPLING_IN_PROGRESS,
+  WATTKIT_STATUS_FORKED_PROCESS,
+  WATTKIT_STATUS_CAPTURE,
+  WATTKIT_STATUS_INTERVAL_TOO_SHORT,
+  WATTKIT_STATUS_SESSION_ALREADY_OPEN,
+  WATTKIT_STATUS_UNKNOWN_SESSION,
+  WATTKIT_STATUS_SMC,
+  WATTKIT_STATUS_POWERMETRICS,
+} WattkitStatus;
+
+/**
+ * Opaque sampler handle.
+ */
+typedef struct WattkitSampler WattkitSampler;
+
+/**
+ * Highest single-sample power in milliwatts, `timestamp` in ms since sampling started.
+ */
+typedef struct WattkitPeakPower {
+  uint64_t power;
+  uint64_t timestamp;
+} WattkitPeakPower;
+
+/**
+ * Energies in millijoules, powers in milliwatts, durations in milliseconds.
+ */
+typedef struct WattkitProfile {
+  uint64_t total_cpu_energy;
+  uint64_t total_gpu_energy;
+  uint64_t total_ane_energy;
+  uint64_t average_cpu_power;
+  uint64_t average_gpu_power;
+  uint64_t average_ane_power;
+  uint64_t total_energy;
+  uint64_t average_power;
+  uint64_t total_duration;
+  /**
+   * Whether the backend measures the unit, its energy is zero otherwise.
+   */
+  bool cpu_available;
+  bool gpu_available;
+  bool ane_available;
+  struct WattkitPeakPower peak_cpu_power;
+  struct WattkitPeakPower peak_gpu_power;
+  struct WattkitPeakPower peak_ane_power;
+} WattkitProfile;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a sampler, free it with `wattkit_sampler_free`.
+ */
+struct WattkitSampler *wattkit_sampler_new(void);
+
+/**
+ * Frees a sampler, stopping it if a session is still running.
+ *
+ * # Safety
+ * `sampler` must be null or a pointer returned by `wattkit_sampler_new`.
+ */
+void wattkit_sampler_free(struct WattkitSampler *sampler);
+
+/**
+ * Starts sampling, taking `num_samples` samples every `duration_ms` milliseconds.
+ *
+ * # Safety
+ * `sampler` must be null or a valid pointer returned by `wattkit_sampler_new`.
+ */
+enum WattkitStatus wattkit_sampler_start(struct WattkitSampler *sampler,
+                                         uint64_t duration_ms,
+                                         size_t num_samples);
+
+/**
+ * Stops sampling, blocking until the sampling thread has finished.
+ *
+ * # Safety
+ * `sampler` must be null or a valid pointer returned by `wattkit_sampler_new`.
+ */
+enum WattkitStatus wattkit_sampler_stop(struct WattkitSampler *sampler);
+
+/**
+ * Returns whether a session is in progress, false for a null `sampler`.
+ *
+ * # Safety
+ * `sampler` must be null or a valid pointer returned by `wattkit_sampler_new`.
+ */
+bool wattkit_sampler_is_sampling(const struct WattkitSampler *sampler);
+
+/**
+ * Writes the profile of the last finished session to `out`.
+ *
+ * # Safety
+ * `sampler` must be null or a valid pointer returned by `wattkit_sampler_new`,
+ * `out` must be null or valid for writes.
+ */
+enum WattkitStatus wattkit_sampler_profile(const struct WattkitSampler *sampler,
+                                           struct WattkitProfile *out);
+
+/**
+ * Writes the profile of every region named `name` of the last session to `out`.
+ *
+ * # Safety
+ * `sampler` as for `wattkit_sampler_profile`, `name` must be null or a valid
+ * NUL-terminated string.
+ */
+enum WattkitStatus wattkit_sampler_region_profile(const struct WattkitSampler *sampler,
+                                                  const char *name,
+                                                  struct WattkitProfile *out);
+
+/**
+ * Opens a named region of the running session, close it with `wattkit_sampler_end_region`.
+ * Returns -1 on failure.
+ *
+ * # Safety
+ * As for `wattkit_sampler_region_profile`.
+ */
+int64_t wattkit_sampler_begin_region(struct WattkitSampler *sampler, const char *name);
+
+/**
+ * Closes a region returned by `wattkit_sampler_begin_region`.
+ *
+ * # Safety
+ * `sampler` must be null or a valid pointer returned by `wattkit_sampler_new`.
+ */
+void wattkit_sampler_end_region(struct WattkitSampler *sampler, int64_t region);
+
+/**
+ * Description of the last error on this thread, or null. Valid until the next call
+ * into wattkit on the same thread.
+ */
+const char *wattkit_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* WATTKIT_H */
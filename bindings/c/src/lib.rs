@@ -0,0 +1,279 @@
+//! C API for wattkit, see `include/wattkit.h`.
+//!
+//! Every fallible function returns a `WattkitStatus`, `wattkit_last_error` returns a
+//! description of the most recent failure on the calling thread.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::ptr;
+use std::time::Duration;
+
+use wattkit::{PeakPower, PowerProfile, SamplerError, Sampling, StartStopSampler};
+
+/// Opaque sampler handle.
+pub struct WattkitSampler(StartStopSampler);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WattkitStatus {
+    Ok = 0,
+    NullPointer,
+    IOReport,
+    Hwmon,
+    PowerGadget,
+    SamplesNotAvailable,
+    SamplingInProgress,
+    NoSamplingInProgress,
+    ForkedProcess,
+    Capture,
+    IntervalTooShort,
+    SessionAlreadyOpen,
+    UnknownSession,
+    Smc,
+    Powermetrics,
+}
+
+/// Energies in millijoules, powers in milliwatts, durations in milliseconds.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WattkitProfile {
+    pub total_cpu_energy: u64,
+    pub total_gpu_energy: u64,
+    pub total_ane_energy: u64,
+    pub average_cpu_power: u64,
+    pub average_gpu_power: u64,
+    pub average_ane_power: u64,
+    pub total_energy: u64,
+    pub average_power: u64,
+    pub total_duration: u64,
+    /// Whether the backend measures the unit, its energy is zero otherwise.
+    pub cpu_available: bool,
+    pub gpu_available: bool,
+    pub ane_available: bool,
+    pub peak_cpu_power: WattkitPeakPower,
+    pub peak_gpu_power: WattkitPeakPower,
+    pub peak_ane_power: WattkitPeakPower,
+}
+
+/// Highest single-sample power in milliwatts, `timestamp` in ms since sampling started.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WattkitPeakPower {
+    pub power: u64,
+    pub timestamp: u64,
+}
+
+impl From<PeakPower> for WattkitPeakPower {
+    fn from(p: PeakPower) -> Self {
+        WattkitPeakPower {
+            power: p.power,
+            timestamp: p.timestamp,
+        }
+    }
+}
+
+impl From<PowerProfile> for WattkitProfile {
+    fn from(p: PowerProfile) -> Self {
+        WattkitProfile {
+            total_cpu_energy: p.total_cpu_energy as u64,
+            total_gpu_energy: p.total_gpu_energy as u64,
+            total_ane_energy: p.total_ane_energy as u64,
+            average_cpu_power: p.average_cpu_power,
+            average_gpu_power: p.average_gpu_power,
+            average_ane_power: p.average_ane_power,
+            total_energy: p.total_energy as u64,
+            average_power: p.average_power,
+            total_duration: p.total_duration,
+            cpu_available: p.cpu_available,
+            gpu_available: p.gpu_available,
+            ane_available: p.ane_available,
+            peak_cpu_power: p.peak_cpu_power.into(),
+            peak_gpu_power: p.peak_gpu_power.into(),
+            peak_ane_power: p.peak_ane_power.into(),
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: &str) {
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+fn status(result: Result<(), SamplerError>) -> WattkitStatus {
+    let Err(e) = result else {
+        return WattkitStatus::Ok;
+    };
+    set_last_error(&e.to_string());
+    match e {
+        SamplerError::IOReportError(_) => WattkitStatus::IOReport,
+        SamplerError::HwmonError(_) => WattkitStatus::Hwmon,
+        SamplerError::PowerGadgetError(_) => WattkitStatus::PowerGadget,
+        SamplerError::SmcError(_) => WattkitStatus::Smc,
+        SamplerError::PowermetricsError(_) => WattkitStatus::Powermetrics,
+        SamplerError::CaptureError(_) => WattkitStatus::Capture,
+        SamplerError::SamplesNotAvailable => WattkitStatus::SamplesNotAvailable,
+        SamplerError::SamplingInProgress => WattkitStatus::SamplingInProgress,
+        SamplerError::NoSamplingInProgress => WattkitStatus::NoSamplingInProgress,
+        SamplerError::ForkedProcess(_) => WattkitStatus::ForkedProcess,
+        SamplerError::IntervalTooShort(_) => WattkitStatus::IntervalTooShort,
+        SamplerError::SessionAlreadyOpen(_) => WattkitStatus::SessionAlreadyOpen,
+        SamplerError::UnknownSession(_) => WattkitStatus::UnknownSession,
+    }
+}
+
+fn null_pointer() -> WattkitStatus {
+    set_last_error("Null pointer passed to wattkit");
+    WattkitStatus::NullPointer
+}
+
+/// Creates a sampler, free it with `wattkit_sampler_free`.
+#[no_mangle]
+pub extern "C" fn wattkit_sampler_new() -> *mut WattkitSampler {
+    Box::into_raw(Box::new(WattkitSampler(StartStopSampler::new())))
+}
+
+/// Frees a sampler, stopping it if a session is still running.
+///
+/// # Safety
+/// `sampler` must be null or a pointer returned by `wattkit_sampler_new`.
+#[no_mangle]
+pub unsafe extern "C" fn wattkit_sampler_free(sampler: *mut WattkitSampler) {
+    if !sampler.is_null() {
+        drop(unsafe { Box::from_raw(sampler) });
+    }
+}
+
+/// Starts sampling, taking `num_samples` samples every `duration_ms` milliseconds.
+///
+/// # Safety
+/// `sampler` must be null or a valid pointer returned by `wattkit_sampler_new`.
+#[no_mangle]
+pub unsafe extern "C" fn wattkit_sampler_start(
+    sampler: *mut WattkitSampler,
+    duration_ms: u64,
+    num_samples: usize,
+) -> WattkitStatus {
+    match unsafe { sampler.as_mut() } {
+        Some(sampler) => status(
+            sampler
+                .0
+                .start(Duration::from_millis(duration_ms), num_samples),
+        ),
+        None => null_pointer(),
+    }
+}
+
+/// Stops sampling, blocking until the sampling thread has finished.
+///
+/// # Safety
+/// `sampler` must be null or a valid pointer returned by `wattkit_sampler_new`.
+#[no_mangle]
+pub unsafe extern "C" fn wattkit_sampler_stop(sampler: *mut WattkitSampler) -> WattkitStatus {
+    match unsafe { sampler.as_mut() } {
+        Some(sampler) => status(sampler.0.stop()),
+        None => null_pointer(),
+    }
+}
+
+/// Returns whether a session is in progress, false for a null `sampler`.
+///
+/// # Safety
+/// `sampler` must be null or a valid pointer returned by `wattkit_sampler_new`.
+#[no_mangle]
+pub unsafe extern "C" fn wattkit_sampler_is_sampling(sampler: *const WattkitSampler) -> bool {
+    unsafe { sampler.as_ref() }.is_some_and(|sampler| sampler.0.is_sampling())
+}
+
+/// Writes the profile of the last finished session to `out`.
+///
+/// # Safety
+/// `sampler` must be null or a valid pointer returned by `wattkit_sampler_new`,
+/// `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn wattkit_sampler_profile(
+    sampler: *const WattkitSampler,
+    out: *mut WattkitProfile,
+) -> WattkitStatus {
+    let (Some(sampler), false) = (unsafe { sampler.as_ref() }, out.is_null()) else {
+        return null_pointer();
+    };
+    match sampler.0.profile() {
+        Ok(profile) => {
+            unsafe { out.write(profile.into()) };
+            WattkitStatus::Ok
+        }
+        Err(e) => status(Err(e)),
+    }
+}
+
+/// Writes the profile of every region named `name` of the last session to `out`.
+///
+/// # Safety
+/// `sampler` as for `wattkit_sampler_profile`, `name` must be null or a valid
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn wattkit_sampler_region_profile(
+    sampler: *const WattkitSampler,
+    name: *const c_char,
+    out: *mut WattkitProfile,
+) -> WattkitStatus {
+    let (Some(sampler), false, false) =
+        (unsafe { sampler.as_ref() }, name.is_null(), out.is_null())
+    else {
+        return null_pointer();
+    };
+    let name = unsafe { std::ffi::CStr::from_ptr(name) }.to_string_lossy();
+    match sampler.0.region_profile(&name) {
+        Ok(profile) => {
+            unsafe { out.write(profile.into()) };
+            WattkitStatus::Ok
+        }
+        Err(e) => status(Err(e)),
+    }
+}
+
+/// Opens a named region of the running session, close it with `wattkit_sampler_end_region`.
+/// Returns -1 on failure.
+///
+/// # Safety
+/// As for `wattkit_sampler_region_profile`.
+#[no_mangle]
+pub unsafe extern "C" fn wattkit_sampler_begin_region(
+    sampler: *mut WattkitSampler,
+    name: *const c_char,
+) -> i64 {
+    let (Some(sampler), false) = (unsafe { sampler.as_mut() }, name.is_null()) else {
+        null_pointer();
+        return -1;
+    };
+    let name = unsafe { std::ffi::CStr::from_ptr(name) }.to_string_lossy();
+    match sampler.0.begin_region(&name) {
+        Ok(id) => id.index() as i64,
+        Err(e) => {
+            status(Err(e));
+            -1
+        }
+    }
+}
+
+/// Closes a region returned by `wattkit_sampler_begin_region`.
+///
+/// # Safety
+/// `sampler` must be null or a valid pointer returned by `wattkit_sampler_new`.
+#[no_mangle]
+pub unsafe extern "C" fn wattkit_sampler_end_region(sampler: *mut WattkitSampler, region: i64) {
+    if let (Some(sampler), Ok(index)) = (unsafe { sampler.as_mut() }, usize::try_from(region)) {
+        sampler.0.end_region(wattkit::RegionId::from_index(index));
+    }
+}
+
+/// Description of the last error on this thread, or null. Valid until the next call
+/// into wattkit on the same thread.
+#[no_mangle]
+pub extern "C" fn wattkit_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
@@ -65,7 +65,7 @@ model_inputs = random_inputs_for_model(cml_model)
 
 model_iterations = 1000
 cml_model.predict(model_inputs) # Once before to "warm up" hardware
-with Profiler(sample_duration=100, num_samples=2) as profiler:
+with Profiler(duration=0.1, num_samples=2) as profiler:
     for i in range(model_iterations):
         cml_model.predict(model_inputs)
 
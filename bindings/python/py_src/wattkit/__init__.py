@@ -1 +1,11 @@
 from ._wattkit_pyo3 import *
+from .command import CommandResult, profile_command
+from .inference import InferenceEnergy, measure_inference
+from .live import live_monitor
+
+
+def load_ipython_extension(ipython):
+    """Enables the `%%wattkit` cell magic via `%load_ext wattkit`."""
+    from .magic import load_ipython_extension
+
+    load_ipython_extension(ipython)
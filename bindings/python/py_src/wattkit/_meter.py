@@ -0,0 +1,57 @@
+import threading
+from datetime import timedelta
+
+from ._wattkit_pyo3 import Profiler
+
+
+class EnergyMeter:
+    """
+    A long-running sampling session that accumulates energy as samples arrive,
+    so integrations can read energy between arbitrary points (steps, epochs)
+    without restarting the sampler.
+    """
+
+    def __init__(self, sample_duration=100, num_samples=1, profiler=None):
+        self.profiler = profiler or Profiler(
+            duration=timedelta(milliseconds=sample_duration), num_samples=num_samples
+        )
+        self.profiler.on_sample(self._on_sample)
+        self._lock = threading.Lock()
+        self._energy = 0  # mJ
+        self._duration = 0  # ms
+        self._lap_energy = 0
+        self._lap_duration = 0
+
+    def _on_sample(self, sample):
+        with self._lock:
+            self._energy += sample.cpu_energy + sample.gpu_energy + sample.ane_energy
+            self._duration += sample.duration
+
+    def start(self):
+        self.profiler.start()
+
+    def stop(self):
+        """Stops sampling and returns the profile of the whole session."""
+        self.profiler.stop()
+        return self.profiler.get_profile()
+
+    @property
+    def energy(self):
+        """Energy in millijoules since `start`."""
+        with self._lock:
+            return self._energy
+
+    def lap(self):
+        """Returns `(energy_mj, duration_ms)` accumulated since the previous lap."""
+        with self._lock:
+            energy = self._energy - self._lap_energy
+            duration = self._duration - self._lap_duration
+            self._lap_energy = self._energy
+            self._lap_duration = self._duration
+        return energy, duration
+
+
+def energy_metrics(energy, duration, prefix="energy/"):
+    """Converts a lap into joules / watts metrics."""
+    power = energy / duration if duration else 0.0  # mJ/ms == W
+    return {f"{prefix}joules": energy / 1000.0, f"{prefix}power_watts": power}
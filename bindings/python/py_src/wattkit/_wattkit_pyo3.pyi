@@ -0,0 +1,459 @@
+from datetime import timedelta
+from types import TracebackType
+from typing import Any, Callable, Dict, Iterable, Iterator, List, Literal, Optional, Tuple, Type, Union
+
+class WattKitError(Exception):
+    """Raised when sampling fails or is used out of order."""
+
+class PyEnergySample:
+    @property
+    def timestamp(self) -> int:
+        """Milliseconds since sampling started."""
+    @property
+    def wall_time(self) -> Optional[float]:
+        """Wall clock time at the start of the sample in seconds since the Unix epoch, to
+        line it up with logs. `timestamp` stays monotonic."""
+    @property
+    def duration(self) -> int:
+        """Milliseconds."""
+    @property
+    def cpu_energy(self) -> int:
+        """Millijoules."""
+    @property
+    def gpu_energy(self) -> int:
+        """Millijoules."""
+    @property
+    def ane_energy(self) -> int:
+        """Millijoules."""
+    @property
+    def cpu_power(self) -> float:
+        """Milliwatts."""
+    @property
+    def gpu_power(self) -> float:
+        """Milliwatts."""
+    @property
+    def ane_power(self) -> float:
+        """Milliwatts."""
+    @property
+    def on_battery(self) -> Optional[bool]:
+        """Whether the machine ran on battery, `None` where the power source can't be read."""
+    @property
+    def battery_percent(self) -> Optional[int]: ...
+    @property
+    def cpu_utilization(self) -> Optional[float]:
+        """Fraction of the host's CPU time, over all cores, spent busy during the sample."""
+    @property
+    def display_energy(self) -> Optional[int]:
+        """Millijoules drawn by the display, `None` on machines that don't report it."""
+    @property
+    def package_energy(self) -> Optional[int]:
+        """Millijoules drawn by the whole SoC, DRAM and display included. `None` where the
+        backend doesn't report it."""
+    @property
+    def package_power(self) -> Optional[float]:
+        """Milliwatts."""
+    @property
+    def system_power(self) -> Optional[float]:
+        """Milliwatts drawn by the whole machine, from the SMC. `None` where it can't be read."""
+    @property
+    def dc_in_power(self) -> Optional[float]:
+        """Milliwatts drawn through the DC input, zero on battery. `None` where it can't be
+        read."""
+    @property
+    def dies(self) -> List[Tuple[int, int, int]]:
+        """`(cpu, gpu, ane)` millijoules of each die of an M1 or M2 Ultra, empty on
+        single-die chips."""
+    @property
+    def thermal_pressure(
+        self,
+    ) -> Optional[Literal["nominal", "moderate", "heavy", "trapping", "sleeping"]]:
+        """Thermal pressure at the end of the sample, only read on macOS."""
+    @property
+    def cpu_residency(self) -> Optional[Tuple[float, float]]:
+        """`(active, performance_state)` of the busiest CPU cluster: the fraction of the
+        sample it was active, and where between its lowest (0) and highest (1) performance
+        state it ran meanwhile."""
+    @property
+    def throttled(self) -> bool:
+        """Whether the SoC was throttling during the sample."""
+    @property
+    def memory_traffic(self) -> Optional[Tuple[int, int]]:
+        """`(read, written)` bytes of memory traffic, see `Profiler(memory_bandwidth=True)`."""
+    @property
+    def memory_bandwidth(self) -> Optional[float]:
+        """Memory read plus write bandwidth in bytes per second."""
+    @property
+    def disk_io(self) -> Optional[Tuple[int, int]]:
+        """`(read, written)` bytes of all disks of the host during the sample."""
+    @property
+    def network_io(self) -> Optional[Tuple[int, int]]:
+        """`(received, sent)` bytes over all network interfaces but loopback during the sample."""
+    def to_dict(self) -> Dict[str, Any]: ...
+
+class SampleStream(Iterator[PyEnergySample]):
+    def __iter__(self) -> SampleStream: ...
+    def __next__(self) -> PyEnergySample: ...
+
+class PyPowerProfile:
+    @property
+    def total_cpu_energy(self) -> int: ...
+    @property
+    def total_gpu_energy(self) -> int: ...
+    @property
+    def total_ane_energy(self) -> int: ...
+    @property
+    def average_cpu_power(self) -> int: ...
+    @property
+    def average_gpu_power(self) -> int: ...
+    @property
+    def average_ane_power(self) -> int: ...
+    @property
+    def total_energy(self) -> int: ...
+    @property
+    def average_power(self) -> int: ...
+    @property
+    def total_duration(self) -> int: ...
+    @property
+    def cpu_available(self) -> bool:
+        """Whether the backend measures CPU energy, it is always zero otherwise."""
+    @property
+    def gpu_available(self) -> bool: ...
+    @property
+    def ane_available(self) -> bool: ...
+    @property
+    def peak_cpu_power(self) -> Tuple[int, int]:
+        """`(power_mw, timestamp_ms)` of the sample with the highest CPU power."""
+    @property
+    def peak_gpu_power(self) -> Tuple[int, int]:
+        """`(power_mw, timestamp_ms)` of the sample with the highest GPU power."""
+    @property
+    def peak_ane_power(self) -> Tuple[int, int]:
+        """`(power_mw, timestamp_ms)` of the sample with the highest ANE power."""
+    def energy(self, unit: str = "mJ") -> float:
+        """`total_energy` in `unit`, "mJ", "J" or "Wh"."""
+    def power(self, unit: str = "mW") -> float:
+        """`average_power` in `unit`, "mW" or "W"."""
+    def format(self, energy_unit: str = "mJ", power_unit: str = "mW") -> str:
+        """The profile summary with energy and power in the given units."""
+    def __add__(self, other: PyPowerProfile) -> PyPowerProfile:
+        """Profile of this session followed by `other`'s, energies and durations add up. The regions are this profile's."""
+    def __sub__(self, other: PyPowerProfile) -> PyPowerProfile:
+        """Energy and power above the `other` baseline, durations and samples stay these."""
+    def __eq__(self, other: object) -> bool: ...
+    def approx_eq(self, other: PyPowerProfile, relative: float = 0.05) -> bool:
+        """Whether energy, average power and duration are each within `relative` of `other`'s."""
+    @property
+    def peak_power(self) -> int:
+        """Highest total power of any one sample in milliwatts."""
+    def channel_tree(self) -> Dict[str, Dict[str, Dict[str, Dict[str, Any]]]]:
+        """Every sampled channel as group -> subgroup -> channel -> `{"unit": str, "points": [(ms, value), ...]}`, the same layout for every backend."""
+    def to_markdown(
+        self,
+        baseline: Optional[PyPowerProfile] = None,
+        energy_unit: str = "J",
+        power_unit: str = "W",
+    ) -> str:
+        """Markdown table of energy, average and peak power and the change from `baseline`, e.g. for a pull request comment. Energy and power are in `energy_unit` and `power_unit`."""
+    def to_prometheus(self, names: str = "wattkit") -> str:
+        """The profile in the Prometheus text format, with "wattkit" or scaphandre's "scaphandre" metric names and labels."""
+    def duty_cycle(self, threshold_w: float) -> Tuple[float, float, float]:
+        """`(cpu, gpu, ane)` fractions of the time each unit drew more than `threshold_w` watts."""
+    @property
+    def samples(self) -> List[PyEnergySample]: ...
+    @property
+    def metadata(self) -> Dict[str, str]:
+        """Metadata of the session, see `Profiler.set_metadata`."""
+    @property
+    def power_source_changed(self) -> bool:
+        """Whether the machine switched between AC and battery during the session, such a
+        profile doesn't compare with others."""
+    @property
+    def cpu_utilization(self) -> Optional[float]:
+        """Average fraction of the host's CPU time spent busy, `None` where it can't be read."""
+    @property
+    def display_energy(self) -> Optional[int]:
+        """Millijoules drawn by the display, not part of `total_energy`. `None` on machines
+        that don't report it."""
+    @property
+    def package_energy(self) -> Optional[int]:
+        """Millijoules drawn by the whole SoC, DRAM and display included, unlike
+        `total_energy`. `None` where the backend doesn't report it."""
+    @property
+    def average_package_power(self) -> Optional[int]:
+        """Milliwatts."""
+    @property
+    def average_system_power(self) -> Optional[int]:
+        """Milliwatts drawn by the whole machine, from the SMC. `None` where it can't be read."""
+    @property
+    def average_dc_in_power(self) -> Optional[int]:
+        """Milliwatts drawn through the DC input, zero on battery."""
+    @property
+    def dies(self) -> List[Tuple[int, int, int]]:
+        """`(cpu, gpu, ane)` millijoules of each die of an M1 or M2 Ultra, summing to the
+        unit totals. Empty on single-die chips."""
+    @property
+    def idle_periods(self) -> List[Tuple[int, int]]:
+        """`(start_ms, duration_ms)` of every stretch spent near the baseline power, see
+        `Profiler.detect_idle`."""
+    @property
+    def idle_duration(self) -> int:
+        """Milliseconds."""
+    @property
+    def average_active_power(self) -> Optional[int]:
+        """Milliwatts drawn outside the idle periods, `None` without idle detection."""
+    @property
+    def throttled_periods(self) -> List[Tuple[int, int, str]]:
+        """`(start_ms, duration_ms, pressure)` of every run of throttled samples, throttled
+        runs don't compare with cool ones."""
+    @property
+    def throttled_duration(self) -> int:
+        """Milliseconds."""
+    @property
+    def regions(self) -> Dict[str, PyPowerProfile]:
+        """Profiles of each named region, regions sharing a name are combined and time they overlap counts once."""
+    @property
+    def region_spans(self) -> List[Tuple[str, int, int]]:
+        """`(name, start_ms, end_ms)` for every region."""
+    def plot(
+        self,
+        ax: Optional[Any] = None,
+        regions: Optional[Iterable[Tuple[str, float, float]]] = None,
+    ) -> Any: ...
+    def to_dict(self) -> Dict[str, Any]: ...
+    def to_dataframe(self) -> Any: ...
+    @staticmethod
+    def merge(profiles: List[PyPowerProfile]) -> PyAggregateProfile:
+        """Mean and standard deviation of each quantity across the profiles of repeated runs."""
+
+class PySummary:
+    @property
+    def mean(self) -> float: ...
+    @property
+    def stddev(self) -> float:
+        """Sample standard deviation, zero for fewer than two runs."""
+    @property
+    def min(self) -> float: ...
+    @property
+    def max(self) -> float: ...
+    def relative_stddev(self) -> float:
+        """Standard deviation relative to the mean, 0.05 is 5%."""
+    def to_dict(self) -> Dict[str, float]: ...
+
+class PyAggregateProfile:
+    """Profiles of several runs summarized, energies in mJ, powers in mW, durations in ms."""
+
+    @property
+    def runs(self) -> int: ...
+    @property
+    def total_energy(self) -> PySummary: ...
+    @property
+    def total_cpu_energy(self) -> PySummary: ...
+    @property
+    def total_gpu_energy(self) -> PySummary: ...
+    @property
+    def total_ane_energy(self) -> PySummary: ...
+    @property
+    def average_power(self) -> PySummary: ...
+    @property
+    def total_duration(self) -> PySummary: ...
+    @property
+    def metadata(self) -> Dict[str, str]:
+        """Metadata entries every run agrees on."""
+    def to_dict(self) -> Dict[str, Any]: ...
+
+class PyComparison:
+    """Whether per-run energy differs between two sets of runs."""
+
+    @property
+    def mean_a(self) -> float:
+        """Mean total energy per run of `a` in mJ."""
+    @property
+    def mean_b(self) -> float: ...
+    @property
+    def relative_difference(self) -> float:
+        """`(mean_b - mean_a) / mean_a`, -0.1 is 10% less energy."""
+    @property
+    def t_test_p(self) -> float:
+        """Two-sided p-value of Welch's t-test, NaN with fewer than two runs on a side."""
+    @property
+    def mann_whitney_p(self) -> float:
+        """Two-sided p-value of the Mann-Whitney U test."""
+    @property
+    def alpha(self) -> float: ...
+    @property
+    def significant(self) -> bool:
+        """Whether both tests reject equal energy at `alpha`."""
+
+def compare_runs(
+    a: List[PyPowerProfile], b: List[PyPowerProfile], alpha: float = 0.05
+) -> PyComparison:
+    """Tests whether the total energy per run of `b` differs from that of `a` at `alpha`."""
+
+def assert_energy_below(
+    profiles: Union[PyPowerProfile, List[PyPowerProfile]],
+    joules: float,
+    tolerance: float = 0.0,
+    percentile: float = 100.0,
+) -> None:
+    """Raises `AssertionError` with the profile when `profiles`, one or a list of repeated runs, took more than `joules`. `tolerance` is the fraction above it tolerated and `percentile` picks the run checked by energy, the most expensive by default."""
+
+def junit_xml(
+    cases: List[Tuple[str, PyPowerProfile, Dict[str, float]]],
+    suite: str = "energy",
+    energy_unit: str = "J",
+    power_unit: str = "W",
+) -> str:
+    """JUnit XML test suite with a test case per `(name, profile, budget)`, failing those exceeding their budget, a dict of `energy` in joules and `average_power` and `peak_power` in watts. Failures and profiles show energy and power in `energy_unit` and `power_unit`."""
+
+def measure_overhead(
+    intervals: Optional[List[Union[timedelta, float]]] = None,
+    session: Union[timedelta, float, None] = None,
+) -> List[Dict[str, Any]]:
+    """Samples for `session` at each of `intervals` while idle, reporting what sampling costs."""
+
+def capabilities() -> Dict[str, Any]:
+    """Probes which units and IOReport groups produce data for this process.
+
+    `reported` and `measured` map "cpu", "gpu" and "ane" to whether the backend has
+    channels for the unit and whether they read nonzero, `restricted` is set when the
+    CPU's read zero. `groups` has a dict per IOReport group, empty off macOS.
+    `unmapped` names the Energy Model channels wattkit has no typed channel for.
+    """
+
+def soc_info() -> Dict[str, Any]:
+    """The chip and machine: `name`, `model`, `cores`, `performance_cores`,
+    `efficiency_cores`, `memory` in bytes, each None where unknown, and `apple_silicon`."""
+
+class ProfilerRegion:
+    def __enter__(self) -> ProfilerRegion: ...
+    def __exit__(
+        self,
+        exc_type: Optional[Type[BaseException]] = None,
+        exc_value: Optional[BaseException] = None,
+        traceback: Optional[TracebackType] = None,
+    ) -> bool: ...
+
+class Profiler:
+    def __init__(
+        self,
+        duration: Union[timedelta, float, None] = None,
+        num_samples: int = 1,
+        suppress_exceptions: bool = False,
+        attribute_children: bool = False,
+        *,
+        metadata: Optional[Dict[str, Any]] = None,
+        memory_bandwidth: bool = False,
+        channels: Optional[List[str]] = None,
+        sample_duration: Optional[int] = None,
+    ) -> None:
+        """`duration` is a timedelta or seconds, `sample_duration` (ms) is deprecated.
+        `memory_bandwidth=True` also reads memory traffic into every sample where IOReport
+        has it. `channels` limits the IOReport subscription to energy and any of
+        `"residency"` and `"memory_bandwidth"`."""
+    @staticmethod
+    def mock(
+        cpu_power: float = 0.0,
+        gpu_power: float = 0.0,
+        ane_power: float = 0.0,
+        duration: Union[timedelta, float, None] = None,
+        num_samples: int = 1,
+        *,
+        sample_duration: Optional[int] = None,
+    ) -> Profiler:
+        """A profiler reading synthetic samples at a constant power (mW)."""
+    @staticmethod
+    def adaptive(
+        min_interval: Union[timedelta, float, None] = None,
+        max_interval: Union[timedelta, float, None] = None,
+        threshold: float = 0.1,
+        suppress_exceptions: bool = False,
+    ) -> Profiler:
+        """A profiler whose sample interval follows power between the bounds."""
+    def on_sample(self, callback: Optional[Callable[[PyEnergySample], None]]) -> None: ...
+    def set_metadata(self, key: str, value: Any) -> None:
+        """Attach `key: str(value)` to the profiles of this and later sessions."""
+    def discard_warmup(
+        self, samples: Optional[int] = None, duration: Union[timedelta, float, None] = None
+    ) -> None:
+        """Leave the first `samples`, or the first `duration`, of every session out of its profile."""
+    def reject_outliers(self, threshold: Optional[float] = 3.5) -> None:
+        """Leave samples more than `threshold` MADs from the median power out of the profile."""
+    def detect_idle(
+        self,
+        margin: Optional[float] = 0.5,
+        min_duration: Union[timedelta, float, None] = None,
+        exclude: bool = False,
+    ) -> None:
+        """Annotate profiles with the stretches of at least `min_duration` (5 s by default)
+        spent within `margin` watts of the baseline power, and `exclude` them if set.
+        `margin=None` turns detection off."""
+    def set_qos(
+        self,
+        qos: Literal["user_interactive", "user_initiated", "default", "utility", "background"],
+    ) -> None:
+        """Run the sampling thread of later sessions at a QoS class, only has an effect on macOS."""
+    def set_metric_rates(
+        self,
+        thermal: Optional[Union[timedelta, float]] = None,
+        cpu_load: Optional[Union[timedelta, float]] = None,
+        io: Optional[Union[timedelta, float]] = None,
+        system_power: Optional[Union[timedelta, float]] = None,
+        power_source: Optional[Union[timedelta, float]] = None,
+    ) -> None:
+        """Read host metrics of later sessions less often than energy, None reads it with every sample. Between reads samples carry the last value, disk and network I/O only come with the reads."""
+    def on_power_above(self, watts: float, callback: Callable[[PyEnergySample], None]) -> None:
+        """Call `callback(sample)` each time total power rises above `watts`."""
+    def on_energy_budget_exceeded(
+        self, joules: float, callback: Callable[[PyEnergySample], None]
+    ) -> None:
+        """Call `callback(sample)` once per session when its energy exceeds `joules`."""
+    def stream(self) -> SampleStream: ...
+    def start(self) -> None: ...
+    def stop(self) -> None: ...
+    @property
+    def energy_by_pid(self) -> Dict[int, float]:
+        """Millijoules of CPU energy per PID, requires `attribute_children=True`."""
+    def region(self, name: str) -> ProfilerRegion: ...
+    @property
+    def is_sampling(self) -> bool: ...
+    @property
+    def error(self) -> Optional[str]:
+        """Why the last session ended before `stop`, e.g. a failing sensor read."""
+    @property
+    def discarded_samples(self) -> int:
+        """Reads of the last session dropped for counters going backwards or implausible power."""
+    @property
+    def interval_accuracy(self) -> Optional[Dict[str, float]]:
+        """Requested vs achieved sample interval of the last session, `None` before any samples.
+
+        Keys are `requested`, `mean`, `min`, `max` (milliseconds) and `relative_error`."""
+    @property
+    def gaps(self) -> List[Tuple[int, int]]:
+        """`(start_ms, duration_ms)` of every stretch the last session slept through."""
+    def __enter__(self) -> Profiler: ...
+    def __exit__(
+        self,
+        exc_type: Optional[Type[BaseException]] = None,
+        exc_value: Optional[BaseException] = None,
+        traceback: Optional[TracebackType] = None,
+    ) -> bool: ...
+    @property
+    def profile(self) -> PyPowerProfile:
+        """Profile of the last session, available as soon as its `with` block exits."""
+    @property
+    def sessions(self) -> List[PyPowerProfile]:
+        """Profile of every finished session of this profiler, oldest first, so one profiler
+        can be entered repeatedly. `profile` is the last one."""
+    @staticmethod
+    def measure(
+        func: Callable[..., Any], *args: Any, **kwargs: Any
+    ) -> Tuple[Any, PyPowerProfile]:
+        """Runs `func(*args, **kwargs)` under a default profiler sampling every 100 ms and
+        returns its result with the profile of the call. Sampling stops even if `func`
+        raises."""
+    def get_profile(self) -> PyPowerProfile: ...
+    def snapshot(self) -> PyPowerProfile:
+        """Profile of the running session so far, without stopping it."""
+    @property
+    def samples(self) -> List[PyEnergySample]: ...
+    def samples_dataframe(self) -> Any: ...
@@ -0,0 +1,213 @@
+import asyncio
+import itertools
+import json
+import threading
+import time
+from dataclasses import dataclass
+
+from ._wattkit_pyo3 import Profiler
+
+
+@dataclass
+class RequestStats:
+    requests: int = 0
+    joules: float = 0.0
+
+    @property
+    def joules_per_request(self):
+        return self.joules / self.requests if self.requests else 0.0
+
+
+class _InFlight:
+    __slots__ = ("start", "end", "energy", "done")
+
+    def __init__(self, start):
+        self.start = start  # ms since sampling started
+        self.end = None
+        self.energy = 0.0  # mJ
+        self.done = None  # (loop, future)
+
+
+class _Ledger:
+    """Requests in flight and the energy each was attributed so far, like the Rust layer's."""
+
+    def __init__(self):
+        self._lock = threading.Lock()
+        self._ids = itertools.count()
+        self._in_flight = {}
+        self._covered = 0
+        self.stats = RequestStats()
+
+    def begin(self, now):
+        with self._lock:
+            id = next(self._ids)
+            self._in_flight[id] = _InFlight(now)
+            return id
+
+    def finish(self, id, now):
+        """A future of the request's joules, resolved once a sample covers `now`."""
+        loop = asyncio.get_running_loop()
+        future = loop.create_future()
+        with self._lock:
+            request = self._in_flight[id]
+            request.end = now
+            request.done = (loop, future)
+            self._complete()
+        return future
+
+    def cancel(self, id):
+        with self._lock:
+            self._in_flight.pop(id, None)
+
+    def record(self, sample):
+        """Splits the energy of `sample` between the requests in flight during it, in
+        proportion to how long each was."""
+        start, end = sample.timestamp, sample.timestamp + sample.duration
+
+        def overlap(request):
+            to = end if request.end is None else min(request.end, end)
+            return max(0, to - max(request.start, start))
+
+        with self._lock:
+            total = sum(overlap(r) for r in self._in_flight.values())
+            if total:
+                energy = sample.cpu_energy + sample.gpu_energy + sample.ane_energy
+                for request in self._in_flight.values():
+                    request.energy += energy * overlap(request) / total
+            self._covered = max(self._covered, end)
+            self._complete()
+
+    def _complete(self):
+        answered = [
+            id
+            for id, r in self._in_flight.items()
+            if r.end is not None and r.end <= self._covered
+        ]
+        for id in answered:
+            request = self._in_flight.pop(id)
+            joules = request.energy / 1000.0
+            self.stats.requests += 1
+            self.stats.joules += joules
+            if request.done is not None:
+                loop, future = request.done
+                loop.call_soon_threadsafe(_resolve, future, joules)
+
+
+def _resolve(future, joules):
+    if not future.done():
+        future.set_result(joules)
+
+
+class EnergyMiddleware:
+    """
+    ASGI middleware attributing the energy of every sample to the HTTP requests in
+    flight during it, in proportion to how long each was, e.g. for FastAPI or Starlette:
+    `app.add_middleware(EnergyMiddleware)`.
+
+    The joules of a request are sent in the `header` of its response, which waits for
+    the sample covering the start of the response, at most about one `interval` in
+    seconds. A streamed body's energy after that isn't counted. A GET of
+    `metrics_path` answers the requests answered so far and their joules as JSON, pass
+    None to leave it out. A `profiler`, e.g. `Profiler.mock(...)`, replaces the one
+    sampling every `interval`.
+    """
+
+    def __init__(
+        self,
+        app,
+        interval=0.02,
+        header="x-energy-joules",
+        metrics_path="/wattkit/metrics",
+        profiler=None,
+    ):
+        self.app = app
+        self.interval = interval
+        self.header = header.lower().encode()
+        self.metrics_path = metrics_path
+        self.ledger = _Ledger()
+        self.profiler = profiler or Profiler(duration=interval)
+        self.profiler.on_sample(self.ledger.record)
+        self._start = None
+        self._starting = threading.Lock()
+
+    @property
+    def stats(self):
+        return self.ledger.stats
+
+    def _now(self):
+        return int((time.monotonic() - self._start) * 1000)
+
+    def _ensure_sampling(self):
+        with self._starting:
+            if self._start is None:
+                self.profiler.start()
+                self._start = time.monotonic()
+
+    def close(self):
+        """Stops sampling, also done on ASGI lifespan shutdown."""
+        with self._starting:
+            if self._start is not None and self.profiler.is_sampling:
+                self.profiler.stop()
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] == "lifespan":
+            return await self._lifespan(scope, receive, send)
+        if scope["type"] != "http":
+            return await self.app(scope, receive, send)
+        if self.metrics_path is not None and scope["path"] == self.metrics_path:
+            return await self._metrics(send)
+
+        self._ensure_sampling()
+        id = self.ledger.begin(self._now())
+        started = False
+
+        async def send_with_energy(message):
+            nonlocal started
+            if message["type"] == "http.response.start" and not started:
+                started = True
+                joules = await self._energy(id)
+                if joules is not None:
+                    headers = list(message.get("headers", []))
+                    headers.append((self.header, f"{joules:.6f}".encode()))
+                    message = {**message, "headers": headers}
+            await send(message)
+
+        try:
+            await self.app(scope, receive, send_with_energy)
+        finally:
+            if not started:
+                self.ledger.cancel(id)
+
+    async def _energy(self, id):
+        if not self.profiler.is_sampling:
+            self.ledger.cancel(id)
+            return None
+        future = self.ledger.finish(id, self._now())
+        try:
+            # samples may stop arriving, e.g. when the session fails
+            return await asyncio.wait_for(future, timeout=max(1.0, 10 * self.interval))
+        except asyncio.TimeoutError:
+            self.ledger.cancel(id)
+            return None
+
+    async def _metrics(self, send):
+        stats = self.stats
+        body = json.dumps(
+            {
+                "requests": stats.requests,
+                "joules": stats.joules,
+                "joules_per_request": stats.joules_per_request,
+            }
+        ).encode()
+        headers = [(b"content-type", b"application/json")]
+        await send({"type": "http.response.start", "status": 200, "headers": headers})
+        await send({"type": "http.response.body", "body": body})
+
+    async def _lifespan(self, scope, receive, send):
+        async def receive_shutdown():
+            message = await receive()
+            if message["type"] == "lifespan.shutdown":
+                self.close()
+            return message
+
+        await self.app(scope, receive_shutdown, send)
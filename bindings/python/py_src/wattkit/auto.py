@@ -0,0 +1,68 @@
+"""
+Profiling driven by environment variables, like `wattkit::auto` in Rust. Importing this
+module starts sampling when `WATTKIT_PROFILE=1` and writes the profile at exit to
+`WATTKIT_OUTPUT`, stderr without it. `WATTKIT_INTERVAL_MS` is the sample interval, 100 ms
+by default.
+
+Without touching the script at all: `WATTKIT_PROFILE=1 python -m wattkit.auto script.py`.
+"""
+
+import atexit
+import os
+import runpy
+import sys
+import warnings
+from datetime import timedelta
+
+from ._wattkit_pyo3 import Profiler
+
+_profiler = None
+
+
+def init():
+    """
+    Starts sampling when `WATTKIT_PROFILE=1`, returns whether profiling is on. A session
+    that fails to start is a warning, the program runs on unprofiled.
+    """
+    global _profiler
+    if os.environ.get("WATTKIT_PROFILE") != "1":
+        return False
+    if _profiler is None:
+        interval = int(os.environ.get("WATTKIT_INTERVAL_MS") or 100)
+        profiler = Profiler(duration=timedelta(milliseconds=interval))
+        try:
+            profiler.start()
+        except Exception as e:
+            warnings.warn(f"wattkit: not profiling, {e}")
+            return False
+        _profiler = profiler
+        atexit.register(finish)
+    return True
+
+
+def finish():
+    """Stops the session `init` started and writes its profile, what happens at exit."""
+    global _profiler
+    if _profiler is None:
+        return None
+    profiler, _profiler = _profiler, None
+    profiler.stop()
+    profile = profiler.profile
+    output = os.environ.get("WATTKIT_OUTPUT")
+    if output:
+        with open(output, "w") as file:
+            file.write(f"{profile}\n")
+    else:
+        print(profile, file=sys.stderr)
+    return profile
+
+
+if __name__ == "__main__":
+    if len(sys.argv) < 2:
+        sys.exit("usage: python -m wattkit.auto script.py [args...]")
+    os.environ.setdefault("WATTKIT_PROFILE", "1")
+    init()
+    sys.argv = sys.argv[1:]
+    runpy.run_path(sys.argv[0], run_name="__main__")
+else:
+    init()
@@ -0,0 +1,115 @@
+import csv
+import json
+import os
+import platform
+import uuid
+from datetime import datetime
+
+from ._wattkit_pyo3 import soc_info
+
+# Columns of codecarbon's emissions.csv, in its order.
+FIELDS = [
+    "timestamp", "project_name", "run_id", "experiment_id", "duration", "emissions",
+    "emissions_rate", "cpu_power", "gpu_power", "ram_power", "cpu_energy", "gpu_energy",
+    "ram_energy", "energy_consumed", "country_name", "country_iso_code", "region",
+    "cloud_provider", "cloud_region", "os", "python_version", "codecarbon_version",
+    "cpu_count", "cpu_model", "gpu_count", "gpu_model", "longitude", "latitude",
+    "ram_total_size", "tracking_mode", "on_cloud", "pue",
+]
+
+# codecarbon's fallback when the grid's carbon intensity is unknown, g CO2eq/kWh.
+WORLD_CARBON_INTENSITY = 475.0
+
+
+def _kwh(millijoules):
+    return millijoules / 3.6e9
+
+
+def _timestamp(profile):
+    """End of the session, codecarbon stamps rows when tracking stops."""
+    timed = [s for s in profile.samples if s.wall_time is not None]
+    if not timed:
+        return datetime.now().isoformat(timespec="seconds")
+    end = timed[-1].wall_time + timed[-1].duration / 1000.0
+    return datetime.fromtimestamp(end).isoformat(timespec="seconds")
+
+
+def to_codecarbon(
+    profile,
+    project_name="wattkit",
+    experiment_id="",
+    run_id=None,
+    carbon_intensity=WORLD_CARBON_INTENSITY,
+    country_iso_code="",
+    country_name="",
+    region="",
+    pue=1.0,
+):
+    """
+    A row of codecarbon's emissions.csv for `profile`, with measured instead of
+    estimated energy. Energies are in kWh, powers in W, emissions in kg CO2eq from
+    `carbon_intensity` in g CO2eq/kWh.
+
+    codecarbon has no column for the ANE, its energy counts towards the CPU's, which
+    share a package. wattkit doesn't measure DRAM, RAM columns are zero.
+    """
+    info = soc_info()
+    duration = profile.total_duration / 1000.0
+    cpu_energy = _kwh(profile.total_cpu_energy + profile.total_ane_energy) * pue
+    gpu_energy = _kwh(profile.total_gpu_energy) * pue
+    energy = cpu_energy + gpu_energy
+    emissions = energy * carbon_intensity / 1000.0
+    return {
+        "timestamp": _timestamp(profile),
+        "project_name": project_name,
+        "run_id": run_id or str(uuid.uuid4()),
+        "experiment_id": experiment_id,
+        "duration": duration,
+        "emissions": emissions,
+        "emissions_rate": emissions / duration if duration else 0.0,
+        "cpu_power": (profile.average_cpu_power + profile.average_ane_power) / 1000.0,
+        "gpu_power": profile.average_gpu_power / 1000.0,
+        "ram_power": 0.0,
+        "cpu_energy": cpu_energy,
+        "gpu_energy": gpu_energy,
+        "ram_energy": 0.0,
+        "energy_consumed": energy,
+        "country_name": country_name,
+        "country_iso_code": country_iso_code,
+        "region": region,
+        "cloud_provider": "",
+        "cloud_region": "",
+        "os": platform.platform(),
+        "python_version": platform.python_version(),
+        "codecarbon_version": "",
+        "cpu_count": info["cores"] or os.cpu_count(),
+        "cpu_model": info["name"] or platform.processor(),
+        "gpu_count": 1 if info["apple_silicon"] else 0,
+        "gpu_model": info["name"] if info["apple_silicon"] else "",
+        "longitude": "",
+        "latitude": "",
+        "ram_total_size": (info["memory"] or 0) / 2**30,
+        "tracking_mode": "machine",
+        "on_cloud": "N",
+        "pue": pue,
+    }
+
+
+def write_codecarbon_csv(profile, path="emissions.csv", **kwargs):
+    """
+    Appends the row of `to_codecarbon(profile, **kwargs)` to `path`, writing the header
+    first when the file is new, like codecarbon does. Returns the row.
+    """
+    row = to_codecarbon(profile, **kwargs)
+    new = not os.path.exists(path) or os.path.getsize(path) == 0
+    with open(path, "a", newline="") as file:
+        writer = csv.DictWriter(file, fieldnames=FIELDS)
+        if new:
+            writer.writeheader()
+        writer.writerow(row)
+    return row
+
+
+def to_codecarbon_json(profile, **kwargs):
+    """The row of `to_codecarbon(profile, **kwargs)` as a JSON object."""
+    return json.dumps(to_codecarbon(profile, **kwargs))
@@ -0,0 +1,44 @@
+import subprocess
+from dataclasses import dataclass
+from datetime import timedelta
+from typing import Optional, Sequence, Union
+
+from ._wattkit_pyo3 import Profiler, PyPowerProfile
+
+
+@dataclass
+class CommandResult:
+    """Outcome of `profile_command`, `stdout` and `stderr` are None unless captured."""
+
+    returncode: int
+    stdout: Optional[Union[str, bytes]]
+    stderr: Optional[Union[str, bytes]]
+    profile: PyPowerProfile
+
+
+def profile_command(
+    args: Sequence[str],
+    capture_output: bool = False,
+    text: bool = False,
+    check: bool = False,
+    sample_duration: int = 100,
+    num_samples: int = 1,
+    profiler: Optional[Profiler] = None,
+    **kwargs,
+) -> CommandResult:
+    """
+    Runs `args` to completion while sampling and returns its exit code, its output
+    when `capture_output` is set and the profile of its lifetime.
+
+    Further keyword arguments, e.g. `cwd`, `env`, `timeout` or `stdout`, go to
+    `subprocess.run`. With `check`, a non-zero exit code raises
+    `subprocess.CalledProcessError` once sampling stopped.
+    """
+    profiler = profiler or Profiler(
+        duration=timedelta(milliseconds=sample_duration), num_samples=num_samples
+    )
+    with profiler:
+        completed = subprocess.run(args, capture_output=capture_output, text=text, **kwargs)
+    if check:
+        completed.check_returncode()
+    return CommandResult(completed.returncode, completed.stdout, completed.stderr, profiler.profile)
@@ -0,0 +1,89 @@
+import math
+import statistics
+import time
+from datetime import timedelta
+from dataclasses import dataclass, field
+from typing import Callable, List
+
+from ._wattkit_pyo3 import Profiler
+
+# Two-sided 95% critical values of Student's t distribution, indexed by degrees of freedom.
+_T_95 = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+]
+
+
+def _t_critical(df):
+    return _T_95[df - 1] if df <= len(_T_95) else 1.96
+
+
+@dataclass
+class InferenceEnergy:
+    """Baseline-subtracted energy per inference, in joules, with a 95% confidence interval."""
+
+    joules_per_inference: float
+    ci_low: float
+    ci_high: float
+    baseline_power: float  # watts
+    runs: List[float] = field(default_factory=list)  # joules per inference, one entry per run
+
+    def __str__(self):
+        return (
+            f"{self.joules_per_inference:.6f} J/inference "
+            f"(95% CI {self.ci_low:.6f} - {self.ci_high:.6f}, "
+            f"baseline {self.baseline_power:.3f} W, {len(self.runs)} runs)"
+        )
+
+
+def measure_inference(
+    fn: Callable[[], object],
+    warmup: int = 10,
+    iters: int = 100,
+    runs: int = 5,
+    baseline: float = 2.0,
+    sample_duration: int = 100,
+    num_samples: int = 1,
+) -> InferenceEnergy:
+    """
+    Measure the energy of a single call to `fn`, e.g. an MLX or Core ML `predict`.
+
+    `fn` is called `warmup` times unmeasured, then `runs` sessions of `iters` calls are
+    profiled. The idle power measured over `baseline` seconds beforehand is subtracted
+    from every run.
+    """
+    if iters < 1 or runs < 1:
+        raise ValueError("iters and runs must be at least 1")
+
+    for _ in range(warmup):
+        fn()
+
+    duration = timedelta(milliseconds=sample_duration)
+    with Profiler(duration=duration, num_samples=num_samples) as idle:
+        time.sleep(baseline)
+    baseline_power = idle.get_profile().average_power  # mW
+
+    per_inference = []
+    for _ in range(runs):
+        with Profiler(duration=duration, num_samples=num_samples) as profiler:
+            for _ in range(iters):
+                fn()
+        profile = profiler.get_profile()
+        baseline_energy = baseline_power * profile.total_duration / 1000.0  # mJ
+        per_inference.append((profile.total_energy - baseline_energy) / iters / 1000.0)
+
+    mean = statistics.fmean(per_inference)
+    if len(per_inference) > 1:
+        sem = statistics.stdev(per_inference) / math.sqrt(len(per_inference))
+        margin = _t_critical(len(per_inference) - 1) * sem
+    else:
+        margin = float("nan")
+
+    return InferenceEnergy(
+        joules_per_inference=mean,
+        ci_low=mean - margin,
+        ci_high=mean + margin,
+        baseline_power=baseline_power / 1000.0,
+        runs=per_inference,
+    )
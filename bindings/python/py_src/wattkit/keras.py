@@ -0,0 +1,44 @@
+from datetime import timedelta
+
+try:
+    import keras
+except ImportError:
+    from tensorflow import keras
+
+from ._wattkit_pyo3 import Profiler
+
+
+class WattkitCallback(keras.callbacks.Callback):
+    """
+    Samples energy during every epoch of `model.fit()`.
+
+    `energy_joules` and `power_watts` are added to each epoch's logs, so they show up
+    in the returned `History` next to the loss. Per-epoch profiles are kept in
+    `callback.profiles`.
+    """
+
+    def __init__(self, sample_duration=100, num_samples=1):
+        super().__init__()
+        self.sample_duration = sample_duration
+        self.num_samples = num_samples
+        self.profiles = []
+        self._profiler = None
+
+    def on_epoch_begin(self, epoch, logs=None):
+        self._profiler = Profiler(
+            duration=timedelta(milliseconds=self.sample_duration), num_samples=self.num_samples
+        )
+        self._profiler.start()
+
+    def on_epoch_end(self, epoch, logs=None):
+        self._profiler.stop()
+        profile = self._profiler.get_profile()
+        self.profiles.append(profile)
+        if logs is not None:
+            logs["energy_joules"] = profile.total_energy / 1000.0
+            logs["power_watts"] = profile.average_power / 1000.0
+
+    def on_train_end(self, logs=None):
+        # Training interrupted mid-epoch, e.g. by early stopping.
+        if self._profiler is not None and self._profiler.is_sampling:
+            self._profiler.stop()
@@ -0,0 +1,82 @@
+import sys
+import time
+from typing import Optional, TextIO
+
+from ._wattkit_pyo3 import Profiler, PyPowerProfile
+
+
+def _watts(energy, duration):
+    # mJ over ms
+    return energy / duration if duration else 0.0
+
+
+def _readout(sample):
+    units = [
+        ("CPU", sample.cpu_energy),
+        ("GPU", sample.gpu_energy),
+        ("ANE", sample.ane_energy),
+        ("Total", sample.cpu_energy + sample.gpu_energy + sample.ane_energy),
+    ]
+    return [(name, _watts(energy, sample.duration)) for name, energy in units]
+
+
+def _rich_table(readout):
+    from rich.table import Table
+
+    table = Table(title="wattkit")
+    table.add_column("Unit")
+    table.add_column("Power", justify="right")
+    for name, watts in readout:
+        table.add_row(name, f"{watts:.3f} W")
+    return table
+
+
+def live_monitor(
+    interval: float = 1.0,
+    duration: Optional[float] = None,
+    *,
+    rich: Optional[bool] = None,
+    file: Optional[TextIO] = None,
+    profiler: Optional[Profiler] = None,
+) -> PyPowerProfile:
+    """
+    Show the power of every `interval` seconds until `duration` seconds have passed or
+    Ctrl-C is pressed, and return the profile of the whole session.
+
+    The readout is a `rich` table when `rich` is installed, or a single line rewritten
+    in place otherwise. `rich=False` forces the plain line. A `profiler` to read from,
+    e.g. `Profiler.mock(...)`, replaces the one sampling every `interval`.
+    """
+    if rich is None:
+        try:
+            import rich.live  # noqa: F401
+
+            rich = True
+        except ImportError:
+            rich = False
+    file = file or sys.stdout
+    profiler = profiler or Profiler(duration=interval)
+
+    start = time.monotonic()
+    with profiler:
+        stream = profiler.stream()
+        try:
+            if rich:
+                from rich.console import Console
+                from rich.live import Live
+
+                with Live(console=Console(file=file), auto_refresh=False) as live:
+                    for sample in stream:
+                        live.update(_rich_table(_readout(sample)), refresh=True)
+                        if duration is not None and time.monotonic() - start >= duration:
+                            break
+            else:
+                for sample in stream:
+                    line = "  ".join(f"{name} {watts:7.3f} W" for name, watts in _readout(sample))
+                    print(f"\r{line}", end="", file=file, flush=True)
+                    if duration is not None and time.monotonic() - start >= duration:
+                        break
+                print(file=file)
+        except KeyboardInterrupt:
+            pass
+    return profiler.profile
@@ -0,0 +1,53 @@
+from datetime import timedelta
+
+from IPython.core.magic import Magics, cell_magic, magics_class
+from IPython.core.magic_arguments import argument, magic_arguments, parse_argstring
+from IPython.display import HTML, display
+
+from ._wattkit_pyo3 import Profiler
+
+
+def _summary_html(profile):
+    rows = [
+        ("Total Energy", profile.total_energy, "mJ"),
+        ("Average Power", profile.average_power, "mW"),
+        ("Duration", profile.total_duration, "ms"),
+        ("CPU Energy", profile.total_cpu_energy, "mJ"),
+        ("GPU Energy", profile.total_gpu_energy, "mJ"),
+        ("ANE Energy", profile.total_ane_energy, "mJ"),
+        ("CPU Power", profile.average_cpu_power, "mW"),
+        ("GPU Power", profile.average_gpu_power, "mW"),
+        ("ANE Power", profile.average_ane_power, "mW"),
+    ]
+    body = "".join(
+        f"<tr><td>{name}</td><td>{value}</td><td>{unit}</td></tr>"
+        for name, value, unit in rows
+    )
+    return f"<table><tr><th>Metric</th><th>Value</th><th>Unit</th></tr>{body}</table>"
+
+
+@magics_class
+class WattkitMagics(Magics):
+    @magic_arguments()
+    @argument("--sample-duration", "-d", type=int, default=100, help="Sample duration in ms.")
+    @argument("--num-samples", "-n", type=int, default=1, help="Samples per duration.")
+    @argument("--plot", "-p", action="store_true", help="Also render a power-over-time plot.")
+    @cell_magic
+    def wattkit(self, line, cell):
+        """Profile the energy usage of the cell and display a summary."""
+        args = parse_argstring(self.wattkit, line)
+        profiler = Profiler(
+            duration=timedelta(milliseconds=args.sample_duration), num_samples=args.num_samples
+        )
+        with profiler:
+            self.shell.run_cell(cell)
+
+        profile = profiler.get_profile()
+        display(HTML(_summary_html(profile)))
+        if args.plot:
+            display(profile.plot())
+        self.shell.user_ns["_wattkit_profile"] = profile
+
+
+def load_ipython_extension(ipython):
+    ipython.register_magics(WattkitMagics)
@@ -0,0 +1,55 @@
+import re
+from contextlib import contextmanager
+from datetime import timedelta
+
+import mlflow
+
+from ._wattkit_pyo3 import Profiler
+
+
+def _metric_name(name):
+    # MLflow only accepts alphanumerics, underscores, dashes, periods, spaces and slashes
+    return re.sub(r"[^\w\-. /]", "_", name)
+
+
+def profile_metrics(profile, prefix="energy_"):
+    """Energy in joules and power in watts of `profile` and of each of its regions."""
+    # mJ over ms
+    powers = [
+        (s.cpu_energy + s.gpu_energy + s.ane_energy) / s.duration
+        for s in profile.samples
+        if s.duration
+    ]
+    metrics = {
+        f"{prefix}total_joules": profile.total_energy / 1000.0,
+        f"{prefix}cpu_joules": profile.total_cpu_energy / 1000.0,
+        f"{prefix}gpu_joules": profile.total_gpu_energy / 1000.0,
+        f"{prefix}ane_joules": profile.total_ane_energy / 1000.0,
+        f"{prefix}average_power_watts": profile.average_power / 1000.0,
+        f"{prefix}peak_power_watts": max(powers, default=0.0),
+        f"{prefix}duration_seconds": profile.total_duration / 1000.0,
+    }
+    for name, region in profile.regions.items():
+        metrics[f"{prefix}region_{_metric_name(name)}_joules"] = region.total_energy / 1000.0
+    return metrics
+
+
+def log_profile(profile, prefix="energy_", step=None, artifact_path="wattkit"):
+    """
+    Logs `profile_metrics(profile)` to the active MLflow run, and the whole profile as
+    `<artifact_path>/profile.json` unless `artifact_path` is None.
+    """
+    mlflow.log_metrics(profile_metrics(profile, prefix), step=step)
+    if artifact_path is not None:
+        mlflow.log_dict(profile.to_dict(), f"{artifact_path}/profile.json")
+
+
+@contextmanager
+def log_energy(sample_duration=100, num_samples=1, profiler=None, **kwargs):
+    """Profiles the `with` block and logs it with `log_profile(..., **kwargs)`."""
+    profiler = profiler or Profiler(
+        duration=timedelta(milliseconds=sample_duration), num_samples=num_samples
+    )
+    with profiler:
+        yield profiler
+    log_profile(profiler.profile, **kwargs)
@@ -0,0 +1,46 @@
+def plot_profile(profile, ax=None, regions=None):
+    """
+    Draw a stacked CPU/GPU/ANE power-over-time chart of `profile`.
+
+    `regions` is an iterable of `(name, start_ms, end_ms)` tuples shaded and labelled
+    on the chart, defaulting to the profile's own regions. Returns the matplotlib figure.
+    """
+    try:
+        import matplotlib.pyplot as plt
+    except ImportError as e:
+        raise ImportError(
+            "plotting requires matplotlib, install it with `pip install wattkit[plot]`"
+        ) from e
+
+    if ax is None:
+        fig, ax = plt.subplots(figsize=(10, 4))
+    else:
+        fig = ax.figure
+
+    samples = profile.samples
+    times = [s.timestamp / 1000.0 for s in samples]
+    cpu = [s.cpu_power / 1000.0 for s in samples]
+    gpu = [s.gpu_power / 1000.0 for s in samples]
+    ane = [s.ane_power / 1000.0 for s in samples]
+
+    ax.stackplot(times, cpu, gpu, ane, labels=["CPU", "GPU", "ANE"], step="post", alpha=0.8)
+
+    if regions is None:
+        regions = profile.region_spans
+    for name, start, end in regions:
+        ax.axvspan(start / 1000.0, end / 1000.0, color="grey", alpha=0.15)
+        ax.annotate(
+            name,
+            xy=(start / 1000.0, 1.0),
+            xycoords=("data", "axes fraction"),
+            xytext=(2, -2),
+            textcoords="offset points",
+            va="top",
+            fontsize="small",
+        )
+
+    ax.set_xlabel("Time (s)")
+    ax.set_ylabel("Power (W)")
+    ax.set_xlim(left=0)
+    ax.legend(loc="upper right")
+    return fig
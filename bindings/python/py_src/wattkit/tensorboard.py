@@ -0,0 +1,58 @@
+from ._meter import EnergyMeter, energy_metrics
+
+
+class TensorBoardLogger:
+    """
+    Logs energy and power scalars into a TensorBoard `SummaryWriter`, from
+    `torch.utils.tensorboard`, `tensorboardX` or anything else with `add_scalar`.
+
+    `step(global_step)` logs `energy/joules` and `energy/power_watts` for the interval
+    since the previous step, `epoch(epoch)` logs `energy/epoch_joules` and
+    `energy/epoch_power_watts` since the previous epoch. Sampling runs from `start` to
+    `stop`, or over a `with` block.
+    """
+
+    def __init__(self, writer, sample_duration=100, num_samples=1, prefix="energy/", profiler=None):
+        self.writer = writer
+        self.prefix = prefix
+        self.meter = EnergyMeter(
+            sample_duration=sample_duration, num_samples=num_samples, profiler=profiler
+        )
+        self.profile = None
+        self._epoch_energy = 0
+        self._epoch_duration = 0
+
+    def start(self):
+        self.meter.start()
+
+    def stop(self):
+        """Stops sampling and returns the profile of the whole session."""
+        self.profile = self.meter.stop()
+        return self.profile
+
+    def step(self, global_step):
+        energy, duration = self.meter.lap()
+        self._epoch_energy += energy
+        self._epoch_duration += duration
+        self._log(energy_metrics(energy, duration, prefix=self.prefix), global_step)
+
+    def epoch(self, epoch):
+        # energy since the last step counts towards the epoch too
+        energy, duration = self.meter.lap()
+        energy += self._epoch_energy
+        duration += self._epoch_duration
+        self._epoch_energy = self._epoch_duration = 0
+        metrics = energy_metrics(energy, duration, prefix=f"{self.prefix}epoch_")
+        self._log(metrics, epoch)
+
+    def _log(self, metrics, step):
+        for tag, value in metrics.items():
+            self.writer.add_scalar(tag, value, step)
+
+    def __enter__(self):
+        self.start()
+        return self
+
+    def __exit__(self, *exc):
+        self.stop()
+        return False
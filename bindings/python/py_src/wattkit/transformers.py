@@ -0,0 +1,41 @@
+from transformers import TrainerCallback
+
+from ._meter import EnergyMeter, energy_metrics
+
+
+class WattkitCallback(TrainerCallback):
+    """
+    Records energy during `Trainer.train()`.
+
+    Every log event gains `energy/joules` and `energy/power_watts` for the interval since
+    the previous log, and each epoch appends an entry with the epoch's energy to
+    `state.log_history`. The profile of the whole run is available as `callback.profile`.
+    """
+
+    def __init__(self, sample_duration=100, num_samples=1):
+        self.meter = EnergyMeter(sample_duration=sample_duration, num_samples=num_samples)
+        self.profile = None
+        self._epoch_start = 0
+
+    def on_train_begin(self, args, state, control, **kwargs):
+        self.meter.start()
+        self._epoch_start = 0
+
+    def on_log(self, args, state, control, logs=None, **kwargs):
+        if logs is not None:
+            logs.update(energy_metrics(*self.meter.lap()))
+
+    def on_epoch_end(self, args, state, control, **kwargs):
+        energy = self.meter.energy
+        state.log_history.append(
+            {
+                "epoch": state.epoch,
+                "step": state.global_step,
+                "energy/epoch_joules": (energy - self._epoch_start) / 1000.0,
+                "energy/total_joules": energy / 1000.0,
+            }
+        )
+        self._epoch_start = energy
+
+    def on_train_end(self, args, state, control, **kwargs):
+        self.profile = self.meter.stop()
@@ -0,0 +1,91 @@
+import platform
+import time
+from datetime import timedelta
+
+import wandb
+
+from ._wattkit_pyo3 import Profiler, soc_info
+
+
+def _system_metadata():
+    info = soc_info()
+    return {
+        "chip": info["name"],
+        "model": info["model"],
+        "cores": info["cores"],
+        "memory": info["memory"],
+        "macos": platform.mac_ver()[0] or None,
+    }
+
+
+def _sample_metrics(sample, prefix):
+    # mJ over ms
+    watts = lambda energy: energy / sample.duration if sample.duration else 0.0
+    return {
+        f"{prefix}cpu_power_watts": watts(sample.cpu_energy),
+        f"{prefix}gpu_power_watts": watts(sample.gpu_energy),
+        f"{prefix}ane_power_watts": watts(sample.ane_energy),
+        f"{prefix}power_watts": watts(sample.cpu_energy + sample.gpu_energy + sample.ane_energy),
+    }
+
+
+def _profile_metrics(profile, prefix):
+    return {
+        f"{prefix}total_joules": profile.total_energy / 1000.0,
+        f"{prefix}cpu_joules": profile.total_cpu_energy / 1000.0,
+        f"{prefix}gpu_joules": profile.total_gpu_energy / 1000.0,
+        f"{prefix}ane_joules": profile.total_ane_energy / 1000.0,
+        f"{prefix}average_power_watts": profile.average_power / 1000.0,
+        f"{prefix}duration_seconds": profile.total_duration / 1000.0,
+        f"{prefix}throttled_seconds": profile.throttled_duration / 1000.0,
+    }
+
+
+class WattkitLogger:
+    """
+    Streams power samples into a W&B run, `wandb.run` unless another is given.
+
+    Every sample logs `energy/power_watts` and the power of each unit against
+    `energy/elapsed_seconds`, so the training's own step isn't disturbed. On `stop` the
+    profile's totals go into the run summary. The chip and macOS version are added to
+    the run config under `wattkit`.
+    """
+
+    def __init__(self, run=None, sample_duration=100, num_samples=1, prefix="energy/", profiler=None):
+        self.run = run or wandb.run
+        if self.run is None:
+            raise RuntimeError("no active W&B run, call wandb.init() first")
+        self.prefix = prefix
+        self.profiler = profiler or Profiler(
+            duration=timedelta(milliseconds=sample_duration), num_samples=num_samples
+        )
+        self.profiler.on_sample(self._on_sample)
+        self.profile = None
+        self._start = None
+
+    def _on_sample(self, sample):
+        metrics = _sample_metrics(sample, self.prefix)
+        metrics[f"{self.prefix}elapsed_seconds"] = time.monotonic() - self._start
+        self.run.log(metrics)
+
+    def start(self):
+        self.run.config.update({"wattkit": _system_metadata()}, allow_val_change=True)
+        self.run.define_metric(f"{self.prefix}elapsed_seconds")
+        self.run.define_metric(f"{self.prefix}*", step_metric=f"{self.prefix}elapsed_seconds")
+        self._start = time.monotonic()
+        self.profiler.start()
+
+    def stop(self):
+        """Stops sampling, writes the profile's totals to the run summary and returns it."""
+        self.profiler.stop()
+        self.profile = self.profiler.profile
+        self.run.summary.update(_profile_metrics(self.profile, self.prefix))
+        return self.profile
+
+    def __enter__(self):
+        self.start()
+        return self
+
+    def __exit__(self, *exc):
+        self.stop()
+        return False
@@ -1,84 +1,1395 @@
+// pyo3 0.22 macro expansions trip this lint on newer toolchains.
+#![allow(clippy::useless_conversion)]
+
+use pyo3::exceptions::{PyAssertionError, PyDeprecationWarning, PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use wattkit::{PowerProfile, Sampling, StartStopSampler};
+use pyo3::types::{PyDict, PyTuple};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Arc, Mutex,
+};
+use std::time::Duration;
+use wattkit::backend::ioreport::ChannelSet;
+use wattkit::backend::open_default;
+use wattkit::overhead;
+use wattkit::process::ProcessAttribution;
+use wattkit::{
+    AdaptiveRate, AggregateProfile, ChannelTree, Comparison, DieEnergy, EnergySample,
+    IdleDetection, MetricRates, MockBackend, OutlierFilter, PeakPower, PowerBackend, PowerProfile,
+    QosClass, Region, RegionId, SampleCallback, SamplerError, Sampling, StartStopSampler, Summary,
+    ThermalPressure, Units, Warmup,
+};
+
+pyo3::create_exception!(
+    wattkit,
+    WattKitError,
+    pyo3::exceptions::PyException,
+    "Raised when sampling fails or is used out of order."
+);
+
+fn to_py_err(e: SamplerError) -> PyErr {
+    WattKitError::new_err(e.to_string())
+}
+
+type DynSampler = StartStopSampler<Box<dyn PowerBackend>>;
+
+/// A `datetime.timedelta` or seconds as a number.
+fn extract_duration(value: &Bound<'_, PyAny>) -> PyResult<Duration> {
+    if let Ok(duration) = value.extract::<Duration>() {
+        return Ok(duration);
+    }
+    let secs: f64 = value.extract()?;
+    Duration::try_from_secs_f64(secs).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Sample duration from `duration`, or the deprecated `sample_duration` in milliseconds.
+fn sample_duration(
+    py: Python<'_>,
+    duration: Option<&Bound<'_, PyAny>>,
+    sample_duration: Option<u64>,
+) -> PyResult<Duration> {
+    match (duration, sample_duration) {
+        (Some(duration), None) => extract_duration(duration),
+        (None, Some(ms)) => {
+            PyErr::warn_bound(
+                py,
+                &py.get_type_bound::<PyDeprecationWarning>(),
+                "sample_duration (milliseconds) is deprecated, pass duration as a timedelta or seconds",
+                2,
+            )?;
+            Ok(Duration::from_millis(ms))
+        }
+        (None, None) => Err(PyTypeError::new_err("missing duration")),
+        (Some(_), Some(_)) => Err(PyTypeError::new_err(
+            "pass either duration or sample_duration, not both",
+        )),
+    }
+}
+
+/// IOReport groups to subscribe to besides energy, `None` for the default backend.
+fn channel_set(names: Option<Vec<String>>, memory_bandwidth: bool) -> PyResult<Option<ChannelSet>> {
+    let Some(names) = names else {
+        return Ok(memory_bandwidth.then(|| ChannelSet {
+            memory_bandwidth: true,
+            ..Default::default()
+        }));
+    };
+    let mut channels = ChannelSet {
+        memory_bandwidth,
+        ..ChannelSet::ENERGY
+    };
+    for name in names {
+        match name.as_str() {
+            "energy" => {}
+            "residency" => channels.residency = true,
+            "memory_bandwidth" => channels.memory_bandwidth = true,
+            _ => return Err(PyValueError::new_err(format!("unknown channels `{name}`"))),
+        }
+    }
+    Ok(Some(channels))
+}
 
 #[pyclass]
 struct Profiler {
-    sampler: StartStopSampler,
-    sample_duration: u64,
+    sampler: DynSampler,
+    sample_duration: Duration,
     num_samples: usize,
+    /// Paces sessions by power instead of `sample_duration`/`num_samples` when set.
+    adaptive: Option<AdaptiveRate>,
+    suppress_exceptions: bool,
+    callback: Option<PyObject>,
+    streams: Arc<Mutex<Vec<Sender<EnergySample>>>>,
+    attribute_children: bool,
+    attribution: Arc<Mutex<Option<ProcessAttribution>>>,
+    sampling_parent: Option<u32>,
+    /// Profiles of the finished sessions, oldest first.
+    sessions: Vec<Py<PyPowerProfile>>,
+    /// Sessions started so far, regions only end in the session they began in.
+    session: usize,
+}
+
+/// Set while a process is sampling, so child processes it starts (forked or spawned)
+/// don't open a second, conflicting subscription.
+const SAMPLER_PID_VAR: &str = "WATTKIT_SAMPLER_PID";
+
+fn sampling_parent(py: Python<'_>) -> PyResult<Option<u32>> {
+    let environ = py.import_bound("os")?.getattr("environ")?;
+    let pid: Option<String> = environ.call_method1("get", (SAMPLER_PID_VAR,))?.extract()?;
+    Ok(pid
+        .and_then(|pid| pid.parse().ok())
+        .filter(|pid| *pid != std::process::id()))
 }
 
 #[pyclass]
-pub struct PyPowerProfile(pub PowerProfile);
+#[derive(Clone)]
+pub struct PyEnergySample(pub EnergySample);
 
 #[pymethods]
-impl PyPowerProfile {
-    fn __str__(&self) -> PyResult<String> {
+impl PyEnergySample {
+    fn __repr__(&self) -> PyResult<String> {
         Ok(format!("{:?}", self.0))
     }
 
+    #[getter]
+    fn timestamp(&self) -> PyResult<u64> {
+        Ok(self.0.timestamp)
+    }
+
+    /// Wall clock time at the start of the sample in seconds since the Unix epoch, to
+    /// line it up with logs. `timestamp` stays monotonic.
+    #[getter]
+    fn wall_time(&self) -> PyResult<Option<f64>> {
+        Ok(wall_time(&self.0))
+    }
+
+    #[getter]
+    fn duration(&self) -> PyResult<u64> {
+        Ok(self.0.duration)
+    }
+
+    #[getter]
+    fn cpu_energy(&self) -> PyResult<u128> {
+        Ok(self.0.cpu_energy)
+    }
+
+    #[getter]
+    fn gpu_energy(&self) -> PyResult<u128> {
+        Ok(self.0.gpu_energy)
+    }
+
+    #[getter]
+    fn ane_energy(&self) -> PyResult<u128> {
+        Ok(self.0.ane_energy)
+    }
+
+    #[getter]
+    fn cpu_power(&self) -> PyResult<f64> {
+        Ok(self.0.cpu_power())
+    }
+
+    #[getter]
+    fn gpu_power(&self) -> PyResult<f64> {
+        Ok(self.0.gpu_power())
+    }
+
+    #[getter]
+    fn ane_power(&self) -> PyResult<f64> {
+        Ok(self.0.ane_power())
+    }
+
+    /// Whether the machine ran on battery, `None` where the power source can't be read.
+    #[getter]
+    fn on_battery(&self) -> PyResult<Option<bool>> {
+        Ok(self.0.power_source.map(|source| source.on_battery))
+    }
+
+    #[getter]
+    fn battery_percent(&self) -> PyResult<Option<u8>> {
+        Ok(self
+            .0
+            .power_source
+            .and_then(|source| source.battery_percent))
+    }
+
+    /// Fraction of the host's CPU time, over all cores, spent busy during the sample.
+    #[getter]
+    fn cpu_utilization(&self) -> PyResult<Option<f64>> {
+        Ok(self.0.cpu_utilization)
+    }
+
+    /// Millijoules drawn by the display, `None` on machines that don't report it.
+    #[getter]
+    fn display_energy(&self) -> PyResult<Option<u128>> {
+        Ok(self.0.display_energy)
+    }
+
+    /// Millijoules drawn by the whole SoC, DRAM and display included. `None` where the
+    /// backend doesn't report it.
+    #[getter]
+    fn package_energy(&self) -> PyResult<Option<u128>> {
+        Ok(self.0.package_energy)
+    }
+
+    /// Milliwatts.
+    #[getter]
+    fn package_power(&self) -> PyResult<Option<f64>> {
+        Ok(self.0.package_power())
+    }
+
+    /// Milliwatts drawn by the whole machine, from the SMC. `None` where it can't be read.
+    #[getter]
+    fn system_power(&self) -> PyResult<Option<f64>> {
+        Ok(self.0.system_power)
+    }
+
+    /// Milliwatts drawn through the DC input, zero on battery. `None` where it can't be
+    /// read.
+    #[getter]
+    fn dc_in_power(&self) -> PyResult<Option<f64>> {
+        Ok(self.0.dc_in_power)
+    }
+
+    /// `(cpu, gpu, ane)` millijoules of each die of an M1 or M2 Ultra, empty on
+    /// single-die chips.
+    #[getter]
+    fn dies(&self) -> PyResult<Vec<(u128, u128, u128)>> {
+        Ok(die_tuples(&self.0.dies))
+    }
+
+    /// "nominal", "moderate", "heavy", "trapping" or "sleeping", only read on macOS.
+    #[getter]
+    fn thermal_pressure(&self) -> PyResult<Option<&'static str>> {
+        Ok(self.0.thermal_pressure.map(pressure_name))
+    }
+
+    /// `(active, performance_state)` of the busiest CPU cluster: the fraction of the
+    /// sample it was active, and where between its lowest (0) and highest (1)
+    /// performance state it ran meanwhile.
+    #[getter]
+    fn cpu_residency(&self) -> PyResult<Option<(f64, f64)>> {
+        Ok(self
+            .0
+            .cpu_residency
+            .map(|r| (r.active, r.performance_state)))
+    }
+
+    /// Whether the SoC was throttling during the sample.
+    #[getter]
+    fn throttled(&self) -> PyResult<bool> {
+        Ok(self.0.is_throttled())
+    }
+
+    /// `(read, written)` bytes of memory traffic, see `Profiler(memory_bandwidth=True)`.
+    #[getter]
+    fn memory_traffic(&self) -> PyResult<Option<(u64, u64)>> {
+        Ok(self.0.memory_traffic.map(|t| (t.read, t.write)))
+    }
+
+    /// Memory read plus write bandwidth in bytes per second.
+    #[getter]
+    fn memory_bandwidth(&self) -> PyResult<Option<f64>> {
+        Ok(self.0.memory_bandwidth())
+    }
+
+    /// `(read, written)` bytes of all disks of the host during the sample.
+    #[getter]
+    fn disk_io(&self) -> PyResult<Option<(u64, u64)>> {
+        Ok(self.0.extra.disk_io.map(|io| (io.read, io.write)))
+    }
+
+    /// `(received, sent)` bytes over all network interfaces but loopback during the sample.
+    #[getter]
+    fn network_io(&self) -> PyResult<Option<(u64, u64)>> {
+        Ok(self.0.extra.network_io.map(|io| (io.rx, io.tx)))
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("timestamp", self.0.timestamp)?;
+        dict.set_item("wall_time", wall_time(&self.0))?;
+        dict.set_item("duration", self.0.duration)?;
+        dict.set_item("cpu_energy", self.0.cpu_energy)?;
+        dict.set_item("gpu_energy", self.0.gpu_energy)?;
+        dict.set_item("ane_energy", self.0.ane_energy)?;
+        dict.set_item("display_energy", self.0.display_energy)?;
+        dict.set_item("package_energy", self.0.package_energy)?;
+        dict.set_item("system_power", self.0.system_power)?;
+        dict.set_item("dc_in_power", self.0.dc_in_power)?;
+        dict.set_item("dies", die_tuples(&self.0.dies))?;
+        dict.set_item("thermal_pressure", self.thermal_pressure()?)?;
+        dict.set_item("throttled", self.0.is_throttled())?;
+        dict.set_item("cpu_power", self.0.cpu_power())?;
+        dict.set_item("gpu_power", self.0.gpu_power())?;
+        dict.set_item("ane_power", self.0.ane_power())?;
+        Ok(dict)
+    }
+}
+
+/// Iterator over the samples of the active session, ends when the session stops.
+#[pyclass]
+pub struct SampleStream {
+    receiver: Receiver<EnergySample>,
+}
+
+#[pymethods]
+impl SampleStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<PyEnergySample> {
+        let py = slf.py();
+        let receiver = &mut slf.receiver;
+        py.allow_threads(move || receiver.recv().ok().map(PyEnergySample))
+    }
+}
+
+#[pyclass]
+pub struct PyPowerProfile {
+    profile: PowerProfile,
+    samples: Vec<EnergySample>,
+    regions: Vec<Region>,
+}
+
+impl PyPowerProfile {
+    fn new(profile: PowerProfile, samples: Vec<EnergySample>, regions: Vec<Region>) -> Self {
+        PyPowerProfile {
+            profile,
+            samples,
+            regions,
+        }
+    }
+}
+
+/// Calls `callback(sample)` from the sampling thread, errors are reported as unraisable.
+fn call_with_sample(callback: &PyObject, sample: &EnergySample) {
+    Python::with_gil(|py| {
+        if let Err(e) = callback.call1(py, (PyEnergySample(sample.clone()),)) {
+            e.write_unraisable_bound(py, Some(callback.bind(py)));
+        }
+    });
+}
+
+fn peak(peak: PeakPower) -> (u64, u64) {
+    (peak.power, peak.timestamp)
+}
+
+fn die_tuples(dies: &[DieEnergy]) -> Vec<(u128, u128, u128)> {
+    dies.iter().map(|die| (die.cpu, die.gpu, die.ane)).collect()
+}
+
+fn pressure_name(pressure: ThermalPressure) -> &'static str {
+    match pressure {
+        ThermalPressure::Nominal => "nominal",
+        ThermalPressure::Moderate => "moderate",
+        ThermalPressure::Heavy => "heavy",
+        ThermalPressure::Trapping => "trapping",
+        ThermalPressure::Sleeping => "sleeping",
+    }
+}
+
+fn wall_time(sample: &EnergySample) -> Option<f64> {
+    let since_epoch = sample
+        .wall_time?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?;
+    Some(since_epoch.as_secs_f64())
+}
+
+/// Builds a `pandas.DataFrame` with one row per sample.
+fn samples_to_dataframe(py: Python<'_>, samples: &[EnergySample]) -> PyResult<PyObject> {
+    let pandas = py.import_bound("pandas")?;
+    let columns = PyDict::new_bound(py);
+    columns.set_item(
+        "timestamp",
+        samples.iter().map(|s| s.timestamp).collect::<Vec<_>>(),
+    )?;
+    columns.set_item(
+        "wall_time",
+        samples.iter().map(wall_time).collect::<Vec<_>>(),
+    )?;
+    columns.set_item(
+        "duration",
+        samples.iter().map(|s| s.duration).collect::<Vec<_>>(),
+    )?;
+    columns.set_item(
+        "cpu_energy",
+        samples.iter().map(|s| s.cpu_energy).collect::<Vec<_>>(),
+    )?;
+    columns.set_item(
+        "gpu_energy",
+        samples.iter().map(|s| s.gpu_energy).collect::<Vec<_>>(),
+    )?;
+    columns.set_item(
+        "ane_energy",
+        samples.iter().map(|s| s.ane_energy).collect::<Vec<_>>(),
+    )?;
+    columns.set_item(
+        "cpu_power",
+        samples.iter().map(|s| s.cpu_power()).collect::<Vec<_>>(),
+    )?;
+    columns.set_item(
+        "gpu_power",
+        samples.iter().map(|s| s.gpu_power()).collect::<Vec<_>>(),
+    )?;
+    columns.set_item(
+        "ane_power",
+        samples.iter().map(|s| s.ane_power()).collect::<Vec<_>>(),
+    )?;
+    Ok(pandas.call_method1("DataFrame", (columns,))?.unbind())
+}
+
+/// `Units` from "mJ", "J" or "Wh" and "mW" or "W".
+fn parse_units(energy_unit: &str, power_unit: &str) -> PyResult<Units> {
+    let value_error = |e: wattkit::UnknownUnit| PyValueError::new_err(e.to_string());
+    Ok(Units {
+        energy: energy_unit.parse().map_err(value_error)?,
+        power: power_unit.parse().map_err(value_error)?,
+    })
+}
+
+#[pymethods]
+impl PyPowerProfile {
+    fn __str__(&self) -> String {
+        self.profile.to_string()
+    }
+
     #[getter]
     fn total_cpu_energy(&self) -> PyResult<u128> {
-        Ok(self.0.total_cpu_energy)
+        Ok(self.profile.total_cpu_energy)
     }
 
     #[getter]
     fn total_gpu_energy(&self) -> PyResult<u128> {
-        Ok(self.0.total_gpu_energy)
+        Ok(self.profile.total_gpu_energy)
     }
 
     #[getter]
     fn total_ane_energy(&self) -> PyResult<u128> {
-        Ok(self.0.total_ane_energy)
+        Ok(self.profile.total_ane_energy)
     }
 
     #[getter]
     fn average_cpu_power(&self) -> PyResult<u64> {
-        Ok(self.0.average_cpu_power)
+        Ok(self.profile.average_cpu_power)
     }
 
     #[getter]
     fn average_gpu_power(&self) -> PyResult<u64> {
-        Ok(self.0.average_gpu_power)
+        Ok(self.profile.average_gpu_power)
     }
 
     #[getter]
     fn average_ane_power(&self) -> PyResult<u64> {
-        Ok(self.0.average_ane_power)
+        Ok(self.profile.average_ane_power)
     }
 
     #[getter]
     fn total_energy(&self) -> PyResult<u128> {
-        Ok(self.0.total_energy)
+        Ok(self.profile.total_energy)
     }
 
     #[getter]
     fn average_power(&self) -> PyResult<u64> {
-        Ok(self.0.average_power)
+        Ok(self.profile.average_power)
     }
 
     #[getter]
     fn total_duration(&self) -> PyResult<u64> {
-        Ok(self.0.total_duration)
+        Ok(self.profile.total_duration)
+    }
+
+    /// Whether the backend measures CPU energy, it is always zero otherwise.
+    #[getter]
+    fn cpu_available(&self) -> PyResult<bool> {
+        Ok(self.profile.cpu_available)
+    }
+
+    #[getter]
+    fn gpu_available(&self) -> PyResult<bool> {
+        Ok(self.profile.gpu_available)
+    }
+
+    #[getter]
+    fn ane_available(&self) -> PyResult<bool> {
+        Ok(self.profile.ane_available)
+    }
+
+    /// `(power_mw, timestamp_ms)` of the sample with the highest CPU power.
+    #[getter]
+    fn peak_cpu_power(&self) -> PyResult<(u64, u64)> {
+        Ok(peak(self.profile.peak_cpu_power))
+    }
+
+    /// `(power_mw, timestamp_ms)` of the sample with the highest GPU power.
+    #[getter]
+    fn peak_gpu_power(&self) -> PyResult<(u64, u64)> {
+        Ok(peak(self.profile.peak_gpu_power))
+    }
+
+    /// `(power_mw, timestamp_ms)` of the sample with the highest ANE power.
+    #[getter]
+    fn peak_ane_power(&self) -> PyResult<(u64, u64)> {
+        Ok(peak(self.profile.peak_ane_power))
+    }
+
+    /// `(cpu, gpu, ane)` fractions of the time each unit drew more than `threshold_w` watts.
+    fn duty_cycle(&self, threshold_w: f64) -> (f64, f64, f64) {
+        let duty = self.profile.duty_cycle(threshold_w);
+        (duty.cpu, duty.gpu, duty.ane)
+    }
+
+    #[getter]
+    fn samples(&self) -> PyResult<Vec<PyEnergySample>> {
+        Ok(self.samples.iter().cloned().map(PyEnergySample).collect())
+    }
+
+    /// Metadata of the session, see `Profiler.set_metadata`.
+    #[getter]
+    fn metadata(&self) -> PyResult<BTreeMap<String, String>> {
+        Ok(self.profile.metadata.clone())
+    }
+
+    /// Whether the machine switched between AC and battery during the session, such a
+    /// profile doesn't compare with others.
+    #[getter]
+    fn power_source_changed(&self) -> PyResult<bool> {
+        Ok(self.profile.power_source_changed)
+    }
+
+    /// Average fraction of the host's CPU time spent busy, `None` where it can't be read.
+    #[getter]
+    fn cpu_utilization(&self) -> PyResult<Option<f64>> {
+        Ok(self.profile.cpu_utilization)
+    }
+
+    /// Millijoules drawn by the display, not part of `total_energy`. `None` on machines
+    /// that don't report it.
+    #[getter]
+    fn display_energy(&self) -> PyResult<Option<u128>> {
+        Ok(self.profile.display_energy)
+    }
+
+    /// Millijoules drawn by the whole SoC, DRAM and display included, unlike
+    /// `total_energy`. `None` where the backend doesn't report it.
+    #[getter]
+    fn package_energy(&self) -> PyResult<Option<u128>> {
+        Ok(self.profile.package_energy)
+    }
+
+    /// Milliwatts.
+    #[getter]
+    fn average_package_power(&self) -> PyResult<Option<u64>> {
+        Ok(self.profile.average_package_power)
+    }
+
+    /// Milliwatts drawn by the whole machine, from the SMC. `None` where it can't be read.
+    #[getter]
+    fn average_system_power(&self) -> PyResult<Option<u64>> {
+        Ok(self.profile.average_system_power)
+    }
+
+    /// Milliwatts drawn through the DC input, zero on battery.
+    #[getter]
+    fn average_dc_in_power(&self) -> PyResult<Option<u64>> {
+        Ok(self.profile.average_dc_in_power)
+    }
+
+    /// `(cpu, gpu, ane)` millijoules of each die of an M1 or M2 Ultra, summing to the
+    /// unit totals. Empty on single-die chips.
+    #[getter]
+    fn dies(&self) -> PyResult<Vec<(u128, u128, u128)>> {
+        Ok(die_tuples(&self.profile.dies))
+    }
+
+    /// `(start_ms, duration_ms)` of every stretch spent near the baseline power, see
+    /// `Profiler.detect_idle`.
+    #[getter]
+    fn idle_periods(&self) -> PyResult<Vec<(u64, u64)>> {
+        Ok(self
+            .profile
+            .idle_periods
+            .iter()
+            .map(|p| (p.start, p.duration))
+            .collect())
+    }
+
+    /// Milliseconds.
+    #[getter]
+    fn idle_duration(&self) -> PyResult<u64> {
+        Ok(self.profile.idle_duration)
+    }
+
+    /// Milliwatts drawn outside the idle periods, `None` without idle detection.
+    #[getter]
+    fn average_active_power(&self) -> PyResult<Option<u64>> {
+        Ok(self.profile.average_active_power)
+    }
+
+    /// `(start_ms, duration_ms, pressure)` of every run of throttled samples, throttled
+    /// runs don't compare with cool ones.
+    #[getter]
+    fn throttled_periods(&self) -> PyResult<Vec<(u64, u64, &'static str)>> {
+        Ok(self
+            .profile
+            .throttled_periods
+            .iter()
+            .map(|p| (p.start, p.duration, pressure_name(p.pressure)))
+            .collect())
+    }
+
+    /// Milliseconds.
+    #[getter]
+    fn throttled_duration(&self) -> PyResult<u64> {
+        Ok(self.profile.throttled_duration)
+    }
+
+    /// Profiles of each named region, regions sharing a name are combined and time they
+    /// overlap counts once.
+    #[getter]
+    fn regions(&self) -> PyResult<HashMap<String, PyPowerProfile>> {
+        let mut regions = HashMap::new();
+        for region in &self.regions {
+            regions.entry(region.name.clone()).or_insert_with(|| {
+                let matching = self.regions.iter().filter(|r| r.name == region.name);
+                PyPowerProfile::new(
+                    PowerProfile::for_regions(&self.samples, matching.clone())
+                        .with_capabilities(self.profile.capabilities()),
+                    Region::clip_all(&self.samples, matching),
+                    Vec::new(),
+                )
+            });
+        }
+        Ok(regions)
+    }
+
+    /// `(name, start_ms, end_ms)` for every region, in the order they were opened.
+    #[getter]
+    fn region_spans(&self) -> PyResult<Vec<(String, u64, u64)>> {
+        Ok(self
+            .regions
+            .iter()
+            .map(|r| {
+                (
+                    r.name.clone(),
+                    r.start,
+                    r.end.unwrap_or(self.profile.total_duration),
+                )
+            })
+            .collect())
+    }
+
+    /// Stacked power-over-time chart, returns the matplotlib figure.
+    #[pyo3(signature = (ax=None, regions=None))]
+    fn plot(
+        slf: &Bound<'_, Self>,
+        ax: Option<PyObject>,
+        regions: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let plotting = slf.py().import_bound("wattkit.plotting")?;
+        Ok(plotting
+            .call_method1("plot_profile", (slf, ax, regions))?
+            .unbind())
+    }
+
+    /// Profile totals plus the raw samples, suitable for `json.dumps`.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("total_energy", self.profile.total_energy)?;
+        dict.set_item("average_power", self.profile.average_power)?;
+        dict.set_item("total_duration", self.profile.total_duration)?;
+        dict.set_item("total_cpu_energy", self.profile.total_cpu_energy)?;
+        dict.set_item("total_gpu_energy", self.profile.total_gpu_energy)?;
+        dict.set_item("total_ane_energy", self.profile.total_ane_energy)?;
+        dict.set_item("average_cpu_power", self.profile.average_cpu_power)?;
+        dict.set_item("average_gpu_power", self.profile.average_gpu_power)?;
+        dict.set_item("average_ane_power", self.profile.average_ane_power)?;
+        dict.set_item("cpu_available", self.profile.cpu_available)?;
+        dict.set_item("gpu_available", self.profile.gpu_available)?;
+        dict.set_item("ane_available", self.profile.ane_available)?;
+        dict.set_item("peak_cpu_power", peak(self.profile.peak_cpu_power))?;
+        dict.set_item("peak_gpu_power", peak(self.profile.peak_gpu_power))?;
+        dict.set_item("peak_ane_power", peak(self.profile.peak_ane_power))?;
+        let samples = self
+            .samples
+            .iter()
+            .map(|s| PyEnergySample(s.clone()).to_dict(py))
+            .collect::<PyResult<Vec<_>>>()?;
+        dict.set_item("samples", samples)?;
+        dict.set_item("regions", self.region_spans()?)?;
+        dict.set_item("metadata", self.metadata()?)?;
+        dict.set_item("power_source_changed", self.profile.power_source_changed)?;
+        dict.set_item("cpu_utilization", self.profile.cpu_utilization)?;
+        dict.set_item("display_energy", self.profile.display_energy)?;
+        dict.set_item("package_energy", self.profile.package_energy)?;
+        dict.set_item("average_package_power", self.profile.average_package_power)?;
+        dict.set_item("average_system_power", self.profile.average_system_power)?;
+        dict.set_item("average_dc_in_power", self.profile.average_dc_in_power)?;
+        dict.set_item("dies", die_tuples(&self.profile.dies))?;
+        dict.set_item("idle_periods", self.idle_periods()?)?;
+        dict.set_item("idle_duration", self.profile.idle_duration)?;
+        dict.set_item("average_active_power", self.profile.average_active_power)?;
+        dict.set_item("throttled_periods", self.throttled_periods()?)?;
+        dict.set_item("throttled_duration", self.profile.throttled_duration)?;
+        Ok(dict)
+    }
+
+    /// Every sampled channel as nested dicts, group -> subgroup -> channel -> `{"unit":
+    /// str, "points": [(ms, value), ...]}`, the same layout for every backend.
+    fn channel_tree<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let groups = PyDict::new_bound(py);
+        for (group, subgroups) in ChannelTree::from_samples(&self.samples).groups {
+            let subgroups_dict = PyDict::new_bound(py);
+            for (subgroup, channels) in subgroups {
+                let channels_dict = PyDict::new_bound(py);
+                for (channel, series) in channels {
+                    let series_dict = PyDict::new_bound(py);
+                    series_dict.set_item("unit", series.unit)?;
+                    series_dict.set_item("points", series.points)?;
+                    channels_dict.set_item(channel, series_dict)?;
+                }
+                subgroups_dict.set_item(subgroup, channels_dict)?;
+            }
+            groups.set_item(group, subgroups_dict)?;
+        }
+        Ok(groups)
+    }
+
+    /// Per-sample time series as a `pandas.DataFrame` (ms, mJ, mW), the metadata is in
+    /// its `attrs`.
+    fn to_dataframe(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let frame = samples_to_dataframe(py, &self.samples)?;
+        for (key, value) in &self.profile.metadata {
+            frame
+                .getattr(py, "attrs")?
+                .call_method1(py, "__setitem__", (key, value))?;
+        }
+        Ok(frame)
+    }
+
+    /// Markdown table of energy, average and peak power and the change from `baseline`,
+    /// e.g. for a pull request comment. Energy and power are in `energy_unit` and
+    /// `power_unit`.
+    #[pyo3(signature = (baseline=None, energy_unit="J", power_unit="W"))]
+    fn to_markdown(
+        &self,
+        baseline: Option<PyRef<'_, PyPowerProfile>>,
+        energy_unit: &str,
+        power_unit: &str,
+    ) -> PyResult<String> {
+        let units = parse_units(energy_unit, power_unit)?;
+        Ok(self
+            .profile
+            .to_markdown_with(baseline.as_ref().map(|b| &b.profile), units))
+    }
+
+    /// The profile in the Prometheus text format, with "wattkit" or scaphandre's
+    /// "scaphandre" metric names and labels.
+    #[pyo3(signature = (names="wattkit"))]
+    fn to_prometheus(&self, names: &str) -> PyResult<String> {
+        let names = names
+            .parse::<wattkit::MetricNames>()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(self.profile.to_prometheus(names))
+    }
+
+    /// `total_energy` in `unit`, "mJ", "J" or "Wh".
+    #[pyo3(signature = (unit="mJ"))]
+    fn energy(&self, unit: &str) -> PyResult<f64> {
+        let unit = unit
+            .parse::<wattkit::EnergyUnit>()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(unit.from_millijoules(self.profile.total_energy as f64))
+    }
+
+    /// `average_power` in `unit`, "mW" or "W".
+    #[pyo3(signature = (unit="mW"))]
+    fn power(&self, unit: &str) -> PyResult<f64> {
+        let unit = unit
+            .parse::<wattkit::PowerUnit>()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(unit.from_milliwatts(self.profile.average_power as f64))
+    }
+
+    /// The profile summary with energy and power in the given units.
+    #[pyo3(signature = (energy_unit="mJ", power_unit="mW"))]
+    fn format(&self, energy_unit: &str, power_unit: &str) -> PyResult<String> {
+        let units = parse_units(energy_unit, power_unit)?;
+        Ok(self.profile.display(units).to_string())
+    }
+
+    /// Profile of this session followed by `other`'s, see `PowerProfile + PowerProfile`.
+    /// The regions are this profile's.
+    fn __add__(&self, other: PyRef<'_, PyPowerProfile>) -> PyPowerProfile {
+        let offset = self.profile.total_duration;
+        let samples = self
+            .samples
+            .iter()
+            .cloned()
+            .chain(other.samples.iter().map(|s| EnergySample {
+                timestamp: s.timestamp + offset,
+                ..s.clone()
+            }))
+            .collect();
+        PyPowerProfile::new(
+            self.profile.clone() + other.profile.clone(),
+            samples,
+            self.regions.clone(),
+        )
+    }
+
+    /// Energy and power above the `other` baseline, durations and samples stay these.
+    fn __sub__(&self, other: PyRef<'_, PyPowerProfile>) -> PyPowerProfile {
+        PyPowerProfile::new(
+            self.profile.clone() - other.profile.clone(),
+            self.samples.clone(),
+            self.regions.clone(),
+        )
+    }
+
+    fn __eq__(&self, other: PyRef<'_, PyPowerProfile>) -> bool {
+        self.profile == other.profile
+    }
+
+    /// Whether energy, average power and duration are each within `relative` of `other`'s.
+    #[pyo3(signature = (other, relative=0.05))]
+    fn approx_eq(&self, other: PyRef<'_, PyPowerProfile>, relative: f64) -> bool {
+        self.profile.approx_eq(&other.profile, relative)
+    }
+
+    /// Highest total power of any one sample in milliwatts.
+    #[getter]
+    fn peak_power(&self) -> u64 {
+        self.profile.peak_power()
+    }
+
+    /// Mean and standard deviation of each quantity across the profiles of repeated runs.
+    #[staticmethod]
+    fn merge(profiles: Vec<PyRef<'_, PyPowerProfile>>) -> PyAggregateProfile {
+        let profiles: Vec<PowerProfile> = profiles.iter().map(|p| p.profile.clone()).collect();
+        PyAggregateProfile(PowerProfile::merge(&profiles))
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct PySummary(Summary);
+
+#[pymethods]
+impl PySummary {
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    #[getter]
+    fn mean(&self) -> f64 {
+        self.0.mean
+    }
+
+    /// Sample standard deviation, zero for fewer than two runs.
+    #[getter]
+    fn stddev(&self) -> f64 {
+        self.0.stddev
+    }
+
+    #[getter]
+    fn min(&self) -> f64 {
+        self.0.min
+    }
+
+    #[getter]
+    fn max(&self) -> f64 {
+        self.0.max
+    }
+
+    /// Standard deviation relative to the mean, 0.05 is 5%.
+    fn relative_stddev(&self) -> f64 {
+        self.0.relative_stddev()
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("mean", self.0.mean)?;
+        dict.set_item("stddev", self.0.stddev)?;
+        dict.set_item("min", self.0.min)?;
+        dict.set_item("max", self.0.max)?;
+        Ok(dict)
+    }
+}
+
+/// Profiles of several runs summarized, energies in mJ, powers in mW, durations in ms.
+#[pyclass]
+pub struct PyAggregateProfile(AggregateProfile);
+
+#[pymethods]
+impl PyAggregateProfile {
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    #[getter]
+    fn runs(&self) -> usize {
+        self.0.runs
+    }
+
+    #[getter]
+    fn total_energy(&self) -> PySummary {
+        PySummary(self.0.total_energy)
+    }
+
+    #[getter]
+    fn total_cpu_energy(&self) -> PySummary {
+        PySummary(self.0.total_cpu_energy)
+    }
+
+    #[getter]
+    fn total_gpu_energy(&self) -> PySummary {
+        PySummary(self.0.total_gpu_energy)
+    }
+
+    #[getter]
+    fn total_ane_energy(&self) -> PySummary {
+        PySummary(self.0.total_ane_energy)
+    }
+
+    #[getter]
+    fn average_power(&self) -> PySummary {
+        PySummary(self.0.average_power)
+    }
+
+    #[getter]
+    fn total_duration(&self) -> PySummary {
+        PySummary(self.0.total_duration)
+    }
+
+    /// Metadata entries every run agrees on.
+    #[getter]
+    fn metadata(&self) -> BTreeMap<String, String> {
+        self.0.metadata.clone()
+    }
+
+    /// Every summary as a nested dict, suitable for `json.dumps`.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("runs", self.0.runs)?;
+        dict.set_item("total_energy", self.total_energy().to_dict(py)?)?;
+        dict.set_item("total_cpu_energy", self.total_cpu_energy().to_dict(py)?)?;
+        dict.set_item("total_gpu_energy", self.total_gpu_energy().to_dict(py)?)?;
+        dict.set_item("total_ane_energy", self.total_ane_energy().to_dict(py)?)?;
+        dict.set_item("average_power", self.average_power().to_dict(py)?)?;
+        dict.set_item("total_duration", self.total_duration().to_dict(py)?)?;
+        dict.set_item("metadata", self.metadata())?;
+        Ok(dict)
+    }
+}
+
+impl Profiler {
+    fn with_sampler(
+        sampler: DynSampler,
+        sample_duration: Duration,
+        num_samples: usize,
+        suppress_exceptions: bool,
+        attribute_children: bool,
+    ) -> Self {
+        Profiler {
+            sampler,
+            sample_duration,
+            num_samples,
+            adaptive: None,
+            suppress_exceptions,
+            callback: None,
+            streams: Arc::new(Mutex::new(Vec::new())),
+            attribute_children,
+            attribution: Arc::new(Mutex::new(None)),
+            sampling_parent: None,
+            sessions: Vec::new(),
+            session: 0,
+        }
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        // Stopping joins the sampling thread, which may be waiting on the GIL to run the
+        // callback, so it can't be left to the sampler's own drop.
+        let sampler = &mut self.sampler;
+        Python::with_gil(|py| {
+            py.allow_threads(move || {
+                let _ = sampler.stop();
+            })
+        });
     }
 }
 
 #[pymethods]
 impl Profiler {
+    /// Takes `num_samples` samples every `duration`, a `timedelta` or seconds.
+    /// `suppress_exceptions=True` swallows exceptions raised inside the `with` block.
+    /// `attribute_children=True` splits CPU energy across this process and its children
+    /// by CPU time, see `energy_by_pid`. `metadata` is attached to every profile, see
+    /// `set_metadata`. `memory_bandwidth=True` also reads memory traffic into every
+    /// sample where IOReport has it. `channels` limits the IOReport subscription to energy
+    /// and any of "residency" and "memory_bandwidth". `sample_duration` in milliseconds is
+    /// deprecated.
     #[new]
-    fn new(sample_duration: u64, num_samples: usize) -> PyResult<Self> {
-        Ok(Profiler {
-            sampler: StartStopSampler::new(),
+    #[pyo3(signature = (duration=None, num_samples=1, suppress_exceptions=false, attribute_children=false, *, metadata=None, memory_bandwidth=false, channels=None, sample_duration=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        duration: Option<&Bound<'_, PyAny>>,
+        num_samples: usize,
+        suppress_exceptions: bool,
+        attribute_children: bool,
+        metadata: Option<&Bound<'_, PyDict>>,
+        memory_bandwidth: bool,
+        channels: Option<Vec<String>>,
+        sample_duration: Option<u64>,
+    ) -> PyResult<Self> {
+        let sample_duration = self::sample_duration(py, duration, sample_duration)?;
+        let channels = channel_set(channels, memory_bandwidth)?;
+        let mut sampler = StartStopSampler::with_backend(move || {
+            #[cfg(target_os = "macos")]
+            if let Some(channels) = channels {
+                let backend = wattkit::backend::ioreport::IOReportBackend::with_channels(channels)?;
+                return Ok(Box::new(backend) as Box<dyn PowerBackend>);
+            }
+            #[cfg(not(target_os = "macos"))]
+            let _ = channels;
+            Ok(Box::new(open_default()?) as Box<dyn PowerBackend>)
+        });
+        for (key, value) in metadata.into_iter().flatten() {
+            sampler
+                .config_mut()
+                .metadata
+                .insert(key.str()?.to_string(), value.str()?.to_string());
+        }
+        Ok(Profiler::with_sampler(
+            sampler,
             sample_duration,
             num_samples,
+            suppress_exceptions,
+            attribute_children,
+        ))
+    }
+
+    /// A profiler reading synthetic samples at a constant power (mW) instead of the
+    /// hardware counters, for testing code that uses wattkit on any machine.
+    #[staticmethod]
+    #[pyo3(signature = (cpu_power=0.0, gpu_power=0.0, ane_power=0.0, duration=None, num_samples=1, *, sample_duration=None))]
+    fn mock(
+        py: Python<'_>,
+        cpu_power: f64,
+        gpu_power: f64,
+        ane_power: f64,
+        duration: Option<&Bound<'_, PyAny>>,
+        num_samples: usize,
+        sample_duration: Option<u64>,
+    ) -> PyResult<Self> {
+        let sample_duration = match (duration, sample_duration) {
+            (None, None) => Duration::from_millis(100),
+            _ => self::sample_duration(py, duration, sample_duration)?,
+        };
+        let sampler = StartStopSampler::with_backend(move || {
+            let backend = MockBackend::constant(cpu_power, gpu_power, ane_power);
+            Ok(Box::new(backend) as Box<dyn PowerBackend>)
+        });
+        Ok(Profiler::with_sampler(
+            sampler,
+            sample_duration,
+            num_samples,
+            false,
+            false,
+        ))
+    }
+
+    /// A profiler whose sample interval follows power: it starts at `min_interval`,
+    /// doubles up to `max_interval` while total power stays within `threshold` (relative)
+    /// of the previous sample and halves as soon as it moves further. Intervals are
+    /// `timedelta`s or seconds, 0.1 and 2 by default.
+    #[staticmethod]
+    #[pyo3(signature = (min_interval=None, max_interval=None, threshold=0.1, suppress_exceptions=false))]
+    fn adaptive(
+        min_interval: Option<&Bound<'_, PyAny>>,
+        max_interval: Option<&Bound<'_, PyAny>>,
+        threshold: f64,
+        suppress_exceptions: bool,
+    ) -> PyResult<Self> {
+        let mut rate = AdaptiveRate {
+            threshold,
+            ..Default::default()
+        };
+        if let Some(min_interval) = min_interval {
+            rate.min_interval = extract_duration(min_interval)?;
+        }
+        if let Some(max_interval) = max_interval {
+            rate.max_interval = extract_duration(max_interval)?;
+        }
+        let sampler = StartStopSampler::with_backend(|| {
+            Ok(Box::new(open_default()?) as Box<dyn PowerBackend>)
+        });
+        let mut profiler =
+            Profiler::with_sampler(sampler, Duration::ZERO, 1, suppress_exceptions, false);
+        profiler.adaptive = Some(rate);
+        Ok(profiler)
+    }
+
+    /// Register `callback(sample)` to be called from the sampling thread for every sample.
+    /// Pass `None` to remove it. Takes effect from the next session.
+    #[pyo3(signature = (callback))]
+    fn on_sample(&mut self, callback: Option<PyObject>) {
+        self.callback = callback;
+    }
+
+    /// Attach `key: str(value)` to the profiles of this and later sessions, e.g. a git sha,
+    /// model name or batch size, so exported results can be grouped by them.
+    fn set_metadata(&mut self, key: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.sampler
+            .config_mut()
+            .metadata
+            .insert(key.to_string(), value.str()?.to_string());
+        Ok(())
+    }
+
+    /// Leave the first `samples`, or the first `duration` (a `timedelta` or seconds), of
+    /// every session out of its profile. `samples` on the profile still has them.
+    #[pyo3(signature = (samples=None, duration=None))]
+    fn discard_warmup(
+        &mut self,
+        samples: Option<usize>,
+        duration: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<()> {
+        let warmup = match (samples, duration) {
+            (Some(samples), None) => Warmup::Samples(samples),
+            (None, Some(duration)) => Warmup::Duration(extract_duration(duration)?),
+            (None, None) => Warmup::None,
+            (Some(_), Some(_)) => {
+                return Err(PyTypeError::new_err(
+                    "pass either samples or duration, not both",
+                ))
+            }
+        };
+        self.sampler.config_mut().warmup = warmup;
+        Ok(())
+    }
+
+    /// Leave samples whose total power is more than `threshold` median absolute deviations
+    /// from the session's median out of its profile, `None` keeps every sample.
+    /// `samples` on the profile still has them.
+    #[pyo3(signature = (threshold=Some(3.5)))]
+    fn reject_outliers(&mut self, threshold: Option<f64>) {
+        let filter = match threshold {
+            Some(threshold) => OutlierFilter::Mad { threshold },
+            None => OutlierFilter::None,
+        };
+        self.sampler.config_mut().outliers = filter;
+    }
+
+    /// Annotate profiles with the stretches of at least `min_duration` seconds spent
+    /// within `margin` watts of the session's baseline power, and `exclude` them from
+    /// the totals and averages if set. `margin=None` turns detection off.
+    #[pyo3(signature = (margin=Some(0.5), min_duration=None, exclude=false))]
+    fn detect_idle(
+        &mut self,
+        margin: Option<f64>,
+        min_duration: Option<&Bound<'_, PyAny>>,
+        exclude: bool,
+    ) -> PyResult<()> {
+        let detection = match margin {
+            Some(margin_w) => Some(IdleDetection {
+                margin_w,
+                min_duration: match min_duration {
+                    Some(duration) => extract_duration(duration)?,
+                    None => IdleDetection::default().min_duration,
+                },
+                exclude,
+            }),
+            None => None,
+        };
+        self.sampler.config_mut().idle = detection;
+        Ok(())
+    }
+
+    /// Run the sampling thread of later sessions at a QoS class, one of "user_interactive",
+    /// "user_initiated", "default", "utility" or "background". Only has an effect on macOS.
+    fn set_qos(&mut self, qos: &str) -> PyResult<()> {
+        let qos = match qos {
+            "user_interactive" => QosClass::UserInteractive,
+            "user_initiated" => QosClass::UserInitiated,
+            "default" => QosClass::Default,
+            "utility" => QosClass::Utility,
+            "background" => QosClass::Background,
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown QoS class {other:?}"
+                )))
+            }
+        };
+        self.sampler.config_mut().qos = Some(qos);
+        Ok(())
+    }
+
+    /// Read host metrics of later sessions less often than energy, each a
+    /// `datetime.timedelta` or seconds, None reads it with every sample. Between reads
+    /// samples carry the last value, disk and network I/O only come with the reads.
+    #[pyo3(signature = (thermal=None, cpu_load=None, io=None, system_power=None, power_source=None))]
+    fn set_metric_rates(
+        &mut self,
+        thermal: Option<&Bound<'_, PyAny>>,
+        cpu_load: Option<&Bound<'_, PyAny>>,
+        io: Option<&Bound<'_, PyAny>>,
+        system_power: Option<&Bound<'_, PyAny>>,
+        power_source: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<()> {
+        let rate = |value: Option<&Bound<'_, PyAny>>| value.map(extract_duration).transpose();
+        self.sampler.config_mut().metric_rates = MetricRates {
+            thermal: rate(thermal)?,
+            cpu_load: rate(cpu_load)?,
+            io: rate(io)?,
+            system_power: rate(system_power)?,
+            power_source: rate(power_source)?,
+        };
+        Ok(())
+    }
+
+    /// Register `callback(sample)` to be called from the sampling thread with the sample at
+    /// which total power rises above `watts`, and again after each fall back below it.
+    fn on_power_above(&mut self, watts: f64, callback: PyObject) {
+        self.sampler
+            .on_power_above(watts, Box::new(move |s| call_with_sample(&callback, s)));
+    }
+
+    /// Register `callback(sample)` to be called from the sampling thread, once per session,
+    /// with the sample at which the session's total energy exceeds `joules`.
+    fn on_energy_budget_exceeded(&mut self, joules: f64, callback: PyObject) {
+        self.sampler
+            .on_energy_budget_exceeded(joules, Box::new(move |s| call_with_sample(&callback, s)));
+    }
+
+    /// Returns an iterator yielding samples of the active session as they arrive.
+    fn stream(&self) -> PyResult<SampleStream> {
+        if !self.sampler.is_sampling() {
+            return Err(to_py_err(SamplerError::NoSamplingInProgress));
+        }
+        let (tx, rx) = channel();
+        self.streams.lock().unwrap().push(tx);
+        Ok(SampleStream { receiver: rx })
+    }
+
+    /// Starts a sampling session, the GIL is released while the subscription is set up.
+    /// Inside a worker of a process that is already sampling, the session is passive:
+    /// the parent's measurement covers this process.
+    fn start(&mut self, py: Python<'_>) -> PyResult<()> {
+        self.sampling_parent = sampling_parent(py)?;
+        if self.sampling_parent.is_some() {
+            return Ok(());
+        }
+
+        let duration = self.sample_duration;
+        let num_samples = self.num_samples;
+        let adaptive = self.adaptive;
+        let callback = self.callback.as_ref().map(|c| c.clone_ref(py));
+        let streams = self.streams.clone();
+        *self.attribution.lock().unwrap() = self
+            .attribute_children
+            .then(|| ProcessAttribution::new(std::process::id()));
+        let attribution = self.attribution.clone();
+        let on_sample: SampleCallback = Box::new(move |sample| {
+            if let Some(attribution) = attribution.lock().unwrap().as_mut() {
+                attribution.record(sample);
+            }
+            streams
+                .lock()
+                .unwrap()
+                .retain(|tx| tx.send(sample.clone()).is_ok());
+            if let Some(callback) = &callback {
+                call_with_sample(callback, sample);
+            }
+        });
+        let sampler = &mut self.sampler;
+        py.allow_threads(move || match adaptive {
+            Some(rate) => sampler.start_adaptive_with_callback(rate, on_sample),
+            None => sampler.start_with_callback(duration, num_samples, on_sample),
         })
+        .map_err(to_py_err)?;
+        self.session += 1;
+
+        let environ = py.import_bound("os")?.getattr("environ")?;
+        environ.set_item(SAMPLER_PID_VAR, std::process::id().to_string())?;
+        Ok(())
+    }
+
+    /// Stops the session, the GIL is released while the sampling thread is drained and joined.
+    fn stop(&mut self, py: Python<'_>) -> PyResult<()> {
+        if self.sampling_parent.is_some() {
+            return Ok(());
+        }
+
+        // The sampling thread may be waiting on the GIL to run the callback.
+        let sampler = &mut self.sampler;
+        let stopped = py.allow_threads(move || sampler.stop());
+        self.streams.lock().unwrap().clear();
+
+        let environ = py.import_bound("os")?.getattr("environ")?;
+        environ.call_method1("pop", (SAMPLER_PID_VAR, py.None()))?;
+        stopped.map_err(to_py_err)?;
+        if let Ok(profile) = self.get_profile() {
+            self.sessions.push(Py::new(py, profile)?);
+        }
+        Ok(())
+    }
+
+    /// Profile of every finished session of this profiler, oldest first, so one profiler
+    /// can be entered repeatedly. `profile` is the last one.
+    #[getter]
+    fn sessions(&self, py: Python<'_>) -> Vec<Py<PyPowerProfile>> {
+        self.sessions.iter().map(|p| p.clone_ref(py)).collect()
+    }
+
+    /// CPU energy in millijoules attributed to each PID of the process tree,
+    /// empty unless created with `attribute_children=True`.
+    #[getter]
+    fn energy_by_pid(&self) -> HashMap<u32, f64> {
+        self.attribution
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|a| a.energy_by_pid().clone())
+            .unwrap_or_default()
+    }
+
+    /// Context manager measuring a named region of the active session, regions may nest.
+    fn region(slf: Py<Self>, name: String) -> ProfilerRegion {
+        ProfilerRegion {
+            profiler: slf,
+            name,
+            id: None,
+        }
+    }
+
+    #[getter]
+    fn is_sampling(&self) -> bool {
+        self.sampler.is_sampling()
+    }
+
+    /// Why the last session ended before `stop`, e.g. a failing sensor read.
+    #[getter]
+    fn error(&self) -> Option<String> {
+        self.sampler.error().map(|e| e.to_string())
+    }
+
+    /// Reads of the last session dropped for counters going backwards or implausible power.
+    #[getter]
+    fn discarded_samples(&self) -> usize {
+        self.sampler.discarded_samples()
+    }
+
+    /// Requested vs achieved sample interval of the last session, `None` before any samples.
+    #[getter]
+    fn interval_accuracy(&self) -> Option<HashMap<&'static str, f64>> {
+        let accuracy = self.sampler.interval_accuracy()?;
+        Some(HashMap::from([
+            ("requested", accuracy.requested as f64),
+            ("mean", accuracy.mean),
+            ("min", accuracy.min as f64),
+            ("max", accuracy.max as f64),
+            ("relative_error", accuracy.relative_error),
+        ]))
+    }
+
+    /// `(start_ms, duration_ms)` of every stretch the last session slept through.
+    #[getter]
+    fn gaps(&self) -> Vec<(u64, u64)> {
+        self.sampler
+            .gaps()
+            .iter()
+            .map(|g| (g.start, g.duration))
+            .collect()
     }
 
     fn __enter__(mut slf: PyRefMut<'_, Self>) -> PyResult<PyRefMut<'_, Self>> {
-        let duration = slf.sample_duration;
-        let num_samples = slf.num_samples;
-        slf.sampler.start(duration, num_samples).unwrap();
-        assert!(slf.sampler.is_sampling());
+        let py = slf.py();
+        slf.start(py)?;
         Ok(slf)
     }
 
@@ -89,19 +1400,379 @@ impl Profiler {
         _exc_value: Option<PyObject>,
         _traceback: Option<PyObject>,
     ) -> PyResult<bool> {
-        slf.sampler.stop().unwrap();
-        assert!(!slf.sampler.is_sampling());
-        Ok(true)
+        let py = slf.py();
+        slf.stop(py)?;
+        Ok(slf.suppress_exceptions)
+    }
+
+    /// Profile of the last session, available as soon as its `with` block exits.
+    #[getter(profile)]
+    fn last_profile(&self) -> PyResult<PyPowerProfile> {
+        self.get_profile()
+    }
+
+    /// Runs `func(*args, **kwargs)` under a default profiler sampling every 100 ms and
+    /// returns its result with the profile of the call. Sampling stops even if `func`
+    /// raises.
+    #[staticmethod]
+    #[pyo3(signature = (func, *args, **kwargs))]
+    fn measure(
+        py: Python<'_>,
+        func: &Bound<'_, PyAny>,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<(PyObject, PyPowerProfile)> {
+        let duration = 0.1f64.into_py(py).into_bound(py);
+        let mut profiler = Profiler::new(
+            py,
+            Some(&duration),
+            1,
+            false,
+            false,
+            None,
+            false,
+            None,
+            None,
+        )?;
+        profiler.start(py)?;
+        let result = func.call(args, kwargs);
+        // an exception of `func` takes precedence over one stopping the session
+        let stopped = profiler.stop(py);
+        let result = result?.unbind();
+        stopped?;
+        Ok((result, profiler.get_profile()?))
     }
 
     fn get_profile(&self) -> PyResult<PyPowerProfile> {
-        let profile = self.sampler.profile();
-        Ok(PyPowerProfile(profile.unwrap())) //TODO: proper error handling
+        if let Some(parent) = self.sampling_parent {
+            return Err(to_py_err(SamplerError::ForkedProcess(parent)));
+        }
+        let profile = self.sampler.profile().map_err(to_py_err)?;
+        let samples = self.sampler.samples().cloned().unwrap_or_default();
+        let regions = self.sampler.regions().to_vec();
+        Ok(PyPowerProfile::new(profile, samples, regions))
+    }
+
+    /// Profile of the running session so far, without stopping it.
+    fn snapshot(&self) -> PyResult<PyPowerProfile> {
+        if let Some(parent) = self.sampling_parent {
+            return Err(to_py_err(SamplerError::ForkedProcess(parent)));
+        }
+        let profile = self.sampler.profile_so_far().map_err(to_py_err)?;
+        let samples = self.sampler.samples_so_far().map_err(to_py_err)?;
+        let regions = self.sampler.regions().to_vec();
+        Ok(PyPowerProfile::new(profile, samples, regions))
+    }
+
+    /// Raw samples of the last session, empty until a session has finished.
+    #[getter]
+    fn samples(&self) -> PyResult<Vec<PyEnergySample>> {
+        Ok(self
+            .sampler
+            .samples()
+            .map(|samples| samples.iter().cloned().map(PyEnergySample).collect())
+            .unwrap_or_default())
     }
+
+    /// Per-sample time series of the last session as a `pandas.DataFrame` (ms, mJ, mW).
+    fn samples_dataframe(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let samples = self
+            .sampler
+            .samples()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        samples_to_dataframe(py, samples)
+    }
+}
+
+#[pyclass]
+pub struct ProfilerRegion {
+    profiler: Py<Profiler>,
+    name: String,
+    /// Region and the session it began in.
+    id: Option<(usize, RegionId)>,
+}
+
+#[pymethods]
+impl ProfilerRegion {
+    fn __enter__(mut slf: PyRefMut<'_, Self>) -> PyResult<PyRefMut<'_, Self>> {
+        let py = slf.py();
+        let mut profiler = slf.profiler.borrow_mut(py);
+        // Workers of a sampling parent have no session of their own.
+        let id = if profiler.sampling_parent.is_some() {
+            None
+        } else {
+            let id = profiler
+                .sampler
+                .begin_region(&slf.name)
+                .map_err(to_py_err)?;
+            Some((profiler.session, id))
+        };
+        drop(profiler);
+        slf.id = id;
+        Ok(slf)
+    }
+
+    #[pyo3(signature = (_exc_type=None, _exc_value=None, _traceback=None))]
+    fn __exit__(
+        &mut self,
+        py: Python<'_>,
+        _exc_type: Option<PyObject>,
+        _exc_value: Option<PyObject>,
+        _traceback: Option<PyObject>,
+    ) -> bool {
+        if let Some((session, id)) = self.id.take() {
+            let mut profiler = self.profiler.borrow_mut(py);
+            // ids of an earlier session would end a region of the current one
+            if profiler.session == session {
+                profiler.sampler.end_region(id);
+            }
+        }
+        false
+    }
+}
+
+/// Whether per-run energy differs between two sets of runs.
+#[pyclass]
+pub struct PyComparison(Comparison);
+
+#[pymethods]
+impl PyComparison {
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    /// Mean total energy per run of `a` in mJ.
+    #[getter]
+    fn mean_a(&self) -> f64 {
+        self.0.mean_a
+    }
+
+    #[getter]
+    fn mean_b(&self) -> f64 {
+        self.0.mean_b
+    }
+
+    /// `(mean_b - mean_a) / mean_a`, -0.1 is 10% less energy.
+    #[getter]
+    fn relative_difference(&self) -> f64 {
+        self.0.relative_difference
+    }
+
+    /// Two-sided p-value of Welch's t-test, NaN with fewer than two runs on a side.
+    #[getter]
+    fn t_test_p(&self) -> f64 {
+        self.0.t_test_p
+    }
+
+    /// Two-sided p-value of the Mann-Whitney U test.
+    #[getter]
+    fn mann_whitney_p(&self) -> f64 {
+        self.0.mann_whitney_p
+    }
+
+    #[getter]
+    fn alpha(&self) -> f64 {
+        self.0.alpha
+    }
+
+    /// Whether both tests reject equal energy at `alpha`.
+    #[getter]
+    fn significant(&self) -> bool {
+        self.0.is_significant()
+    }
+}
+
+/// Tests whether the total energy per run of `b` differs from that of `a` at `alpha`.
+#[pyfunction]
+#[pyo3(signature = (a, b, alpha=0.05))]
+fn compare_runs(
+    a: Vec<PyRef<'_, PyPowerProfile>>,
+    b: Vec<PyRef<'_, PyPowerProfile>>,
+    alpha: f64,
+) -> PyComparison {
+    let profiles = |runs: Vec<PyRef<'_, PyPowerProfile>>| -> Vec<PowerProfile> {
+        runs.iter().map(|p| p.profile.clone()).collect()
+    };
+    PyComparison(wattkit::compare_runs(&profiles(a), &profiles(b), alpha))
+}
+
+/// Raises `AssertionError` with the profile when `profiles`, one or a list of repeated
+/// runs, took more than `joules`. `tolerance` is the fraction above it tolerated and
+/// `percentile` picks the run checked by energy, the most expensive by default.
+#[pyfunction]
+#[pyo3(signature = (profiles, joules, tolerance=0.0, percentile=100.0))]
+fn assert_energy_below(
+    profiles: &Bound<'_, PyAny>,
+    joules: f64,
+    tolerance: f64,
+    percentile: f64,
+) -> PyResult<()> {
+    let profiles: Vec<PowerProfile> = match profiles.extract::<PyRef<'_, PyPowerProfile>>() {
+        Ok(profile) => vec![profile.profile.clone()],
+        Err(_) => profiles
+            .extract::<Vec<PyRef<'_, PyPowerProfile>>>()?
+            .iter()
+            .map(|p| p.profile.clone())
+            .collect(),
+    };
+    wattkit::EnergyBudget::new(joules)
+        .with_tolerance(tolerance)
+        .with_percentile(percentile)
+        .check_runs(&profiles)
+        .map_err(|e| PyAssertionError::new_err(e.to_string()))
+}
+
+/// JUnit XML test suite with a test case per `(name, profile, budget)`, failing those
+/// exceeding their budget, a dict of `energy` in joules and `average_power` and
+/// `peak_power` in watts. Failures and profiles show energy and power in `energy_unit`
+/// and `power_unit`.
+#[pyfunction]
+#[pyo3(signature = (cases, suite="energy", energy_unit="J", power_unit="W"))]
+fn junit_xml(
+    cases: Vec<(String, PyRef<'_, PyPowerProfile>, HashMap<String, f64>)>,
+    suite: &str,
+    energy_unit: &str,
+    power_unit: &str,
+) -> PyResult<String> {
+    let units = parse_units(energy_unit, power_unit)?;
+    let cases = cases
+        .into_iter()
+        .map(|(name, profile, budget)| {
+            if let Some(key) = budget
+                .keys()
+                .find(|k| !["energy", "average_power", "peak_power"].contains(&k.as_str()))
+            {
+                return Err(PyValueError::new_err(format!("unknown budget `{key}`")));
+            }
+            Ok(wattkit::EnergyTestCase {
+                name,
+                profile: profile.profile.clone(),
+                budget: wattkit::Budget {
+                    energy: budget.get("energy").copied(),
+                    average_power: budget.get("average_power").copied(),
+                    peak_power: budget.get("peak_power").copied(),
+                },
+            })
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(wattkit::junit_xml_with(suite, &cases, units))
+}
+
+/// Samples the default backend for `session` at each of `intervals` while the caller
+/// idles, two seconds by default, returning a dict per interval with the CPU time and power sampling costs.
+#[pyfunction]
+#[pyo3(signature = (intervals=None, session=None))]
+fn measure_overhead<'py>(
+    py: Python<'py>,
+    intervals: Option<Vec<Bound<'py, PyAny>>>,
+    session: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    let intervals = match intervals {
+        Some(intervals) => intervals
+            .iter()
+            .map(extract_duration)
+            .collect::<PyResult<Vec<_>>>()?,
+        None => overhead::DEFAULT_INTERVALS.to_vec(),
+    };
+    let session = match session {
+        Some(session) => extract_duration(session)?,
+        None => Duration::from_secs(2),
+    };
+    let overheads = py
+        .allow_threads(|| {
+            let mut sampler = StartStopSampler::new();
+            overhead::measure_with(&mut sampler, &intervals, session)
+        })
+        .map_err(to_py_err)?;
+    overheads
+        .iter()
+        .map(|o| {
+            let dict = PyDict::new_bound(py);
+            dict.set_item("interval", o.interval)?;
+            dict.set_item("samples", o.samples)?;
+            dict.set_item("cpu_time_per_sample", o.cpu_time_per_sample)?;
+            dict.set_item("cpu_utilization", o.cpu_utilization)?;
+            dict.set_item("average_power", o.average_power)?;
+            dict.set_item("extra_power", o.extra_power)?;
+            dict.set_item("energy_per_sample", o.energy_per_sample)?;
+            Ok(dict)
+        })
+        .collect()
+}
+
+/// Probes which units and IOReport groups produce data for this process, e.g. to warn
+/// when missing privileges would make every sample read zero.
+#[pyfunction]
+fn capabilities(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let probed = py.allow_threads(wattkit::capabilities).map_err(to_py_err)?;
+    let units = |c: wattkit::Capabilities| -> PyResult<Bound<'_, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("cpu", c.cpu)?;
+        dict.set_item("gpu", c.gpu)?;
+        dict.set_item("ane", c.ane)?;
+        Ok(dict)
+    };
+    let groups = probed
+        .groups
+        .iter()
+        .map(|g| {
+            let dict = PyDict::new_bound(py);
+            dict.set_item("group", &g.group)?;
+            dict.set_item("subscribed", g.subscribed)?;
+            dict.set_item("channels", g.channels)?;
+            dict.set_item("active", g.active)?;
+            Ok(dict)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    let dict = PyDict::new_bound(py);
+    dict.set_item("reported", units(probed.reported)?)?;
+    dict.set_item("measured", units(probed.measured)?)?;
+    dict.set_item("groups", groups)?;
+    dict.set_item(
+        "unmapped",
+        probed
+            .unmapped
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>(),
+    )?;
+    dict.set_item("restricted", probed.is_restricted())?;
+    Ok(dict)
+}
+
+/// The chip and machine this runs on, to skip or adapt measurements on machines without
+/// the counters needed.
+#[pyfunction]
+fn soc_info(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let soc = wattkit::SocInfo::detect();
+    let dict = PyDict::new_bound(py);
+    dict.set_item("name", &soc.name)?;
+    dict.set_item("model", &soc.model)?;
+    dict.set_item("cores", soc.cores)?;
+    dict.set_item("performance_cores", soc.performance_cores)?;
+    dict.set_item("efficiency_cores", soc.efficiency_cores)?;
+    dict.set_item("memory", soc.memory)?;
+    dict.set_item("apple_silicon", soc.is_apple_silicon())?;
+    Ok(dict)
 }
 
 #[pymodule]
 fn _wattkit_pyo3(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Profiler>()?;
+    m.add_class::<PyEnergySample>()?;
+    m.add_class::<PyPowerProfile>()?;
+    m.add_class::<PySummary>()?;
+    m.add_class::<PyAggregateProfile>()?;
+    m.add_class::<PyComparison>()?;
+    m.add_function(wrap_pyfunction!(compare_runs, m)?)?;
+    m.add_function(wrap_pyfunction!(junit_xml, m)?)?;
+    m.add_function(wrap_pyfunction!(assert_energy_below, m)?)?;
+    m.add_function(wrap_pyfunction!(measure_overhead, m)?)?;
+    m.add_function(wrap_pyfunction!(capabilities, m)?)?;
+    m.add_function(wrap_pyfunction!(soc_info, m)?)?;
+    m.add_class::<SampleStream>()?;
+    m.add_class::<ProfilerRegion>()?;
+    m.add("WattKitError", m.py().get_type_bound::<WattKitError>())?;
     Ok(())
 }
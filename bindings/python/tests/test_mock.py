@@ -0,0 +1,535 @@
+import asyncio
+import csv
+import io
+import json
+import math
+import os
+import sys
+import tempfile
+import time
+import types
+from datetime import timedelta
+
+import pytest
+
+from wattkit import (
+    Profiler,
+    PyPowerProfile,
+    assert_energy_below,
+    compare_runs,
+    junit_xml,
+    live_monitor,
+    profile_command,
+    soc_info,
+)
+
+
+def test_mock_profile_matches_constant_power():
+    with Profiler.mock(cpu_power=2000.0, gpu_power=500.0, duration=0.05) as profiler:
+        time.sleep(0.3)
+
+    profile = profiler.get_profile()
+    assert profile.average_cpu_power == 2000
+    assert profile.average_gpu_power == 500
+    assert profile.total_ane_energy == 0
+    assert profile.total_cpu_energy == 2000 * profile.total_duration // 1000
+
+
+def test_mock_profile_after_with_block():
+    with Profiler.mock(cpu_power=1000.0, duration=0.02) as profiler:
+        time.sleep(0.05)
+
+    assert profiler.profile.total_cpu_energy == profiler.get_profile().total_cpu_energy
+    assert profiler.profile.total_cpu_energy > 0
+
+
+def test_mock_sessions():
+    profiler = Profiler.mock(cpu_power=1000.0, duration=0.02)
+    for sleep in (0.05, 0.1):
+        with profiler:
+            with profiler.region("work"):
+                time.sleep(sleep)
+
+    first, second = profiler.sessions
+    assert first.total_duration < second.total_duration
+    assert second.total_cpu_energy == profiler.profile.total_cpu_energy
+    assert list(first.regions) == list(second.regions) == ["work"]
+
+
+def test_mock_live_monitor():
+    out = io.StringIO()
+    profiler = Profiler.mock(cpu_power=2000.0, duration=0.02)
+    profile = live_monitor(duration=0.1, rich=False, file=out, profiler=profiler)
+
+    assert "CPU   2.000 W" in out.getvalue()
+    assert profile.average_cpu_power == 2000
+
+
+def test_mock_tensorboard_logger():
+    from wattkit.tensorboard import TensorBoardLogger
+
+    class Writer:
+        scalars = []
+
+        def add_scalar(self, tag, value, step):
+            self.scalars.append((tag, value, step))
+
+    writer = Writer()
+    profiler = Profiler.mock(cpu_power=1000.0, duration=0.02)
+    with TensorBoardLogger(writer, profiler=profiler) as logger:
+        for step in range(2):
+            time.sleep(0.1)
+            logger.step(step)
+        logger.epoch(0)
+
+    tags = [(tag, step) for tag, _, step in writer.scalars]
+    assert tags[:2] == [("energy/joules", 0), ("energy/power_watts", 0)]
+    assert ("energy/epoch_joules", 0) in tags
+    watts = [value for tag, value, _ in writer.scalars if tag.endswith("power_watts")]
+    assert watts and all(value == 1.0 for value in watts)
+    assert logger.profile.average_cpu_power == 1000
+
+
+def test_mock_wandb_logger():
+    # the run is passed in, wandb itself is only needed for `wandb.run`
+    sys.modules.setdefault("wandb", types.ModuleType("wandb"))
+    from wattkit.wandb import WattkitLogger
+
+    class Config(dict):
+        def update(self, values, allow_val_change=False):
+            super().update(values)
+
+    class Run:
+        def __init__(self):
+            self.rows, self.config, self.summary = [], Config(), {}
+
+        def log(self, row):
+            self.rows.append(row)
+
+        def define_metric(self, name, step_metric=None):
+            pass
+
+    run = Run()
+    profiler = Profiler.mock(cpu_power=2000.0, duration=0.02)
+    with WattkitLogger(run, profiler=profiler):
+        time.sleep(0.1)
+
+    assert run.rows and all(row["energy/cpu_power_watts"] == 2.0 for row in run.rows)
+    assert "chip" in run.config["wattkit"]
+    assert run.summary["energy/average_power_watts"] == 2.0
+
+
+def test_mock_mlflow_log_energy():
+    logged = {}
+    fake = types.ModuleType("mlflow")
+    fake.log_metrics = lambda metrics, step=None: logged.update(metrics)
+    fake.log_dict = lambda values, path: logged.update({path: values})
+    sys.modules["mlflow"] = fake
+    from wattkit.mlflow import log_energy
+
+    with log_energy(profiler=Profiler.mock(cpu_power=1000.0, duration=0.02)) as profiler:
+        with profiler.region("eval/step"):
+            time.sleep(0.1)
+
+    assert logged["energy_average_power_watts"] == 1.0
+    assert logged["energy_peak_power_watts"] == 1.0
+    assert logged["energy_region_eval/step_joules"] > 0
+    assert logged["wattkit/profile.json"]["total_energy"] == profiler.profile.total_energy
+
+
+def test_mock_metric_rates():
+    profiler = Profiler.mock(cpu_power=1000.0, duration=0.02)
+    profiler.set_metric_rates(cpu_load=timedelta(seconds=10), io=10.0)
+    with profiler:
+        time.sleep(0.15)
+
+    samples = profiler.profile.samples
+    assert len(samples) > 2
+    assert all(s.cpu_utilization == samples[0].cpu_utilization for s in samples)
+    assert all(s.disk_io is None and s.network_io is None for s in samples[1:])
+
+
+def test_mock_channel_tree():
+    with Profiler.mock(cpu_power=1000.0, gpu_power=500.0, duration=0.02) as profiler:
+        time.sleep(0.1)
+
+    profile = profiler.profile
+    tree = profile.channel_tree()
+    cpu = tree["Energy"][""]["CPU"]
+    assert cpu["unit"] == "mJ"
+    assert len(cpu["points"]) == len(profile.samples)
+    assert sum(value for _, value in cpu["points"]) == profile.total_cpu_energy
+    assert tree["Energy"][""]["GPU"]["points"][0][1] == 10
+
+
+def test_mock_markdown_report():
+    with Profiler.mock(cpu_power=1000.0, duration=0.02) as profiler:
+        time.sleep(0.1)
+    baseline = profiler.profile
+    with Profiler.mock(cpu_power=2000.0, duration=0.02) as profiler:
+        time.sleep(0.1)
+
+    report = profiler.profile.to_markdown(baseline)
+    assert report.startswith("| | Value | Baseline | Change |")
+    assert "| Peak power | 2.000 W | 1.000 W | +100.0% |" in report
+    assert profiler.profile.peak_power == 2000
+    assert "Baseline" not in baseline.to_markdown()
+    milli = profiler.profile.to_markdown(baseline, energy_unit="mJ", power_unit="mW")
+    assert "| Peak power | 2000 mW | 1000 mW | +100.0% |" in milli
+    assert str(profiler.profile) == profiler.profile.format()
+
+
+def test_mock_drop_while_sampling():
+    seen = []
+    profiler = Profiler.mock(cpu_power=1000.0, duration=0.01)
+    profiler.on_sample(lambda sample: seen.append(sample))
+    profiler.__enter__()
+    time.sleep(0.1)
+    # the sampling thread is stopped without waiting on the GIL held here
+    del profiler
+    assert seen
+
+
+def test_mock_channels():
+    Profiler(0.1, channels=["energy", "residency"])
+    with pytest.raises(ValueError):
+        Profiler(0.1, channels=["thermal"])
+
+
+def test_mock_units():
+    with Profiler.mock(cpu_power=2000.0, duration=0.02) as profiler:
+        time.sleep(0.1)
+    profile = profiler.profile
+
+    assert math.isclose(profile.energy("J"), profile.total_energy / 1000.0)
+    assert math.isclose(profile.energy("Wh"), profile.total_energy / 3.6e6)
+    assert profile.power("W") == 2.0
+    assert profile.format("J", "W").startswith("Total Energy: ")
+    assert "Total Power: 2.000 W" in profile.format("J", "W")
+    with pytest.raises(ValueError):
+        profile.energy("kWh")
+
+
+def test_mock_profile_arithmetic():
+    with Profiler.mock(cpu_power=1000.0, duration=0.02) as profiler:
+        time.sleep(0.1)
+    idle = profiler.profile
+    with Profiler.mock(cpu_power=3000.0, duration=0.02) as profiler:
+        time.sleep(0.1)
+    busy = profiler.profile
+
+    both = idle + busy
+    assert both.total_energy == idle.total_energy + busy.total_energy
+    assert both.total_duration == idle.total_duration + busy.total_duration
+    assert len(both.samples) == len(idle.samples) + len(busy.samples)
+    assert (busy - idle).average_power == 2000
+    assert (busy - idle).total_duration == busy.total_duration
+    assert busy == busy
+    assert busy != idle
+    assert idle.approx_eq(idle)
+    assert not busy.approx_eq(idle, relative=0.5)
+
+
+def test_mock_assert_energy_below():
+    with Profiler.mock(cpu_power=1000.0, duration=0.02) as profiler:
+        time.sleep(0.1)
+    profile = profiler.profile
+    joules = profile.total_energy / 1000.0
+
+    assert_energy_below(profile, joules)
+    assert_energy_below([profile, profile], joules * 0.9, tolerance=0.2)
+    with pytest.raises(AssertionError):
+        assert_energy_below(profile, joules * 0.5)
+
+
+def test_mock_junit_xml():
+    with Profiler.mock(cpu_power=1000.0, duration=0.02) as profiler:
+        time.sleep(0.1)
+
+    xml = junit_xml(
+        [
+            ("fits", profiler.profile, {"average_power": 1.5}),
+            ("too_hot", profiler.profile, {"peak_power": 0.5}),
+        ],
+        suite="inference",
+    )
+    assert '<testsuite name="inference" tests="2" failures="1"' in xml
+    assert xml.count("<failure") == 1
+    assert 'message="peak power 1.000 W exceeds the budget of 0.500 W"' in xml
+    milli = junit_xml([("too_hot", profiler.profile, {"peak_power": 0.5})], power_unit="mW")
+    assert 'message="peak power 1000 mW exceeds the budget of 500 mW"' in milli
+    with pytest.raises(ValueError):
+        junit_xml([("typo", profiler.profile, {"energie": 1.0})])
+
+
+def test_mock_codecarbon_csv():
+    from wattkit.codecarbon import FIELDS, write_codecarbon_csv
+
+    with Profiler.mock(cpu_power=1000.0, gpu_power=1000.0, duration=0.02) as profiler:
+        time.sleep(0.1)
+
+    path = os.path.join(tempfile.mkdtemp(), "emissions.csv")
+    for _ in range(2):
+        write_codecarbon_csv(profiler.profile, path, project_name="train", carbon_intensity=500.0)
+
+    with open(path, newline="") as file:
+        rows = list(csv.DictReader(file))
+    assert len(rows) == 2 and list(rows[0]) == FIELDS
+    energy = float(rows[0]["energy_consumed"])
+    assert math.isclose(energy, float(rows[0]["cpu_energy"]) + float(rows[0]["gpu_energy"]))
+    assert math.isclose(energy, profiler.profile.total_energy / 3.6e9)
+    assert math.isclose(float(rows[0]["emissions"]), energy * 0.5)
+    assert float(rows[0]["cpu_power"]) == 1.0
+    assert rows[0]["project_name"] == "train"
+
+
+def test_mock_asgi_middleware():
+    from wattkit.asgi import EnergyMiddleware
+
+    async def app(scope, receive, send):
+        await asyncio.sleep(0.1)
+        await send({"type": "http.response.start", "status": 200, "headers": []})
+        await send({"type": "http.response.body", "body": b"ok"})
+
+    async def get(middleware, path):
+        sent = []
+
+        async def send(message):
+            sent.append(message)
+
+        scope = {"type": "http", "method": "GET", "path": path}
+        await middleware(scope, None, send)
+        return dict(sent[0]["headers"]), sent[1]["body"]
+
+    middleware = EnergyMiddleware(app, profiler=Profiler.mock(cpu_power=2000.0, duration=0.01))
+    headers, _ = asyncio.run(get(middleware, "/generate"))
+    _, metrics = asyncio.run(get(middleware, "/wattkit/metrics"))
+    middleware.close()
+
+    joules = float(headers[b"x-energy-joules"])
+    assert 0.15 < joules < 0.3
+    metrics = json.loads(metrics)
+    assert metrics["requests"] == 1
+    assert math.isclose(metrics["joules_per_request"], joules, abs_tol=1e-6)
+
+
+def test_mock_profile_command():
+    script = "import sys, time; time.sleep(0.1); print('done'); sys.exit(3)"
+    profiler = Profiler.mock(cpu_power=1000.0, duration=0.02)
+    result = profile_command(
+        [sys.executable, "-c", script], capture_output=True, text=True, profiler=profiler
+    )
+
+    assert result.returncode == 3
+    assert result.stdout == "done\n"
+    assert result.profile.total_duration >= 100
+    assert result.profile.average_cpu_power == 1000
+
+
+def test_mock_auto_profile():
+    from wattkit import auto
+
+    assert os.environ.get("WATTKIT_PROFILE") != "1" and not auto.init()
+    output = os.path.join(tempfile.mkdtemp(), "profile.txt")
+    os.environ["WATTKIT_OUTPUT"] = output
+    try:
+        auto._profiler = Profiler.mock(cpu_power=1000.0, duration=0.02)
+        auto._profiler.start()
+        time.sleep(0.1)
+        profile = auto.finish()
+    finally:
+        del os.environ["WATTKIT_OUTPUT"]
+
+    with open(output) as file:
+        assert file.read() == f"{profile}\n"
+    assert auto.finish() is None
+
+
+def test_mock_regions():
+    with Profiler.mock(cpu_power=1000.0, duration=0.05) as profiler:
+        with profiler.region("work"):
+            time.sleep(0.3)
+
+    region = profiler.get_profile().regions["work"]
+    assert region.total_duration > 0
+
+
+def test_mock_nested_regions_same_name():
+    with Profiler.mock(cpu_power=1000.0, duration=0.05) as profiler:
+        with profiler.region("step"):
+            with profiler.region("step"):
+                time.sleep(0.3)
+
+    profile = profiler.get_profile()
+    region = profile.regions["step"]
+    assert 0 < region.total_duration <= profile.total_duration
+    assert region.total_energy <= profile.total_energy
+
+
+def test_mock_duration_types():
+    with Profiler.mock(cpu_power=1000.0, duration=timedelta(milliseconds=50)) as profiler:
+        time.sleep(0.2)
+    assert profiler.interval_accuracy["requested"] == 50
+
+    with pytest.warns(DeprecationWarning):
+        legacy = Profiler.mock(cpu_power=1000.0, sample_duration=50)
+    with legacy:
+        time.sleep(0.2)
+    assert legacy.interval_accuracy["requested"] == 50
+
+
+def test_mock_snapshot():
+    profiler = Profiler.mock(cpu_power=1000.0, duration=0.02)
+    profiler.discard_warmup(samples=2)
+    with profiler:
+        time.sleep(0.1)
+        early = profiler.snapshot()
+        time.sleep(0.1)
+        later = profiler.snapshot()
+        assert profiler.is_sampling
+
+    assert early.average_cpu_power == 1000
+    # the two warmup samples are in `samples` but not in the totals, as in the profile
+    assert 0 < early.total_duration < sum(s.duration for s in early.samples)
+    assert later.total_cpu_energy > early.total_cpu_energy
+    assert profiler.get_profile().total_cpu_energy >= later.total_cpu_energy
+
+
+def test_mock_peak_power():
+    with Profiler.mock(cpu_power=1500.0, duration=0.05) as profiler:
+        time.sleep(0.2)
+
+    profile = profiler.get_profile()
+    power, timestamp = profile.peak_cpu_power
+    assert power == 1500
+    assert timestamp == 0  # constant power peaks at the first sample
+    assert profile.to_dict()["peak_ane_power"] == (0, 0)
+
+
+def test_mock_duty_cycle():
+    with Profiler.mock(cpu_power=2000.0, duration=0.02) as profiler:
+        time.sleep(0.1)
+
+    profile = profiler.get_profile()
+    assert profile.duty_cycle(1.0) == (1.0, 0.0, 0.0)
+    assert profile.duty_cycle(5.0) == (0.0, 0.0, 0.0)
+
+
+def test_mock_energy_budget_alert():
+    fired = []
+    profiler = Profiler.mock(cpu_power=1000.0, duration=0.02)
+    # 20 mJ per sample, exceeded by the third
+    profiler.on_energy_budget_exceeded(0.05, lambda s: fired.append(s.timestamp))
+    profiler.on_power_above(5.0, lambda s: fired.append("power"))
+    with profiler:
+        time.sleep(0.2)
+
+    assert fired == [40]
+
+
+def test_mock_metadata():
+    profiler = Profiler.mock(cpu_power=1000.0, duration=0.02)
+    profiler.set_metadata("model", "resnet50")
+    profiler.set_metadata("batch_size", 32)
+    with profiler:
+        time.sleep(0.05)
+
+    profile = profiler.get_profile()
+    assert profile.metadata == {"batch_size": "32", "model": "resnet50"}
+    assert profile.to_dict()["metadata"] == profile.metadata
+
+
+def test_mock_merge_runs():
+    profiles = []
+    for power in (1000.0, 2000.0, 3000.0):
+        profiler = Profiler.mock(cpu_power=power, duration=0.02)
+        with profiler:
+            time.sleep(0.05)
+        profiles.append(profiler.get_profile())
+
+    merged = PyPowerProfile.merge(profiles)
+    assert merged.runs == 3
+    assert merged.average_power.mean == 2000.0
+    assert merged.average_power.stddev == 1000.0
+    assert merged.to_dict()["average_power"]["min"] == 1000.0
+
+
+def test_mock_compare_runs():
+    def runs(power):
+        profiles = []
+        for _ in range(3):
+            with Profiler.mock(cpu_power=power, duration=0.02) as profiler:
+                time.sleep(0.05)
+            profiles.append(profiler.get_profile())
+        return profiles
+
+    comparison = compare_runs(runs(1000.0), runs(1000.0))
+    assert not comparison.significant
+    assert comparison.alpha == 0.05
+
+
+def test_mock_discard_warmup():
+    profiler = Profiler.mock(cpu_power=1000.0, duration=0.02)
+    profiler.discard_warmup(samples=2)
+    with profiler:
+        time.sleep(0.1)
+
+    profile = profiler.get_profile()
+    assert len(profile.samples) >= 3
+    assert profile.total_cpu_energy == 20 * (len(profile.samples) - 2)
+
+    with pytest.raises(TypeError):
+        profiler.discard_warmup(samples=1, duration=0.1)
+
+
+def test_mock_reject_outliers_keeps_steady_power():
+    profiler = Profiler.mock(cpu_power=1000.0, duration=0.02)
+    profiler.reject_outliers()
+    with profiler:
+        time.sleep(0.1)
+
+    profile = profiler.get_profile()
+    assert profile.total_cpu_energy == 20 * len(profile.samples)
+
+
+def test_mock_detect_idle_steady_power():
+    profiler = Profiler.mock(cpu_power=1000.0, duration=0.02)
+    profiler.detect_idle(min_duration=0.04)
+    with profiler:
+        time.sleep(0.1)
+
+    profile = profiler.get_profile()
+    # steady power is all baseline
+    assert profile.idle_duration == profile.total_duration
+    assert profile.average_active_power == 0
+    assert profile.to_dict()["idle_periods"] == profile.idle_periods
+
+
+def test_mock_set_qos():
+    profiler = Profiler.mock(cpu_power=1000.0, duration=0.02)
+    profiler.set_qos("background")
+    with profiler:
+        time.sleep(0.05)
+    assert profiler.get_profile().total_cpu_energy > 0
+
+    with pytest.raises(ValueError):
+        profiler.set_qos("realtime")
+
+
+def test_soc_info():
+    soc = soc_info()
+    assert soc["cores"] >= 1
+    assert soc["apple_silicon"] == (soc["name"] or "").startswith("Apple")
+
+
+def test_mock_to_prometheus():
+    with Profiler.mock(cpu_power=1000.0, duration=0.05) as profiler:
+        time.sleep(0.2)
+
+    profile = profiler.get_profile()
+    assert 'wattkit_energy_joules_total{unit="cpu"}' in profile.to_prometheus()
+    scaphandre = profile.to_prometheus("scaphandre")
+    assert "scaph_host_power_microwatts " in scaphandre
+    assert 'scaph_domain_power_microwatts{domain_name="core",socket_id="0"}' in scaphandre
+    with pytest.raises(ValueError):
+        profile.to_prometheus("statsd")
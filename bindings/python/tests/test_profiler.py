@@ -0,0 +1,112 @@
+import os
+import sys
+import time
+
+import pytest
+
+from wattkit import Profiler, WattKitError
+
+pytestmark = pytest.mark.skipif(sys.platform != "darwin", reason="wattkit requires macOS")
+
+
+def test_exceptions_propagate():
+    profiler = Profiler(duration=0.1, num_samples=1)
+    with pytest.raises(ValueError):
+        with profiler:
+            time.sleep(0.3)
+            raise ValueError("boom")
+
+    # The session is still finalized when the block raises.
+    assert profiler.get_profile().total_duration > 0
+
+
+def test_exceptions_suppressed_when_requested():
+    with Profiler(duration=0.1, num_samples=1, suppress_exceptions=True) as profiler:
+        time.sleep(0.3)
+        raise ValueError("boom")
+
+    assert profiler.get_profile().total_duration > 0
+
+
+def test_no_exception():
+    with Profiler(duration=0.1, num_samples=1) as profiler:
+        time.sleep(0.3)
+
+    assert profiler.get_profile().total_duration > 0
+
+
+def test_start_stop_with_callback_does_not_deadlock():
+    seen = []
+    profiler = Profiler(duration=0.1, num_samples=1)
+    profiler.on_sample(seen.append)
+
+    profiler.start()
+    time.sleep(0.5)
+    profiler.stop()
+
+    assert [s.timestamp for s in seen] == [s.timestamp for s in profiler.samples]
+
+
+def test_keyboard_interrupt_finalizes_session():
+    import _thread
+    import threading
+
+    profiler = Profiler(duration=0.1, num_samples=1)
+    with pytest.raises(KeyboardInterrupt):
+        with profiler:
+            threading.Timer(0.3, _thread.interrupt_main).start()
+            while True:
+                time.sleep(0.01)
+
+    assert not profiler.is_sampling
+    assert profiler.get_profile().total_duration > 0
+
+
+def _worker(queue):
+    profiler = Profiler(duration=0.1, num_samples=1)
+    with profiler:
+        time.sleep(0.2)
+    try:
+        profiler.get_profile()
+        queue.put("sampled")
+    except WattKitError:
+        queue.put("passive")
+
+
+def test_workers_of_sampling_parent_are_passive():
+    import multiprocessing
+
+    ctx = multiprocessing.get_context("spawn")
+    queue = ctx.Queue()
+    with Profiler(duration=0.1, num_samples=1, attribute_children=True) as profiler:
+        worker = ctx.Process(target=_worker, args=(queue,))
+        worker.start()
+        worker.join()
+
+    assert queue.get() == "passive"
+    assert os.getpid() in profiler.energy_by_pid
+    assert profiler.get_profile().total_duration > 0
+
+
+def test_nested_regions():
+    profiler = Profiler(duration=0.1, num_samples=1)
+    with profiler:
+        with profiler.region("outer"):
+            time.sleep(0.3)
+            with profiler.region("inner"):
+                time.sleep(0.3)
+
+    profile = profiler.get_profile()
+    regions = profile.regions
+    assert set(regions) == {"outer", "inner"}
+    assert regions["inner"].total_duration <= regions["outer"].total_duration
+    assert regions["outer"].total_energy <= profile.total_energy
+
+
+def test_measure_returns_result_and_profile():
+    result, profile = Profiler.measure(lambda n: sum(range(n)), 1_000_000)
+    assert result == sum(range(1_000_000))
+    assert profile.total_duration > 0
+
+    with pytest.raises(ValueError):
+        Profiler.measure(int, "not a number")
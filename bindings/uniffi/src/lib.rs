@@ -0,0 +1,210 @@
+//! UniFFI definitions of the wattkit API, generate Kotlin/Swift/Python bindings with
+//! `cargo run --bin uniffi-bindgen generate --library <libwattkit_uniffi> --language swift`.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+uniffi::setup_scaffolding!();
+
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum WattkitError {
+    #[error("IOReportError: {message}")]
+    IOReport { message: String },
+    #[error("HwmonError: {message}")]
+    Hwmon { message: String },
+    #[error("PowerGadgetError: {message}")]
+    PowerGadget { message: String },
+    #[error("SmcError: {message}")]
+    Smc { message: String },
+    #[error("PowermetricsError: {message}")]
+    Powermetrics { message: String },
+    #[error("CaptureError: {message}")]
+    Capture { message: String },
+    #[error("No samples available")]
+    SamplesNotAvailable,
+    #[error("Sampling in progress")]
+    SamplingInProgress,
+    #[error("No sampling currently in progress")]
+    NoSamplingInProgress,
+    #[error("Sampling session belongs to parent process {pid}")]
+    ForkedProcess { pid: u32 },
+    #[error("Sample interval of {interval_ms} ms is too short")]
+    IntervalTooShort { interval_ms: u64 },
+    #[error("Session {name} is already open")]
+    SessionAlreadyOpen { name: String },
+    #[error("No open session named {name}")]
+    UnknownSession { name: String },
+}
+
+impl From<wattkit::SamplerError> for WattkitError {
+    fn from(e: wattkit::SamplerError) -> Self {
+        use wattkit::SamplerError::*;
+        match e {
+            IOReportError(e) => WattkitError::IOReport {
+                message: e.to_string(),
+            },
+            HwmonError(e) => WattkitError::Hwmon {
+                message: e.to_string(),
+            },
+            PowerGadgetError(e) => WattkitError::PowerGadget {
+                message: e.to_string(),
+            },
+            SmcError(e) => WattkitError::Smc {
+                message: e.to_string(),
+            },
+            PowermetricsError(e) => WattkitError::Powermetrics {
+                message: e.to_string(),
+            },
+            CaptureError(e) => WattkitError::Capture {
+                message: e.to_string(),
+            },
+            SamplesNotAvailable => WattkitError::SamplesNotAvailable,
+            SamplingInProgress => WattkitError::SamplingInProgress,
+            NoSamplingInProgress => WattkitError::NoSamplingInProgress,
+            ForkedProcess(pid) => WattkitError::ForkedProcess { pid },
+            IntervalTooShort(interval) => WattkitError::IntervalTooShort {
+                interval_ms: interval.as_millis() as u64,
+            },
+            SessionAlreadyOpen(name) => WattkitError::SessionAlreadyOpen { name },
+            UnknownSession(name) => WattkitError::UnknownSession { name },
+        }
+    }
+}
+
+/// Energies in millijoules, powers in milliwatts, durations in milliseconds.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct PowerProfile {
+    pub total_cpu_energy: u64,
+    pub total_gpu_energy: u64,
+    pub total_ane_energy: u64,
+    pub average_cpu_power: u64,
+    pub average_gpu_power: u64,
+    pub average_ane_power: u64,
+    pub total_energy: u64,
+    pub average_power: u64,
+    pub total_duration: u64,
+    pub cpu_available: bool,
+    pub gpu_available: bool,
+    pub ane_available: bool,
+    pub peak_cpu_power: PeakPower,
+    pub peak_gpu_power: PeakPower,
+    pub peak_ane_power: PeakPower,
+    pub metadata: HashMap<String, String>,
+}
+
+/// Highest single-sample power in milliwatts, `timestamp` in ms since sampling started.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct PeakPower {
+    pub power: u64,
+    pub timestamp: u64,
+}
+
+impl From<wattkit::PeakPower> for PeakPower {
+    fn from(p: wattkit::PeakPower) -> Self {
+        PeakPower {
+            power: p.power,
+            timestamp: p.timestamp,
+        }
+    }
+}
+
+impl From<wattkit::PowerProfile> for PowerProfile {
+    fn from(p: wattkit::PowerProfile) -> Self {
+        PowerProfile {
+            total_cpu_energy: p.total_cpu_energy as u64,
+            total_gpu_energy: p.total_gpu_energy as u64,
+            total_ane_energy: p.total_ane_energy as u64,
+            average_cpu_power: p.average_cpu_power,
+            average_gpu_power: p.average_gpu_power,
+            average_ane_power: p.average_ane_power,
+            total_energy: p.total_energy as u64,
+            average_power: p.average_power,
+            total_duration: p.total_duration,
+            cpu_available: p.cpu_available,
+            gpu_available: p.gpu_available,
+            ane_available: p.ane_available,
+            peak_cpu_power: p.peak_cpu_power.into(),
+            peak_gpu_power: p.peak_gpu_power.into(),
+            peak_ane_power: p.peak_ane_power.into(),
+            metadata: p.metadata.into_iter().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct EnergySample {
+    pub cpu_energy: u64,
+    pub gpu_energy: u64,
+    pub ane_energy: u64,
+    pub timestamp: u64,
+    pub duration: u64,
+}
+
+impl From<&wattkit::EnergySample> for EnergySample {
+    fn from(s: &wattkit::EnergySample) -> Self {
+        EnergySample {
+            cpu_energy: s.cpu_energy as u64,
+            gpu_energy: s.gpu_energy as u64,
+            ane_energy: s.ane_energy as u64,
+            timestamp: s.timestamp,
+            duration: s.duration,
+        }
+    }
+}
+
+/// Start/stop sampler, shared between threads of the foreign language.
+#[derive(Debug, Default, uniffi::Object)]
+pub struct Sampler(Mutex<wattkit::StartStopSampler>);
+
+#[uniffi::export]
+impl Sampler {
+    #[uniffi::constructor]
+    pub fn new() -> Self {
+        Sampler::default()
+    }
+
+    /// Takes `num_samples` samples every `duration` (a `TimeInterval` in Swift, a
+    /// `java.time.Duration` in Kotlin).
+    pub fn start(&self, duration: Duration, num_samples: u32) -> Result<(), WattkitError> {
+        let mut sampler = self.0.lock().unwrap();
+        Ok(sampler.start(duration, num_samples as usize)?)
+    }
+
+    /// Deprecated, `start` with `duration_ms` in milliseconds.
+    pub fn start_ms(&self, duration_ms: u64, num_samples: u32) -> Result<(), WattkitError> {
+        self.start(Duration::from_millis(duration_ms), num_samples)
+    }
+
+    /// Attaches `key: value` to the profiles of this and later sessions.
+    pub fn set_metadata(&self, key: String, value: String) {
+        self.0
+            .lock()
+            .unwrap()
+            .config_mut()
+            .metadata
+            .insert(key, value);
+    }
+
+    pub fn stop(&self) -> Result<(), WattkitError> {
+        Ok(self.0.lock().unwrap().stop()?)
+    }
+
+    pub fn is_sampling(&self) -> bool {
+        self.0.lock().unwrap().is_sampling()
+    }
+
+    pub fn profile(&self) -> Result<PowerProfile, WattkitError> {
+        use wattkit::Sampling;
+        Ok(self.0.lock().unwrap().profile()?.into())
+    }
+
+    pub fn samples(&self) -> Vec<EnergySample> {
+        use wattkit::Sampling;
+        let sampler = self.0.lock().unwrap();
+        sampler
+            .samples()
+            .map(|samples| samples.iter().map(EnergySample::from).collect())
+            .unwrap_or_default()
+    }
+}
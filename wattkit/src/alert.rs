@@ -0,0 +1,128 @@
+use std::sync::{Arc, Mutex};
+
+use crate::sampler::{EnergySample, SampleCallback};
+
+#[derive(Debug, Clone, Copy)]
+enum Trigger {
+    /// Total power in milliwatts.
+    PowerAbove(f64),
+    /// Total energy of the session in millijoules.
+    EnergyBudget(f64),
+}
+
+struct Alert {
+    trigger: Trigger,
+    callback: SampleCallback,
+    /// Whether the condition held at the previous sample, alerts fire on the transition.
+    triggered: bool,
+}
+
+/// Alerts shared between a sampler and its sampling thread, evaluated for every sample.
+#[derive(Default)]
+pub(crate) struct Alerts {
+    alerts: Vec<Alert>,
+    /// Millijoules used by the session so far.
+    energy: f64,
+}
+
+pub(crate) type SharedAlerts = Arc<Mutex<Alerts>>;
+
+impl std::fmt::Debug for Alerts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let triggers: Vec<Trigger> = self.alerts.iter().map(|a| a.trigger).collect();
+        f.debug_struct("Alerts")
+            .field("triggers", &triggers)
+            .field("energy", &self.energy)
+            .finish()
+    }
+}
+
+impl Alerts {
+    pub(crate) fn power_above(&mut self, watts: f64, callback: SampleCallback) {
+        self.push(Trigger::PowerAbove(watts * 1000.0), callback);
+    }
+
+    pub(crate) fn energy_budget(&mut self, joules: f64, callback: SampleCallback) {
+        self.push(Trigger::EnergyBudget(joules * 1000.0), callback);
+    }
+
+    fn push(&mut self, trigger: Trigger, callback: SampleCallback) {
+        self.alerts.push(Alert {
+            trigger,
+            callback,
+            triggered: false,
+        });
+    }
+
+    /// Rearms every alert for a new session.
+    pub(crate) fn reset(&mut self) {
+        self.energy = 0.0;
+        for alert in &mut self.alerts {
+            alert.triggered = false;
+        }
+    }
+
+    /// Calls the callback of every alert whose condition starts to hold with `sample`.
+    pub(crate) fn check(&mut self, sample: &EnergySample) {
+        self.energy += (sample.cpu_energy + sample.gpu_energy + sample.ane_energy) as f64;
+        let power = sample.cpu_power() + sample.gpu_power() + sample.ane_power();
+        for alert in &mut self.alerts {
+            let holds = match alert.trigger {
+                Trigger::PowerAbove(threshold) => power > threshold,
+                Trigger::EnergyBudget(budget) => self.energy > budget,
+            };
+            if holds && !alert.triggered {
+                (alert.callback)(sample);
+            }
+            alert.triggered = holds;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(cpu_energy: u128) -> EnergySample {
+        EnergySample {
+            cpu_energy,
+            duration: 1000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_power_alert_fires_on_each_crossing() {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let mut alerts = Alerts::default();
+        let log = fired.clone();
+        alerts.power_above(
+            2.0,
+            Box::new(move |s| log.lock().unwrap().push(s.cpu_energy)),
+        );
+
+        for energy in [1000, 3000, 4000, 1000, 2500] {
+            alerts.check(&sample(energy));
+        }
+        assert_eq!(*fired.lock().unwrap(), [3000, 2500]);
+    }
+
+    #[test]
+    fn test_energy_budget_fires_once() {
+        let fired = Arc::new(Mutex::new(0));
+        let mut alerts = Alerts::default();
+        let count = fired.clone();
+        alerts.energy_budget(5.0, Box::new(move |_| *count.lock().unwrap() += 1));
+
+        for _ in 0..10 {
+            alerts.check(&sample(1000));
+        }
+        assert_eq!(*fired.lock().unwrap(), 1);
+
+        alerts.reset();
+        for _ in 0..6 {
+            alerts.check(&sample(1000));
+        }
+        assert_eq!(*fired.lock().unwrap(), 2);
+    }
+}
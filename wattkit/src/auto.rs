@@ -0,0 +1,130 @@
+//! Profiling driven by environment variables, for measurements without code changes
+//! beyond one call at startup, e.g. in CI:
+//!
+//! - `WATTKIT_PROFILE=1` turns it on.
+//! - `WATTKIT_OUTPUT` is the file the profile is written to at exit, stderr without it.
+//! - `WATTKIT_INTERVAL_MS` is the sample interval, 100 ms by default.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::sampler::{PowerProfile, SamplerError, Sampling, StartStopSampler};
+
+pub const PROFILE_VAR: &str = "WATTKIT_PROFILE";
+pub const OUTPUT_VAR: &str = "WATTKIT_OUTPUT";
+pub const INTERVAL_VAR: &str = "WATTKIT_INTERVAL_MS";
+
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Settings read from the environment.
+#[derive(Clone, Debug, PartialEq)]
+struct Config {
+    output: Option<PathBuf>,
+    interval: Duration,
+}
+
+impl Config {
+    /// `None` unless profiling is turned on.
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        if var(PROFILE_VAR).as_deref() != Some("1") {
+            return None;
+        }
+        let interval = var(INTERVAL_VAR)
+            .and_then(|ms| ms.trim().parse().ok())
+            .map_or(DEFAULT_INTERVAL, Duration::from_millis);
+        Some(Config {
+            output: var(OUTPUT_VAR).filter(|o| !o.is_empty()).map(PathBuf::from),
+            interval,
+        })
+    }
+}
+
+static SESSION: Mutex<Option<(StartStopSampler, Config)>> = Mutex::new(None);
+
+/// Starts sampling the default backend when `WATTKIT_PROFILE=1`, the profile is written
+/// when the process exits through `main` returning or `std::process::exit`. Returns
+/// whether profiling is on, calling it again does nothing.
+pub fn init() -> Result<bool, SamplerError> {
+    let Some(config) = Config::from_vars(|name| std::env::var(name).ok()) else {
+        return Ok(false);
+    };
+    let Ok(mut session) = SESSION.lock() else {
+        return Ok(false);
+    };
+    if session.is_some() {
+        return Ok(true);
+    }
+    let mut sampler = StartStopSampler::new();
+    sampler.start(config.interval, 1)?;
+    *session = Some((sampler, config));
+    unsafe { libc::atexit(finish_at_exit) };
+    Ok(true)
+}
+
+/// Stops the session [`init`] started and writes its profile, what happens at exit.
+/// Returns the profile, `None` without a session.
+pub fn finish() -> Option<PowerProfile> {
+    let (mut sampler, config) = SESSION.lock().ok()?.take()?;
+    let profile = sampler.stop().and_then(|()| sampler.profile());
+    let profile = match profile {
+        Ok(profile) => profile,
+        Err(e) => {
+            warn!("no profile to write: {e}");
+            return None;
+        }
+    };
+    if let Err(e) = write(&profile, config.output.as_deref()) {
+        warn!("failed to write the profile: {e}");
+    }
+    Some(profile)
+}
+
+fn write(profile: &PowerProfile, output: Option<&Path>) -> std::io::Result<()> {
+    match output {
+        Some(path) => std::fs::write(path, format!("{profile}\n")),
+        None => {
+            eprintln!("{profile}");
+            Ok(())
+        }
+    }
+}
+
+extern "C" fn finish_at_exit() {
+    // unwinding out of an atexit handler aborts
+    let _ = std::panic::catch_unwind(finish);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_config() {
+        let config = |vars: &[(&str, &str)]| {
+            let vars: HashMap<_, _> = vars.iter().copied().collect();
+            Config::from_vars(|name| vars.get(name).map(|v| v.to_string()))
+        };
+        assert_eq!(config(&[]), None);
+        assert_eq!(config(&[(PROFILE_VAR, "0"), (OUTPUT_VAR, "out.txt")]), None);
+        assert_eq!(
+            config(&[(PROFILE_VAR, "1")]),
+            Some(Config {
+                output: None,
+                interval: DEFAULT_INTERVAL
+            })
+        );
+        assert_eq!(
+            config(&[
+                (PROFILE_VAR, "1"),
+                (OUTPUT_VAR, "out.txt"),
+                (INTERVAL_VAR, "20")
+            ]),
+            Some(Config {
+                output: Some(PathBuf::from("out.txt")),
+                interval: Duration::from_millis(20)
+            })
+        );
+    }
+}
@@ -0,0 +1,451 @@
+//! Raw per-channel IOReport captures, so a session from one machine can be replayed
+//! through the same unit and channel handling on another.
+//!
+//! A capture is a tab separated text file, each sample starts with a
+//! `sample <duration ms>` line followed by one
+//! `<group> <subgroup> <channel> <unit> <value>` line per channel.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::backend::{Capabilities, ChannelInfo, PowerBackend};
+use crate::io_report::{
+    cluster_residency, EnergyModelSample, IOReportChannelGroup, IOReportChannelName, IOReportError,
+    Interner, CLUSTER_STATES,
+};
+use crate::sampler::{EnergySample, MemoryTraffic, SamplerError};
+
+const HEADER: &str = "# wattkit raw capture v1";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CaptureError {
+    #[error("Failed to access capture: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Malformed capture at line {line}: {message}")]
+    Parse { line: usize, message: String },
+}
+
+type Result<T> = std::result::Result<T, CaptureError>;
+
+/// Delta of a single channel over one sample, as reported by IOReport. The names are
+/// shared with the same channel of the other samples.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawChannelValue {
+    pub group: Arc<str>,
+    pub subgroup: Arc<str>,
+    pub channel: Arc<str>,
+    pub unit: Arc<str>,
+    pub value: i64,
+}
+
+/// Residency of each state of a state channel over one sample, e.g. of a CPU cluster's
+/// performance states.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawStateChannel {
+    pub group: Arc<str>,
+    pub subgroup: Arc<str>,
+    pub channel: Arc<str>,
+    pub states: Vec<(Arc<str>, i64)>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RawSample {
+    pub duration: u64,
+    pub values: Vec<RawChannelValue>,
+    /// State channels, which captures don't record.
+    pub states: Vec<RawStateChannel>,
+}
+
+impl RawSample {
+    /// The "Energy Model" channels of the sample, see [`EnergyModelSample::parse`].
+    pub fn energy_model(&self) -> std::result::Result<EnergyModelSample, IOReportError> {
+        let channels = self
+            .values
+            .iter()
+            .filter(|value| {
+                matches!(
+                    IOReportChannelGroup::from(&value.group),
+                    IOReportChannelGroup::EnergyModel
+                )
+            })
+            .map(|value| (&*value.channel, &*value.unit, value.value));
+        EnergyModelSample::parse(channels, self.duration)
+    }
+
+    /// CPU, GPU, ANE, display and package energy of the sample, see
+    /// [`RawSample::energy_model`]. Memory traffic is summed from the "AMC Stats" DCS
+    /// channels when the capture has them, the CPU residency from the cluster states.
+    pub fn energy(&self) -> std::result::Result<EnergySample, IOReportError> {
+        let energy_model = self.energy_model()?;
+        Ok(EnergySample {
+            cpu_energy: energy_model.cpu,
+            gpu_energy: energy_model.gpu,
+            ane_energy: energy_model.ane,
+            display_energy: energy_model.display,
+            package_energy: Some(energy_model.package()),
+            duration: self.duration,
+            memory_traffic: self.memory_traffic(),
+            cpu_residency: cluster_residency(
+                self.states
+                    .iter()
+                    .filter(|s| &*s.subgroup == CLUSTER_STATES)
+                    .map(|s| &s.states[..]),
+            ),
+            dies: energy_model.dies,
+            ..Default::default()
+        })
+    }
+
+    /// Bytes read and written by the memory controllers (DCS), `None` without any of
+    /// their channels. Per-agent channels like "GFX RD" would count traffic twice.
+    fn memory_traffic(&self) -> Option<MemoryTraffic> {
+        let mut traffic = None;
+        for value in &self.values {
+            if !matches!(
+                IOReportChannelGroup::from(&value.group),
+                IOReportChannelGroup::AMCStats
+            ) || !value.channel.starts_with("DCS")
+            {
+                continue;
+            }
+            let scale = match &*value.unit {
+                "" | "B" => 1,
+                "KB" | "KiB" => 1 << 10,
+                "MB" | "MiB" => 1 << 20,
+                _ => continue,
+            };
+            let bytes = value.value.max(0) as u64 * scale;
+            let total: &mut MemoryTraffic = traffic.get_or_insert_with(Default::default);
+            if value.channel.ends_with("RD") {
+                total.read += bytes;
+            } else if value.channel.ends_with("WR") {
+                total.write += bytes;
+            }
+        }
+        traffic
+    }
+}
+
+/// Appends raw samples to a capture file.
+#[derive(Debug)]
+pub struct CaptureWriter {
+    writer: BufWriter<File>,
+}
+
+impl CaptureWriter {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{HEADER}")?;
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, sample: &RawSample) -> Result<()> {
+        writeln!(self.writer, "sample\t{}", sample.duration)?;
+        for v in &sample.values {
+            writeln!(
+                self.writer,
+                "{}\t{}\t{}\t{}\t{}",
+                v.group, v.subgroup, v.channel, v.unit, v.value
+            )?;
+        }
+        // a capture should survive the process being killed mid-session
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads every sample of a capture file.
+pub fn read_capture<P: AsRef<Path>>(path: P) -> Result<Vec<RawSample>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut samples: Vec<RawSample> = Vec::new();
+    let mut names = Interner::default();
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let parse_error = |message: &str| CaptureError::Parse {
+            line: i + 1,
+            message: message.to_string(),
+        };
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields.as_slice() {
+            ["sample", duration] => samples.push(RawSample {
+                duration: duration
+                    .parse()
+                    .map_err(|_| parse_error("invalid duration"))?,
+                ..Default::default()
+            }),
+            [group, subgroup, channel, unit, value] => {
+                let sample = samples
+                    .last_mut()
+                    .ok_or_else(|| parse_error("channel value before the first sample"))?;
+                sample.values.push(RawChannelValue {
+                    group: names.intern(group),
+                    subgroup: names.intern(subgroup),
+                    channel: names.intern(channel),
+                    unit: names.intern(unit),
+                    value: value.parse().map_err(|_| parse_error("invalid value"))?,
+                });
+            }
+            _ => return Err(parse_error("unexpected number of fields")),
+        }
+    }
+    Ok(samples)
+}
+
+/// # CaptureReplay
+///
+/// Replays a capture recorded with [`crate::backend::ioreport::IOReportBackend::record_to`].
+/// Like [`crate::backend::mock::MockBackend::replay`], reads fail once the capture is exhausted.
+#[derive(Debug)]
+pub struct CaptureReplay {
+    samples: std::vec::IntoIter<RawSample>,
+    channels: Vec<ChannelInfo>,
+    realtime: bool,
+}
+
+impl CaptureReplay {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(read_capture(path)?))
+    }
+
+    pub fn new(samples: Vec<RawSample>) -> Self {
+        let channels = samples
+            .first()
+            .map(|s| {
+                s.values
+                    .iter()
+                    .map(|v| ChannelInfo {
+                        group: v.group.to_string(),
+                        subgroup: v.subgroup.to_string(),
+                        name: v.channel.to_string(),
+                        unit: v.unit.to_string(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            samples: samples.into_iter(),
+            channels,
+            realtime: false,
+        }
+    }
+
+    /// Whether each read sleeps for the recorded duration.
+    pub fn realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+        self
+    }
+}
+
+impl PowerBackend for CaptureReplay {
+    fn sample(&mut self, _duration: Duration) -> std::result::Result<EnergySample, SamplerError> {
+        let raw = self
+            .samples
+            .next()
+            .ok_or(SamplerError::SamplesNotAvailable)?;
+        if self.realtime {
+            std::thread::sleep(Duration::from_millis(raw.duration));
+        }
+        Ok(raw.energy()?)
+    }
+
+    fn channels(&self) -> Vec<ChannelInfo> {
+        self.channels.clone()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let has = |name: IOReportChannelName| {
+            self.channels
+                .iter()
+                .any(|c| IOReportChannelName::from(c.name.clone()).as_str() == name.as_str())
+        };
+        Capabilities {
+            cpu: has(IOReportChannelName::CPUEnergy),
+            gpu: has(IOReportChannelName::GPUEnergy),
+            ane: has(IOReportChannelName::ANE),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(channel: &str, unit: &str, value: i64) -> RawChannelValue {
+        RawChannelValue {
+            group: "Energy Model".into(),
+            subgroup: "".into(),
+            channel: channel.into(),
+            unit: unit.into(),
+            value,
+        }
+    }
+
+    #[test]
+    fn test_capture_roundtrip() {
+        let path = std::env::temp_dir().join(format!("wattkit-capture-{}.tsv", std::process::id()));
+        let sample = RawSample {
+            duration: 100,
+            values: vec![
+                value("CPU Energy", "mJ", 120),
+                value("GPU Energy", "nJ", 5_000_000),
+                value("ANE0", "uJ", 3_000),
+                value("DRAM", "mJ", 7),
+            ],
+            ..Default::default()
+        };
+
+        let mut writer = CaptureWriter::create(&path).unwrap();
+        writer.write(&sample).unwrap();
+        writer.write(&sample).unwrap();
+        drop(writer);
+
+        let samples = read_capture(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(samples, vec![sample.clone(), sample]);
+
+        let mut replay = CaptureReplay::new(samples);
+        assert!(replay.capabilities().ane);
+        let energy = replay.sample(Duration::ZERO).unwrap();
+        assert_eq!(
+            (energy.cpu_energy, energy.gpu_energy, energy.ane_energy),
+            (120, 5, 3)
+        );
+        assert_eq!(energy.duration, 100);
+    }
+
+    #[test]
+    fn test_unknown_units() {
+        let mut sample = RawSample {
+            duration: 100,
+            values: vec![value("CPU Energy", "mJ", 120), value("DRAM", "pJ", 1)],
+            ..Default::default()
+        };
+        assert_eq!(sample.energy().unwrap().cpu_energy, 120);
+
+        sample.values.push(value("GPU Energy", "pJ", 1));
+        assert!(matches!(
+            sample.energy(),
+            Err(IOReportError::UnknownUnit(unit)) if unit == "pJ"
+        ));
+    }
+
+    #[test]
+    fn test_package_energy() {
+        let sample = RawSample {
+            duration: 100,
+            values: vec![
+                value("CPU Energy", "mJ", 120),
+                // part of the CPU Energy
+                value("PCPU", "mJ", 100),
+                value("GPU Energy", "nJ", 5_000_000),
+                value("ANE0", "uJ", 3_000),
+                value("DRAM", "mJ", 7),
+                value("DISP", "mJ", 4),
+                value("AMCC", "pJ", 1),
+            ],
+            ..Default::default()
+        };
+        let energy = sample.energy().unwrap();
+        assert_eq!(energy.package_energy, Some(120 + 5 + 3 + 7 + 4));
+        assert_eq!(energy.package_power(), Some(1390.0));
+    }
+
+    #[test]
+    fn test_display_energy() {
+        let mut sample = RawSample {
+            duration: 100,
+            values: vec![value("CPU Energy", "mJ", 120)],
+            ..Default::default()
+        };
+        assert_eq!(sample.energy().unwrap().display_energy, None);
+
+        sample
+            .values
+            .extend([value("DISP", "uJ", 30_000), value("DISPEXT", "mJ", 5)]);
+        let energy = sample.energy().unwrap();
+        assert_eq!(energy.display_energy, Some(35));
+        assert_eq!(energy.cpu_energy, 120);
+    }
+
+    #[test]
+    fn test_memory_traffic() {
+        let amc = |channel: &str, unit: &str, value: i64| RawChannelValue {
+            group: "AMC Stats".into(),
+            subgroup: "Perf Counters".into(),
+            channel: channel.into(),
+            unit: unit.into(),
+            value,
+        };
+        let mut sample = RawSample {
+            duration: 100,
+            values: vec![value("CPU Energy", "mJ", 120)],
+            ..Default::default()
+        };
+        assert_eq!(sample.energy().unwrap().memory_traffic, None);
+
+        sample.values.extend([
+            amc("DCS RD", "B", 3_000_000),
+            amc("DCS WR", "KB", 1_000),
+            // already part of the DCS totals
+            amc("GFX RD", "B", 1_000_000),
+        ]);
+        let energy = sample.energy().unwrap();
+        assert_eq!(
+            energy.memory_traffic,
+            Some(MemoryTraffic {
+                read: 3_000_000,
+                write: 1_024_000
+            })
+        );
+        // 4.024 MB over 100 ms
+        assert_eq!(energy.memory_bandwidth(), Some(40_240_000.0));
+    }
+
+    #[test]
+    fn test_cpu_residency() {
+        let cluster = |channel: &str, states: &[(&str, i64)]| RawStateChannel {
+            group: "CPU Stats".into(),
+            subgroup: CLUSTER_STATES.into(),
+            channel: channel.into(),
+            states: states
+                .iter()
+                .map(|&(name, residency)| (name.into(), residency))
+                .collect(),
+        };
+        let mut sample = RawSample {
+            duration: 100,
+            values: vec![value("CPU Energy", "mJ", 120)],
+            ..Default::default()
+        };
+        assert_eq!(sample.energy().unwrap().cpu_residency, None);
+
+        sample.states.extend([
+            cluster("ECPU", &[("IDLE", 50), ("V0P6", 50)]),
+            cluster("PCPU", &[("IDLE", 0), ("V0P5", 25), ("V1P5", 75)]),
+        ]);
+        let residency = sample.energy().unwrap().cpu_residency.unwrap();
+        assert_eq!(residency.active, 1.0);
+        assert_eq!(residency.performance_state, 0.75);
+    }
+
+    #[test]
+    fn test_negative_delta() {
+        let sample = RawSample {
+            duration: 100,
+            values: vec![value("CPU Energy", "mJ", -5), value("DRAM", "mJ", -1)],
+            ..Default::default()
+        };
+        assert!(matches!(
+            sample.energy(),
+            Err(IOReportError::InvalidDelta { channel, duration: 100 }) if channel == "CPU Energy"
+        ));
+    }
+}
@@ -0,0 +1,328 @@
+//! Linux hwmon backend, the only Linux backend: there is no RAPL/powercap one.
+//!
+//! Reads cumulative energy counters (`energyN_input`, microjoules) or instantaneous
+//! power sensors (`powerN_input`/`powerN_average`, microwatts) exposed under
+//! `/sys/class/hwmon` by `amd_energy`, `zenpower`, `fam15h_power`, GPU drivers and
+//! generic power meters such as `acpi_power_meter`.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::backend::{Capabilities, ChannelInfo, PowerBackend};
+use crate::sampler::{EnergySample, SamplerError};
+
+const HWMON_ROOT: &str = "/sys/class/hwmon";
+
+#[derive(Debug, thiserror::Error)]
+pub enum HwmonError {
+    #[error("No supported hwmon energy or power sensors found")]
+    NoSensors,
+    #[error("Failed to read hwmon sensor: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("hwmon counter {channel} went backwards")]
+    InvalidDelta { channel: String, duration: u64 },
+}
+
+type Result<T> = std::result::Result<T, HwmonError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HwmonUnit {
+    Cpu,
+    Gpu,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HwmonKind {
+    /// Monotonic counter in microjoules.
+    Energy,
+    /// Instantaneous reading in microwatts.
+    Power,
+}
+
+#[derive(Debug, Clone)]
+pub struct HwmonChannel {
+    pub device: String,
+    pub label: String,
+    pub unit: HwmonUnit,
+    pub kind: HwmonKind,
+    path: PathBuf,
+}
+
+impl HwmonChannel {
+    fn read(&self) -> Result<u64> {
+        let raw = std::fs::read_to_string(&self.path)?;
+        raw.trim()
+            .parse()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, raw).into())
+    }
+}
+
+/// Which compute unit a hwmon device measures. Devices of GPU drivers measure the GPU,
+/// any other, e.g. `amd_energy` or `acpi_power_meter`, counts as CPU/package.
+fn device_unit(name: &str) -> HwmonUnit {
+    match name {
+        "amdgpu" | "nouveau" | "i915" | "xe" => HwmonUnit::Gpu,
+        _ => HwmonUnit::Cpu,
+    }
+}
+
+/// Whether a channel counts towards the unit total. `amd_energy` reports per-core
+/// counters alongside the socket counters that already include them.
+fn counts_towards_total(device: &str, label: &str) -> bool {
+    device != "amd_energy" || label.starts_with("Esocket")
+}
+
+fn read_label(dir: &Path, prefix: &str) -> String {
+    std::fs::read_to_string(dir.join(format!("{prefix}_label")))
+        .map(|l| l.trim().to_string())
+        .unwrap_or_else(|_| prefix.to_string())
+}
+
+fn discover(root: &Path) -> Result<Vec<HwmonChannel>> {
+    let mut channels = Vec::new();
+    for entry in std::fs::read_dir(root)? {
+        let dir = entry?.path();
+        let Ok(device) = std::fs::read_to_string(dir.join("name")) else {
+            continue;
+        };
+        let device = device.trim().to_string();
+        let unit = device_unit(&device);
+
+        let mut files: Vec<String> = std::fs::read_dir(&dir)?
+            .filter_map(|e| e.ok()?.file_name().into_string().ok())
+            .collect();
+        files.sort();
+        for file in files {
+            let (prefix, kind) = if let Some(p) = file.strip_suffix("_input") {
+                if p.starts_with("energy") {
+                    (p, HwmonKind::Energy)
+                } else if p.starts_with("power") {
+                    (p, HwmonKind::Power)
+                } else {
+                    continue;
+                }
+            } else if let Some(p) = file.strip_suffix("_average") {
+                // only used when the sensor has no instantaneous reading
+                if !p.starts_with("power") || dir.join(format!("{p}_input")).exists() {
+                    continue;
+                }
+                (p, HwmonKind::Power)
+            } else {
+                continue;
+            };
+
+            let label = read_label(&dir, prefix);
+            if !counts_towards_total(&device, &label) {
+                continue;
+            }
+            channels.push(HwmonChannel {
+                device: device.clone(),
+                label,
+                unit,
+                kind,
+                path: dir.join(&file),
+            });
+        }
+    }
+
+    if channels.is_empty() {
+        return Err(HwmonError::NoSensors);
+    }
+    Ok(channels)
+}
+
+/// # Hwmon
+///
+/// Samples CPU (and GPU, where the driver reports it) energy from Linux hwmon sensors.
+#[derive(Debug)]
+pub struct Hwmon {
+    channels: Vec<HwmonChannel>,
+    previous: Option<(Vec<u64>, Instant)>,
+}
+
+impl Hwmon {
+    pub fn new() -> Result<Self> {
+        Self::with_root(HWMON_ROOT)
+    }
+
+    /// Discover sensors below `root` instead of `/sys/class/hwmon`.
+    pub fn with_root<P: AsRef<Path>>(root: P) -> Result<Self> {
+        Ok(Self {
+            channels: discover(root.as_ref())?,
+            previous: None,
+        })
+    }
+
+    pub fn channels(&self) -> &[HwmonChannel] {
+        &self.channels
+    }
+
+    fn read_all(&self) -> Result<(Vec<u64>, Instant)> {
+        let values = self
+            .channels
+            .iter()
+            .map(HwmonChannel::read)
+            .collect::<Result<Vec<_>>>()?;
+        Ok((values, Instant::now()))
+    }
+
+    pub fn sample(&mut self, duration: Duration) -> Result<EnergySample> {
+        let prev = match self.previous.take() {
+            Some(x) => x,
+            None => self.read_all()?,
+        };
+
+        std::thread::sleep(duration);
+
+        let next = self.read_all()?;
+        let elapsed = next.1.duration_since(prev.1);
+        let mut sample = EnergySample {
+            duration: (elapsed.as_millis() as u64).max(1),
+            ..Default::default()
+        };
+
+        let mut invalid = None;
+        for (i, channel) in self.channels.iter().enumerate() {
+            let micro_joules = match channel.kind {
+                HwmonKind::Energy => match next.0[i].checked_sub(prev.0[i]) {
+                    Some(delta) => delta as u128,
+                    None => {
+                        invalid = Some(format!("{}/{}", channel.device, channel.label));
+                        continue;
+                    }
+                },
+                // trapezoidal integration of µW over the interval
+                HwmonKind::Power => {
+                    let micro_watts = (prev.0[i] as u128 + next.0[i] as u128) / 2;
+                    micro_watts * elapsed.as_micros() / 1_000_000
+                }
+            };
+            let milli_joules = micro_joules / 1_000;
+            match channel.unit {
+                HwmonUnit::Cpu => sample.cpu_energy += milli_joules,
+                HwmonUnit::Gpu => sample.gpu_energy += milli_joules,
+            }
+        }
+
+        // a wrapped or reset counter is measured from its new value next time
+        self.previous = Some(next);
+        match invalid {
+            Some(channel) => Err(HwmonError::InvalidDelta {
+                channel,
+                duration: sample.duration,
+            }),
+            None => Ok(sample),
+        }
+    }
+}
+
+impl PowerBackend for Hwmon {
+    fn sample(&mut self, duration: Duration) -> std::result::Result<EnergySample, SamplerError> {
+        Ok(Hwmon::sample(self, duration)?)
+    }
+
+    fn channels(&self) -> Vec<ChannelInfo> {
+        self.channels
+            .iter()
+            .map(|c| ChannelInfo {
+                group: "hwmon".to_string(),
+                subgroup: c.device.clone(),
+                name: c.label.clone(),
+                unit: match c.kind {
+                    HwmonKind::Energy => "uJ".to_string(),
+                    HwmonKind::Power => "uW".to_string(),
+                },
+            })
+            .collect()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            cpu: self.channels.iter().any(|c| c.unit == HwmonUnit::Cpu),
+            gpu: self.channels.iter().any(|c| c.unit == HwmonUnit::Gpu),
+            ane: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, file: &str, contents: &str) {
+        std::fs::write(dir.join(file), contents).unwrap();
+    }
+
+    #[test]
+    fn test_discover_amd_energy_sockets_only() {
+        let root = std::env::temp_dir().join(format!("wattkit-hwmon-{}", std::process::id()));
+        let cpu = root.join("hwmon0");
+        let other = root.join("hwmon1");
+        std::fs::create_dir_all(&cpu).unwrap();
+        std::fs::create_dir_all(&other).unwrap();
+        write(&cpu, "name", "amd_energy\n");
+        write(&cpu, "energy1_input", "1000\n");
+        write(&cpu, "energy1_label", "Ecore000\n");
+        write(&cpu, "energy2_input", "5000\n");
+        write(&cpu, "energy2_label", "Esocket0\n");
+        write(&other, "name", "nvme\n");
+        write(&other, "temp1_input", "40000\n");
+
+        let hwmon = Hwmon::with_root(&root).unwrap();
+        let channels = hwmon.channels();
+        assert_eq!(channels.len(), 1);
+        assert_eq!(channels[0].label, "Esocket0");
+        assert_eq!(channels[0].unit, HwmonUnit::Cpu);
+        assert_eq!(channels[0].kind, HwmonKind::Energy);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_discover_generic_power_meter() {
+        let root = std::env::temp_dir().join(format!("wattkit-hwmon-meter-{}", std::process::id()));
+        let meter = root.join("hwmon0");
+        let gpu = root.join("hwmon1");
+        std::fs::create_dir_all(&meter).unwrap();
+        std::fs::create_dir_all(&gpu).unwrap();
+        write(&meter, "name", "acpi_power_meter\n");
+        write(&meter, "power1_average", "120000000\n");
+        write(&gpu, "name", "amdgpu\n");
+        write(&gpu, "power1_average", "15000000\n");
+        write(&gpu, "temp1_input", "50000\n");
+
+        let hwmon = Hwmon::with_root(&root).unwrap();
+        let units: Vec<_> = hwmon
+            .channels()
+            .iter()
+            .map(|c| (c.device.as_str(), c.unit, c.kind))
+            .collect();
+        assert_eq!(units.len(), 2);
+        assert!(units.contains(&("acpi_power_meter", HwmonUnit::Cpu, HwmonKind::Power)));
+        assert!(units.contains(&("amdgpu", HwmonUnit::Gpu, HwmonKind::Power)));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_counter_reset_is_invalid() {
+        let root = std::env::temp_dir().join(format!("wattkit-hwmon-reset-{}", std::process::id()));
+        let cpu = root.join("hwmon0");
+        std::fs::create_dir_all(&cpu).unwrap();
+        write(&cpu, "name", "zenpower\n");
+        write(&cpu, "energy1_input", "5000000\n");
+
+        let mut hwmon = Hwmon::with_root(&root).unwrap();
+        hwmon.sample(Duration::ZERO).unwrap();
+        write(&cpu, "energy1_input", "1000\n");
+        assert!(matches!(
+            hwmon.sample(Duration::ZERO),
+            Err(HwmonError::InvalidDelta { .. })
+        ));
+        // measured from the reset value afterwards
+        write(&cpu, "energy1_input", "3001000\n");
+        assert_eq!(hwmon.sample(Duration::ZERO).unwrap().cpu_energy, 3000);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,414 @@
+//! macOS backend reading the "Energy Model" IOReport channel group.
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::backend::capture::{CaptureError, CaptureWriter};
+use crate::backend::{Capabilities, ChannelInfo, PowerBackend};
+use crate::io_report::{
+    split_die, IOReportChannelGroup, IOReportChannelName, IOReportError, CLUSTER_STATES,
+};
+use crate::sampler::{EnergySample, SamplerError};
+
+#[cfg(all(target_os = "macos", feature = "macos"))]
+mod sys {
+    use super::{ChannelSet, GroupProbe, PROBED_GROUPS};
+    use crate::backend::capture::{RawChannelValue, RawSample, RawStateChannel};
+    use crate::backend::ChannelInfo;
+    use crate::cf_utils::autoreleasepool;
+    use crate::io_report::{IOReport, IOReportChannelName, IOReportChannelRequest, IOReportError};
+    use std::time::Duration;
+
+    #[derive(Debug)]
+    pub struct Report(IOReport);
+
+    impl Report {
+        pub fn open(channels: ChannelSet) -> Result<(Self, Vec<ChannelInfo>), IOReportError> {
+            let requests = channels
+                .groups()
+                .into_iter()
+                .map(|(group, subgroup)| IOReportChannelRequest::new(group, subgroup))
+                .collect();
+            let report = match IOReport::new(requests) {
+                Ok(report) => report,
+                // not every SoC and macOS version has the AMC Stats group or the
+                // cluster states, energy alone is still worth sampling
+                Err(_) if channels.memory_bandwidth => {
+                    return Self::open(ChannelSet {
+                        memory_bandwidth: false,
+                        ..channels
+                    })
+                }
+                Err(_) if channels.residency => return Self::open(ChannelSet::ENERGY),
+                Err(e) => return Err(e),
+            };
+            let channels = report
+                .channels()?
+                .map(|entry| ChannelInfo {
+                    group: entry.group.to_string(),
+                    subgroup: entry.subgroup.to_string(),
+                    name: entry.channel.to_string(),
+                    unit: entry.unit.to_string(),
+                })
+                .collect();
+            Ok((Self(report), channels))
+        }
+
+        pub fn raw_sample(&mut self, duration: Duration) -> Result<RawSample, IOReportError> {
+            // a fresh pool per read keeps multi-hour sessions from growing
+            autoreleasepool(|| self.read(duration))
+        }
+
+        fn read(&mut self, duration: Duration) -> Result<RawSample, IOReportError> {
+            let mut raw = RawSample::default();
+            for mut sample in self.0.get_samples(duration, 1)? {
+                raw.duration += sample.duration();
+                for entry in sample.iterator_mut() {
+                    if !entry.states.is_empty() {
+                        raw.states.push(RawStateChannel {
+                            group: entry.group,
+                            subgroup: entry.subgroup,
+                            channel: entry.channel,
+                            states: entry.states,
+                        });
+                        continue;
+                    }
+                    raw.values.push(RawChannelValue {
+                        group: entry.group,
+                        subgroup: entry.subgroup,
+                        channel: entry.channel,
+                        unit: entry.unit,
+                        value: entry.value,
+                    });
+                }
+            }
+            Ok(raw)
+        }
+    }
+
+    pub fn probe_groups(duration: Duration) -> Vec<GroupProbe> {
+        autoreleasepool(|| {
+            let mut reports: Vec<_> = PROBED_GROUPS
+                .into_iter()
+                .map(|group| {
+                    let name = group.as_str().to_string();
+                    let report = IOReport::new(vec![IOReportChannelRequest::new(
+                        group,
+                        None as Option<IOReportChannelName>,
+                    )])
+                    .ok();
+                    (name, report)
+                })
+                .collect();
+            // every group is read over the same interval, priming them all first
+            for (_, report) in reports.iter_mut() {
+                if report
+                    .as_mut()
+                    .is_some_and(|r| r.get_samples(Duration::ZERO, 1).is_err())
+                {
+                    *report = None;
+                }
+            }
+            std::thread::sleep(duration);
+            reports
+                .into_iter()
+                .map(|(group, report)| {
+                    let mut probe = GroupProbe {
+                        group,
+                        subscribed: report.is_some(),
+                        channels: 0,
+                        active: 0,
+                    };
+                    let samples = report.and_then(|mut r| r.get_samples(Duration::ZERO, 1).ok());
+                    for mut sample in samples.into_iter().flatten() {
+                        for entry in sample.iterator_mut() {
+                            probe.channels += 1;
+                            probe.active += (entry.value != 0) as usize;
+                        }
+                    }
+                    probe
+                })
+                .collect()
+        })
+    }
+}
+
+#[cfg(not(all(target_os = "macos", feature = "macos")))]
+mod sys {
+    use super::{ChannelSet, GroupProbe};
+    use crate::backend::capture::RawSample;
+    use crate::backend::ChannelInfo;
+    use crate::io_report::IOReportError;
+    use std::time::Duration;
+
+    #[derive(Debug)]
+    pub enum Report {}
+
+    impl Report {
+        pub fn open(_channels: ChannelSet) -> Result<(Self, Vec<ChannelInfo>), IOReportError> {
+            Err(IOReportError::Unavailable)
+        }
+
+        pub fn raw_sample(&mut self, _duration: Duration) -> Result<RawSample, IOReportError> {
+            match *self {}
+        }
+    }
+
+    pub fn probe_groups(_duration: Duration) -> Vec<GroupProbe> {
+        Vec::new()
+    }
+}
+
+/// IOReport groups a backend subscribes to. Subscriptions are per group or subgroup,
+/// the Energy Model group is always subscribed to, the others only fill optional sample
+/// fields and cost a little per read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelSet {
+    /// CPU cluster performance states, [`EnergySample::cpu_residency`].
+    pub residency: bool,
+    /// Memory controller traffic, [`EnergySample::memory_traffic`].
+    pub memory_bandwidth: bool,
+}
+
+impl ChannelSet {
+    /// Energy channels alone, the smallest subscription.
+    pub const ENERGY: ChannelSet = ChannelSet {
+        residency: false,
+        memory_bandwidth: false,
+    };
+
+    /// Group and subgroup of every subscription request.
+    #[cfg_attr(not(all(target_os = "macos", feature = "macos")), allow(dead_code))]
+    fn groups(&self) -> Vec<(IOReportChannelGroup, Option<&'static str>)> {
+        let mut groups = vec![(IOReportChannelGroup::EnergyModel, None)];
+        if self.residency {
+            groups.push((IOReportChannelGroup::CPUStats, Some(CLUSTER_STATES)));
+        }
+        if self.memory_bandwidth {
+            groups.push((IOReportChannelGroup::AMCStats, None));
+        }
+        groups
+    }
+}
+
+/// Energy and cluster residency.
+impl Default for ChannelSet {
+    fn default() -> Self {
+        ChannelSet {
+            residency: true,
+            memory_bandwidth: false,
+        }
+    }
+}
+
+/// Groups [`IOReportBackend::probe_groups`] checks, those wattkit reads and their
+/// neighbours, which need the same access.
+#[cfg_attr(not(all(target_os = "macos", feature = "macos")), allow(dead_code))]
+const PROBED_GROUPS: [IOReportChannelGroup; 6] = [
+    IOReportChannelGroup::EnergyModel,
+    IOReportChannelGroup::CPUStats,
+    IOReportChannelGroup::GPUStats,
+    IOReportChannelGroup::AMCStats,
+    IOReportChannelGroup::SoCStats,
+    IOReportChannelGroup::H11ANE,
+];
+
+/// Whether the process could subscribe to an IOReport group, and whether its channels
+/// moved. Some macOS versions let unprivileged processes subscribe but only report zeros.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupProbe {
+    pub group: String,
+    pub subscribed: bool,
+    pub channels: usize,
+    /// Channels that changed while probing. Idle units, e.g. the ANE, stay at zero with
+    /// full access too.
+    pub active: usize,
+}
+
+/// Energy Model channels in `channels` that none of the typed channels cover, e.g. one a
+/// macOS update renamed. They still count towards the package energy. Per-core channels
+/// like "ECPU0" are listed too, they have no typed channel of their own.
+pub fn unmapped_channels(channels: &[ChannelInfo]) -> Vec<&ChannelInfo> {
+    channels
+        .iter()
+        .filter(|c| {
+            matches!(
+                IOReportChannelGroup::from(&c.group),
+                IOReportChannelGroup::EnergyModel
+            ) && matches!(
+                IOReportChannelName::from(split_die(&c.name).1),
+                IOReportChannelName::Unknown(_)
+            )
+        })
+        .collect()
+}
+
+/// Units the Energy Model channels in `channels` measure, the channels named as
+/// [`EnergyModelSample::parse`](crate::EnergyModelSample::parse) reads them, so "ANE0"
+/// and the "DIE_1_" channels of an Ultra count.
+fn capabilities_of(channels: &[ChannelInfo]) -> Capabilities {
+    let names: Vec<IOReportChannelName> = channels
+        .iter()
+        .filter(|c| {
+            matches!(
+                IOReportChannelGroup::from(&c.group),
+                IOReportChannelGroup::EnergyModel
+            )
+        })
+        .map(|c| IOReportChannelName::from(split_die(&c.name).1))
+        .collect();
+    let has = |name: IOReportChannelName| names.contains(&name);
+    Capabilities {
+        cpu: has(IOReportChannelName::CPUEnergy),
+        gpu: has(IOReportChannelName::GPUEnergy),
+        ane: has(IOReportChannelName::ANE),
+    }
+}
+
+/// # IOReportBackend
+///
+/// Samples CPU, GPU and ANE energy of Apple Silicon through IOReport. Not `Send`, see
+/// [`PowerBackend`].
+///
+/// Subscriptions are kept when the backend drops, opening another one for the same
+/// channels, e.g. in the next session of a test suite, skips setting them up again.
+#[derive(Debug)]
+pub struct IOReportBackend {
+    report: sys::Report,
+    channels: Vec<ChannelInfo>,
+    capture: Option<CaptureWriter>,
+}
+
+impl IOReportBackend {
+    pub fn new() -> Result<Self, IOReportError> {
+        Self::with_channels(ChannelSet::default())
+    }
+
+    /// Also reads the memory controllers' traffic into [`EnergySample::memory_traffic`],
+    /// where IOReport has the "AMC Stats" group. Samples have none elsewhere.
+    pub fn with_memory_bandwidth() -> Result<Self, IOReportError> {
+        Self::with_channels(ChannelSet {
+            memory_bandwidth: true,
+            ..Default::default()
+        })
+    }
+
+    /// Subscribes to `channels` only. Groups the machine lacks are dropped, energy is
+    /// always read.
+    pub fn with_channels(channels: ChannelSet) -> Result<Self, IOReportError> {
+        let (report, channels) = sys::Report::open(channels)?;
+        Ok(Self {
+            report,
+            channels,
+            capture: None,
+        })
+    }
+
+    /// Subscribes to each of the groups wattkit reads, or may read, and reads them over
+    /// `duration`. Empty off macOS.
+    pub fn probe_groups(duration: Duration) -> Vec<GroupProbe> {
+        sys::probe_groups(duration)
+    }
+
+    /// Also writes the raw channel deltas of every sample to a capture at `path`,
+    /// see [`crate::backend::capture::CaptureReplay`].
+    pub fn record_to<P: AsRef<Path>>(mut self, path: P) -> Result<Self, CaptureError> {
+        self.capture = Some(CaptureWriter::create(path)?);
+        Ok(self)
+    }
+}
+
+impl PowerBackend for IOReportBackend {
+    fn sample(&mut self, duration: Duration) -> Result<EnergySample, SamplerError> {
+        let raw = self.report.raw_sample(duration)?;
+        if let Some(capture) = self.capture.as_mut() {
+            capture.write(&raw)?;
+        }
+        Ok(raw.energy()?)
+    }
+
+    fn channels(&self) -> Vec<ChannelInfo> {
+        self.channels.clone()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        capabilities_of(&self.channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_set() {
+        let groups = |set: ChannelSet| -> Vec<String> {
+            set.groups()
+                .iter()
+                .map(|(group, subgroup)| format!("{}/{}", group.as_str(), subgroup.unwrap_or("")))
+                .collect()
+        };
+        assert_eq!(groups(ChannelSet::ENERGY), ["Energy Model/"]);
+        assert_eq!(
+            groups(ChannelSet::default()),
+            ["Energy Model/", "CPU Stats/CPU Complex Performance States"]
+        );
+        let memory = ChannelSet {
+            memory_bandwidth: true,
+            ..ChannelSet::ENERGY
+        };
+        assert_eq!(groups(memory), ["Energy Model/", "AMC Stats/"]);
+    }
+
+    #[test]
+    fn test_unmapped_channels() {
+        let channel = |group: &str, name: &str| ChannelInfo {
+            group: group.to_string(),
+            subgroup: String::new(),
+            name: name.to_string(),
+            unit: "mJ".to_string(),
+        };
+        let channels = [
+            channel("Energy Model", "DIE_1_CPU Energy"),
+            channel("Energy Model", "ANE0_1"),
+            channel("Energy Model", "PACC0_CPU"),
+            channel("Energy Model", "ISP"),
+            channel("CPU Stats", "CPU Core Performance States"),
+        ];
+        let unmapped: Vec<_> = unmapped_channels(&channels)
+            .into_iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(unmapped, ["ISP"]);
+    }
+
+    #[test]
+    fn test_capabilities_of() {
+        let channel = |group: &str, name: &str| ChannelInfo {
+            group: group.to_string(),
+            subgroup: String::new(),
+            name: name.to_string(),
+            unit: "mJ".to_string(),
+        };
+        let pro = [
+            channel("Energy Model", "CPU Energy"),
+            channel("Energy Model", "GPU Energy"),
+            channel("Energy Model", "ANE0"),
+        ];
+        let all = Capabilities {
+            cpu: true,
+            gpu: true,
+            ane: true,
+        };
+        assert_eq!(capabilities_of(&pro), all);
+        let ultra = [
+            channel("Energy Model", "DIE_0_CPU Energy"),
+            channel("Energy Model", "DIE_1_CPU Energy"),
+            channel("Energy Model", "DIE_1_GPU Energy"),
+            channel("Energy Model", "ANE0_1"),
+        ];
+        assert_eq!(capabilities_of(&ultra), all);
+        let other_group = [channel("CPU Stats", "CPU Energy")];
+        assert_eq!(capabilities_of(&other_group), Capabilities::default());
+    }
+}
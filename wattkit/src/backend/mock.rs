@@ -0,0 +1,222 @@
+//! Backend replaying recorded or synthetic samples, for tests and CI machines
+//! without access to the hardware counters.
+
+use std::time::Duration;
+
+use crate::backend::{Capabilities, ChannelInfo, PowerBackend};
+use crate::sampler::{EnergySample, SamplerError};
+
+const ALL_UNITS: Capabilities = Capabilities {
+    cpu: true,
+    gpu: true,
+    ane: true,
+};
+
+#[derive(Debug, Clone)]
+enum Source {
+    Replay {
+        samples: Vec<EnergySample>,
+        next: usize,
+    },
+    /// mW drawn by the CPU, GPU and ANE
+    Constant { cpu: f64, gpu: f64, ane: f64 },
+}
+
+/// # MockBackend
+///
+/// Replays a fixed sample stream or synthesizes samples at a constant power.
+#[derive(Debug, Clone)]
+pub struct MockBackend {
+    source: Source,
+    realtime: bool,
+    capabilities: Capabilities,
+}
+
+impl MockBackend {
+    /// Replays `samples` in order, their durations are kept. Once exhausted every read
+    /// fails with [`SamplerError::SamplesNotAvailable`], which ends a sampling session.
+    ///
+    /// Does not sleep unless [`MockBackend::realtime`] is set.
+    pub fn replay<S: Into<Vec<EnergySample>>>(samples: S) -> Self {
+        MockBackend {
+            source: Source::Replay {
+                samples: samples.into(),
+                next: 0,
+            },
+            realtime: false,
+            capabilities: ALL_UNITS,
+        }
+    }
+
+    /// Endless stream drawing a constant power, in milliwatts, on every unit.
+    ///
+    /// Sleeps for each requested duration unless [`MockBackend::realtime`] is unset.
+    pub fn constant(cpu_power: f64, gpu_power: f64, ane_power: f64) -> Self {
+        MockBackend {
+            source: Source::Constant {
+                cpu: cpu_power,
+                gpu: gpu_power,
+                ane: ane_power,
+            },
+            realtime: true,
+            capabilities: ALL_UNITS,
+        }
+    }
+
+    /// Pretend only the units in `capabilities` have channels.
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Whether each read sleeps for the sample's duration like a hardware backend.
+    pub fn realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+        self
+    }
+}
+
+impl PowerBackend for MockBackend {
+    fn sample(&mut self, duration: Duration) -> Result<EnergySample, SamplerError> {
+        let sample = match &mut self.source {
+            Source::Replay { samples, next } => {
+                let sample = samples
+                    .get(*next)
+                    .cloned()
+                    .ok_or(SamplerError::SamplesNotAvailable)?;
+                *next += 1;
+                sample
+            }
+            Source::Constant { cpu, gpu, ane } => {
+                // mW * s == mJ
+                let energy = |power: f64| (power * duration.as_secs_f64()).round() as u128;
+                EnergySample {
+                    cpu_energy: energy(*cpu),
+                    gpu_energy: energy(*gpu),
+                    ane_energy: energy(*ane),
+                    duration: duration.as_millis() as u64,
+                    ..Default::default()
+                }
+            }
+        };
+
+        if self.realtime {
+            std::thread::sleep(Duration::from_millis(sample.duration));
+        }
+        Ok(sample)
+    }
+
+    fn channels(&self) -> Vec<ChannelInfo> {
+        let c = self.capabilities;
+        [(c.cpu, "CPU Energy"), (c.gpu, "GPU Energy"), (c.ane, "ANE")]
+            .into_iter()
+            .filter(|(available, _)| *available)
+            .map(|(_, name)| ChannelInfo {
+                group: "Mock".to_string(),
+                subgroup: String::new(),
+                name: name.to_string(),
+                unit: "mJ".to_string(),
+            })
+            .collect()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::{PowerProfile, Sampling, StartStopSampler};
+
+    fn sample(cpu_energy: u128, duration: u64) -> EnergySample {
+        EnergySample {
+            cpu_energy,
+            duration,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_constant_power() {
+        let mut backend = MockBackend::constant(2000.0, 500.0, 0.0).realtime(false);
+        let samples = backend.get_samples(Duration::from_secs(1), 4).unwrap();
+        let profile = PowerProfile::from(&samples);
+        assert_eq!(profile.total_cpu_energy, 2000);
+        assert_eq!(profile.total_gpu_energy, 500);
+        assert_eq!(profile.average_cpu_power, 2000);
+        assert_eq!(profile.total_duration, 1000);
+        assert!(matches!(
+            backend.get_samples(Duration::from_secs(1), 0),
+            Err(SamplerError::IntervalTooShort(d)) if d.is_zero()
+        ));
+    }
+
+    #[test]
+    fn test_replay_through_sampler() {
+        let recorded = vec![sample(10, 100), sample(30, 100), sample(20, 200)];
+        let replay = recorded.clone();
+        let mut sampler =
+            StartStopSampler::with_backend(move || Ok(MockBackend::replay(replay.clone())));
+        sampler.start(Duration::from_millis(100), 1).unwrap();
+        // the replay runs dry long before this
+        std::thread::sleep(Duration::from_millis(100));
+        sampler.stop().unwrap();
+
+        let samples = sampler.samples().unwrap();
+        let timestamps: Vec<u64> = samples.iter().map(|s| s.timestamp).collect();
+        assert_eq!(timestamps, vec![0, 100, 200]);
+        assert_eq!(sampler.profile().unwrap().total_cpu_energy, 60);
+        assert!(sampler.capabilities().unwrap().ane);
+        // running dry is reported like any failing read
+        assert!(matches!(
+            sampler.error(),
+            Some(SamplerError::SamplesNotAvailable)
+        ));
+    }
+
+    #[test]
+    fn test_implausible_samples_discarded() {
+        let mut sampler = StartStopSampler::with_backend(|| {
+            Ok(MockBackend::replay(vec![
+                sample(10, 100),
+                sample(1_000_000_000, 100),
+                sample(10, 100),
+            ]))
+        });
+        sampler.start(Duration::from_millis(100), 1).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        sampler.stop().unwrap();
+
+        assert_eq!(sampler.discarded_samples(), 1);
+        let timestamps: Vec<u64> = sampler
+            .samples()
+            .unwrap()
+            .iter()
+            .map(|s| s.timestamp)
+            .collect();
+        assert_eq!(timestamps, vec![0, 200]);
+    }
+
+    #[test]
+    fn test_missing_channels() {
+        let mut sampler = StartStopSampler::with_backend(|| {
+            let backend = MockBackend::replay(vec![sample(10, 100)]).capabilities(Capabilities {
+                cpu: true,
+                gpu: true,
+                ane: false,
+            });
+            Ok(backend)
+        });
+        sampler.start(Duration::from_millis(100), 1).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        sampler.stop().unwrap();
+
+        assert_eq!(sampler.channels().len(), 2);
+        let profile = sampler.profile().unwrap();
+        assert!(profile.cpu_available && profile.gpu_available);
+        assert!(!profile.ane_available);
+        assert!(profile.to_string().contains("ANE Energy: n/a"));
+    }
+}
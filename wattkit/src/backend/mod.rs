@@ -0,0 +1,113 @@
+//! Platform energy sources the samplers are generic over.
+
+pub mod capture;
+pub mod hwmon;
+pub mod ioreport;
+pub mod mock;
+pub mod power_gadget;
+pub mod powermetrics;
+pub mod smc;
+
+use std::time::Duration;
+
+use crate::sampler::{EnergySample, SamplerError};
+
+/// One raw channel read by a backend.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelInfo {
+    pub group: String,
+    pub subgroup: String,
+    pub name: String,
+    pub unit: String,
+}
+
+/// Which compute units a backend reports energy for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub cpu: bool,
+    pub gpu: bool,
+    pub ane: bool,
+}
+
+/// # PowerBackend
+///
+/// A source of energy samples. Backends are opened on the sampling thread and
+/// never leave it, so they need not be `Send`.
+pub trait PowerBackend {
+    /// Waits `duration` and returns the energy used since the previous call, or since
+    /// the backend was opened. Timestamps are assigned by the sampler.
+    fn sample(&mut self, duration: Duration) -> Result<EnergySample, SamplerError>;
+
+    /// Takes `count` samples spread over `duration`.
+    fn get_samples(
+        &mut self,
+        duration: Duration,
+        count: usize,
+    ) -> Result<Vec<EnergySample>, SamplerError> {
+        let step = duration
+            .checked_div(count as u32)
+            .ok_or(SamplerError::IntervalTooShort(Duration::ZERO))?;
+        (0..count).map(|_| self.sample(step)).collect()
+    }
+
+    fn channels(&self) -> Vec<ChannelInfo>;
+
+    fn capabilities(&self) -> Capabilities;
+}
+
+impl<B: PowerBackend + ?Sized> PowerBackend for Box<B> {
+    fn sample(&mut self, duration: Duration) -> Result<EnergySample, SamplerError> {
+        (**self).sample(duration)
+    }
+
+    fn get_samples(
+        &mut self,
+        duration: Duration,
+        count: usize,
+    ) -> Result<Vec<EnergySample>, SamplerError> {
+        (**self).get_samples(duration, count)
+    }
+
+    fn channels(&self) -> Vec<ChannelInfo> {
+        (**self).channels()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        (**self).capabilities()
+    }
+}
+
+/// Opens a backend on the sampling thread.
+pub type BackendFactory<B> = std::sync::Arc<dyn Fn() -> Result<B, SamplerError> + Send + Sync>;
+
+#[cfg(target_os = "linux")]
+pub type DefaultBackend = hwmon::Hwmon;
+#[cfg(windows)]
+pub type DefaultBackend = power_gadget::PowerGadget;
+#[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+pub type DefaultBackend = smc::SmcBackend;
+#[cfg(not(any(
+    target_os = "linux",
+    windows,
+    all(target_os = "macos", target_arch = "x86_64")
+)))]
+pub type DefaultBackend = ioreport::IOReportBackend;
+
+/// IOReport on Apple Silicon Macs, the SMC on Intel Macs, hwmon sensors on Linux, Intel
+/// Power Gadget on Windows.
+pub fn open_default() -> Result<DefaultBackend, SamplerError> {
+    Ok(DefaultBackend::new()?)
+}
+
+/// [`open_default`], or a [`powermetrics::PowermetricsBackend`] where it fails, e.g.
+/// when IOReport can't be subscribed to. The error is the default backend's if neither
+/// opens, powermetrics needs root.
+pub fn open_with_fallback() -> Result<Box<dyn PowerBackend>, SamplerError> {
+    match open_default() {
+        Ok(backend) => Ok(Box::new(backend)),
+        Err(e) => match powermetrics::PowermetricsBackend::new() {
+            Ok(backend) => Ok(Box::new(backend)),
+            Err(_) => Err(e),
+        },
+    }
+}
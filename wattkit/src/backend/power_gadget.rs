@@ -0,0 +1,266 @@
+//! Windows backend using Intel Power Gadget.
+//!
+//! `EnergyLib64.dll` is loaded at runtime from `%IPG_Dir%` (set by the Power Gadget
+//! installer) or the DLL search path, and its cumulative per-MSR energy is sampled.
+//! The "Processor" MSR is the package, "GT" the integrated graphics inside it.
+
+use std::time::{Duration, Instant};
+
+use crate::backend::{Capabilities, ChannelInfo, PowerBackend};
+use crate::sampler::{EnergySample, SamplerError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PowerGadgetError {
+    #[error("Intel Power Gadget is only available on Windows")]
+    Unsupported,
+    #[error("Failed to load EnergyLib64.dll, is Intel Power Gadget installed?")]
+    LibraryNotFound,
+    #[error("EnergyLib64.dll is missing {0}")]
+    MissingSymbol(&'static str),
+    #[error("Intel Power Gadget call {0} failed")]
+    CallFailed(&'static str),
+    #[error("No package power MSR available")]
+    NoPackageMsr,
+    #[error("Intel Power Gadget energy counter went backwards")]
+    InvalidDelta { duration: u64 },
+}
+
+type Result<T> = std::result::Result<T, PowerGadgetError>;
+
+#[cfg(windows)]
+mod sys {
+    use super::{PowerGadgetError, Result};
+    use std::ffi::c_void;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LoadLibraryW(name: *const u16) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, name: *const u8) -> *mut c_void;
+    }
+
+    type Initialize = unsafe extern "C" fn() -> bool;
+    type GetCount = unsafe extern "C" fn(*mut i32) -> bool;
+    type GetMsrName = unsafe extern "C" fn(i32, *mut u16) -> bool;
+    type GetMsrFunc = unsafe extern "C" fn(i32, *mut i32) -> bool;
+    type ReadSample = unsafe extern "C" fn() -> bool;
+    type GetPowerData = unsafe extern "C" fn(i32, i32, *mut f64, *mut i32) -> bool;
+
+    const MSR_FUNC_POWER: i32 = 1;
+
+    pub struct EnergyLib {
+        read_sample: ReadSample,
+        get_power_data: GetPowerData,
+        pub nodes: i32,
+        /// (msr index, name) of every power MSR
+        pub power_msrs: Vec<(i32, String)>,
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    unsafe fn symbol<T: Copy>(module: *mut c_void, name: &'static str) -> Result<T> {
+        let cname = format!("{name}\0");
+        let ptr = unsafe { GetProcAddress(module, cname.as_ptr()) };
+        if ptr.is_null() {
+            return Err(PowerGadgetError::MissingSymbol(name));
+        }
+        Ok(unsafe { std::mem::transmute_copy(&ptr) })
+    }
+
+    fn check(ok: bool, call: &'static str) -> Result<()> {
+        ok.then_some(()).ok_or(PowerGadgetError::CallFailed(call))
+    }
+
+    impl EnergyLib {
+        pub fn load() -> Result<Self> {
+            let mut candidates = Vec::new();
+            if let Ok(dir) = std::env::var("IPG_Dir") {
+                candidates.push(format!("{dir}\\EnergyLib64.dll"));
+            }
+            candidates.push("EnergyLib64.dll".to_string());
+
+            let module = candidates
+                .iter()
+                .map(|path| unsafe { LoadLibraryW(wide(path).as_ptr()) })
+                .find(|module| !module.is_null())
+                .ok_or(PowerGadgetError::LibraryNotFound)?;
+
+            unsafe {
+                let initialize: Initialize = symbol(module, "IntelEnergyLibInitialize")?;
+                let get_num_nodes: GetCount = symbol(module, "GetNumNodes")?;
+                let get_num_msrs: GetCount = symbol(module, "GetNumMsrs")?;
+                let get_msr_name: GetMsrName = symbol(module, "GetMsrName")?;
+                let get_msr_func: GetMsrFunc = symbol(module, "GetMsrFunc")?;
+
+                check(initialize(), "IntelEnergyLibInitialize")?;
+                let mut nodes = 0;
+                check(get_num_nodes(&mut nodes), "GetNumNodes")?;
+                let mut msrs = 0;
+                check(get_num_msrs(&mut msrs), "GetNumMsrs")?;
+
+                let mut power_msrs = Vec::new();
+                for msr in 0..msrs {
+                    let mut func = -1;
+                    if !get_msr_func(msr, &mut func) || func != MSR_FUNC_POWER {
+                        continue;
+                    }
+                    let mut name = [0u16; 1024];
+                    check(get_msr_name(msr, name.as_mut_ptr()), "GetMsrName")?;
+                    let len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+                    power_msrs.push((msr, String::from_utf16_lossy(&name[..len])));
+                }
+
+                Ok(EnergyLib {
+                    read_sample: symbol(module, "ReadSample")?,
+                    get_power_data: symbol(module, "GetPowerData")?,
+                    nodes,
+                    power_msrs,
+                })
+            }
+        }
+
+        pub fn read_sample(&self) -> Result<()> {
+            check(unsafe { (self.read_sample)() }, "ReadSample")
+        }
+
+        /// Cumulative energy in joules of `msr` on `node`.
+        pub fn energy(&self, node: i32, msr: i32) -> Result<f64> {
+            let mut result = [0f64; 3];
+            let mut n = 0;
+            let ok = unsafe { (self.get_power_data)(node, msr, result.as_mut_ptr(), &mut n) };
+            check(ok && n >= 2, "GetPowerData")?;
+            Ok(result[1])
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod sys {
+    use super::{PowerGadgetError, Result};
+
+    pub struct EnergyLib {
+        pub nodes: i32,
+        pub power_msrs: Vec<(i32, String)>,
+    }
+
+    impl EnergyLib {
+        pub fn load() -> Result<Self> {
+            Err(PowerGadgetError::Unsupported)
+        }
+
+        pub fn read_sample(&self) -> Result<()> {
+            Err(PowerGadgetError::Unsupported)
+        }
+
+        pub fn energy(&self, _node: i32, _msr: i32) -> Result<f64> {
+            Err(PowerGadgetError::Unsupported)
+        }
+    }
+}
+
+/// # PowerGadget
+///
+/// Samples CPU package (and integrated GPU) energy on Intel CPUs under Windows.
+pub struct PowerGadget {
+    lib: sys::EnergyLib,
+    package_msr: i32,
+    gt_msr: Option<i32>,
+    previous: Option<(f64, f64, Instant)>,
+}
+
+impl std::fmt::Debug for PowerGadget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PowerGadget")
+            .field("nodes", &self.lib.nodes)
+            .field("power_msrs", &self.lib.power_msrs)
+            .finish()
+    }
+}
+
+impl PowerGadget {
+    pub fn new() -> Result<Self> {
+        let lib = sys::EnergyLib::load()?;
+        let find = |name: &str| {
+            lib.power_msrs
+                .iter()
+                .find(|(_, n)| n == name)
+                .map(|(msr, _)| *msr)
+        };
+        let package_msr = find("Processor").ok_or(PowerGadgetError::NoPackageMsr)?;
+        let gt_msr = find("GT");
+        Ok(Self {
+            lib,
+            package_msr,
+            gt_msr,
+            previous: None,
+        })
+    }
+
+    /// Cumulative (package, graphics) joules summed over all sockets.
+    fn read(&self) -> Result<(f64, f64, Instant)> {
+        self.lib.read_sample()?;
+        let mut package = 0.0;
+        let mut gt = 0.0;
+        for node in 0..self.lib.nodes {
+            package += self.lib.energy(node, self.package_msr)?;
+            if let Some(msr) = self.gt_msr {
+                gt += self.lib.energy(node, msr)?;
+            }
+        }
+        Ok((package, gt, Instant::now()))
+    }
+
+    pub fn sample(&mut self, duration: Duration) -> Result<EnergySample> {
+        let prev = match self.previous.take() {
+            Some(x) => x,
+            None => self.read()?,
+        };
+
+        std::thread::sleep(duration);
+
+        let next = self.read()?;
+        self.previous = Some(next);
+        let duration = (next.2.duration_since(prev.2).as_millis() as u64).max(1);
+
+        let (package, gpu) = (next.0 - prev.0, next.1 - prev.1);
+        if package < 0.0 || gpu < 0.0 {
+            return Err(PowerGadgetError::InvalidDelta { duration });
+        }
+        let (package, gpu) = ((package * 1000.0) as u128, (gpu * 1000.0) as u128);
+        Ok(EnergySample {
+            // the package counter includes the integrated graphics
+            cpu_energy: package.saturating_sub(gpu),
+            gpu_energy: gpu,
+            duration,
+            ..Default::default()
+        })
+    }
+}
+
+impl PowerBackend for PowerGadget {
+    fn sample(&mut self, duration: Duration) -> std::result::Result<EnergySample, SamplerError> {
+        Ok(PowerGadget::sample(self, duration)?)
+    }
+
+    fn channels(&self) -> Vec<ChannelInfo> {
+        self.lib
+            .power_msrs
+            .iter()
+            .map(|(_, name)| ChannelInfo {
+                group: "Intel Power Gadget".to_string(),
+                subgroup: String::new(),
+                name: name.clone(),
+                unit: "J".to_string(),
+            })
+            .collect()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            cpu: true,
+            gpu: self.gt_msr.is_some(),
+            ane: false,
+        }
+    }
+}
@@ -0,0 +1,367 @@
+//! macOS backend running `powermetrics --samplers cpu_power -f plist`, a fallback and a
+//! cross-check for when IOReport can't be subscribed to. powermetrics has to run as
+//! root, so the process using this backend does too.
+//!
+//! powermetrics writes one XML property list per interval, separated by NUL bytes. Its
+//! "processor" dict has the energy of each unit over the interval in millijoules, or
+//! their power in milliwatts on older releases.
+
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::time::Duration;
+
+use crate::backend::{Capabilities, ChannelInfo, PowerBackend};
+use crate::sampler::{EnergySample, SamplerError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PowermetricsError {
+    #[error("powermetrics not found, it ships with macOS")]
+    NotFound,
+    #[error("powermetrics has to run as root, e.g. with sudo")]
+    NotRoot,
+    #[error("Failed to start powermetrics: {0}")]
+    Spawn(#[from] std::io::Error),
+    #[error("powermetrics exited: {0}")]
+    Exited(String),
+    #[error("Malformed powermetrics output")]
+    Parse,
+    #[error("powermetrics output has no {0}")]
+    MissingKey(&'static str),
+}
+
+type Result<T> = std::result::Result<T, PowermetricsError>;
+
+const PROGRAM: &str = "/usr/bin/powermetrics";
+
+/// A value of an XML property list, the part of the format powermetrics writes.
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Dict(Vec<(String, Value)>),
+    Array(Vec<Value>),
+    Integer(i64),
+    Real(f64),
+    String(String),
+    Bool(bool),
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Dict(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(value) => Some(*value as f64),
+            Value::Real(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a property list document tag by tag. Attributes are ignored and only the
+/// predefined entities are unescaped.
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    /// Name of the next tag and whether it closes itself, skipping the XML declaration,
+    /// doctype and comments.
+    fn open(&mut self) -> Option<(&'a str, bool)> {
+        loop {
+            let start = self.rest.find('<')?;
+            let end = start + self.rest[start..].find('>')?;
+            let tag = &self.rest[start + 1..end];
+            self.rest = &self.rest[end + 1..];
+            if tag.starts_with('?') || tag.starts_with('!') {
+                continue;
+            }
+            let self_closing = tag.ends_with('/');
+            let name = tag.trim_end_matches('/').split_whitespace().next()?;
+            return Some((name, self_closing));
+        }
+    }
+
+    /// Text up to the closing tag of `name`.
+    fn text(&mut self, name: &str) -> Option<&'a str> {
+        let end = self.rest.find('<')?;
+        let text = &self.rest[..end];
+        self.rest = &self.rest[end..];
+        self.close(name)?;
+        Some(text)
+    }
+
+    fn close(&mut self, name: &str) -> Option<()> {
+        let rest = self.rest.trim_start().strip_prefix("</")?;
+        let rest = rest.strip_prefix(name)?.trim_start().strip_prefix('>')?;
+        self.rest = rest;
+        Some(())
+    }
+
+    fn at_close(&self) -> bool {
+        self.rest.trim_start().starts_with("</")
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        let (name, self_closing) = self.open()?;
+        let value = match name {
+            "plist" => {
+                let value = self.value()?;
+                self.close("plist")?;
+                value
+            }
+            "dict" => {
+                let mut entries = Vec::new();
+                while !self_closing && !self.at_close() {
+                    if self.open()? != ("key", false) {
+                        return None;
+                    }
+                    let key = unescape(self.text("key")?);
+                    entries.push((key, self.value()?));
+                }
+                if !self_closing {
+                    self.close("dict")?;
+                }
+                Value::Dict(entries)
+            }
+            "array" => {
+                let mut items = Vec::new();
+                while !self_closing && !self.at_close() {
+                    items.push(self.value()?);
+                }
+                if !self_closing {
+                    self.close("array")?;
+                }
+                Value::Array(items)
+            }
+            "true" | "false" if self_closing => Value::Bool(name == "true"),
+            _ if self_closing => Value::String(String::new()),
+            "integer" => Value::Integer(self.text(name)?.trim().parse().ok()?),
+            "real" => Value::Real(self.text(name)?.trim().parse().ok()?),
+            // dates and data are kept as text, nothing here reads them
+            _ => Value::String(unescape(self.text(name)?)),
+        };
+        Some(value)
+    }
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn parse(document: &str) -> Result<Value> {
+    Parser { rest: document }
+        .value()
+        .ok_or(PowermetricsError::Parse)
+}
+
+/// Energy of one powermetrics interval.
+fn sample_from(document: &Value) -> Result<EnergySample> {
+    let elapsed_ns = document
+        .get("elapsed_ns")
+        .and_then(Value::as_f64)
+        .ok_or(PowermetricsError::MissingKey("elapsed_ns"))?;
+    let duration = ((elapsed_ns / 1e6).round() as u64).max(1);
+    let processor = document
+        .get("processor")
+        .ok_or(PowermetricsError::MissingKey("processor"))?;
+    // milliwatts over milliseconds are microjoules
+    let energy = |energy: &str, power: &str| {
+        let value = |key| processor.get(key).and_then(Value::as_f64);
+        value(energy)
+            .or_else(|| value(power).map(|power| power * duration as f64 / 1000.0))
+            .map(|energy| energy.max(0.0).round() as u128)
+    };
+    Ok(EnergySample {
+        cpu_energy: energy("cpu_energy", "cpu_power")
+            .ok_or(PowermetricsError::MissingKey("cpu_energy"))?,
+        gpu_energy: energy("gpu_energy", "gpu_power").unwrap_or(0),
+        ane_energy: energy("ane_energy", "ane_power").unwrap_or(0),
+        duration,
+        ..Default::default()
+    })
+}
+
+/// A powermetrics process sampling at a fixed interval.
+#[derive(Debug)]
+struct Running {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    interval: Duration,
+}
+
+impl Running {
+    fn spawn(interval: Duration) -> Result<Self> {
+        let mut child = Command::new(PROGRAM)
+            .args(["--samplers", "cpu_power", "-f", "plist", "-i"])
+            .arg(interval.as_millis().max(1).to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = BufReader::new(child.stdout.take().ok_or(PowermetricsError::Parse)?);
+        Ok(Self {
+            child,
+            stdout,
+            interval,
+        })
+    }
+
+    /// The next document, blocking until powermetrics writes it.
+    fn read(&mut self) -> Result<String> {
+        let mut buffer = Vec::new();
+        loop {
+            buffer.clear();
+            if self.stdout.read_until(0, &mut buffer)? == 0 {
+                return Err(self.exited());
+            }
+            let document = String::from_utf8_lossy(&buffer);
+            let document = document.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+            if !document.is_empty() {
+                return Ok(document.to_string());
+            }
+        }
+    }
+
+    fn exited(&mut self) -> PowermetricsError {
+        let _ = self.child.wait();
+        let mut message = String::new();
+        if let Some(mut stderr) = self.child.stderr.take() {
+            let _ = stderr.read_to_string(&mut message);
+        }
+        PowermetricsError::Exited(message.trim().to_string())
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// # PowermetricsBackend
+///
+/// Samples CPU, GPU and ANE energy through a powermetrics child process. powermetrics
+/// samples at a fixed interval, it is restarted whenever the requested one changes.
+#[derive(Debug)]
+pub struct PowermetricsBackend {
+    running: Option<Running>,
+}
+
+impl PowermetricsBackend {
+    pub fn new() -> Result<Self> {
+        if !std::path::Path::new(PROGRAM).exists() {
+            return Err(PowermetricsError::NotFound);
+        }
+        #[cfg(unix)]
+        if unsafe { libc::geteuid() } != 0 {
+            return Err(PowermetricsError::NotRoot);
+        }
+        Ok(Self { running: None })
+    }
+
+    pub fn sample(&mut self, duration: Duration) -> Result<EnergySample> {
+        let running = match self.running.take() {
+            Some(running) if running.interval == duration => running,
+            _ => Running::spawn(duration)?,
+        };
+        let running = self.running.insert(running);
+        let document = running.read()?;
+        sample_from(&parse(&document)?)
+    }
+}
+
+impl PowerBackend for PowermetricsBackend {
+    fn sample(&mut self, duration: Duration) -> std::result::Result<EnergySample, SamplerError> {
+        Ok(PowermetricsBackend::sample(self, duration)?)
+    }
+
+    fn channels(&self) -> Vec<ChannelInfo> {
+        ["cpu_energy", "gpu_energy", "ane_energy"]
+            .into_iter()
+            .map(|name| ChannelInfo {
+                group: "powermetrics".to_string(),
+                subgroup: "processor".to_string(),
+                name: name.to_string(),
+                unit: "mJ".to_string(),
+            })
+            .collect()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        // Intel Macs only report the package with the cpu_power sampler
+        let apple_silicon = cfg!(target_arch = "aarch64");
+        Capabilities {
+            cpu: true,
+            gpu: apple_silicon,
+            ane: apple_silicon,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+<key>is_delta</key><true/>
+<key>elapsed_ns</key><integer>100412583</integer>
+<key>hw_model</key><string>Mac14,2</string>
+<key>timestamp</key><date>2024-05-01T10:00:00Z</date>
+<key>processor</key>
+<dict>
+<key>clusters</key>
+<array>
+<dict><key>name</key><string>E-Cluster</string><key>freq_hz</key><real>972000000</real><key>cpus</key><array/></dict>
+</array>
+<key>ane_energy</key><integer>0</integer>
+<key>cpu_energy</key><integer>57</integer>
+<key>gpu_power</key><real>120.5</real>
+<key>combined_power</key><real>688.4</real>
+</dict>
+</dict>
+</plist>
+"#;
+
+    #[test]
+    fn test_parse_plist() {
+        let document = parse(DOCUMENT).unwrap();
+        assert_eq!(document.get("is_delta"), Some(&Value::Bool(true)));
+        assert_eq!(
+            document.get("hw_model"),
+            Some(&Value::String("Mac14,2".to_string()))
+        );
+        let clusters = document.get("processor").unwrap().get("clusters").unwrap();
+        let Value::Array(clusters) = clusters else {
+            panic!("{clusters:?}");
+        };
+        assert_eq!(clusters[0].get("cpus"), Some(&Value::Array(Vec::new())));
+
+        let sample = sample_from(&document).unwrap();
+        assert_eq!(sample.duration, 100);
+        assert_eq!(sample.cpu_energy, 57);
+        // from 120.5 mW over 100 ms
+        assert_eq!(sample.gpu_energy, 12);
+        assert_eq!(sample.ane_energy, 0);
+
+        assert!(matches!(
+            parse("<plist><dict><key>a</key></dict></plist>"),
+            Err(PowermetricsError::Parse)
+        ));
+        assert!(matches!(
+            sample_from(&parse("<plist><dict/></plist>").unwrap()),
+            Err(PowermetricsError::MissingKey("elapsed_ns"))
+        ));
+    }
+}
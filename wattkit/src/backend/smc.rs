@@ -0,0 +1,488 @@
+//! macOS backend for Intel Macs, which have no "Energy Model" IOReport group. The
+//! System Management Controller reports the power of the CPU package instead: "PCPC"
+//! its cores, "PCPG" the integrated GPU and "PCPT" the whole package, in watts.
+//! [`Smc`] reads any other key, on Apple Silicon too.
+
+// Only the value decoding is built, and tested, off macOS.
+#![cfg_attr(not(all(target_os = "macos", feature = "macos")), allow(dead_code))]
+
+use std::time::{Duration, Instant};
+
+use crate::backend::{Capabilities, ChannelInfo, PowerBackend};
+use crate::sampler::{EnergySample, SamplerError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SmcError {
+    #[error("The SMC is only available on macOS with the `macos` feature")]
+    Unsupported,
+    #[error("Running under Rosetta, an arm64 build reads IOReport instead")]
+    Translated,
+    #[error("AppleSMC service not found")]
+    NotFound,
+    #[error("Failed to open AppleSMC, IOServiceOpen returned {0}")]
+    OpenFailed(i32),
+    #[error("The SMC has no CPU package power key")]
+    NoPackagePower,
+    #[error("Failed to read SMC key {0}")]
+    ReadFailed(&'static str),
+    #[error("SMC keys are four ASCII characters, not {0:?}")]
+    InvalidKey(String),
+    #[error("The SMC has no key {0}")]
+    KeyNotFound(String),
+}
+
+type Result<T> = std::result::Result<T, SmcError>;
+
+const CPU_KEY: &str = "PCPC";
+const GPU_KEY: &str = "PCPG";
+const PACKAGE_KEY: &str = "PCPT";
+const SYSTEM_KEY: &str = "PSTR";
+const DC_IN_KEY: &str = "PDTR";
+
+/// Decodes an SMC value of type `data_type`, `None` for types that aren't numbers.
+/// Fixed point types name their integer and fraction bits in hex, "sp78" is signed
+/// with 7 and 8.
+fn decode(data_type: &[u8; 4], bytes: &[u8]) -> Option<f64> {
+    match data_type {
+        b"flt " => Some(f32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as f64),
+        b"ui8 " => Some(*bytes.first()? as f64),
+        b"ui16" => Some(u16::from_be_bytes(bytes.get(..2)?.try_into().ok()?) as f64),
+        b"ui32" => Some(u32::from_be_bytes(bytes.get(..4)?.try_into().ok()?) as f64),
+        [sign @ (b's' | b'f'), b'p', _, fraction] => {
+            let raw = u16::from_be_bytes(bytes.get(..2)?.try_into().ok()?);
+            let fraction = (*fraction as char).to_digit(16)?;
+            let value = if *sign == b's' {
+                raw as i16 as f64
+            } else {
+                raw as f64
+            };
+            Some(value / (1u32 << fraction) as f64)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(all(target_os = "macos", feature = "macos"))]
+mod sys {
+    use super::{Result, SmcError};
+    use crate::ioreg::Service;
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOServiceOpen(
+            service: u32,
+            owning_task: u32,
+            kind: u32,
+            connect: *mut u32,
+        ) -> libc::c_int;
+        fn IOServiceClose(connect: u32) -> libc::c_int;
+        fn IOConnectCallStructMethod(
+            connection: u32,
+            selector: u32,
+            input: *const libc::c_void,
+            input_size: usize,
+            output: *mut libc::c_void,
+            output_size: *mut usize,
+        ) -> libc::c_int;
+    }
+
+    extern "C" {
+        static mach_task_self_: u32;
+        fn sysctlbyname(
+            name: *const libc::c_char,
+            old: *mut libc::c_void,
+            old_size: *mut usize,
+            new: *mut libc::c_void,
+            new_size: usize,
+        ) -> libc::c_int;
+    }
+
+    // the AppleSMC user client interface, as used by smcFanControl and friends
+    const KERNEL_INDEX_SMC: u32 = 2;
+    const CMD_READ_BYTES: u8 = 5;
+    const CMD_READ_KEYINFO: u8 = 9;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct Version {
+        major: u8,
+        minor: u8,
+        build: u8,
+        reserved: u8,
+        release: u16,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct PowerLimits {
+        version: u16,
+        length: u16,
+        cpu: u32,
+        gpu: u32,
+        memory: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct KeyInfo {
+        data_size: u32,
+        data_type: u32,
+        data_attributes: u8,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct KeyData {
+        key: u32,
+        version: Version,
+        power_limits: PowerLimits,
+        key_info: KeyInfo,
+        result: u8,
+        status: u8,
+        data8: u8,
+        data32: u32,
+        bytes: [u8; 32],
+    }
+
+    /// Connection to the AppleSMC service, closed on drop.
+    pub struct Connection(u32);
+
+    impl Connection {
+        pub fn open() -> Result<Self> {
+            let service = Service::first("AppleSMC").ok_or(SmcError::NotFound)?;
+            let mut connection = 0;
+            let status =
+                unsafe { IOServiceOpen(service.as_raw(), mach_task_self_, 0, &mut connection) };
+            if status != 0 {
+                return Err(SmcError::OpenFailed(status));
+            }
+            Ok(Self(connection))
+        }
+
+        fn call(&self, input: &KeyData) -> Option<KeyData> {
+            let mut output = KeyData::default();
+            let mut size = std::mem::size_of::<KeyData>();
+            let status = unsafe {
+                IOConnectCallStructMethod(
+                    self.0,
+                    KERNEL_INDEX_SMC,
+                    (input as *const KeyData).cast(),
+                    size,
+                    (&mut output as *mut KeyData).cast(),
+                    &mut size,
+                )
+            };
+            (status == 0 && output.result == 0).then_some(output)
+        }
+
+        /// Type and bytes of `key`, `None` if the SMC doesn't have it.
+        pub fn read(&self, key: [u8; 4]) -> Option<([u8; 4], Vec<u8>)> {
+            let key = u32::from_be_bytes(key);
+            let info = self.call(&KeyData {
+                key,
+                data8: CMD_READ_KEYINFO,
+                ..Default::default()
+            })?;
+            let value = self.call(&KeyData {
+                key,
+                key_info: info.key_info,
+                data8: CMD_READ_BYTES,
+                ..Default::default()
+            })?;
+            let size = (info.key_info.data_size as usize).min(value.bytes.len());
+            Some((
+                info.key_info.data_type.to_be_bytes(),
+                value.bytes[..size].to_vec(),
+            ))
+        }
+    }
+
+    impl Drop for Connection {
+        fn drop(&mut self) {
+            unsafe { IOServiceClose(self.0) };
+        }
+    }
+
+    /// Whether this is an x86_64 process translated by Rosetta on Apple Silicon.
+    pub fn translated() -> bool {
+        let mut translated: libc::c_int = 0;
+        let mut size = std::mem::size_of::<libc::c_int>();
+        let status = unsafe {
+            sysctlbyname(
+                c"sysctl.proc_translated".as_ptr(),
+                (&mut translated as *mut libc::c_int).cast(),
+                &mut size,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        status == 0 && translated == 1
+    }
+}
+
+#[cfg(not(all(target_os = "macos", feature = "macos")))]
+mod sys {
+    use super::{Result, SmcError};
+
+    pub enum Connection {}
+
+    impl Connection {
+        pub fn open() -> Result<Self> {
+            Err(SmcError::Unsupported)
+        }
+
+        pub fn read(&self, _key: [u8; 4]) -> Option<([u8; 4], Vec<u8>)> {
+            match *self {}
+        }
+    }
+
+    pub fn translated() -> bool {
+        false
+    }
+}
+
+/// The raw value of an SMC key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SmcValue {
+    /// Type code, e.g. "flt ", "ui16" or "sp78".
+    pub data_type: [u8; 4],
+    pub bytes: Vec<u8>,
+}
+
+impl SmcValue {
+    pub fn data_type_str(&self) -> &str {
+        std::str::from_utf8(&self.data_type).unwrap_or("")
+    }
+
+    /// The value as a number, `None` for types that aren't numbers: "flt ", unsigned
+    /// integers and fixed point types like "sp78".
+    pub fn as_f64(&self) -> Option<f64> {
+        decode(&self.data_type, &self.bytes)
+    }
+}
+
+fn key_code(key: &str) -> Result<[u8; 4]> {
+    key.as_bytes()
+        .try_into()
+        .ok()
+        .filter(|code: &[u8; 4]| code.is_ascii())
+        .ok_or_else(|| SmcError::InvalidKey(key.to_string()))
+}
+
+/// # Smc
+///
+/// Read-only connection to the System Management Controller, for the voltage, current
+/// and power sensors wattkit doesn't model. Keys differ between models, e.g. "PSTR" is
+/// the system power of many Macs.
+pub struct Smc(sys::Connection);
+
+impl std::fmt::Debug for Smc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Smc").finish_non_exhaustive()
+    }
+}
+
+impl Smc {
+    pub fn open() -> Result<Self> {
+        Ok(Self(sys::Connection::open()?))
+    }
+
+    pub fn read_key(&self, key: &str) -> Result<SmcValue> {
+        let (data_type, bytes) = self
+            .0
+            .read(key_code(key)?)
+            .ok_or_else(|| SmcError::KeyNotFound(key.to_string()))?;
+        Ok(SmcValue { data_type, bytes })
+    }
+
+    /// Value of `key` as a number, `None` if the SMC doesn't have it or it isn't one.
+    fn read(&self, key: &str) -> Option<f64> {
+        self.read_key(key).ok()?.as_f64()
+    }
+}
+
+/// Reads the whole-system and DC-in power sensors once per sampler read, on any Mac
+/// that has them.
+#[derive(Debug)]
+pub(crate) struct SystemPowerMonitor {
+    smc: Option<Smc>,
+    last: (Option<f64>, Option<f64>),
+}
+
+impl SystemPowerMonitor {
+    pub(crate) fn new() -> Self {
+        let smc = Smc::open().ok();
+        let mut monitor = SystemPowerMonitor {
+            smc,
+            last: (None, None),
+        };
+        monitor.last = monitor.read();
+        monitor
+    }
+
+    fn read(&self) -> (Option<f64>, Option<f64>) {
+        let Some(smc) = &self.smc else {
+            return (None, None);
+        };
+        (smc.read(SYSTEM_KEY), smc.read(DC_IN_KEY))
+    }
+
+    /// System and DC-in power in milliwatts, the average of this and the last poll.
+    pub(crate) fn poll(&mut self) -> (Option<f64>, Option<f64>) {
+        let next = self.read();
+        let average = |last: Option<f64>, next: Option<f64>| {
+            let next = next?;
+            Some((last.unwrap_or(next) + next) / 2.0 * 1000.0)
+        };
+        let power = (average(self.last.0, next.0), average(self.last.1, next.1));
+        self.last = next;
+        power
+    }
+}
+
+/// Power of the package parts in watts when it was read.
+#[derive(Clone, Copy, Debug)]
+struct Reading {
+    cpu: f64,
+    gpu: f64,
+    package: Option<f64>,
+    at: Instant,
+}
+
+/// # SmcBackend
+///
+/// Samples the CPU package and integrated GPU power of Intel Macs through the SMC.
+/// Powers are read at both ends of each sample, its energy is their average over the
+/// interval.
+pub struct SmcBackend {
+    smc: Smc,
+    gpu: bool,
+    package: bool,
+    previous: Option<Reading>,
+}
+
+impl std::fmt::Debug for SmcBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SmcBackend")
+            .field("gpu", &self.gpu)
+            .field("package", &self.package)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SmcBackend {
+    pub fn new() -> Result<Self> {
+        if sys::translated() {
+            return Err(SmcError::Translated);
+        }
+        let smc = Smc::open()?;
+        if smc.read(CPU_KEY).is_none() {
+            return Err(SmcError::NoPackagePower);
+        }
+        Ok(Self {
+            gpu: smc.read(GPU_KEY).is_some(),
+            package: smc.read(PACKAGE_KEY).is_some(),
+            smc,
+            previous: None,
+        })
+    }
+
+    fn read(&self) -> Result<Reading> {
+        let read = |key: &'static str| self.smc.read(key).ok_or(SmcError::ReadFailed(key));
+        Ok(Reading {
+            cpu: read(CPU_KEY)?,
+            gpu: if self.gpu { read(GPU_KEY)? } else { 0.0 },
+            package: if self.package {
+                Some(read(PACKAGE_KEY)?)
+            } else {
+                None
+            },
+            at: Instant::now(),
+        })
+    }
+
+    pub fn sample(&mut self, duration: Duration) -> Result<EnergySample> {
+        let prev = match self.previous.take() {
+            Some(x) => x,
+            None => self.read()?,
+        };
+
+        std::thread::sleep(duration);
+
+        let next = self.read()?;
+        self.previous = Some(next);
+        let duration = (next.at.duration_since(prev.at).as_millis() as u64).max(1);
+
+        // watts over milliseconds are millijoules
+        let energy = |before: f64, after: f64| ((before + after) / 2.0 * duration as f64).max(0.0);
+        Ok(EnergySample {
+            cpu_energy: energy(prev.cpu, next.cpu) as u128,
+            gpu_energy: energy(prev.gpu, next.gpu) as u128,
+            package_energy: prev
+                .package
+                .zip(next.package)
+                .map(|(before, after)| energy(before, after) as u128),
+            duration,
+            ..Default::default()
+        })
+    }
+}
+
+impl PowerBackend for SmcBackend {
+    fn sample(&mut self, duration: Duration) -> std::result::Result<EnergySample, SamplerError> {
+        Ok(SmcBackend::sample(self, duration)?)
+    }
+
+    fn channels(&self) -> Vec<ChannelInfo> {
+        [
+            (CPU_KEY, true),
+            (GPU_KEY, self.gpu),
+            (PACKAGE_KEY, self.package),
+        ]
+        .into_iter()
+        .filter(|(_, available)| *available)
+        .map(|(key, _)| ChannelInfo {
+            group: "SMC".to_string(),
+            subgroup: String::new(),
+            name: key.to_string(),
+            unit: "W".to_string(),
+        })
+        .collect()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            cpu: true,
+            gpu: self.gpu,
+            ane: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b"flt ", &12.5f32.to_le_bytes()), Some(12.5));
+        // 800 64ths
+        assert_eq!(decode(b"sp96", &[0x03, 0x20]), Some(12.5));
+        assert_eq!(decode(b"sp78", &[0xff, 0x80]), Some(-0.5));
+        assert_eq!(decode(b"fpe2", &[0x00, 0x0a]), Some(2.5));
+        assert_eq!(decode(b"ui16", &[0x01, 0x00]), Some(256.0));
+        assert_eq!(decode(b"ch8*", b"text"), None);
+        assert_eq!(decode(b"flt ", &[0, 0]), None);
+
+        let value = SmcValue {
+            data_type: *b"ui16",
+            bytes: vec![0x12, 0x34],
+        };
+        assert_eq!(value.data_type_str(), "ui16");
+        assert_eq!(value.as_f64(), Some(0x1234 as f64));
+
+        assert_eq!(key_code("PSTR").unwrap(), *b"PSTR");
+        assert!(matches!(key_code("PST"), Err(SmcError::InvalidKey(_))));
+        assert!(matches!(key_code("PS\u{e9}"), Err(SmcError::InvalidKey(_))));
+    }
+}
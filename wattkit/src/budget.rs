@@ -0,0 +1,285 @@
+//! Energy budgets for test suites to enforce, see [`EnergyBudget`] and
+//! [`assert_energy_below!`](crate::assert_energy_below).
+
+use crate::sampler::PowerProfile;
+use crate::units::Units;
+
+/// The message of a limit a profile exceeds, `value` and `limit` formatted in the unit.
+fn exceeded(name: &str, value: String, limit: String) -> String {
+    format!("{name} {value} exceeds the budget of {limit}")
+}
+
+/// Limits a profile must stay within, unset ones aren't checked. An [`EnergyBudget`]
+/// converts into one limiting energy, tolerance included.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Budget {
+    /// Joules.
+    pub energy: Option<f64>,
+    /// Watts.
+    pub average_power: Option<f64>,
+    /// Watts, see [`PowerProfile::peak_power`].
+    pub peak_power: Option<f64>,
+}
+
+impl Budget {
+    /// A message for each limit `profile` exceeds, empty when it stays within all.
+    /// Values are in joules and watts, see [`Budget::violations_with`].
+    pub fn violations(&self, profile: &PowerProfile) -> Vec<String> {
+        self.violations_with(profile, Units::SI)
+    }
+
+    /// [`Budget::violations`] with values in `units`.
+    pub fn violations_with(&self, profile: &PowerProfile, units: Units) -> Vec<String> {
+        let format = |is_energy: bool, value: f64| {
+            if is_energy {
+                units.energy.format(value)
+            } else {
+                units.power.format(value)
+            }
+        };
+        // mJ and mW, the limits are in J and W
+        let checks = [
+            ("energy", true, profile.total_energy as f64, self.energy),
+            (
+                "average power",
+                false,
+                profile.average_power as f64,
+                self.average_power,
+            ),
+            (
+                "peak power",
+                false,
+                profile.peak_power() as f64,
+                self.peak_power,
+            ),
+        ];
+        checks
+            .into_iter()
+            .filter_map(|(name, is_energy, value, limit)| {
+                let limit = limit.map(|limit| limit * 1000.0).filter(|&l| value > l)?;
+                Some(exceeded(
+                    name,
+                    format(is_energy, value),
+                    format(is_energy, limit),
+                ))
+            })
+            .collect()
+    }
+}
+
+/// # EnergyBudget
+///
+/// The most energy a run of a workload may take, checked against one profile or
+/// against a percentile of repeated runs so a few noisy ones don't fail a test.
+///
+/// ```
+/// use wattkit::{EnergyBudget, PowerProfile};
+///
+/// let runs: Vec<PowerProfile> = Vec::new();
+/// // 5% above 50 J is tolerated, one run in ten may exceed it
+/// let budget = EnergyBudget::new(50.0).with_tolerance(0.05).with_percentile(90.0);
+/// budget.assert_runs(&runs);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EnergyBudget {
+    joules: f64,
+    tolerance: f64,
+    percentile: f64,
+    units: Units,
+}
+
+impl EnergyBudget {
+    /// A budget of `joules` per run, every run must stay within it.
+    pub fn new(joules: f64) -> Self {
+        EnergyBudget {
+            joules,
+            tolerance: 0.0,
+            percentile: 100.0,
+            units: Units::SI,
+        }
+    }
+
+    /// Tolerates runs exceeding the budget by up to `fraction` of it, 0.05 is 5%.
+    pub fn with_tolerance(mut self, fraction: f64) -> Self {
+        self.tolerance = fraction.max(0.0);
+        self
+    }
+
+    /// Checks the run at `percentile`, from 0 to 100, of repeated runs by energy
+    /// instead of the most expensive one.
+    pub fn with_percentile(mut self, percentile: f64) -> Self {
+        self.percentile = percentile.clamp(0.0, 100.0);
+        self
+    }
+
+    /// Shows energy and the profile of a run over the budget in `units`, joules and
+    /// watts by default.
+    pub fn with_units(mut self, units: Units) -> Self {
+        self.units = units;
+        self
+    }
+
+    /// Joules a run may take including the tolerance.
+    pub fn limit(&self) -> f64 {
+        self.joules * (1.0 + self.tolerance)
+    }
+
+    pub fn check(&self, profile: &PowerProfile) -> Result<(), BudgetExceeded> {
+        self.check_runs(std::slice::from_ref(profile))
+    }
+
+    /// Checks the run at the percentile by energy, passes without runs.
+    pub fn check_runs(&self, profiles: &[PowerProfile]) -> Result<(), BudgetExceeded> {
+        let mut runs: Vec<&PowerProfile> = profiles.iter().collect();
+        runs.sort_by_key(|p| p.total_energy);
+        // nearest rank
+        let rank = (self.percentile / 100.0 * runs.len() as f64).ceil() as usize;
+        let Some(run) = runs.get(rank.clamp(1, runs.len().max(1)) - 1) else {
+            return Ok(());
+        };
+        if Budget::from(*self).violations(run).is_empty() {
+            return Ok(());
+        }
+        Err(BudgetExceeded {
+            energy: run.total_energy as f64 / 1000.0,
+            budget: *self,
+            runs: runs.len(),
+            profile: Box::new((*run).clone()),
+        })
+    }
+
+    /// Panics with the [`BudgetExceeded`] message when `profile` exceeds the budget.
+    #[track_caller]
+    pub fn assert(&self, profile: &PowerProfile) {
+        if let Err(e) = self.check(profile) {
+            panic!("{e}");
+        }
+    }
+
+    #[track_caller]
+    pub fn assert_runs(&self, profiles: &[PowerProfile]) {
+        if let Err(e) = self.check_runs(profiles) {
+            panic!("{e}");
+        }
+    }
+}
+
+impl From<EnergyBudget> for Budget {
+    fn from(budget: EnergyBudget) -> Self {
+        Budget {
+            energy: Some(budget.limit()),
+            ..Default::default()
+        }
+    }
+}
+
+/// A run over its [`EnergyBudget`], the message includes its whole profile.
+#[derive(Clone, Debug)]
+pub struct BudgetExceeded {
+    /// Joules of the run checked.
+    pub energy: f64,
+    pub budget: EnergyBudget,
+    /// Number of runs the checked one was picked from.
+    pub runs: usize,
+    pub profile: Box<PowerProfile>,
+}
+
+impl std::fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let EnergyBudget {
+            joules,
+            tolerance,
+            percentile,
+            units,
+        } = self.budget;
+        if self.runs > 1 {
+            write!(f, "p{percentile} of {} runs: ", self.runs)?;
+        }
+        let energy = |joules: f64| units.energy.format(joules * 1000.0);
+        f.write_str(&exceeded("energy", energy(self.energy), energy(joules)))?;
+        if tolerance > 0.0 {
+            write!(f, " + {:.1}% tolerance", tolerance * 100.0)?;
+        }
+        write!(f, "\n{}", self.profile.display(units))
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// Asserts that a [`PowerProfile`] took less than a number of joules, the failure
+/// message includes the profile. A message with format arguments may follow.
+///
+/// ```should_panic
+/// let profile = wattkit::PowerProfile::default();
+/// wattkit::assert_energy_below!(profile, 50.0);
+/// wattkit::assert_energy_below!(profile, -1.0, "decoding {} tokens", 128);
+/// ```
+#[macro_export]
+macro_rules! assert_energy_below {
+    ($profile:expr, $joules:expr $(,)?) => {
+        $crate::EnergyBudget::new($joules).assert(&$profile)
+    };
+    ($profile:expr, $joules:expr, $($arg:tt)+) => {
+        if let Err(e) = $crate::EnergyBudget::new($joules).check(&$profile) {
+            panic!("{}: {e}", format_args!($($arg)+));
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::EnergySample;
+
+    fn run(cpu_energy: u128) -> PowerProfile {
+        PowerProfile::from(
+            [EnergySample {
+                cpu_energy,
+                duration: 100,
+                ..Default::default()
+            }]
+            .as_slice(),
+        )
+    }
+
+    #[test]
+    fn test_energy_budget() {
+        assert_energy_below!(run(400), 0.5);
+        let e = EnergyBudget::new(0.3).check(&run(400)).unwrap_err();
+        assert!(
+            e.to_string()
+                .starts_with("energy 0.400 J exceeds the budget of 0.300 J\nTotal Energy: 0.400 J"),
+            "{e}"
+        );
+        let e = EnergyBudget::new(0.3)
+            .with_units(Units::default())
+            .check(&run(400))
+            .unwrap_err();
+        assert!(
+            e.to_string()
+                .starts_with("energy 400 mJ exceeds the budget of 300 mJ\nTotal Energy: 400 mJ"),
+            "{e}"
+        );
+        assert!(EnergyBudget::new(0.3)
+            .with_tolerance(0.5)
+            .check(&run(400))
+            .is_ok());
+
+        // one outlier in ten passes at p90 but not at p100
+        let runs: Vec<_> = (0..10)
+            .map(|i| run(if i == 3 { 900 } else { 400 }))
+            .collect();
+        let budget = EnergyBudget::new(0.5);
+        let e = budget.check_runs(&runs).unwrap_err();
+        assert_eq!(e.profile.total_energy, 900);
+        assert!(e.to_string().starts_with("p100 of 10 runs: "), "{e}");
+        budget.with_percentile(90.0).assert_runs(&runs);
+        assert!(budget.check_runs(&[]).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "decoding: energy 0.400 J exceeds")]
+    fn test_assert_energy_below_message() {
+        assert_energy_below!(run(400), 0.3, "decoding");
+    }
+}
@@ -0,0 +1,96 @@
+//! Slower cadences for the host metrics read alongside energy, see [`MetricRates`].
+
+use std::time::Duration;
+
+/// How often each group of host metrics is read during a session, `None` reads it
+/// along with every energy read. Slow-moving metrics read less often cost less.
+///
+/// Between reads the samples carry the last value of a metric, its timestamp being
+/// that of the sample it was read with. Counters, disk and network I/O, are reported
+/// in full with the samples of the read that polls them and are `None` on the samples
+/// in between, so their totals still add up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MetricRates {
+    /// [`EnergySample::thermal_pressure`](crate::EnergySample::thermal_pressure).
+    pub thermal: Option<Duration>,
+    /// [`EnergySample::cpu_utilization`](crate::EnergySample::cpu_utilization).
+    pub cpu_load: Option<Duration>,
+    /// [`EnergySample::extra`](crate::EnergySample::extra).
+    pub io: Option<Duration>,
+    /// [`EnergySample::system_power`](crate::EnergySample::system_power) and the DC
+    /// input power.
+    pub system_power: Option<Duration>,
+    /// [`EnergySample::power_source`](crate::EnergySample::power_source).
+    pub power_source: Option<Duration>,
+}
+
+/// When one group is read next, in milliseconds since sampling started.
+#[derive(Debug)]
+pub(crate) struct Cadence {
+    every: Option<u64>,
+    next: u64,
+}
+
+impl Cadence {
+    pub(crate) fn new(every: Option<Duration>) -> Self {
+        Cadence {
+            every: every.map(|every| every.as_millis() as u64),
+            next: 0,
+        }
+    }
+
+    /// Whether the group is read at `now`, schedules the next read if so.
+    pub(crate) fn due(&mut self, now: u64) -> bool {
+        let Some(every) = self.every else {
+            return true;
+        };
+        if now < self.next {
+            return false;
+        }
+        self.next = now + every;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExtraMetrics, MockBackend, Sampling, StartStopSampler};
+
+    #[test]
+    fn test_cadence() {
+        let mut always = Cadence::new(None);
+        assert!((0..5).all(|i| always.due(i * 100)));
+
+        let mut every_second = Cadence::new(Some(Duration::from_secs(1)));
+        let due: Vec<u64> = (0..25)
+            .map(|i| i * 100)
+            .filter(|&now| every_second.due(now))
+            .collect();
+        assert_eq!(due, [0, 1000, 2000]);
+    }
+
+    #[test]
+    fn test_metric_rates() {
+        let mut sampler =
+            StartStopSampler::with_backend(|| Ok(MockBackend::constant(1000.0, 0.0, 0.0)));
+        sampler.config_mut().metric_rates = MetricRates {
+            cpu_load: Some(Duration::from_secs(10)),
+            io: Some(Duration::from_secs(10)),
+            ..Default::default()
+        };
+        sampler.start(Duration::from_millis(20), 1).unwrap();
+        std::thread::sleep(Duration::from_millis(150));
+        sampler.stop().unwrap();
+
+        // read once with the first sample, carried or left out after
+        let samples = sampler.samples().unwrap();
+        assert!(samples.len() > 2);
+        assert!(samples
+            .iter()
+            .all(|s| s.cpu_utilization == samples[0].cpu_utilization));
+        assert!(samples[1..]
+            .iter()
+            .all(|s| s.extra == ExtraMetrics::default()));
+    }
+}
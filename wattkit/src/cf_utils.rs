@@ -1,54 +1,136 @@
 use core_foundation::{
-    base::{kCFAllocatorDefault, kCFAllocatorNull, CFRelease, CFTypeRef},
-    dictionary::{CFDictionaryGetValue, CFDictionaryRef},
-    string::{
-        kCFStringEncodingUTF8, CFStringCreateWithBytesNoCopy, CFStringGetCString, CFStringRef,
+    base::{kCFAllocatorDefault, CFRelease, CFRetain, CFTypeRef},
+    dictionary::{
+        CFDictionaryCreateMutableCopy, CFDictionaryGetCount, CFDictionaryGetValue, CFDictionaryRef,
+        CFMutableDictionaryRef,
     },
+    string::{kCFStringEncodingUTF8, CFStringCreateWithBytes, CFStringGetCString, CFStringRef},
 };
 
-pub fn cfdict_get_val(dict: CFDictionaryRef, key: &str) -> Option<CFTypeRef> {
-    unsafe {
-        let key = cfstr(key);
-        let val = CFDictionaryGetValue(dict, key as _);
-        CFRelease(key as _);
+/// Owned `CFDictionary`, released on drop.
+#[derive(Debug)]
+pub(crate) struct CfDict(CFDictionaryRef);
 
-        match val {
-            _ if val.is_null() => None,
-            _ => Some(val),
+impl CfDict {
+    /// Takes ownership of a dictionary returned by a Create or Copy function,
+    /// `None` if it is null.
+    ///
+    /// # Safety
+    /// `dict` must be null or a dictionary whose reference the caller owns.
+    pub(crate) unsafe fn from_create_rule(dict: CFDictionaryRef) -> Option<Self> {
+        (!dict.is_null()).then_some(Self(dict))
+    }
+
+    pub(crate) fn as_ptr(&self) -> CFDictionaryRef {
+        self.0
+    }
+
+    /// IOReport takes its channel dictionaries as mutable.
+    pub(crate) fn as_mut_ptr(&self) -> CFMutableDictionaryRef {
+        self.0 as _
+    }
+
+    pub(crate) fn len(&self) -> isize {
+        unsafe { CFDictionaryGetCount(self.0) }
+    }
+
+    /// Value for `key`, owned by the dictionary.
+    pub(crate) fn get(&self, key: &str) -> Option<CFTypeRef> {
+        cfdict_get_val(self.0, key)
+    }
+
+    pub(crate) fn mutable_copy(&self) -> Option<CfDict> {
+        unsafe {
+            CfDict::from_create_rule(CFDictionaryCreateMutableCopy(
+                kCFAllocatorDefault,
+                self.len(),
+                self.0,
+            ) as _)
         }
     }
 }
 
-pub fn cfstr(val: &str) -> CFStringRef {
-    // this creates broken objects if string len > 9
-    // CFString::from_static_string(val).as_concrete_TypeRef()
-    // CFString::new(val).as_concrete_TypeRef()
+impl Clone for CfDict {
+    fn clone(&self) -> Self {
+        unsafe { CFRetain(self.0 as _) };
+        Self(self.0)
+    }
+}
 
-    unsafe {
-        CFStringCreateWithBytesNoCopy(
-            kCFAllocatorDefault,
-            val.as_ptr(),
-            val.len() as isize,
-            kCFStringEncodingUTF8,
-            0,
-            kCFAllocatorNull,
-        )
+impl Drop for CfDict {
+    fn drop(&mut self) {
+        unsafe { CFRelease(self.0 as _) };
     }
 }
 
-pub fn from_cfstr(val: CFStringRef) -> String {
-    unsafe {
-        let mut buf = Vec::with_capacity(256); //128 here seems dumb
-        if CFStringGetCString(val, buf.as_mut_ptr(), 256, kCFStringEncodingUTF8) == 0 {
-            return String::new();
-        }
-        std::ffi::CStr::from_ptr(buf.as_ptr())
-            .to_string_lossy()
-            .to_string()
+/// Owned `CFString` holding a copy of a Rust string, released on drop.
+#[derive(Debug)]
+pub(crate) struct CfString(CFStringRef);
+
+impl CfString {
+    pub(crate) fn new(val: &str) -> Self {
+        // CFString::new creates broken objects for strings longer than 9 bytes here
+        let s = unsafe {
+            CFStringCreateWithBytes(
+                kCFAllocatorDefault,
+                val.as_ptr(),
+                val.len() as isize,
+                kCFStringEncodingUTF8,
+                0,
+            )
+        };
+        assert!(!s.is_null(), "CFStringCreateWithBytes failed");
+        Self(s)
+    }
+
+    pub(crate) fn as_ptr(&self) -> CFStringRef {
+        self.0
     }
 }
 
-pub fn get_cf_string<F>(getter: F) -> String
+impl Clone for CfString {
+    fn clone(&self) -> Self {
+        unsafe { CFRetain(self.0 as _) };
+        Self(self.0)
+    }
+}
+
+impl Drop for CfString {
+    fn drop(&mut self) {
+        unsafe { CFRelease(self.0 as _) };
+    }
+}
+
+pub(crate) fn cfdict_get_val(dict: CFDictionaryRef, key: &str) -> Option<CFTypeRef> {
+    let key = CfString::new(key);
+    let val = unsafe { CFDictionaryGetValue(dict, key.as_ptr() as _) };
+    (!val.is_null()).then_some(val)
+}
+
+pub(crate) fn from_cfstr(val: CFStringRef) -> String {
+    with_cfstr(val, str::to_string)
+}
+
+/// Runs `f` on the contents of `val` decoded on the stack, strings that don't fit in
+/// 255 bytes come out empty.
+pub(crate) fn with_cfstr<T>(val: CFStringRef, f: impl FnOnce(&str) -> T) -> T {
+    let mut buf = [0 as std::ffi::c_char; 256];
+    let ok = unsafe {
+        CFStringGetCString(
+            val,
+            buf.as_mut_ptr(),
+            buf.len() as isize,
+            kCFStringEncodingUTF8,
+        ) != 0
+    };
+    if !ok {
+        return f("");
+    }
+    let s = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+    f(&s.to_string_lossy())
+}
+
+pub(crate) fn get_cf_string<F>(getter: F) -> String
 where
     F: FnOnce() -> CFStringRef,
 {
@@ -57,3 +139,22 @@ where
         x => from_cfstr(x),
     }
 }
+
+#[link(name = "objc")]
+extern "C" {
+    fn objc_autoreleasePoolPush() -> *mut std::ffi::c_void;
+    fn objc_autoreleasePoolPop(pool: *mut std::ffi::c_void);
+}
+
+/// Runs `f` inside an autorelease pool. Threads we spawn have none, objects the
+/// frameworks autorelease on them would otherwise pile up for the life of the thread.
+pub(crate) fn autoreleasepool<T>(f: impl FnOnce() -> T) -> T {
+    struct Pool(*mut std::ffi::c_void);
+    impl Drop for Pool {
+        fn drop(&mut self) {
+            unsafe { objc_autoreleasePoolPop(self.0) };
+        }
+    }
+    let _pool = Pool(unsafe { objc_autoreleasePoolPush() });
+    f()
+}
@@ -0,0 +1,204 @@
+//! Sampled channels organized by group, subgroup and channel, see [`ChannelTree`].
+
+use std::collections::BTreeMap;
+
+use crate::backend::capture::RawSample;
+use crate::sampler::EnergySample;
+
+/// One channel's value over each sample.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelSeries {
+    pub unit: String,
+    /// Start of each sample in milliseconds since sampling started and the channel's
+    /// value over it, samples without one are left out.
+    pub points: Vec<(u64, f64)>,
+}
+
+impl ChannelSeries {
+    pub fn total(&self) -> f64 {
+        self.points.iter().map(|(_, value)| value).sum()
+    }
+}
+
+/// Channels of a subgroup by name.
+pub type Channels = BTreeMap<String, ChannelSeries>;
+/// Subgroups of a group by name, the empty name for channels without one.
+pub type Subgroups = BTreeMap<String, Channels>;
+
+/// # ChannelTree
+///
+/// Every sampled channel as group → subgroup → channel → series, so exporters and
+/// viewers can walk any backend's channels the same way.
+///
+/// [`ChannelTree::from_raw`] keeps the channels of a raw IOReport capture as reported,
+/// [`ChannelTree::from_samples`] lays out what a session recorded:
+///
+/// - `Energy`: `CPU`, `GPU`, `ANE`, `Display` and `Package` energy in mJ, and
+///   `CPU`, `GPU` and `ANE` of each die under subgroups `Die 0`, `Die 1`, ...
+/// - `Power`: `System` and `DC In` power in mW.
+/// - `CPU`: `Utilization` and cluster `Active` and `Performance State` residency as
+///   fractions.
+/// - `Memory`: `Read` and `Write` traffic in bytes.
+/// - `Disk` and `Network`: bytes `Read` and `Written`, `Received` and `Sent`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelTree {
+    pub groups: BTreeMap<String, Subgroups>,
+}
+
+impl ChannelTree {
+    /// Channel values of raw IOReport samples, e.g. from
+    /// [`read_capture`](crate::backend::capture::read_capture). State channels, such as
+    /// the cluster performance states, are left out.
+    pub fn from_raw(samples: &[RawSample]) -> Self {
+        let mut tree = ChannelTree::default();
+        let mut timestamp = 0;
+        for sample in samples {
+            for value in &sample.values {
+                tree.push(
+                    &value.group,
+                    &value.subgroup,
+                    &value.channel,
+                    &value.unit,
+                    timestamp,
+                    value.value as f64,
+                );
+            }
+            timestamp += sample.duration;
+        }
+        tree
+    }
+
+    /// The quantities of a session's samples, laid out as in [`ChannelTree`].
+    pub fn from_samples(samples: &[EnergySample]) -> Self {
+        let mut tree = ChannelTree::default();
+        for s in samples {
+            let energy = |energy: u128| energy as f64;
+            let bytes = |bytes: u64| bytes as f64;
+            let (residency, memory) = (s.cpu_residency, s.memory_traffic);
+            let (disk, network) = (s.extra.disk_io, s.extra.network_io);
+            let state = residency.map(|r| r.performance_state);
+            let channels = [
+                ("Energy", "CPU", "mJ", Some(energy(s.cpu_energy))),
+                ("Energy", "GPU", "mJ", Some(energy(s.gpu_energy))),
+                ("Energy", "ANE", "mJ", Some(energy(s.ane_energy))),
+                ("Energy", "Display", "mJ", s.display_energy.map(energy)),
+                ("Energy", "Package", "mJ", s.package_energy.map(energy)),
+                ("Power", "System", "mW", s.system_power),
+                ("Power", "DC In", "mW", s.dc_in_power),
+                ("CPU", "Utilization", "", s.cpu_utilization),
+                ("CPU", "Active", "", residency.map(|r| r.active)),
+                ("CPU", "Performance State", "", state),
+                ("Memory", "Read", "B", memory.map(|m| bytes(m.read))),
+                ("Memory", "Write", "B", memory.map(|m| bytes(m.write))),
+                ("Disk", "Read", "B", disk.map(|io| bytes(io.read))),
+                ("Disk", "Written", "B", disk.map(|io| bytes(io.write))),
+                ("Network", "Received", "B", network.map(|io| bytes(io.rx))),
+                ("Network", "Sent", "B", network.map(|io| bytes(io.tx))),
+            ];
+            for (group, channel, unit, value) in channels {
+                if let Some(value) = value {
+                    tree.push(group, "", channel, unit, s.timestamp, value);
+                }
+            }
+            for (i, die) in s.dies.iter().enumerate() {
+                let subgroup = format!("Die {i}");
+                for (channel, value) in [("CPU", die.cpu), ("GPU", die.gpu), ("ANE", die.ane)] {
+                    tree.push(
+                        "Energy",
+                        &subgroup,
+                        channel,
+                        "mJ",
+                        s.timestamp,
+                        energy(value),
+                    );
+                }
+            }
+        }
+        tree
+    }
+
+    fn push(
+        &mut self,
+        group: &str,
+        subgroup: &str,
+        channel: &str,
+        unit: &str,
+        timestamp: u64,
+        value: f64,
+    ) {
+        let series = self
+            .groups
+            .entry(group.to_string())
+            .or_default()
+            .entry(subgroup.to_string())
+            .or_default()
+            .entry(channel.to_string())
+            .or_insert_with(|| ChannelSeries {
+                unit: unit.to_string(),
+                points: Vec::new(),
+            });
+        series.points.push((timestamp, value));
+    }
+
+    pub fn get(&self, group: &str, subgroup: &str, channel: &str) -> Option<&ChannelSeries> {
+        self.groups.get(group)?.get(subgroup)?.get(channel)
+    }
+
+    /// Every channel as `(group, subgroup, channel, series)`, in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, &str, &ChannelSeries)> {
+        self.groups.iter().flat_map(|(group, subgroups)| {
+            subgroups.iter().flat_map(move |(subgroup, channels)| {
+                channels.iter().map(move |(channel, series)| {
+                    (group.as_str(), subgroup.as_str(), channel.as_str(), series)
+                })
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::capture::RawChannelValue;
+    use crate::sampler::DieEnergy;
+
+    #[test]
+    fn test_channel_tree() {
+        let value = |channel: &str, value| RawChannelValue {
+            group: "Energy Model".into(),
+            subgroup: "".into(),
+            channel: channel.into(),
+            unit: "mJ".into(),
+            value,
+        };
+        let raw = [100, 300].map(|energy| RawSample {
+            duration: 100,
+            values: vec![value("CPU Energy", energy), value("GPU Energy", 5)],
+            states: Vec::new(),
+        });
+        let tree = ChannelTree::from_raw(&raw);
+        let cpu = tree.get("Energy Model", "", "CPU Energy").unwrap();
+        assert_eq!(cpu.unit, "mJ");
+        assert_eq!(cpu.points, [(0, 100.0), (100, 300.0)]);
+        assert_eq!(tree.iter().count(), 2);
+
+        let samples = [0, 100].map(|timestamp| EnergySample {
+            cpu_energy: 100,
+            timestamp,
+            duration: 100,
+            cpu_utilization: (timestamp > 0).then_some(0.5),
+            dies: vec![DieEnergy::default(); 2],
+            ..Default::default()
+        });
+        let tree = ChannelTree::from_samples(&samples);
+        assert_eq!(tree.get("Energy", "", "CPU").unwrap().total(), 200.0);
+        assert_eq!(
+            tree.get("CPU", "", "Utilization").unwrap().points,
+            [(100, 0.5)]
+        );
+        assert!(tree.get("Energy", "Die 1", "ANE").is_some());
+        assert!(tree.get("Energy", "", "Display").is_none());
+        // CPU, GPU and ANE of the unit totals and of both dies, and the utilization
+        assert_eq!(tree.iter().count(), 10);
+    }
+}
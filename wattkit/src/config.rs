@@ -0,0 +1,99 @@
+//! Options shared by every sampler, see [`SamplerConfig`].
+
+use std::collections::BTreeMap;
+
+use crate::cadence::MetricRates;
+use crate::qos::QosClass;
+use crate::sampler::{IdleDetection, Metadata, OutlierFilter, Warmup};
+
+/// # SamplerConfig
+///
+/// How a sampler runs its sessions and profiles them, the same for
+/// [`Sampler`](crate::Sampler) and [`StartStopSampler`](crate::StartStopSampler). Pass
+/// one to `with_config`, or change it between sessions through `config_mut`:
+///
+/// ```
+/// use std::time::Duration;
+/// use wattkit::{OutlierFilter, SamplerConfig, StartStopSampler};
+///
+/// let config = SamplerConfig::default()
+///     .discard_warmup(Duration::from_millis(200))
+///     .reject_outliers(OutlierFilter::Mad { threshold: 3.5 })
+///     .with_metadata("model", "resnet50");
+/// let mut sampler = StartStopSampler::new().with_config(config);
+/// sampler.config_mut().metadata.insert("batch_size".into(), "32".into());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SamplerConfig {
+    /// Attached to the profiles of every session.
+    pub metadata: Metadata,
+    /// Start of each session left out of its profile, the samples keep it.
+    pub warmup: Warmup,
+    /// Samples left out of profiles as outliers, the samples keep them.
+    pub outliers: OutlierFilter,
+    /// How idle periods are found in profiles, `None` if they aren't.
+    pub idle: Option<IdleDetection>,
+    /// QoS class of the sampling thread, the platform default when `None`.
+    pub qos: Option<QosClass>,
+    pub metric_rates: MetricRates,
+}
+
+/// What [`Sampling::config`](crate::Sampling::config) returns by default.
+pub(crate) static DEFAULT_CONFIG: SamplerConfig = SamplerConfig {
+    metadata: BTreeMap::new(),
+    warmup: Warmup::None,
+    outliers: OutlierFilter::None,
+    idle: None,
+    qos: None,
+    metric_rates: MetricRates {
+        thermal: None,
+        cpu_load: None,
+        io: None,
+        system_power: None,
+        power_source: None,
+    },
+};
+
+impl SamplerConfig {
+    /// Attaches `key: value` to the profiles, replacing any previous value of `key`.
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Leaves the first samples, or the first stretch of time, of every session out of
+    /// its profile, e.g. `discard_warmup(1)` or `discard_warmup(Duration::from_millis(200))`.
+    /// [`Sampling::samples`](crate::Sampling::samples) still has them.
+    pub fn discard_warmup(mut self, warmup: impl Into<Warmup>) -> Self {
+        self.warmup = warmup.into();
+        self
+    }
+
+    /// Leaves samples far from the rest of their session out of its profile, which then
+    /// totals the energy and duration of the samples kept.
+    pub fn reject_outliers(mut self, filter: OutlierFilter) -> Self {
+        self.outliers = filter;
+        self
+    }
+
+    /// Annotates profiles with the stretches spent near the session's baseline power and
+    /// their average power outside them, see [`IdleDetection`].
+    pub fn detect_idle(mut self, detection: impl Into<Option<IdleDetection>>) -> Self {
+        self.idle = detection.into();
+        self
+    }
+
+    /// Runs the sampling thread at `qos`, e.g. [`QosClass::Background`] to stay out of
+    /// the workload's way or [`QosClass::UserInteractive`] for tight timing.
+    pub fn with_qos(mut self, qos: QosClass) -> Self {
+        self.qos = Some(qos);
+        self
+    }
+
+    /// Reads the host metrics at `rates` instead of with every energy read, see
+    /// [`MetricRates`].
+    pub fn with_metric_rates(mut self, rates: MetricRates) -> Self {
+        self.metric_rates = rates;
+        self
+    }
+}
@@ -0,0 +1,139 @@
+/// Cumulative CPU time of all cores of the host, in ticks of whatever the platform counts.
+#[derive(Clone, Copy, Debug)]
+struct Ticks {
+    busy: u64,
+    total: u64,
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use super::Ticks;
+
+    extern "C" {
+        static mach_task_self_: u32;
+        fn mach_host_self() -> u32;
+        fn host_processor_info(
+            host: u32,
+            flavor: libc::c_int,
+            processor_count: *mut u32,
+            info: *mut *mut libc::c_int,
+            info_count: *mut u32,
+        ) -> libc::c_int;
+        fn vm_deallocate(task: u32, address: usize, size: usize) -> libc::c_int;
+    }
+
+    // <mach/processor_info.h> and <mach/machine.h>
+    const PROCESSOR_CPU_LOAD_INFO: libc::c_int = 2;
+    const CPU_STATE_MAX: usize = 4;
+    const CPU_STATE_IDLE: usize = 2;
+
+    pub fn ticks() -> Option<Ticks> {
+        let (mut processors, mut info, mut count) = (0, std::ptr::null_mut(), 0);
+        let status = unsafe {
+            host_processor_info(
+                mach_host_self(),
+                PROCESSOR_CPU_LOAD_INFO,
+                &mut processors,
+                &mut info,
+                &mut count,
+            )
+        };
+        if status != 0 || info.is_null() {
+            return None;
+        }
+        let loads = unsafe { std::slice::from_raw_parts(info, count as usize) };
+        let mut ticks = Ticks { busy: 0, total: 0 };
+        for cpu in loads.chunks_exact(CPU_STATE_MAX) {
+            for (state, &value) in cpu.iter().enumerate() {
+                // the counters are 32 bits wide
+                let value = value as u32 as u64;
+                ticks.total += value;
+                if state != CPU_STATE_IDLE {
+                    ticks.busy += value;
+                }
+            }
+        }
+        unsafe {
+            vm_deallocate(
+                mach_task_self_,
+                info as usize,
+                count as usize * std::mem::size_of::<libc::c_int>(),
+            )
+        };
+        Some(ticks)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::Ticks;
+
+    pub fn ticks() -> Option<Ticks> {
+        let stat = std::fs::read_to_string("/proc/stat").ok()?;
+        // cpu  user nice system idle iowait irq softirq steal ...
+        let values: Vec<u64> = stat
+            .lines()
+            .next()?
+            .split_whitespace()
+            .skip(1)
+            .take(8)
+            .map(|v| v.parse().ok())
+            .collect::<Option<_>>()?;
+        let total = values.iter().sum();
+        let idle = values.get(3)? + values.get(4).unwrap_or(&0);
+        Some(Ticks {
+            busy: total - idle,
+            total,
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+mod sys {
+    pub fn ticks() -> Option<super::Ticks> {
+        None
+    }
+}
+
+/// Host CPU utilization between polls.
+#[derive(Debug)]
+pub(crate) struct CpuLoad {
+    last: Option<Ticks>,
+}
+
+impl CpuLoad {
+    pub(crate) fn new() -> Self {
+        CpuLoad { last: sys::ticks() }
+    }
+
+    /// Fraction of the host's CPU time, across all cores, spent busy since the last
+    /// poll. `None` where it can't be read or no time passed.
+    pub(crate) fn poll(&mut self) -> Option<f64> {
+        let now = sys::ticks();
+        let last = std::mem::replace(&mut self.last, now)?;
+        let now = now?;
+        let total = now.total.checked_sub(last.total)?;
+        let busy = now.busy.checked_sub(last.busy)?;
+        (total > 0).then(|| (busy as f64 / total as f64).min(1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(
+        not(any(target_os = "macos", target_os = "linux")),
+        ignore = "requires host CPU counters"
+    )]
+    fn test_poll() {
+        let mut load = CpuLoad::new();
+        let started = std::time::Instant::now();
+        while started.elapsed() < std::time::Duration::from_millis(50) {
+            std::hint::black_box(started.elapsed());
+        }
+        let utilization = load.poll().unwrap();
+        assert!(utilization > 0.0 && utilization <= 1.0);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::sampler::DiskIo;
+
+/// Bytes read and written by all disks of the host since boot.
+#[derive(Clone, Copy, Debug)]
+struct Totals {
+    read: u64,
+    write: u64,
+}
+
+#[cfg(all(target_os = "macos", feature = "macos"))]
+mod sys {
+    use super::Totals;
+    use crate::ioreg::{Property, Service};
+
+    fn number(statistics: &Property, key: &str) -> u64 {
+        statistics
+            .get(key)
+            .and_then(Property::as_i64)
+            .map_or(0, |number| number.max(0) as u64)
+    }
+
+    pub fn totals() -> Option<Totals> {
+        let mut drivers = Service::matching("IOBlockStorageDriver").peekable();
+        drivers.peek()?;
+        let mut totals = Totals { read: 0, write: 0 };
+        for driver in drivers {
+            if let Some(statistics) = driver.property("Statistics") {
+                // keys of <IOKit/storage/IOBlockStorageDriver.h>
+                totals.read += number(&statistics, "Bytes (Read)");
+                totals.write += number(&statistics, "Bytes (Write)");
+            }
+        }
+        Some(totals)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::Totals;
+    use std::path::Path;
+
+    /// Virtual devices whose traffic is already counted on the disks beneath them.
+    const STACKED: [&str; 5] = ["loop", "ram", "zram", "dm-", "md"];
+
+    pub fn totals() -> Option<Totals> {
+        let stats = std::fs::read_to_string("/proc/diskstats").ok()?;
+        // partitions are counted on their disk, /sys/block only lists whole disks
+        Some(sum(&stats, |name| {
+            Path::new("/sys/block").join(name).exists()
+        }))
+    }
+
+    /// Sums the traffic of the disks in `/proc/diskstats` contents for which
+    /// `whole_disk` holds.
+    pub(super) fn sum(diskstats: &str, whole_disk: impl Fn(&str) -> bool) -> Totals {
+        let mut totals = Totals { read: 0, write: 0 };
+        for line in diskstats.lines() {
+            // major minor name reads merged sectors_read ms writes merged sectors_written ...
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (Some(name), Some(read), Some(written)) =
+                (fields.get(2), fields.get(5), fields.get(9))
+            else {
+                continue;
+            };
+            if STACKED.iter().any(|prefix| name.starts_with(prefix)) || !whole_disk(name) {
+                continue;
+            }
+            // sectors are 512 bytes regardless of the device
+            totals.read += read.parse::<u64>().unwrap_or(0) * 512;
+            totals.write += written.parse::<u64>().unwrap_or(0) * 512;
+        }
+        totals
+    }
+}
+
+#[cfg(not(any(all(target_os = "macos", feature = "macos"), target_os = "linux")))]
+mod sys {
+    pub fn totals() -> Option<super::Totals> {
+        None
+    }
+}
+
+/// Host disk traffic between polls.
+#[derive(Debug)]
+pub(crate) struct DiskCounters {
+    last: Option<Totals>,
+}
+
+impl DiskCounters {
+    pub(crate) fn new() -> Self {
+        DiskCounters {
+            last: sys::totals(),
+        }
+    }
+
+    /// Bytes read and written by all disks since the last poll, `None` where they
+    /// can't be read.
+    pub(crate) fn poll(&mut self) -> Option<DiskIo> {
+        let now = sys::totals();
+        let last = std::mem::replace(&mut self.last, now)?;
+        let now = now?;
+        Some(DiskIo {
+            read: now.read.saturating_sub(last.read),
+            write: now.write.saturating_sub(last.write),
+        })
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diskstats() {
+        let stats = "\
+   7       0 loop0 10 0 80 0 0 0 0 0 0 0 0
+ 253       0 vda 100 5 2000 30 50 2 1000 40 0 70 70
+ 253       1 vda1 90 5 1900 30 50 2 1000 40 0 70 70
+ 259       0 nvme0n1 10 0 4 1 20 0 8 2 0 3 3";
+        let totals = sys::sum(stats, |name| {
+            !name.ends_with(char::is_numeric) || name == "nvme0n1"
+        });
+        assert_eq!(totals.read, (2000 + 4) * 512);
+        assert_eq!(totals.write, (1000 + 8) * 512);
+    }
+}
@@ -0,0 +1,179 @@
+//! A process-wide sampler libraries record regions into, see [`global`].
+
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+use crate::backend::{DefaultBackend, PowerBackend};
+use crate::region::RegionId;
+use crate::sampler::{PowerProfile, SamplerError, StartStopSampler};
+
+/// Interval of the process-wide sampler unless [`GlobalSampler::set_interval`] changes it.
+pub const GLOBAL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+struct State<B> {
+    sampler: StartStopSampler<B>,
+    interval: Duration,
+    sessions: usize,
+}
+
+/// # GlobalSampler
+///
+/// A sampler shared by everything in the process. It samples while at least one
+/// [`GlobalSession`] is alive and stops when the last one is dropped, so libraries
+/// can measure regions without a sampler passed through their APIs.
+///
+/// Waiting for the profile of a region holds the sampler, other threads beginning or
+/// ending regions meanwhile wait too, at most one interval.
+#[derive(Debug)]
+pub struct GlobalSampler<B = DefaultBackend> {
+    state: RwLock<State<B>>,
+}
+
+/// The process-wide sampler of the default backend, created on first use.
+///
+/// ```no_run
+/// let session = wattkit::global().session().unwrap();
+/// let region = session.begin_region("tokenize").unwrap();
+/// // ...
+/// session.end_region(region);
+/// println!("{}", session.region_profile(region).unwrap());
+/// ```
+pub fn global() -> &'static GlobalSampler {
+    static GLOBAL: OnceLock<GlobalSampler> = OnceLock::new();
+    GLOBAL.get_or_init(|| GlobalSampler::with_sampler(StartStopSampler::new()))
+}
+
+impl<B: PowerBackend + 'static> GlobalSampler<B> {
+    /// A shared sampler around `sampler`, which must not be sampling already.
+    pub fn with_sampler(sampler: StartStopSampler<B>) -> Self {
+        GlobalSampler {
+            state: RwLock::new(State {
+                sampler,
+                interval: GLOBAL_INTERVAL,
+                sessions: 0,
+            }),
+        }
+    }
+
+    /// A handle keeping the sampler sampling, starting it if no other session is alive.
+    pub fn session(&self) -> Result<GlobalSession<'_, B>, SamplerError> {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        if state.sessions == 0 {
+            let interval = state.interval;
+            state.sampler.start(interval, 1)?;
+        }
+        state.sessions += 1;
+        Ok(GlobalSession { global: self })
+    }
+}
+
+impl<B> GlobalSampler<B> {
+    /// Interval of the next time sampling starts, a running session keeps its own.
+    pub fn set_interval(&self, interval: Duration) {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.interval = interval;
+    }
+
+    /// Number of live [`GlobalSession`]s.
+    pub fn sessions(&self) -> usize {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        state.sessions
+    }
+
+    pub fn is_sampling(&self) -> bool {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        state.sampler.is_sampling()
+    }
+}
+
+/// # GlobalSession
+///
+/// Keeps a [`GlobalSampler`] sampling while alive, clones count as sessions of their own.
+#[derive(Debug)]
+pub struct GlobalSession<'a, B = DefaultBackend> {
+    global: &'a GlobalSampler<B>,
+}
+
+impl<B> GlobalSession<'_, B> {
+    pub fn begin_region(&self, name: &str) -> Result<RegionId, SamplerError> {
+        let mut state = self.global.state.write().unwrap_or_else(|e| e.into_inner());
+        state.sampler.begin_region(name)
+    }
+
+    pub fn end_region(&self, id: RegionId) {
+        let mut state = self.global.state.write().unwrap_or_else(|e| e.into_inner());
+        state.sampler.end_region(id);
+    }
+
+    /// Profile of a region ended through any session. Blocks until the sample covering
+    /// its end arrives, at most one interval.
+    pub fn region_profile(&self, id: RegionId) -> Result<PowerProfile, SamplerError> {
+        let state = self.global.state.read().unwrap_or_else(|e| e.into_inner());
+        state.sampler.ended_region_profile(id)
+    }
+
+    /// Profile of everything sampled since sampling started.
+    pub fn profile_so_far(&self) -> Result<PowerProfile, SamplerError> {
+        let state = self.global.state.read().unwrap_or_else(|e| e.into_inner());
+        state.sampler.profile_so_far()
+    }
+}
+
+impl<B> Clone for GlobalSession<'_, B> {
+    fn clone(&self) -> Self {
+        let mut state = self.global.state.write().unwrap_or_else(|e| e.into_inner());
+        state.sessions += 1;
+        drop(state);
+        GlobalSession {
+            global: self.global,
+        }
+    }
+}
+
+impl<B> Drop for GlobalSession<'_, B> {
+    fn drop(&mut self) {
+        let mut state = self.global.state.write().unwrap_or_else(|e| e.into_inner());
+        state.sessions -= 1;
+        if state.sessions == 0 {
+            if let Err(e) = state.sampler.stop() {
+                warn!("failed to stop the global sampler: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockBackend;
+
+    #[test]
+    fn test_sessions_share_sampler() {
+        let sampler =
+            StartStopSampler::with_backend(|| Ok(MockBackend::constant(1000.0, 0.0, 0.0)));
+        let global = GlobalSampler::with_sampler(sampler);
+        global.set_interval(Duration::from_millis(20));
+
+        let first = global.session().unwrap();
+        let second = first.clone();
+        assert_eq!(global.sessions(), 2);
+        let region = second.begin_region("work").unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        second.end_region(region);
+        drop(second);
+
+        // the region of the dropped session is still there through the other
+        let profile = first.region_profile(region).unwrap();
+        assert!(profile.total_duration >= 60);
+        assert_eq!(profile.total_cpu_energy as u64, profile.total_duration);
+        assert!(global.is_sampling());
+        drop(first);
+        assert!(!global.is_sampling());
+
+        // sampling starts over with the next session
+        let third = global.session().unwrap();
+        assert!(global.is_sampling() && global.sessions() == 1);
+        drop(third);
+    }
+}
@@ -1,18 +1,15 @@
-use std::{
-    marker::{PhantomData, PhantomPinned},
-    mem::MaybeUninit,
-};
-
-use crate::cf_utils::*;
-use core_foundation::{
-    array::{CFArrayGetCount, CFArrayGetValueAtIndex, CFArrayRef},
-    base::{kCFAllocatorDefault, CFRelease, CFTypeRef},
-    dictionary::{
-        CFDictionaryCreateMutableCopy, CFDictionaryGetCount, CFDictionaryRef,
-        CFMutableDictionaryRef,
-    },
-    string::CFStringRef,
-};
+// Parts of the channel naming are only needed to build IOReport requests.
+#![cfg_attr(not(all(target_os = "macos", feature = "macos")), allow(dead_code))]
+
+#[cfg(all(target_os = "macos", feature = "macos"))]
+mod ffi;
+#[cfg(all(target_os = "macos", feature = "macos"))]
+pub(crate) use ffi::*;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::sampler::{CpuResidency, DieEnergy};
 
 #[derive(Debug, thiserror::Error)]
 pub enum IOReportError {
@@ -20,33 +17,14 @@ pub enum IOReportError {
     ChannelError,
     #[error("Failed to create subscription")]
     SubscriptionError,
-}
-
-pub type CVoidRef = *const std::ffi::c_void;
-type Result<T> = std::result::Result<T, IOReportError>;
-
-#[repr(C)]
-pub struct IOReportSubscription {
-    _data: [u8; 0],
-    _phantom: PhantomData<(*mut u8, PhantomPinned)>,
-}
-
-pub type IOReportSubscriptionRef = *const IOReportSubscription;
-
-#[link(name = "IOReport", kind = "dylib")]
-#[rustfmt::skip]
-extern "C" {
-  pub fn IOReportCopyAllChannels(a: u64, b: u64) -> CFMutableDictionaryRef;
-  pub fn IOReportCopyChannelsInGroup(group: CFStringRef, subgroup: CFStringRef, c: u64, d: u64, e: u64) -> CFMutableDictionaryRef;
-  pub fn IOReportMergeChannels(a: CFDictionaryRef, b: CFDictionaryRef, nil: CFTypeRef);
-  pub fn IOReportCreateSubscription(a: CVoidRef, desired_channels: CFMutableDictionaryRef, subbed_channels: *mut CFMutableDictionaryRef, channel_id: u64, b: CFTypeRef) -> IOReportSubscriptionRef;
-  pub fn IOReportCreateSamples(a: IOReportSubscriptionRef, b: CFMutableDictionaryRef, c: CFTypeRef) -> CFDictionaryRef;
-  pub fn IOReportCreateSamplesDelta(a: CFDictionaryRef, b: CFDictionaryRef, c: CFTypeRef) -> CFDictionaryRef;
-  pub fn IOReportChannelGetGroup(a: CFDictionaryRef) -> CFStringRef;
-  pub fn IOReportChannelGetSubGroup(a: CFDictionaryRef) -> CFStringRef;
-  pub fn IOReportChannelGetChannelName(a: CFDictionaryRef) -> CFStringRef;
-  pub fn IOReportSimpleGetIntegerValue(a: CFDictionaryRef, b: *mut i32) -> i64;
-  pub fn IOReportChannelGetUnitLabel(a: CFDictionaryRef) -> CFStringRef;
+    #[error("Failed to create sample")]
+    SampleError,
+    #[error("IOReport is only available on macOS with the `macos` feature")]
+    Unavailable,
+    #[error("Unknown energy unit: {0}")]
+    UnknownUnit(String),
+    #[error("Negative delta on channel {channel}")]
+    InvalidDelta { channel: String, duration: u64 },
 }
 
 #[allow(clippy::enum_variant_names)]
@@ -55,6 +33,19 @@ pub enum EnergyUnit {
     MilliJoules,
     MicroJoules,
     NanoJoules,
+    Unknown(String),
+}
+
+impl EnergyUnit {
+    /// Converts a raw counter value in this unit to millijoules.
+    pub fn to_millijoules(&self, value: u128) -> Result<u128, IOReportError> {
+        match self {
+            Self::MilliJoules => Ok(value),
+            Self::MicroJoules => Ok(value / 1_000),
+            Self::NanoJoules => Ok(value / 1_000_000),
+            Self::Unknown(unit) => Err(IOReportError::UnknownUnit(unit.clone())),
+        }
+    }
 }
 
 impl std::fmt::Display for EnergyUnit {
@@ -63,6 +54,7 @@ impl std::fmt::Display for EnergyUnit {
             Self::MilliJoules => write!(f, "mJ"),
             Self::MicroJoules => write!(f, "μJ"), //careful, u != μ but goddamn it looks better
             Self::NanoJoules => write!(f, "nJ"),
+            Self::Unknown(s) => write!(f, "{s}"),
         }
     }
 }
@@ -73,35 +65,7 @@ impl<S: AsRef<str>> From<S> for EnergyUnit {
             "mJ" => Self::MilliJoules,
             "uJ" => Self::MicroJoules,
             "nJ" => Self::NanoJoules,
-            _ => panic!("Invalid energy unit: {}", s.as_ref()),
-        }
-    }
-}
-
-pub struct IOReportIterator {
-    sample: CFDictionaryRef,
-    index: isize,
-    channels: CFArrayRef,
-    num_channels: isize,
-}
-
-impl IOReportIterator {
-    pub fn new(data: CFDictionaryRef) -> Self {
-        let channels = cfdict_get_val(data, "IOReportChannels").unwrap() as CFArrayRef;
-        let num_channels = unsafe { CFArrayGetCount(channels) } as isize;
-        Self {
-            sample: data,
-            channels,
-            num_channels,
-            index: 0,
-        }
-    }
-}
-
-impl Drop for IOReportIterator {
-    fn drop(&mut self) {
-        unsafe {
-            CFRelease(self.sample as _);
+            s => Self::Unknown(s.to_string()),
         }
     }
 }
@@ -113,6 +77,7 @@ pub enum IOReportChannelGroup {
     GPUStats,
     H11ANE,
     SoCStats,
+    AMCStats,
     Unknown(String),
 }
 
@@ -124,6 +89,7 @@ impl IOReportChannelGroup {
             Self::GPUStats => "GPU Stats",
             Self::H11ANE => "H11ANE",
             Self::SoCStats => "SoC Stats",
+            Self::AMCStats => "AMC Stats",
             Self::Unknown(s) => s.as_str(),
         }
     }
@@ -137,17 +103,24 @@ impl<S: AsRef<str>> From<S> for IOReportChannelGroup {
             "GPU Stats" => Self::GPUStats,
             "H11ANE" => Self::H11ANE,
             "SoC Stats" => Self::SoCStats,
+            "AMC Stats" => Self::AMCStats,
             s => Self::Unknown(s.to_string()),
         }
     }
 }
 
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum IOReportChannelName {
     CPUEnergy,
     GPUEnergy,
     ANE,
+    DRAM,
+    /// Display pipeline and backlight, only on some machines and under varying names.
+    Display,
+    /// Efficiency and performance CPU clusters, breakdowns of `CPUEnergy`.
+    ECPU,
+    PCPU,
     Unknown(String),
 }
 
@@ -157,238 +130,387 @@ impl IOReportChannelName {
             Self::CPUEnergy => "CPU Energy",
             Self::GPUEnergy => "GPU Energy",
             Self::ANE => "ANE",
+            Self::DRAM => "DRAM",
+            Self::Display => "DISP",
+            Self::ECPU => "ECPU",
+            Self::PCPU => "PCPU",
             Self::Unknown(s) => s.as_str(),
         }
     }
 }
 
-impl From<String> for IOReportChannelName {
-    fn from(s: String) -> Self {
-        match s.as_str() {
-            "CPU Energy" => Self::CPUEnergy,
-            "GPU Energy" => Self::GPUEnergy,
-            c if c.starts_with("ANE") => Self::ANE,
-            s => Self::Unknown(s.to_string()),
-        }
-    }
-}
-
-impl std::fmt::Display for IOReportChannelName {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.as_str())
-    }
-}
-
-#[allow(dead_code)]
-#[derive(Debug)]
-pub struct IOReportIteratorItem {
-    pub group: IOReportChannelGroup,
-    pub subgroup: String,
-    pub channel_name: IOReportChannelName,
-    pub unit: String,
-    pub item: CFDictionaryRef,
+/// How [`CHANNEL_NAMES`] matches a channel name.
+enum NamePattern {
+    Exact(&'static str),
+    Prefix(&'static str),
+    Contains(&'static str),
 }
 
-impl Iterator for IOReportIterator {
-    type Item = IOReportIteratorItem;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.num_channels {
-            return None;
+impl NamePattern {
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Exact(pattern) => name == *pattern,
+            Self::Prefix(pattern) => name.starts_with(pattern),
+            Self::Contains(pattern) => name.contains(pattern),
         }
-
-        let item = unsafe { CFArrayGetValueAtIndex(self.channels, self.index) } as CFDictionaryRef;
-
-        let group =
-            IOReportChannelGroup::from(get_cf_string(|| unsafe { IOReportChannelGetGroup(item) }));
-        let subgroup = get_cf_string(|| unsafe { IOReportChannelGetSubGroup(item) });
-        let channel = IOReportChannelName::from(get_cf_string(|| unsafe {
-            IOReportChannelGetChannelName(item)
-        }));
-        let unit = from_cfstr(unsafe { IOReportChannelGetUnitLabel(item) })
-            .trim()
-            .to_string();
-
-        self.index += 1;
-        Some(IOReportIteratorItem {
-            group,
-            subgroup,
-            channel_name: channel,
-            unit,
-            item,
-        })
     }
 }
 
-pub struct IOReportSample {
-    iterator: IOReportIterator,
-    duration: u64,
-}
-
-impl IOReportSample {
-    pub fn iterator(&self) -> &IOReportIterator {
-        &self.iterator
-    }
-
-    pub fn iterator_mut(&mut self) -> &mut IOReportIterator {
-        &mut self.iterator
-    }
-
-    pub fn duration(&self) -> u64 {
-        self.duration
+/// The names each typed channel has gone by across chips and macOS releases, the first
+/// match wins. Names new releases introduce go here, so code using the typed channels
+/// keeps working; [`unmapped_channels`] lists the ones that aren't yet.
+///
+/// [`unmapped_channels`]: crate::backend::ioreport::unmapped_channels
+const CHANNEL_NAMES: &[(NamePattern, IOReportChannelName)] = &[
+    (
+        NamePattern::Exact("CPU Energy"),
+        IOReportChannelName::CPUEnergy,
+    ),
+    (
+        NamePattern::Exact("GPU Energy"),
+        IOReportChannelName::GPUEnergy,
+    ),
+    // "ANE" on base chips, "ANE0" on Pro and Max
+    (NamePattern::Prefix("ANE"), IOReportChannelName::ANE),
+    // "DRAM" and "DRAM0"
+    (NamePattern::Prefix("DRAM"), IOReportChannelName::DRAM),
+    (NamePattern::Prefix("DISP"), IOReportChannelName::Display),
+    (
+        NamePattern::Contains("Display"),
+        IOReportChannelName::Display,
+    ),
+    (
+        NamePattern::Contains("Backlight"),
+        IOReportChannelName::Display,
+    ),
+    // M1 names its clusters by their L2 ("EACC", "PACC0"), later chips by their cores,
+    // whose per-core channels are numbered "ECPU0" and up
+    (NamePattern::Exact("EACC_CPU"), IOReportChannelName::ECPU),
+    (NamePattern::Exact("ECPU"), IOReportChannelName::ECPU),
+    (NamePattern::Exact("PACC_CPU"), IOReportChannelName::PCPU),
+    (NamePattern::Exact("PACC0_CPU"), IOReportChannelName::PCPU),
+    (NamePattern::Exact("PACC1_CPU"), IOReportChannelName::PCPU),
+    (NamePattern::Exact("PCPU"), IOReportChannelName::PCPU),
+];
+
+impl<S: AsRef<str>> From<S> for IOReportChannelName {
+    fn from(s: S) -> Self {
+        let s = s.as_ref();
+        CHANNEL_NAMES
+            .iter()
+            .find(|(pattern, _)| pattern.matches(s))
+            .map_or_else(|| Self::Unknown(s.to_string()), |(_, name)| name.clone())
     }
 }
 
-impl IOReportSample {
-    pub fn new(iterator: IOReportIterator, duration: u64) -> Self {
-        Self { iterator, duration }
+impl std::fmt::Display for IOReportChannelName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
     }
 }
 
-pub struct IOReportChannelRequest {
-    pub group: IOReportChannelGroup,
-    pub subgroup: Option<String>,
-}
+/// Shares one allocation between the copies of a string. Channel metadata repeats in
+/// every sample, interned it costs a lookup rather than an allocation per channel.
+#[derive(Debug, Default)]
+pub struct Interner(HashSet<Arc<str>>);
 
-impl IOReportChannelRequest {
-    pub fn new<S: ToString>(group: IOReportChannelGroup, subgroup: Option<S>) -> Self {
-        Self {
-            group,
-            subgroup: subgroup.map(|s| s.to_string()),
+impl Interner {
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(interned) = self.0.get(s) {
+            return interned.clone();
         }
+        let interned: Arc<str> = Arc::from(s);
+        self.0.insert(interned.clone());
+        interned
     }
 }
 
-#[derive(Debug)]
-pub struct IOReport {
-    subscription: IOReportSubscriptionRef,
-    channels: CFMutableDictionaryRef,
-    previous: Option<(CFDictionaryRef, std::time::Instant)>,
+/// The "Energy Model" channels of one sample in millijoules, by what they measure.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EnergyModelSample {
+    pub cpu: u128,
+    pub gpu: u128,
+    pub ane: u128,
+    pub dram: u128,
+    /// `None` on machines without display channels.
+    pub display: Option<u128>,
+    /// Efficiency and performance clusters, breakdowns of `cpu`. `None` on chips without
+    /// cluster channels.
+    pub efficiency_cpu: Option<u128>,
+    pub performance_cpu: Option<u128>,
+    /// Every other channel by name, the per-core breakdowns of `cpu` and `gpu` included.
+    pub other: HashMap<String, u128>,
+    /// `cpu`, `gpu` and `ane` of each die of an Ultra, which has their channels once per
+    /// die. Empty on single-die chips.
+    pub dies: Vec<DieEnergy>,
 }
 
-impl IOReport {
-    pub fn new(channels: Vec<IOReportChannelRequest>) -> Result<Self> {
-        let channels = Self::create_channels(channels)?;
-        let subscription = Self::subscribe(channels)?;
-
-        Ok(Self {
-            subscription,
-            channels,
-            previous: None,
-        })
-    }
-
-    fn subscribe(channel: CFMutableDictionaryRef) -> Result<IOReportSubscriptionRef> {
-        let mut subscription: MaybeUninit<CFMutableDictionaryRef> = MaybeUninit::uninit();
-        let sub_ref = unsafe {
-            IOReportCreateSubscription(
-                std::ptr::null(),
-                channel,
-                subscription.as_mut_ptr(),
-                0,
-                std::ptr::null(),
-            )
-        };
-        if sub_ref.is_null() {
-            return Err(IOReportError::SubscriptionError);
+/// Die of a channel of a multi-die SoC and the name it has on single-die ones. Ultras
+/// prefix channels with "DIE_1_" and suffix ANE channels with "_1", e.g. "ANE0_1".
+pub(crate) fn split_die(channel: &str) -> (Option<usize>, &str) {
+    if let Some((die, name)) = channel
+        .strip_prefix("DIE_")
+        .and_then(|rest| rest.split_once('_'))
+    {
+        if let Ok(die) = die.parse() {
+            return (Some(die), name);
         }
-
-        unsafe { subscription.assume_init() };
-        Ok(sub_ref)
     }
-
-    fn create_channels(
-        channel_reqs: Vec<IOReportChannelRequest>,
-    ) -> Result<CFMutableDictionaryRef> {
-        // if no items are provided, return all channels
-        if channel_reqs.is_empty() {
-            unsafe {
-                let c = IOReportCopyAllChannels(0, 0);
-                let dict_ref =
-                    CFDictionaryCreateMutableCopy(kCFAllocatorDefault, CFDictionaryGetCount(c), c);
-                CFRelease(c as _);
-                return Ok(dict_ref);
+    if let Some((name, die)) = channel.rsplit_once('_') {
+        if name.starts_with("ANE") {
+            if let Ok(die) = die.parse() {
+                return (Some(die), name);
             }
         }
+    }
+    (None, channel)
+}
 
-        let mut channels = Vec::with_capacity(channel_reqs.len());
-        for request in channel_reqs {
-            let gname = cfstr(request.group.as_str());
-            let sname = request.subgroup.as_deref().map_or(std::ptr::null(), cfstr);
-            let chan = unsafe { IOReportCopyChannelsInGroup(gname, sname, 0, 0, 0) };
-            channels.push(chan);
-
-            unsafe { CFRelease(gname as _) };
-            if request.subgroup.is_some() {
-                unsafe { CFRelease(sname as _) };
+/// Subgroup of "CPU Stats" with the performance state residency of each CPU cluster.
+pub(crate) const CLUSTER_STATES: &str = "CPU Complex Performance States";
+
+/// Residency of the busiest of `clusters`, the states of each "CPU Complex Performance
+/// States" channel from lowest to highest. Idle states are named "IDLE", "OFF" or
+/// "DOWN", the rest are active. `None` without any residency.
+pub(crate) fn cluster_residency<'a>(
+    clusters: impl IntoIterator<Item = &'a [(Arc<str>, i64)]>,
+) -> Option<CpuResidency> {
+    clusters
+        .into_iter()
+        .filter_map(|states| {
+            let is_active = |name: &str| !matches!(name, "IDLE" | "OFF" | "DOWN");
+            let active: Vec<f64> = states
+                .iter()
+                .filter(|(name, _)| is_active(name))
+                .map(|(_, residency)| (*residency).max(0) as f64)
+                .collect();
+            let total: f64 = states.iter().map(|(_, r)| (*r).max(0) as f64).sum();
+            let busy: f64 = active.iter().sum();
+            if total <= 0.0 {
+                return None;
             }
-        }
-
-        let base_channel = channels[0];
-        for channel in channels.iter().skip(1) {
-            unsafe { IOReportMergeChannels(base_channel, *channel, std::ptr::null()) };
-        }
-
-        let size = unsafe { CFDictionaryGetCount(base_channel) };
-        let chan_dict_ref =
-            unsafe { CFDictionaryCreateMutableCopy(kCFAllocatorDefault, size, base_channel) };
-
-        for channel in channels {
-            unsafe { CFRelease(channel as _) };
-        }
+            let top = (active.len().max(2) - 1) as f64;
+            let position: f64 = active
+                .iter()
+                .enumerate()
+                .map(|(i, residency)| residency * i as f64 / top)
+                .sum();
+            Some(CpuResidency {
+                active: busy / total,
+                performance_state: if busy > 0.0 { position / busy } else { 0.0 },
+            })
+        })
+        .max_by(|a, b| a.active.total_cmp(&b.active))
+}
 
-        if cfdict_get_val(chan_dict_ref, "IOReportChannels").is_none() {
-            return Err(IOReportError::ChannelError);
+impl EnergyModelSample {
+    /// Sorts `(channel, unit, value)` deltas of the Energy Model group into a sample.
+    /// An unknown unit or a negative value is an error on the CPU, GPU, ANE and display
+    /// channels, the others are left out rather than failing the sample.
+    pub fn parse<'a>(
+        channels: impl IntoIterator<Item = (&'a str, &'a str, i64)>,
+        duration: u64,
+    ) -> Result<Self, IOReportError> {
+        let mut sample = Self::default();
+        for (channel, unit, value) in channels {
+            let (die, name) = split_die(channel);
+            let name = IOReportChannelName::from(name);
+            if !matches!(
+                name,
+                IOReportChannelName::CPUEnergy
+                    | IOReportChannelName::GPUEnergy
+                    | IOReportChannelName::ANE
+                    | IOReportChannelName::Display
+            ) {
+                let energy = (value >= 0)
+                    .then(|| EnergyUnit::from(unit).to_millijoules(value as u128).ok())
+                    .flatten();
+                match (name, energy) {
+                    (IOReportChannelName::DRAM, Some(energy)) => sample.dram += energy,
+                    (IOReportChannelName::ECPU, Some(energy)) => {
+                        *sample.efficiency_cpu.get_or_insert(0) += energy
+                    }
+                    (IOReportChannelName::PCPU, Some(energy)) => {
+                        *sample.performance_cpu.get_or_insert(0) += energy
+                    }
+                    (IOReportChannelName::Unknown(_), Some(energy)) => {
+                        *sample.other.entry(channel.to_string()).or_default() += energy
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            // counters can go backwards across driver resets
+            if value < 0 {
+                return Err(IOReportError::InvalidDelta {
+                    channel: channel.to_string(),
+                    duration,
+                });
+            }
+            let energy = EnergyUnit::from(unit).to_millijoules(value as u128)?;
+            let die = die.map(|die| {
+                if sample.dies.len() <= die {
+                    sample.dies.resize(die + 1, DieEnergy::default());
+                }
+                &mut sample.dies[die]
+            });
+            match name {
+                IOReportChannelName::CPUEnergy => {
+                    sample.cpu += energy;
+                    if let Some(die) = die {
+                        die.cpu += energy;
+                    }
+                }
+                IOReportChannelName::GPUEnergy => {
+                    sample.gpu += energy;
+                    if let Some(die) = die {
+                        die.gpu += energy;
+                    }
+                }
+                IOReportChannelName::ANE => {
+                    sample.ane += energy;
+                    if let Some(die) = die {
+                        die.ane += energy;
+                    }
+                }
+                _ => *sample.display.get_or_insert(0) += energy,
+            }
         }
-
-        Ok(chan_dict_ref)
+        Ok(sample)
     }
 
-    fn initial_sample(&self) -> (CFDictionaryRef, std::time::Instant) {
-        (
-            unsafe { IOReportCreateSamples(self.subscription, self.channels, std::ptr::null()) },
-            std::time::Instant::now(),
-        )
+    /// Energy of the whole SoC, without counting the breakdowns of `cpu` and `gpu` twice.
+    pub fn package(&self) -> u128 {
+        let other: u128 = self
+            .other
+            .iter()
+            .filter(|(name, _)| !name.contains("CPU") && !name.contains("GPU"))
+            .map(|(_, energy)| energy)
+            .sum();
+        self.cpu + self.gpu + self.ane + self.dram + self.display.unwrap_or(0) + other
     }
+}
 
-    pub fn get_samples(&mut self, duration: u64, count: usize) -> Vec<IOReportSample> {
-        let mut samples: Vec<IOReportSample> = Vec::with_capacity(count);
-        let step_msec = duration / count as u64;
-
-        let mut prev = match self.previous {
-            Some(x) => x,
-            None => self.initial_sample(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cluster_residency() {
+        let states = |residencies: &[(&str, i64)]| -> Vec<(Arc<str>, i64)> {
+            residencies
+                .iter()
+                .map(|&(name, residency)| (name.into(), residency))
+                .collect()
         };
+        let efficiency = states(&[("IDLE", 90), ("V0P6", 10), ("V1P6", 0)]);
+        // busy 80% of the time, half of it in the top state
+        let performance = states(&[
+            ("OFF", 10),
+            ("IDLE", 10),
+            ("V0P5", 20),
+            ("V1P5", 20),
+            ("V2P5", 40),
+        ]);
+        let residency = cluster_residency([&efficiency[..], &performance[..]]).unwrap();
+        assert_eq!(residency.active, 0.8);
+        assert_eq!(residency.performance_state, (20.0 * 0.5 + 40.0) / 80.0);
+
+        assert_eq!(cluster_residency([&states(&[("IDLE", 0)])[..]]), None);
+    }
 
-        for _ in 0..count {
-            std::thread::sleep(std::time::Duration::from_millis(step_msec));
-
-            let next = self.initial_sample();
-            let diff = unsafe { IOReportCreateSamplesDelta(prev.0, next.0, std::ptr::null()) };
-            unsafe { CFRelease(prev.0 as _) };
-
-            let elapsed = next.1.duration_since(prev.1).as_millis() as u64;
-            prev = next;
-
-            samples.push(IOReportSample::new(
-                IOReportIterator::new(diff),
-                elapsed.max(1),
-            ));
-        }
-
-        self.previous = Some(prev);
-        samples
+    #[test]
+    fn test_interner() {
+        let mut interner = Interner::default();
+        let first = interner.intern("CPU Energy");
+        let second = interner.intern(&String::from("CPU Energy"));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &interner.intern("GPU Energy")));
     }
-}
 
-impl Drop for IOReport {
-    fn drop(&mut self) {
-        unsafe {
-            CFRelease(self.channels as _);
-            CFRelease(self.subscription as _);
-            if self.previous.is_some() {
-                CFRelease(self.previous.unwrap().0 as _);
+    #[test]
+    fn test_channel_names() {
+        for (names, expected) in [
+            (&["ANE", "ANE0"][..], IOReportChannelName::ANE),
+            (&["EACC_CPU", "ECPU"], IOReportChannelName::ECPU),
+            (
+                &["PACC0_CPU", "PACC1_CPU", "PCPU"],
+                IOReportChannelName::PCPU,
+            ),
+            (
+                &["DISP", "DISPEXT", "Backlight"],
+                IOReportChannelName::Display,
+            ),
+        ] {
+            for name in names {
+                assert_eq!(IOReportChannelName::from(name), expected, "{name}");
             }
         }
+        assert_eq!(
+            IOReportChannelName::from("ECPU0"),
+            IOReportChannelName::Unknown("ECPU0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_energy_model() {
+        let sample = EnergyModelSample::parse(
+            [
+                ("CPU Energy", "mJ", 120),
+                ("ECPU", "mJ", 20),
+                ("GPU Energy", "nJ", 5_000_000),
+                ("ANE0", "uJ", 3_000),
+                ("DRAM", "mJ", 7),
+                ("ISP", "mJ", 2),
+                // unknown unit, not part of any total
+                ("AMCC", "pJ", 1),
+            ],
+            100,
+        )
+        .unwrap();
+        assert_eq!((sample.cpu, sample.gpu, sample.ane), (120, 5, 3));
+        assert_eq!((sample.dram, sample.display), (7, None));
+        assert_eq!(
+            (sample.efficiency_cpu, sample.performance_cpu),
+            (Some(20), None)
+        );
+        assert_eq!(sample.other.len(), 1);
+        assert_eq!(sample.package(), 120 + 5 + 3 + 7 + 2);
+
+        let ultra = EnergyModelSample::parse(
+            [
+                ("DIE_0_CPU Energy", "mJ", 100),
+                ("DIE_1_CPU Energy", "mJ", 40),
+                ("DIE_0_GPU Energy", "mJ", 10),
+                ("DIE_1_GPU Energy", "mJ", 30),
+                ("ANE0_0", "mJ", 5),
+                ("ANE0_1", "mJ", 0),
+                ("DIE_1_ECPU", "mJ", 8),
+                ("DIE_1_ECPU0", "mJ", 2),
+            ],
+            100,
+        )
+        .unwrap();
+        assert_eq!((ultra.cpu, ultra.gpu, ultra.ane), (140, 40, 5));
+        assert_eq!(
+            ultra.dies,
+            [
+                DieEnergy {
+                    cpu: 100,
+                    gpu: 10,
+                    ane: 5
+                },
+                DieEnergy {
+                    cpu: 40,
+                    gpu: 30,
+                    ane: 0
+                },
+            ]
+        );
+        assert_eq!(ultra.efficiency_cpu, Some(8));
+        assert!(sample.dies.is_empty());
+
+        let reset = EnergyModelSample::parse([("GPU Energy", "mJ", -1)], 100);
+        assert!(matches!(reset, Err(IOReportError::InvalidDelta { .. })));
     }
 }
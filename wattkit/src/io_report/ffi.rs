@@ -0,0 +1,380 @@
+//! IOReport bindings, only built on macOS with the `macos` feature.
+
+use std::{
+    cell::RefCell,
+    marker::{PhantomData, PhantomPinned},
+    mem::{ManuallyDrop, MaybeUninit},
+    sync::{Arc, Mutex},
+};
+
+use super::{IOReportChannelGroup, IOReportError, Interner};
+use crate::cf_utils::*;
+use core_foundation::{
+    array::{CFArrayGetCount, CFArrayGetValueAtIndex, CFArrayRef},
+    base::{CFRelease, CFTypeRef},
+    dictionary::{CFDictionaryRef, CFMutableDictionaryRef},
+    string::CFStringRef,
+};
+
+pub(crate) type CVoidRef = *const std::ffi::c_void;
+type Result<T> = std::result::Result<T, IOReportError>;
+
+#[repr(C)]
+pub(crate) struct IOReportSubscription {
+    _data: [u8; 0],
+    _phantom: PhantomData<(*mut u8, PhantomPinned)>,
+}
+
+pub(crate) type IOReportSubscriptionRef = *const IOReportSubscription;
+
+#[link(name = "IOReport", kind = "dylib")]
+#[rustfmt::skip]
+extern "C" {
+  pub(crate) fn IOReportCopyAllChannels(a: u64, b: u64) -> CFMutableDictionaryRef;
+  pub(crate) fn IOReportCopyChannelsInGroup(group: CFStringRef, subgroup: CFStringRef, c: u64, d: u64, e: u64) -> CFMutableDictionaryRef;
+  pub(crate) fn IOReportMergeChannels(a: CFDictionaryRef, b: CFDictionaryRef, nil: CFTypeRef);
+  pub(crate) fn IOReportCreateSubscription(a: CVoidRef, desired_channels: CFMutableDictionaryRef, subbed_channels: *mut CFMutableDictionaryRef, channel_id: u64, b: CFTypeRef) -> IOReportSubscriptionRef;
+  pub(crate) fn IOReportCreateSamples(a: IOReportSubscriptionRef, b: CFMutableDictionaryRef, c: CFTypeRef) -> CFDictionaryRef;
+  pub(crate) fn IOReportCreateSamplesDelta(a: CFDictionaryRef, b: CFDictionaryRef, c: CFTypeRef) -> CFDictionaryRef;
+  pub(crate) fn IOReportChannelGetGroup(a: CFDictionaryRef) -> CFStringRef;
+  pub(crate) fn IOReportChannelGetSubGroup(a: CFDictionaryRef) -> CFStringRef;
+  pub(crate) fn IOReportChannelGetChannelName(a: CFDictionaryRef) -> CFStringRef;
+  pub(crate) fn IOReportSimpleGetIntegerValue(a: CFDictionaryRef, b: *mut i32) -> i64;
+  pub(crate) fn IOReportChannelGetUnitLabel(a: CFDictionaryRef) -> CFStringRef;
+  pub(crate) fn IOReportChannelGetFormat(a: CFDictionaryRef) -> u8;
+  pub(crate) fn IOReportStateGetCount(a: CFDictionaryRef) -> i32;
+  pub(crate) fn IOReportStateGetNameForIndex(a: CFDictionaryRef, index: i32) -> CFStringRef;
+  pub(crate) fn IOReportStateGetResidency(a: CFDictionaryRef, index: i32) -> i64;
+}
+
+// kIOReportFormatState
+const FORMAT_STATE: u8 = 2;
+
+/// Owned subscription handle, released on drop.
+#[derive(Debug)]
+struct Subscription(IOReportSubscriptionRef);
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        unsafe { CFRelease(self.0 as _) };
+    }
+}
+
+thread_local! {
+    /// Channel metadata seen on this thread, reports never leave the thread they opened on.
+    static NAMES: RefCell<Interner> = RefCell::default();
+}
+
+fn intern<F>(getter: F) -> Arc<str>
+where
+    F: FnOnce() -> CFStringRef,
+{
+    let interned = |s: &str| NAMES.with(|names| names.borrow_mut().intern(s.trim()));
+    match getter() {
+        x if x.is_null() => interned(""),
+        x => with_cfstr(x, interned),
+    }
+}
+
+/// Iterates the channels of a sample it owns. Entries copy what they need out of the
+/// sample, so they can outlive the iterator.
+pub(crate) struct IOReportIterator {
+    // keeps `channels` alive
+    _sample: CfDict,
+    index: isize,
+    channels: CFArrayRef,
+    num_channels: isize,
+}
+
+impl IOReportIterator {
+    pub(crate) fn new(sample: CfDict) -> Result<Self> {
+        let channels = sample
+            .get("IOReportChannels")
+            .ok_or(IOReportError::ChannelError)? as CFArrayRef;
+        let num_channels = unsafe { CFArrayGetCount(channels) } as isize;
+        Ok(Self {
+            _sample: sample,
+            channels,
+            num_channels,
+            index: 0,
+        })
+    }
+}
+
+/// Metadata of a channel, shared with the same channel of every other sample.
+#[derive(Debug)]
+pub(crate) struct IOReportIteratorItem {
+    pub(crate) group: Arc<str>,
+    pub(crate) subgroup: Arc<str>,
+    pub(crate) channel: Arc<str>,
+    pub(crate) unit: Arc<str>,
+    /// Value of a simple channel, meaningless for state and histogram channels.
+    pub(crate) value: i64,
+    /// Name and residency of each state of a state channel, in the channel's order.
+    /// Empty for other channels.
+    pub(crate) states: Vec<(Arc<str>, i64)>,
+}
+
+impl Iterator for IOReportIterator {
+    type Item = IOReportIteratorItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.num_channels {
+            return None;
+        }
+
+        let item = unsafe { CFArrayGetValueAtIndex(self.channels, self.index) } as CFDictionaryRef;
+
+        let group = intern(|| unsafe { IOReportChannelGetGroup(item) });
+        let subgroup = intern(|| unsafe { IOReportChannelGetSubGroup(item) });
+        let channel = intern(|| unsafe { IOReportChannelGetChannelName(item) });
+        let unit = intern(|| unsafe { IOReportChannelGetUnitLabel(item) });
+        let value = unsafe { IOReportSimpleGetIntegerValue(item, std::ptr::null_mut()) };
+        let states = if unsafe { IOReportChannelGetFormat(item) } == FORMAT_STATE {
+            (0..unsafe { IOReportStateGetCount(item) })
+                .map(|i| {
+                    let name = intern(|| unsafe { IOReportStateGetNameForIndex(item, i) });
+                    (name, unsafe { IOReportStateGetResidency(item, i) })
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        self.index += 1;
+        Some(IOReportIteratorItem {
+            group,
+            subgroup,
+            channel,
+            unit,
+            value,
+            states,
+        })
+    }
+}
+
+pub(crate) struct IOReportSample {
+    iterator: IOReportIterator,
+    duration: u64,
+}
+
+impl IOReportSample {
+    pub(crate) fn iterator_mut(&mut self) -> &mut IOReportIterator {
+        &mut self.iterator
+    }
+
+    pub(crate) fn duration(&self) -> u64 {
+        self.duration
+    }
+}
+
+impl IOReportSample {
+    pub(crate) fn new(iterator: IOReportIterator, duration: u64) -> Self {
+        Self { iterator, duration }
+    }
+}
+
+/// Group and subgroup of every request, identifies the channels of a subscription.
+type ChannelKey = Vec<(String, Option<String>)>;
+
+/// Most subscriptions kept around for reuse, one per channel set in practice.
+const POOL_SIZE: usize = 4;
+
+/// Subscriptions of dropped reports, creating one is slow enough to add up over the many
+/// short sessions of a test suite.
+static POOL: Mutex<Vec<(ChannelKey, Handle)>> = Mutex::new(Vec::new());
+
+/// A subscription and the channels it was created for.
+#[derive(Debug)]
+struct Handle {
+    subscription: Subscription,
+    channels: CfDict,
+}
+
+// Moved out of the pool before use, so only one thread ever uses it at a time.
+unsafe impl Send for Handle {}
+
+pub(crate) struct IOReportChannelRequest {
+    pub(crate) group: IOReportChannelGroup,
+    pub(crate) subgroup: Option<String>,
+}
+
+impl IOReportChannelRequest {
+    pub(crate) fn new<S: ToString>(group: IOReportChannelGroup, subgroup: Option<S>) -> Self {
+        Self {
+            group,
+            subgroup: subgroup.map(|s| s.to_string()),
+        }
+    }
+}
+
+/// Deliberately neither `Send` nor `Sync`, the raw handles keep it on the thread that
+/// subscribed. Samplers open it on their sampling thread and only exchange samples.
+///
+/// The subscription goes back to a process-wide pool on drop, the next report for the
+/// same channels reuses it.
+#[derive(Debug)]
+pub(crate) struct IOReport {
+    key: ChannelKey,
+    handle: ManuallyDrop<Handle>,
+    previous: Option<(CfDict, std::time::Instant)>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl IOReport {
+    pub(crate) fn new(channels: Vec<IOReportChannelRequest>) -> Result<Self> {
+        let key: ChannelKey = channels
+            .iter()
+            .map(|request| (request.group.as_str().to_string(), request.subgroup.clone()))
+            .collect();
+        let pooled = {
+            let mut pool = POOL.lock().unwrap_or_else(|e| e.into_inner());
+            let index = pool.iter().position(|(pooled, _)| *pooled == key);
+            index.map(|index| pool.swap_remove(index).1)
+        };
+        let handle = match pooled {
+            Some(handle) => handle,
+            None => {
+                let channels = Self::create_channels(channels)?;
+                let subscription = Self::subscribe(&channels)?;
+                Handle {
+                    subscription,
+                    channels,
+                }
+            }
+        };
+
+        Ok(Self {
+            key,
+            handle: ManuallyDrop::new(handle),
+            previous: None,
+            _not_send: PhantomData,
+        })
+    }
+
+    fn subscribe(channels: &CfDict) -> Result<Subscription> {
+        // Ownership of the subscribed channel dictionary is undocumented, it is never
+        // released rather than risk a double free.
+        let mut subscribed: MaybeUninit<CFMutableDictionaryRef> = MaybeUninit::uninit();
+        let sub_ref = unsafe {
+            IOReportCreateSubscription(
+                std::ptr::null(),
+                channels.as_mut_ptr(),
+                subscribed.as_mut_ptr(),
+                0,
+                std::ptr::null(),
+            )
+        };
+        if sub_ref.is_null() {
+            return Err(IOReportError::SubscriptionError);
+        }
+        Ok(Subscription(sub_ref))
+    }
+
+    fn create_channels(channel_reqs: Vec<IOReportChannelRequest>) -> Result<CfDict> {
+        // if no items are provided, return all channels
+        if channel_reqs.is_empty() {
+            let all = unsafe { CfDict::from_create_rule(IOReportCopyAllChannels(0, 0)) }
+                .ok_or(IOReportError::ChannelError)?;
+            return all.mutable_copy().ok_or(IOReportError::ChannelError);
+        }
+
+        let mut channels = Vec::with_capacity(channel_reqs.len());
+        for request in channel_reqs {
+            let group = CfString::new(request.group.as_str());
+            let subgroup = request.subgroup.as_deref().map(CfString::new);
+            let subgroup = subgroup.as_ref().map_or(std::ptr::null(), CfString::as_ptr);
+            let chan = unsafe { IOReportCopyChannelsInGroup(group.as_ptr(), subgroup, 0, 0, 0) };
+            channels.push(
+                unsafe { CfDict::from_create_rule(chan) }.ok_or(IOReportError::ChannelError)?,
+            );
+        }
+
+        let (base_channel, rest) = channels.split_first().ok_or(IOReportError::ChannelError)?;
+        for channel in rest {
+            unsafe {
+                IOReportMergeChannels(base_channel.as_ptr(), channel.as_ptr(), std::ptr::null())
+            };
+        }
+
+        let chan_dict = base_channel
+            .mutable_copy()
+            .ok_or(IOReportError::ChannelError)?;
+        if chan_dict.get("IOReportChannels").is_none() {
+            return Err(IOReportError::ChannelError);
+        }
+
+        Ok(chan_dict)
+    }
+
+    /// Iterates the subscribed channels of a fresh sample.
+    pub(crate) fn channels(&self) -> Result<IOReportIterator> {
+        IOReportIterator::new(self.initial_sample()?.0)
+    }
+
+    fn initial_sample(&self) -> Result<(CfDict, std::time::Instant)> {
+        let sample = unsafe {
+            IOReportCreateSamples(
+                self.handle.subscription.0,
+                self.handle.channels.as_mut_ptr(),
+                std::ptr::null(),
+            )
+        };
+        let sample =
+            unsafe { CfDict::from_create_rule(sample) }.ok_or(IOReportError::SampleError)?;
+        Ok((sample, std::time::Instant::now()))
+    }
+
+    pub(crate) fn get_samples(
+        &mut self,
+        duration: std::time::Duration,
+        count: usize,
+    ) -> Result<Vec<IOReportSample>> {
+        let mut samples: Vec<IOReportSample> = Vec::with_capacity(count);
+        let Some(step) = duration.checked_div(count as u32) else {
+            return Ok(samples);
+        };
+
+        let mut prev = match self.previous.take() {
+            Some(x) => x,
+            None => self.initial_sample()?,
+        };
+
+        for _ in 0..count {
+            std::thread::sleep(step);
+
+            let next = self.initial_sample()?;
+            let diff = unsafe {
+                CfDict::from_create_rule(IOReportCreateSamplesDelta(
+                    prev.0.as_ptr(),
+                    next.0.as_ptr(),
+                    std::ptr::null(),
+                ))
+            }
+            .ok_or(IOReportError::SampleError)?;
+
+            let elapsed = next.1.duration_since(prev.1).as_millis() as u64;
+            prev = next;
+
+            samples.push(IOReportSample::new(
+                IOReportIterator::new(diff)?,
+                elapsed.max(1),
+            ));
+        }
+
+        self.previous = Some(prev);
+        Ok(samples)
+    }
+}
+
+impl Drop for IOReport {
+    fn drop(&mut self) {
+        let handle = unsafe { ManuallyDrop::take(&mut self.handle) };
+        let mut pool = POOL.lock().unwrap_or_else(|e| e.into_inner());
+        // the most recently used channel sets are the likeliest to be opened again
+        if pool.len() >= POOL_SIZE {
+            pool.remove(0);
+        }
+        pool.push((std::mem::take(&mut self.key), handle));
+    }
+}
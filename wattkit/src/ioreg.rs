@@ -0,0 +1,374 @@
+//! Safe access to the IORegistry, the tree of drivers and devices macOS describes its
+//! hardware with. Services are found by class, e.g. "AppleSMC" or
+//! "IOBlockStorageDriver", and their properties read as [`Property`] values.
+//!
+//! Only available on macOS with the `macos` feature, elsewhere no service matches.
+
+use std::collections::HashMap;
+
+/// A registry property, converted from its CoreFoundation type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Property {
+    Bool(bool),
+    Integer(i64),
+    Real(f64),
+    String(String),
+    Data(Vec<u8>),
+    Array(Vec<Property>),
+    /// Entries with keys that aren't strings are left out.
+    Dict(HashMap<String, Property>),
+}
+
+impl Property {
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Property::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Property::Integer(value) => Some(*value as f64),
+            Property::Real(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Property::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Value for `key` of a dictionary property.
+    pub fn get(&self, key: &str) -> Option<&Property> {
+        match self {
+            Property::Dict(entries) => entries.get(key),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(all(target_os = "macos", feature = "macos"))]
+mod sys {
+    use super::Property;
+    use crate::cf_utils::{CfDict, CfString};
+    use core_foundation::{
+        array::{CFArrayGetCount, CFArrayGetTypeID, CFArrayGetValueAtIndex, CFArrayRef},
+        base::{kCFAllocatorDefault, CFAllocatorRef, CFGetTypeID, CFRelease, CFTypeRef},
+        data::{CFDataGetBytePtr, CFDataGetLength, CFDataGetTypeID, CFDataRef},
+        dictionary::{
+            CFDictionaryGetCount, CFDictionaryGetKeysAndValues, CFDictionaryGetTypeID,
+            CFDictionaryRef, CFMutableDictionaryRef,
+        },
+        number::{
+            kCFNumberFloat64Type, kCFNumberSInt64Type, CFBooleanGetTypeID, CFBooleanGetValue,
+            CFBooleanRef, CFNumberGetTypeID, CFNumberGetValue, CFNumberIsFloatType, CFNumberRef,
+        },
+        string::{
+            kCFStringEncodingUTF8, CFStringGetCString, CFStringGetLength,
+            CFStringGetMaximumSizeForEncoding, CFStringGetTypeID, CFStringRef,
+        },
+    };
+    use std::collections::HashMap;
+    use std::ffi::CString;
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOServiceMatching(name: *const libc::c_char) -> CFMutableDictionaryRef;
+        fn IOServiceGetMatchingServices(
+            main_port: u32,
+            matching: CFDictionaryRef,
+            iterator: *mut u32,
+        ) -> libc::c_int;
+        fn IOIteratorNext(iterator: u32) -> u32;
+        fn IORegistryEntryGetName(entry: u32, name: *mut libc::c_char) -> libc::c_int;
+        fn IORegistryEntryCreateCFProperty(
+            entry: u32,
+            key: CFStringRef,
+            allocator: CFAllocatorRef,
+            options: u32,
+        ) -> CFTypeRef;
+        fn IORegistryEntryCreateCFProperties(
+            entry: u32,
+            properties: *mut CFMutableDictionaryRef,
+            allocator: CFAllocatorRef,
+            options: u32,
+        ) -> libc::c_int;
+        fn IOObjectRelease(object: u32) -> libc::c_int;
+    }
+
+    /// A registry entry, released on drop.
+    #[derive(Debug)]
+    pub struct Entry(u32);
+
+    impl Entry {
+        pub fn id(&self) -> u32 {
+            self.0
+        }
+
+        pub fn name(&self) -> Option<String> {
+            // io_name_t
+            let mut name = [0 as libc::c_char; 128];
+            if unsafe { IORegistryEntryGetName(self.0, name.as_mut_ptr()) } != 0 {
+                return None;
+            }
+            let name = unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) };
+            Some(name.to_string_lossy().into_owned())
+        }
+
+        pub fn property(&self, key: &str) -> Option<Property> {
+            let key = CfString::new(key);
+            let value = unsafe {
+                IORegistryEntryCreateCFProperty(self.0, key.as_ptr(), kCFAllocatorDefault, 0)
+            };
+            if value.is_null() {
+                return None;
+            }
+            let property = convert(value);
+            unsafe { CFRelease(value) };
+            property
+        }
+
+        pub fn properties(&self) -> Option<HashMap<String, Property>> {
+            let mut properties = std::ptr::null_mut();
+            let status = unsafe {
+                IORegistryEntryCreateCFProperties(self.0, &mut properties, kCFAllocatorDefault, 0)
+            };
+            if status != 0 {
+                return None;
+            }
+            let properties = unsafe { CfDict::from_create_rule(properties as _) }?;
+            match convert(properties.as_ptr() as _)? {
+                Property::Dict(entries) => Some(entries),
+                _ => None,
+            }
+        }
+    }
+
+    impl Drop for Entry {
+        fn drop(&mut self) {
+            unsafe { IOObjectRelease(self.0) };
+        }
+    }
+
+    /// Services matching a class, released on drop.
+    #[derive(Debug)]
+    pub struct Matching(u32);
+
+    impl Matching {
+        pub fn new(class: &str) -> Option<Self> {
+            let class = CString::new(class).ok()?;
+            // consumed by IOServiceGetMatchingServices
+            let matching = unsafe { IOServiceMatching(class.as_ptr()) };
+            if matching.is_null() {
+                return None;
+            }
+            let mut iterator = 0;
+            // 0 is kIOMainPortDefault
+            if unsafe { IOServiceGetMatchingServices(0, matching as _, &mut iterator) } != 0 {
+                return None;
+            }
+            Some(Self(iterator))
+        }
+    }
+
+    impl Iterator for Matching {
+        type Item = Entry;
+
+        fn next(&mut self) -> Option<Entry> {
+            match unsafe { IOIteratorNext(self.0) } {
+                0 => None,
+                entry => Some(Entry(entry)),
+            }
+        }
+    }
+
+    impl Drop for Matching {
+        fn drop(&mut self) {
+            unsafe { IOObjectRelease(self.0) };
+        }
+    }
+
+    fn string(value: CFStringRef) -> Option<String> {
+        let length = unsafe { CFStringGetLength(value) };
+        let size = unsafe { CFStringGetMaximumSizeForEncoding(length, kCFStringEncodingUTF8) } + 1;
+        let mut buffer = vec![0 as libc::c_char; size as usize];
+        let ok = unsafe {
+            CFStringGetCString(value, buffer.as_mut_ptr(), size, kCFStringEncodingUTF8) != 0
+        };
+        ok.then(|| {
+            let s = unsafe { std::ffi::CStr::from_ptr(buffer.as_ptr()) };
+            s.to_string_lossy().into_owned()
+        })
+    }
+
+    /// Converts `value` without taking ownership of it, `None` for types without a
+    /// [`Property`].
+    fn convert(value: CFTypeRef) -> Option<Property> {
+        let type_id = unsafe { CFGetTypeID(value) };
+        if type_id == unsafe { CFStringGetTypeID() } {
+            string(value as CFStringRef).map(Property::String)
+        } else if type_id == unsafe { CFBooleanGetTypeID() } {
+            Some(Property::Bool(unsafe {
+                CFBooleanGetValue(value as CFBooleanRef)
+            }))
+        } else if type_id == unsafe { CFNumberGetTypeID() } {
+            let number = value as CFNumberRef;
+            if unsafe { CFNumberIsFloatType(number) } != 0 {
+                let mut real = 0f64;
+                let ok = unsafe {
+                    CFNumberGetValue(number, kCFNumberFloat64Type, (&mut real as *mut f64).cast())
+                };
+                ok.then_some(Property::Real(real))
+            } else {
+                let mut integer = 0i64;
+                let ok = unsafe {
+                    CFNumberGetValue(
+                        number,
+                        kCFNumberSInt64Type,
+                        (&mut integer as *mut i64).cast(),
+                    )
+                };
+                ok.then_some(Property::Integer(integer))
+            }
+        } else if type_id == unsafe { CFDataGetTypeID() } {
+            let data = value as CFDataRef;
+            let length = unsafe { CFDataGetLength(data) } as usize;
+            let bytes = unsafe { CFDataGetBytePtr(data) };
+            if bytes.is_null() {
+                return Some(Property::Data(Vec::new()));
+            }
+            Some(Property::Data(
+                unsafe { std::slice::from_raw_parts(bytes, length) }.to_vec(),
+            ))
+        } else if type_id == unsafe { CFArrayGetTypeID() } {
+            let array = value as CFArrayRef;
+            let items = (0..unsafe { CFArrayGetCount(array) })
+                .filter_map(|i| convert(unsafe { CFArrayGetValueAtIndex(array, i) }))
+                .collect();
+            Some(Property::Array(items))
+        } else if type_id == unsafe { CFDictionaryGetTypeID() } {
+            let dict = value as CFDictionaryRef;
+            let count = unsafe { CFDictionaryGetCount(dict) } as usize;
+            let mut keys = vec![std::ptr::null(); count];
+            let mut values = vec![std::ptr::null(); count];
+            unsafe { CFDictionaryGetKeysAndValues(dict, keys.as_mut_ptr(), values.as_mut_ptr()) };
+            let entries = keys
+                .into_iter()
+                .zip(values)
+                .filter_map(|(key, value)| {
+                    let Some(Property::String(key)) = convert(key) else {
+                        return None;
+                    };
+                    Some((key, convert(value)?))
+                })
+                .collect();
+            Some(Property::Dict(entries))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "macos", feature = "macos")))]
+mod sys {
+    use super::Property;
+    use std::collections::HashMap;
+
+    #[derive(Debug)]
+    pub enum Entry {}
+
+    impl Entry {
+        pub fn id(&self) -> u32 {
+            match *self {}
+        }
+
+        pub fn name(&self) -> Option<String> {
+            match *self {}
+        }
+
+        pub fn property(&self, _key: &str) -> Option<Property> {
+            match *self {}
+        }
+
+        pub fn properties(&self) -> Option<HashMap<String, Property>> {
+            match *self {}
+        }
+    }
+
+    #[derive(Debug)]
+    pub enum Matching {}
+
+    impl Matching {
+        pub fn new(_class: &str) -> Option<Self> {
+            None
+        }
+    }
+
+    impl Iterator for Matching {
+        type Item = Entry;
+
+        fn next(&mut self) -> Option<Entry> {
+            match *self {}
+        }
+    }
+}
+
+/// # Service
+///
+/// An IORegistry entry, e.g. a driver. The reference is released on drop.
+#[derive(Debug)]
+pub struct Service(sys::Entry);
+
+impl Service {
+    /// Every service of `class` or a subclass of it.
+    pub fn matching(class: &str) -> impl Iterator<Item = Service> {
+        sys::Matching::new(class).into_iter().flatten().map(Service)
+    }
+
+    /// The first service of `class`, for classes with a single instance like "AppleSMC".
+    pub fn first(class: &str) -> Option<Service> {
+        Self::matching(class).next()
+    }
+
+    /// Name of the entry in the registry, often its class.
+    pub fn name(&self) -> Option<String> {
+        self.0.name()
+    }
+
+    /// Property `key`, `None` without one or for types [`Property`] doesn't cover.
+    pub fn property(&self, key: &str) -> Option<Property> {
+        self.0.property(key)
+    }
+
+    /// Every property the entry has.
+    pub fn properties(&self) -> HashMap<String, Property> {
+        self.0.properties().unwrap_or_default()
+    }
+
+    /// The raw `io_service_t`, valid while `self` lives, for IOKit calls not wrapped here.
+    pub fn as_raw(&self) -> u32 {
+        self.0.id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_property() {
+        let statistics = Property::Dict(HashMap::from([
+            ("Bytes (Read)".to_string(), Property::Integer(4096)),
+            ("Latency".to_string(), Property::Real(0.5)),
+        ]));
+        let read = statistics.get("Bytes (Read)").unwrap();
+        assert_eq!((read.as_i64(), read.as_f64()), (Some(4096), Some(4096.0)));
+        assert_eq!(statistics.get("Latency").unwrap().as_i64(), None);
+        assert_eq!(statistics.get("Bytes (Write)"), None);
+        assert_eq!(Property::String("x".to_string()).get("x"), None);
+    }
+}
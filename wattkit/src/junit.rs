@@ -0,0 +1,142 @@
+//! JUnit XML reports of energy budgets, so CI dashboards show energy regressions as
+//! failing tests.
+
+use std::fmt::Write;
+
+use crate::budget::Budget;
+use crate::sampler::PowerProfile;
+use crate::units::Units;
+
+/// A profiled workload reported as one test case, failing when it exceeds its budget.
+#[derive(Clone, Debug, Default)]
+pub struct EnergyTestCase {
+    pub name: String,
+    pub profile: PowerProfile,
+    /// Also takes an [`EnergyBudget`](crate::EnergyBudget) through `into()`.
+    pub budget: Budget,
+}
+
+/// A JUnit XML test suite named `suite` with a test case per entry of `cases`. Each case
+/// takes the profiled duration and carries the profile as its output, exceeded limits
+/// are its failures. Energy and power are in joules and watts, see [`junit_xml_with`].
+pub fn junit_xml(suite: &str, cases: &[EnergyTestCase]) -> String {
+    junit_xml_with(suite, cases, Units::SI)
+}
+
+/// [`junit_xml`] with the failures and profiles in `units`.
+pub fn junit_xml_with(suite: &str, cases: &[EnergyTestCase], units: Units) -> String {
+    let violations: Vec<Vec<String>> = cases
+        .iter()
+        .map(|case| case.budget.violations_with(&case.profile, units))
+        .collect();
+    let seconds = |profile: &PowerProfile| profile.total_duration as f64 / 1000.0;
+    let suite = escape(suite);
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<testsuite name=\"{suite}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+        cases.len(),
+        violations.iter().filter(|v| !v.is_empty()).count(),
+        cases.iter().map(|case| seconds(&case.profile)).sum::<f64>(),
+    );
+    for (case, violations) in cases.iter().zip(&violations) {
+        let _ = writeln!(
+            out,
+            "  <testcase name=\"{}\" classname=\"{suite}\" time=\"{:.3}\">",
+            escape(&case.name),
+            seconds(&case.profile),
+        );
+        if !violations.is_empty() {
+            let _ = writeln!(
+                out,
+                "    <failure message=\"{}\" type=\"EnergyBudgetExceeded\"/>",
+                escape(&violations.join("; ")),
+            );
+        }
+        let _ = writeln!(
+            out,
+            "    <system-out>{}</system-out>\n  </testcase>",
+            escape(&case.profile.display(units).to_string()),
+        );
+    }
+    out.push_str("</testsuite>\n");
+    out
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::EnergyBudget;
+    use crate::sampler::EnergySample;
+
+    #[test]
+    fn test_junit_xml() {
+        // 4 W on the CPU for 100 ms
+        let profile = PowerProfile::from(
+            [EnergySample {
+                cpu_energy: 400,
+                duration: 100,
+                ..Default::default()
+            }]
+            .as_slice(),
+        );
+        let case = |name: &str, energy| EnergyTestCase {
+            name: name.to_string(),
+            profile: profile.clone(),
+            budget: Budget {
+                energy: Some(energy),
+                ..Default::default()
+            },
+        };
+        let xml = junit_xml("energy", &[case("fits", 0.5), case("a<b", 0.3)]);
+
+        assert!(
+            xml.contains("<testsuite name=\"energy\" tests=\"2\" failures=\"1\" time=\"0.200\">"),
+            "{xml}"
+        );
+        assert!(
+            xml.contains(
+                "<testcase name=\"fits\" classname=\"energy\" time=\"0.100\">\n    <system-out>"
+            ),
+            "{xml}"
+        );
+        assert!(
+            xml.contains("<testcase name=\"a&lt;b\" classname=\"energy\" time=\"0.100\">\n    <failure message=\"energy 0.400 J exceeds the budget of 0.300 J\""),
+            "{xml}"
+        );
+        assert_eq!(xml.matches("<failure").count(), 1);
+
+        let tolerated = EnergyTestCase {
+            budget: EnergyBudget::new(0.3).with_tolerance(0.5).into(),
+            ..case("tolerated", 0.3)
+        };
+        let xml = junit_xml("energy", &[tolerated, case("over", 0.3)]);
+        assert!(xml.contains("tests=\"2\" failures=\"1\""), "{xml}");
+
+        let milli = junit_xml_with("energy", &[case("a<b", 0.3)], Units::default());
+        assert!(
+            milli.contains("<failure message=\"energy 400 mJ exceeds the budget of 300 mJ\""),
+            "{milli}"
+        );
+        assert!(
+            milli.contains("<system-out>Total Energy: 400 mJ"),
+            "{milli}"
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! `tracing` integration measuring the energy of every instrumented span.
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use tracing::field::Value;
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::backend::{DefaultBackend, PowerBackend};
+use crate::region::RegionId;
+use crate::sampler::{SamplerError, StartStopSampler};
+
+/// Field [`EnergyLayer`] records the joules of a span into, when the span declares it.
+pub const ENERGY_FIELD: &str = "energy_j";
+
+/// Energy of a span so far, kept in the span's extensions for other layers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpanEnergy {
+    /// Joules of all units over every time the span was entered.
+    pub joules: f64,
+    /// Milliseconds the span was entered for.
+    pub duration: u64,
+    region: Option<RegionId>,
+}
+
+/// # EnergyLayer
+///
+/// Keeps one sampler running in the background and measures the energy consumed
+/// while each span is entered, as a region of that session. A span entered several
+/// times, like the span of a future, adds up all of them. Exiting a span blocks
+/// until the sample covering the exit arrives, at most one interval, so pick an
+/// interval short enough for the spans measured.
+///
+/// The total is recorded into the span's [`ENERGY_FIELD`] on every exit and kept as
+/// [`SpanEnergy`] in its extensions. A span only has fields it declared up front:
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use tracing_subscriber::prelude::*;
+/// use wattkit::layer::EnergyLayer;
+///
+/// let layer = EnergyLayer::new(Duration::from_millis(20)).unwrap();
+/// tracing_subscriber::registry().with(layer).init();
+///
+/// let span = tracing::info_span!("inference", energy_j = tracing::field::Empty);
+/// span.in_scope(|| { /* ... */ });
+/// ```
+#[derive(Debug)]
+pub struct EnergyLayer<B = DefaultBackend> {
+    sampler: RwLock<StartStopSampler<B>>,
+}
+
+impl EnergyLayer {
+    /// Starts sampling the default backend every `interval`.
+    pub fn new(interval: Duration) -> Result<Self, SamplerError> {
+        EnergyLayer::with_sampler(StartStopSampler::new(), interval)
+    }
+}
+
+impl<B: PowerBackend + 'static> EnergyLayer<B> {
+    /// Starts `sampler` sampling every `interval`, it must not be sampling already.
+    pub fn with_sampler(
+        mut sampler: StartStopSampler<B>,
+        interval: Duration,
+    ) -> Result<Self, SamplerError> {
+        sampler.start(interval, 1)?;
+        Ok(EnergyLayer {
+            sampler: RwLock::new(sampler),
+        })
+    }
+}
+
+impl<S, B> Layer<S> for EnergyLayer<B>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    B: 'static,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanEnergy::default());
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let region = match self.sampler.write() {
+            Ok(mut sampler) => sampler.begin_region(span.name()),
+            Err(_) => return,
+        };
+        match region {
+            Ok(region) => {
+                if let Some(energy) = span.extensions_mut().get_mut::<SpanEnergy>() {
+                    energy.region = Some(region);
+                }
+            }
+            Err(e) => debug!("not measuring span {}: {e}", span.name()),
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let Some(region) = span
+            .extensions_mut()
+            .get_mut::<SpanEnergy>()
+            .and_then(|energy| energy.region.take())
+        else {
+            return;
+        };
+
+        if let Ok(mut sampler) = self.sampler.write() {
+            sampler.end_region(region);
+        }
+        let profile = match self.sampler.read() {
+            Ok(sampler) => sampler.ended_region_profile(region),
+            Err(_) => return,
+        };
+        let profile = match profile {
+            Ok(profile) => profile,
+            Err(e) => {
+                warn!("no energy for span {}: {e}", span.name());
+                return;
+            }
+        };
+
+        let joules = {
+            let mut extensions = span.extensions_mut();
+            let Some(energy) = extensions.get_mut::<SpanEnergy>() else {
+                return;
+            };
+            // mJ -> J
+            energy.joules += profile.total_energy as f64 / 1000.0;
+            energy.duration += profile.total_duration;
+            energy.joules
+        };
+
+        // the extensions are released, layers recording the field may borrow them
+        let fields = span.metadata().fields();
+        if let Some(field) = fields.field(ENERGY_FIELD) {
+            let values = [(&field, Some(&joules as &dyn Value))];
+            let values = fields.value_set(&values);
+            tracing::dispatcher::get_default(|dispatch| dispatch.record(id, &Record::new(&values)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing_subscriber::prelude::*;
+
+    /// Collects the values recorded into `ENERGY_FIELD`.
+    struct Recorded(Arc<Mutex<Vec<f64>>>);
+
+    impl Visit for &Recorded {
+        fn record_f64(&mut self, field: &Field, value: f64) {
+            if field.name() == ENERGY_FIELD {
+                self.0.lock().unwrap().push(value);
+            }
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+    }
+
+    impl<S: Subscriber> Layer<S> for Recorded {
+        fn on_record(&self, _id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            values.record(&mut &*self);
+        }
+    }
+
+    #[test]
+    fn test_span_energy() {
+        let sampler =
+            StartStopSampler::with_backend(|| Ok(MockBackend::constant(1000.0, 0.0, 0.0)));
+        let layer = EnergyLayer::with_sampler(sampler, Duration::from_millis(10)).unwrap();
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry()
+            .with(layer)
+            .with(Recorded(recorded.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("work", energy_j = tracing::field::Empty);
+            span.in_scope(|| std::thread::sleep(Duration::from_millis(50)));
+            std::thread::sleep(Duration::from_millis(50));
+            span.in_scope(|| std::thread::sleep(Duration::from_millis(50)));
+            // spans without the field are measured all the same
+            tracing::info_span!("other").in_scope(|| {});
+        });
+
+        let recorded = recorded.lock().unwrap();
+        assert_eq!(recorded.len(), 2);
+        // 1 W over each 50 ms the span was entered, the time in between isn't counted
+        assert!(recorded[0] >= 0.05 && recorded[0] < 0.08, "{recorded:?}");
+        assert!(recorded[1] >= 0.1 && recorded[1] < 0.16, "{recorded:?}");
+    }
+}
@@ -1,5 +1,78 @@
+#![deny(unsafe_op_in_unsafe_fn)]
+
+#[macro_use]
+mod logging;
+
+mod alert;
+pub mod auto;
+pub mod backend;
+mod budget;
+mod cadence;
+#[cfg(all(target_os = "macos", feature = "macos"))]
 mod cf_utils;
+mod channels;
+mod config;
+mod cpu_load;
+mod disk_io;
+mod global;
 mod io_report;
+pub mod ioreg;
+mod junit;
+#[cfg(feature = "tracing")]
+pub mod layer;
+#[cfg(feature = "tower")]
+pub mod middleware;
+mod net_io;
+mod ops;
+pub mod overhead;
+mod power_source;
+pub mod prelude;
+pub mod probe;
+pub mod process;
+mod prometheus;
+mod qos;
+mod region;
+mod report;
 mod sampler;
+mod session;
+#[cfg(unix)]
+pub mod signal;
+mod soc;
+mod stats;
+mod thermal;
+mod trigger;
+mod units;
+mod worker;
+
+/// The README's examples, compiled as doctests so they keep up with the API.
+#[cfg(doctest)]
+#[doc = include_str!("../../README.md")]
+struct ReadmeDoctests;
+
+pub use backend::{mock::MockBackend, Capabilities, ChannelInfo, PowerBackend};
+pub use budget::{Budget, BudgetExceeded, EnergyBudget};
+pub use cadence::MetricRates;
+pub use channels::{ChannelSeries, ChannelTree, Channels, Subgroups};
+pub use config::SamplerConfig;
+pub use global::{global, GlobalSampler, GlobalSession, GLOBAL_INTERVAL};
+pub use io_report::EnergyModelSample;
+pub use junit::{junit_xml, junit_xml_with, EnergyTestCase};
+pub use power_source::PowerSource;
+pub use probe::{capabilities, ProbedCapabilities};
+pub use process::{run_profiled, run_profiled_with, ProfiledRun, RunError, RunOptions};
+pub use prometheus::{MetricNames, UnknownMetricNames};
+pub use qos::QosClass;
+pub use region::{Region, RegionId};
+pub use session::SessionManager;
+pub use soc::SocInfo;
+pub use stats::{compare_runs, AggregateProfile, Comparison, Summary};
+pub use thermal::ThermalPressure;
+pub use trigger::{Capture, TriggerCause, TriggerOptions, TriggeredSampler};
+pub use units::{EnergyUnit, PowerUnit, Units, UnknownUnit};
 
-pub use sampler::{GuardSampler as Sampler, PowerProfile, Sampling, StartStopSampler};
+pub use sampler::{
+    AdaptiveRate, CpuResidency, DieEnergy, DiskIo, DutyCycle, EnergySample, ExtraMetrics, Gap,
+    GuardSampler as Sampler, IdleDetection, IdlePeriod, IntervalAccuracy, MemoryTraffic, Metadata,
+    NetworkIo, OutlierFilter, PeakPower, PowerProfile, SampleCallback, SamplerError, SamplerEvent,
+    Sampling, StartStopSampler, ThrottledPeriod, Warmup, MAX_PLAUSIBLE_POWER, MIN_SAMPLE_INTERVAL,
+};
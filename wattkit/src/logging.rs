@@ -0,0 +1,33 @@
+//! Events of sampling sessions through `log` with the `log` feature, compiled out
+//! otherwise. Nothing is ever written to stdout or stderr directly.
+
+#[cfg(feature = "log")]
+macro_rules! debug {
+    ($($arg:tt)*) => { log::debug!(target: "wattkit", $($arg)*) };
+}
+
+#[cfg(feature = "log")]
+macro_rules! info {
+    ($($arg:tt)*) => { log::info!(target: "wattkit", $($arg)*) };
+}
+
+#[cfg(feature = "log")]
+macro_rules! warn {
+    ($($arg:tt)*) => { log::warn!(target: "wattkit", $($arg)*) };
+}
+
+// the arguments still count as used, without formatting anything
+#[cfg(not(feature = "log"))]
+macro_rules! debug {
+    ($($arg:tt)*) => { { let _ = format_args!($($arg)*); } };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! info {
+    ($($arg:tt)*) => { { let _ = format_args!($($arg)*); } };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! warn {
+    ($($arg:tt)*) => { { let _ = format_args!($($arg)*); } };
+}
@@ -0,0 +1,458 @@
+//! `tower` middleware measuring the energy of every HTTP request a service handles.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use http::{Request, Response};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::backend::{DefaultBackend, PowerBackend};
+use crate::sampler::{EnergySample, SamplerError, Sampling, StartStopSampler};
+
+/// Energy attributed to one request, in the extensions of its response.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RequestEnergy {
+    /// Joules of all units, shared with the requests in flight at the same time.
+    pub joules: f64,
+    /// From the request reaching the middleware to its response.
+    pub duration: Duration,
+}
+
+/// Requests handled so far, see [`RequestEnergyLayer::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RequestStats {
+    pub requests: u64,
+    pub joules: f64,
+}
+
+impl RequestStats {
+    pub fn joules_per_request(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        self.joules / self.requests as f64
+    }
+}
+
+#[derive(Debug)]
+struct InFlight {
+    /// Milliseconds since sampling started.
+    start: u64,
+    end: Option<u64>,
+    /// mJ
+    energy: f64,
+    done: Option<oneshot::Sender<RequestEnergy>>,
+}
+
+/// Requests in flight and the energy each was attributed so far.
+#[derive(Debug, Default)]
+struct Ledger {
+    next: u64,
+    in_flight: HashMap<u64, InFlight>,
+    /// Milliseconds since sampling started up to which samples arrived.
+    covered: u64,
+    /// Set once the sampling thread exits, no sample will complete a request after.
+    ended: bool,
+    stats: RequestStats,
+}
+
+impl Ledger {
+    fn begin(&mut self, now: u64) -> u64 {
+        let id = self.next;
+        self.next += 1;
+        let request = InFlight {
+            start: now,
+            end: None,
+            energy: 0.0,
+            done: None,
+        };
+        self.in_flight.insert(id, request);
+        id
+    }
+
+    /// Marks `id` as answered at `now`, the receiver gets its energy once a sample
+    /// covers `now`.
+    fn finish(&mut self, id: u64, now: u64) -> Option<oneshot::Receiver<RequestEnergy>> {
+        let (tx, rx) = oneshot::channel();
+        let request = self.in_flight.get_mut(&id)?;
+        request.end = Some(now);
+        request.done = Some(tx);
+        self.complete();
+        Some(rx)
+    }
+
+    fn cancel(&mut self, id: u64) {
+        self.in_flight.remove(&id);
+    }
+
+    /// Drops every request in flight, and with them the senders their responses wait on.
+    fn end(&mut self) {
+        self.ended = true;
+        self.in_flight.clear();
+    }
+
+    /// Splits the energy of `sample` between the requests in flight during it, in
+    /// proportion to how long each was.
+    fn record(&mut self, sample: &EnergySample) {
+        let (start, end) = (sample.timestamp, sample.timestamp + sample.duration);
+        let overlap = |request: &InFlight| {
+            let to = request.end.map_or(end, |e| e.min(end));
+            to.saturating_sub(request.start.max(start))
+        };
+        let total: u64 = self.in_flight.values().map(overlap).sum();
+        if total > 0 {
+            let energy = (sample.cpu_energy + sample.gpu_energy + sample.ane_energy) as f64;
+            for request in self.in_flight.values_mut() {
+                request.energy += energy * overlap(request) as f64 / total as f64;
+            }
+        }
+        self.covered = self.covered.max(end);
+        self.complete();
+    }
+
+    /// Hands out the energy of answered requests samples cover up to their end.
+    fn complete(&mut self) {
+        let covered = self.covered;
+        let answered: Vec<u64> = self
+            .in_flight
+            .iter()
+            .filter(|(_, r)| r.end.is_some_and(|end| end <= covered))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in answered {
+            let request = self.in_flight.remove(&id).unwrap();
+            // mJ -> J
+            let energy = RequestEnergy {
+                joules: request.energy / 1000.0,
+                duration: Duration::from_millis(request.end.unwrap() - request.start),
+            };
+            self.stats.requests += 1;
+            self.stats.joules += energy.joules;
+            if let Some(done) = request.done {
+                let _ = done.send(energy);
+            }
+        }
+    }
+}
+
+/// Ends the ledger when the sampling thread drops its callback, whether the session
+/// was stopped or the backend failed.
+struct EndOnDrop(Arc<Mutex<Ledger>>);
+
+impl Drop for EndOnDrop {
+    fn drop(&mut self) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).end();
+    }
+}
+
+#[derive(Debug)]
+struct Shared<B> {
+    /// Shared with the sampler's callback.
+    ledger: Arc<Mutex<Ledger>>,
+    /// Sampling stops once the last layer and service are dropped.
+    _sampler: Mutex<StartStopSampler<B>>,
+    start: Instant,
+}
+
+impl<B> Shared<B> {
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+}
+
+/// # RequestEnergyLayer
+///
+/// Keeps one sampler running in the background and attributes the energy of each
+/// sample to the requests in flight during it, in proportion to how long each was.
+/// Every response carries its request's [`RequestEnergy`] in its extensions.
+///
+/// A response is held back until the sample covering it arrives, at most one
+/// interval, so pick an interval short next to the latency of the requests. Energy
+/// is shared between concurrent requests whatever each of them does, it is the
+/// energy of the host, not of the handler.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use wattkit::middleware::RequestEnergyLayer;
+///
+/// let layer = RequestEnergyLayer::new(Duration::from_millis(20)).unwrap();
+/// // e.g. axum::Router::new().route(...).layer(layer.clone())
+/// println!("{} J per request", layer.stats().joules_per_request());
+/// ```
+#[derive(Debug)]
+pub struct RequestEnergyLayer<B = DefaultBackend> {
+    shared: Arc<Shared<B>>,
+}
+
+impl<B> Clone for RequestEnergyLayer<B> {
+    fn clone(&self) -> Self {
+        RequestEnergyLayer {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl RequestEnergyLayer {
+    /// Starts sampling the default backend every `interval`.
+    pub fn new(interval: Duration) -> Result<Self, SamplerError> {
+        RequestEnergyLayer::with_sampler(StartStopSampler::new(), interval)
+    }
+}
+
+impl<B: PowerBackend + 'static> RequestEnergyLayer<B> {
+    /// Starts `sampler` sampling every `interval`, it must not be sampling already.
+    pub fn with_sampler(
+        mut sampler: StartStopSampler<B>,
+        interval: Duration,
+    ) -> Result<Self, SamplerError> {
+        let ledger = Arc::new(Mutex::new(Ledger::default()));
+        let recording = EndOnDrop(ledger.clone());
+        sampler.start_with_callback(
+            interval,
+            1,
+            Box::new(move |sample| {
+                if let Ok(mut ledger) = recording.0.lock() {
+                    ledger.record(sample);
+                }
+            }),
+        )?;
+        let start = sampler.start_time().unwrap_or_else(Instant::now);
+        let shared = Shared {
+            ledger,
+            _sampler: Mutex::new(sampler),
+            start,
+        };
+        Ok(RequestEnergyLayer {
+            shared: Arc::new(shared),
+        })
+    }
+}
+
+impl<B> RequestEnergyLayer<B> {
+    /// Requests answered so far and the energy attributed to them.
+    pub fn stats(&self) -> RequestStats {
+        self.shared
+            .ledger
+            .lock()
+            .map(|l| l.stats)
+            .unwrap_or_default()
+    }
+}
+
+impl<S, B> Layer<S> for RequestEnergyLayer<B> {
+    type Service = RequestEnergyService<S, B>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestEnergyService {
+            inner,
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// Service of [`RequestEnergyLayer`].
+#[derive(Debug)]
+pub struct RequestEnergyService<S, B = DefaultBackend> {
+    inner: S,
+    shared: Arc<Shared<B>>,
+}
+
+impl<S: Clone, B> Clone for RequestEnergyService<S, B> {
+    fn clone(&self) -> Self {
+        RequestEnergyService {
+            inner: self.inner.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// Drops a request from the ledger when its future is dropped before the response.
+struct Pending<B> {
+    id: Option<u64>,
+    shared: Arc<Shared<B>>,
+}
+
+impl<B> Drop for Pending<B> {
+    fn drop(&mut self) {
+        if let (Some(id), Ok(mut ledger)) = (self.id, self.shared.ledger.lock()) {
+            ledger.cancel(id);
+        }
+    }
+}
+
+impl<S, B, ReqBody, ResBody> Service<Request<ReqBody>> for RequestEnergyService<S, B>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+    ResBody: Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let shared = self.shared.clone();
+        let id = shared.ledger.lock().ok().map(|mut l| l.begin(shared.now()));
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let mut pending = Pending { id, shared };
+            let mut response = response.await?;
+
+            let Some(id) = pending.id.take() else {
+                return Ok(response);
+            };
+            let shared = &pending.shared;
+            let done = match shared.ledger.lock() {
+                Ok(mut ledger) if !ledger.ended => ledger.finish(id, shared.now()),
+                Ok(mut ledger) => {
+                    ledger.cancel(id);
+                    None
+                }
+                Err(_) => None,
+            };
+            // the ledger drops the sender if sampling ends before the sample covering the end
+            if let Some(Ok(energy)) = match done {
+                Some(done) => Some(done.await),
+                None => None,
+            } {
+                response.extensions_mut().insert(energy);
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockBackend;
+    use std::convert::Infallible;
+    use std::task::Wake;
+
+    fn sample(timestamp: u64, duration: u64, cpu_energy: u128) -> EnergySample {
+        EnergySample {
+            timestamp,
+            duration,
+            cpu_energy,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_ledger_splits_energy() {
+        let mut ledger = Ledger::default();
+        let first = ledger.begin(0);
+        let second = ledger.begin(50);
+        let first_done = ledger.finish(first, 100).unwrap();
+        ledger.record(&sample(0, 100, 300));
+        // the first request ran for 100 ms of the sample, the second for 50
+        assert_eq!(
+            first_done.recv().unwrap(),
+            RequestEnergy {
+                joules: 0.2,
+                duration: Duration::from_millis(100)
+            }
+        );
+
+        ledger.record(&sample(100, 100, 400));
+        let second_done = ledger.finish(second, 150).unwrap();
+        assert_eq!(second_done.recv().unwrap().joules, 0.5);
+        assert_eq!(ledger.stats.requests, 2);
+        assert!(ledger.in_flight.is_empty());
+
+        let cancelled = ledger.begin(200);
+        ledger.cancel(cancelled);
+        assert!(ledger.finish(cancelled, 250).is_none());
+
+        let unanswered = ledger.begin(300);
+        let unanswered_done = ledger.finish(unanswered, 350).unwrap();
+        ledger.end();
+        assert!(unanswered_done.recv().is_err());
+        assert!(ledger.ended && ledger.in_flight.is_empty());
+    }
+
+    struct Sleep;
+
+    impl Service<Request<()>> for Sleep {
+        type Response = Response<()>;
+        type Error = Infallible;
+        type Future = std::future::Ready<Result<Response<()>, Infallible>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _request: Request<()>) -> Self::Future {
+            std::thread::sleep(Duration::from_millis(60));
+            std::future::ready(Ok(Response::new(())))
+        }
+    }
+
+    struct Unpark(std::thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Arc::new(Unpark(std::thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_response_energy() {
+        // 2 W
+        let sampler =
+            StartStopSampler::with_backend(|| Ok(MockBackend::constant(2000.0, 0.0, 0.0)));
+        let layer = RequestEnergyLayer::with_sampler(sampler, Duration::from_millis(10)).unwrap();
+        let mut service = layer.layer(Sleep);
+
+        let response = block_on(service.call(Request::new(()))).unwrap();
+        let energy = response.extensions().get::<RequestEnergy>().unwrap();
+        assert!(energy.duration >= Duration::from_millis(60));
+        let expected = 2.0 * energy.duration.as_secs_f64();
+        assert!((energy.joules - expected).abs() < 0.05, "{energy:?}");
+        assert_eq!(layer.stats().requests, 1);
+    }
+
+    #[test]
+    fn test_response_after_sampling_failed() {
+        // two samples, then the backend fails and the sampling thread exits
+        let sampler = StartStopSampler::with_backend(|| {
+            let samples = [0, 10].map(|timestamp| sample(timestamp, 10, 20));
+            Ok(MockBackend::replay(samples).realtime(true))
+        });
+        let layer = RequestEnergyLayer::with_sampler(sampler, Duration::from_millis(10)).unwrap();
+        let mut service = layer.layer(Sleep);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(block_on(service.call(Request::new(()))));
+        });
+        let response = receiver
+            .recv_timeout(Duration::from_secs(3))
+            .expect("response still pending after sampling ended")
+            .unwrap();
+        assert!(response.extensions().get::<RequestEnergy>().is_none());
+        assert_eq!(layer.stats().requests, 0);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::sampler::NetworkIo;
+
+/// Bytes received and sent over all network interfaces of the host, loopback aside.
+#[derive(Clone, Copy, Debug)]
+struct Totals {
+    rx: u64,
+    tx: u64,
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use super::Totals;
+
+    pub fn totals() -> Option<Totals> {
+        let mut interfaces = std::ptr::null_mut();
+        if unsafe { libc::getifaddrs(&mut interfaces) } != 0 {
+            return None;
+        }
+        let mut totals = Totals { rx: 0, tx: 0 };
+        let mut next = interfaces;
+        while let Some(interface) = unsafe { next.as_ref() } {
+            next = interface.ifa_next;
+            let link = unsafe { interface.ifa_addr.as_ref() }
+                .is_some_and(|addr| addr.sa_family as libc::c_int == libc::AF_LINK);
+            if !link
+                || interface.ifa_flags & libc::IFF_LOOPBACK as libc::c_uint != 0
+                || interface.ifa_data.is_null()
+            {
+                continue;
+            }
+            // the link counters are 32 bits wide and wrap every 4 GiB
+            let data = unsafe { &*(interface.ifa_data as *const libc::if_data) };
+            totals.rx += data.ifi_ibytes as u64;
+            totals.tx += data.ifi_obytes as u64;
+        }
+        unsafe { libc::freeifaddrs(interfaces) };
+        Some(totals)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::Totals;
+
+    pub fn totals() -> Option<Totals> {
+        let dev = std::fs::read_to_string("/proc/net/dev").ok()?;
+        Some(sum(&dev))
+    }
+
+    /// Sums the traffic of every interface but loopback in `/proc/net/dev` contents.
+    pub(super) fn sum(dev: &str) -> Totals {
+        let mut totals = Totals { rx: 0, tx: 0 };
+        // two header lines, then `name: rx_bytes packets errs drop fifo frame
+        // compressed multicast tx_bytes ...`
+        for line in dev.lines().skip(2) {
+            let Some((name, counters)) = line.split_once(':') else {
+                continue;
+            };
+            if name.trim() == "lo" {
+                continue;
+            }
+            let counters: Vec<&str> = counters.split_whitespace().collect();
+            let parse = |i: usize| counters.get(i).and_then(|v| v.parse::<u64>().ok());
+            totals.rx += parse(0).unwrap_or(0);
+            totals.tx += parse(8).unwrap_or(0);
+        }
+        totals
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+mod sys {
+    pub fn totals() -> Option<super::Totals> {
+        None
+    }
+}
+
+/// Host network traffic between polls.
+#[derive(Debug)]
+pub(crate) struct NetworkCounters {
+    last: Option<Totals>,
+}
+
+impl NetworkCounters {
+    pub(crate) fn new() -> Self {
+        NetworkCounters {
+            last: sys::totals(),
+        }
+    }
+
+    /// Bytes received and sent since the last poll, `None` where they can't be read.
+    /// A poll across a counter wrapping around reports nothing for it.
+    pub(crate) fn poll(&mut self) -> Option<NetworkIo> {
+        let now = sys::totals();
+        let last = std::mem::replace(&mut self.last, now)?;
+        let now = now?;
+        Some(NetworkIo {
+            rx: now.rx.saturating_sub(last.rx),
+            tx: now.tx.saturating_sub(last.tx),
+        })
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_net_dev() {
+        let dev = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 5000      50    0    0    0     0          0         0     5000      50    0    0    0     0       0          0
+  eth0: 1200      10    0    0    0     0          0         0      800       8    0    0    0     0       0          0
+ wlan0:  300       3    0    0    0     0          0         0      100       1    0    0    0     0       0          0";
+        let totals = sys::sum(dev);
+        assert_eq!((totals.rx, totals.tx), (1500, 900));
+    }
+}
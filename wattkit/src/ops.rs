@@ -0,0 +1,232 @@
+//! Combining, baselining and comparing profiles.
+
+use std::ops::{Add, Sub};
+
+use crate::sampler::{DieEnergy, IdlePeriod, PeakPower, PowerProfile, ThrottledPeriod};
+
+/// `a` and `b` averaged over their durations, an empty pair averages to its mean.
+fn weighted(a: u64, a_duration: u64, b: u64, b_duration: u64) -> u64 {
+    let duration = a_duration + b_duration;
+    if duration == 0 {
+        return (a + b) / 2;
+    }
+    f64::round((a as f64 * a_duration as f64 + b as f64 * b_duration as f64) / duration as f64)
+        as u64
+}
+
+fn either<T>(a: Option<T>, b: Option<T>, both: impl FnOnce(T, T) -> T) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(both(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Profile of `self`'s session followed by `rhs`'s, as if both had been one session:
+/// energies and durations add up, powers are averaged over the durations and `rhs`'s
+/// timestamps start where `self` ends. Metadata entries both agree on are kept.
+impl Add for PowerProfile {
+    type Output = PowerProfile;
+
+    fn add(self, rhs: PowerProfile) -> PowerProfile {
+        let (a, b) = (self, rhs);
+        let (da, db) = (a.total_duration, b.total_duration);
+        let offset = da;
+        let peak = |a: PeakPower, b: PeakPower| {
+            if b.power > a.power {
+                PeakPower {
+                    power: b.power,
+                    timestamp: b.timestamp + offset,
+                }
+            } else {
+                a
+            }
+        };
+        let mut metadata = a.metadata;
+        metadata.retain(|key, value| b.metadata.get(key) == Some(value));
+        let mut dies = a.dies;
+        if dies.len() < b.dies.len() {
+            dies.resize(b.dies.len(), DieEnergy::default());
+        }
+        for (total, die) in dies.iter_mut().zip(&b.dies) {
+            total.cpu += die.cpu;
+            total.gpu += die.gpu;
+            total.ane += die.ane;
+        }
+        let utilization = |a: f64, b: f64| {
+            let duration = (da + db).max(1) as f64;
+            (a * da as f64 + b * db as f64) / duration
+        };
+        let average = |a, b| weighted(a, da, b, db);
+        let active_a = da.saturating_sub(a.idle_duration);
+        let active_b = db.saturating_sub(b.idle_duration);
+
+        PowerProfile {
+            total_cpu_energy: a.total_cpu_energy + b.total_cpu_energy,
+            total_gpu_energy: a.total_gpu_energy + b.total_gpu_energy,
+            total_ane_energy: a.total_ane_energy + b.total_ane_energy,
+            average_cpu_power: average(a.average_cpu_power, b.average_cpu_power),
+            average_gpu_power: average(a.average_gpu_power, b.average_gpu_power),
+            average_ane_power: average(a.average_ane_power, b.average_ane_power),
+            total_energy: a.total_energy + b.total_energy,
+            average_power: average(a.average_power, b.average_power),
+            total_duration: da + db,
+            cpu_available: a.cpu_available || b.cpu_available,
+            gpu_available: a.gpu_available || b.gpu_available,
+            ane_available: a.ane_available || b.ane_available,
+            peak_cpu_power: peak(a.peak_cpu_power, b.peak_cpu_power),
+            peak_gpu_power: peak(a.peak_gpu_power, b.peak_gpu_power),
+            peak_ane_power: peak(a.peak_ane_power, b.peak_ane_power),
+            metadata,
+            power_source_changed: a.power_source_changed || b.power_source_changed,
+            cpu_utilization: either(a.cpu_utilization, b.cpu_utilization, utilization),
+            display_energy: either(a.display_energy, b.display_energy, Add::add),
+            package_energy: either(a.package_energy, b.package_energy, Add::add),
+            average_package_power: either(
+                a.average_package_power,
+                b.average_package_power,
+                average,
+            ),
+            average_system_power: either(a.average_system_power, b.average_system_power, average),
+            average_dc_in_power: either(a.average_dc_in_power, b.average_dc_in_power, average),
+            dies,
+            idle_periods: a
+                .idle_periods
+                .into_iter()
+                .chain(b.idle_periods.into_iter().map(|p| IdlePeriod {
+                    start: p.start + offset,
+                    ..p
+                }))
+                .collect(),
+            idle_duration: a.idle_duration + b.idle_duration,
+            average_active_power: either(a.average_active_power, b.average_active_power, |a, b| {
+                weighted(a, active_a, b, active_b)
+            }),
+            throttled_periods: a
+                .throttled_periods
+                .into_iter()
+                .chain(b.throttled_periods.into_iter().map(|p| ThrottledPeriod {
+                    start: p.start + offset,
+                    ..p
+                }))
+                .collect(),
+            throttled_duration: a.throttled_duration + b.throttled_duration,
+            trace: a.trace.into_iter().chain(b.trace).collect(),
+        }
+    }
+}
+
+/// Energy and power of `self` above `rhs`, e.g. a workload over an idle baseline of the
+/// same length. Differences stop at zero. Durations, peaks, periods and metadata stay
+/// those of `self`.
+impl Sub for PowerProfile {
+    type Output = PowerProfile;
+
+    fn sub(self, rhs: PowerProfile) -> PowerProfile {
+        let energy = |a: u128, b: u128| a.saturating_sub(b);
+        let power = |a: u64, b: u64| a.saturating_sub(b);
+        let mut dies = self.dies;
+        for (total, die) in dies.iter_mut().zip(&rhs.dies) {
+            total.cpu = energy(total.cpu, die.cpu);
+            total.gpu = energy(total.gpu, die.gpu);
+            total.ane = energy(total.ane, die.ane);
+        }
+        PowerProfile {
+            total_cpu_energy: energy(self.total_cpu_energy, rhs.total_cpu_energy),
+            total_gpu_energy: energy(self.total_gpu_energy, rhs.total_gpu_energy),
+            total_ane_energy: energy(self.total_ane_energy, rhs.total_ane_energy),
+            average_cpu_power: power(self.average_cpu_power, rhs.average_cpu_power),
+            average_gpu_power: power(self.average_gpu_power, rhs.average_gpu_power),
+            average_ane_power: power(self.average_ane_power, rhs.average_ane_power),
+            total_energy: energy(self.total_energy, rhs.total_energy),
+            average_power: power(self.average_power, rhs.average_power),
+            display_energy: self
+                .display_energy
+                .map(|a| energy(a, rhs.display_energy.unwrap_or(0))),
+            package_energy: self
+                .package_energy
+                .map(|a| energy(a, rhs.package_energy.unwrap_or(0))),
+            average_package_power: self
+                .average_package_power
+                .map(|a| power(a, rhs.average_package_power.unwrap_or(0))),
+            average_system_power: self
+                .average_system_power
+                .map(|a| power(a, rhs.average_system_power.unwrap_or(0))),
+            average_dc_in_power: self
+                .average_dc_in_power
+                .map(|a| power(a, rhs.average_dc_in_power.unwrap_or(0))),
+            dies,
+            ..self
+        }
+    }
+}
+
+impl PowerProfile {
+    /// Whether total and per-unit energy, average power and duration are each within
+    /// `relative` of `other`'s, 0.05 is 5% of the larger value. Equal profiles differ
+    /// run to run, this is how tests compare them.
+    pub fn approx_eq(&self, other: &PowerProfile, relative: f64) -> bool {
+        let pairs = [
+            (self.total_energy as f64, other.total_energy as f64),
+            (self.total_cpu_energy as f64, other.total_cpu_energy as f64),
+            (self.total_gpu_energy as f64, other.total_gpu_energy as f64),
+            (self.total_ane_energy as f64, other.total_ane_energy as f64),
+            (self.average_power as f64, other.average_power as f64),
+            (self.total_duration as f64, other.total_duration as f64),
+        ];
+        pairs
+            .into_iter()
+            .all(|(a, b)| (a - b).abs() <= relative * a.abs().max(b.abs()))
+    }
+
+    /// Panics with both profiles unless they are [`approx_eq`](Self::approx_eq).
+    #[track_caller]
+    pub fn assert_approx_eq(&self, other: &PowerProfile, relative: f64) {
+        if !self.approx_eq(other, relative) {
+            panic!(
+                "profiles differ by more than {:.1}%\nleft:\n{self}\nright:\n{other}",
+                relative * 100.0
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::EnergySample;
+
+    fn run(cpu_energy: u128, duration: u64) -> PowerProfile {
+        PowerProfile::from(
+            [EnergySample {
+                cpu_energy,
+                duration,
+                ..Default::default()
+            }]
+            .as_slice(),
+        )
+    }
+
+    #[test]
+    fn test_profile_arithmetic() {
+        // 1 W for 100 ms, then 3 W for 300 ms
+        let total = run(100, 100) + run(900, 300);
+        assert_eq!(total.total_energy, 1000);
+        assert_eq!(total.total_duration, 400);
+        assert_eq!(total.average_power, 2500);
+        assert_eq!(total.peak_cpu_power.power, 3000);
+        assert_eq!(total.peak_cpu_power.timestamp, 100);
+        assert_eq!(total.peak_power(), 3000);
+
+        let net = run(900, 300) - run(300, 300);
+        assert_eq!(net.total_energy, 600);
+        assert_eq!(net.average_power, 2000);
+        assert_eq!(net.total_duration, 300);
+        assert_eq!((run(100, 100) - run(900, 100)).total_energy, 0);
+
+        assert_eq!(run(100, 100), run(100, 100));
+        assert_ne!(run(100, 100), run(101, 100));
+        assert!(run(100, 100).approx_eq(&run(104, 100), 0.05));
+        assert!(!run(100, 100).approx_eq(&run(110, 100), 0.05));
+        run(100, 100).assert_approx_eq(&run(101, 100), 0.01);
+    }
+}
@@ -0,0 +1,143 @@
+//! Cost of sampling itself, to choose an interval with known measurement overhead.
+
+use std::time::{Duration, Instant};
+
+use crate::backend::PowerBackend;
+use crate::sampler::{SamplerError, Sampling, StartStopSampler};
+
+/// Intervals [`measure`] compares, from as fine as supported to one second.
+pub const DEFAULT_INTERVALS: [Duration; 5] = [
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+];
+
+/// What sampling at one interval costs while the workload idles.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Overhead {
+    pub interval: Duration,
+    pub samples: usize,
+    /// CPU time of the whole process per sample, which idles apart from the sampling
+    /// thread. `None` where process CPU time can't be read.
+    pub cpu_time_per_sample: Option<Duration>,
+    /// CPU time as a fraction of the session's wall time, 0.01 is 1% of one core.
+    pub cpu_utilization: Option<f64>,
+    /// Average power of the idle session in milliwatts.
+    pub average_power: u64,
+    /// Milliwatts above the session at the coarsest interval measured. Background
+    /// activity makes this noisy, values near or below zero mean no overhead showed.
+    pub extra_power: f64,
+    /// `extra_power` spread over the samples, in millijoules per sample.
+    pub energy_per_sample: f64,
+}
+
+impl std::fmt::Display for Overhead {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {} samples", self.interval, self.samples)?;
+        if let (Some(cpu), Some(utilization)) = (self.cpu_time_per_sample, self.cpu_utilization) {
+            write!(
+                f,
+                ", {cpu:?} CPU per sample ({:.2}% of a core)",
+                utilization * 100.0
+            )?;
+        }
+        write!(
+            f,
+            ", {} mW, {:+.1} mW, {:.3} mJ per sample",
+            self.average_power, self.extra_power, self.energy_per_sample
+        )
+    }
+}
+
+/// Samples the default backend for two seconds at each of [`DEFAULT_INTERVALS`] while
+/// the calling thread sleeps.
+pub fn measure() -> Result<Vec<Overhead>, SamplerError> {
+    measure_with(
+        &mut StartStopSampler::new(),
+        &DEFAULT_INTERVALS,
+        Duration::from_secs(2),
+    )
+}
+
+/// Runs one idle `session` per interval on `sampler`, in the order of `intervals`.
+/// Other threads of the process should idle too, their CPU time is counted as overhead.
+pub fn measure_with<B: PowerBackend + 'static>(
+    sampler: &mut StartStopSampler<B>,
+    intervals: &[Duration],
+    session: Duration,
+) -> Result<Vec<Overhead>, SamplerError> {
+    let mut overheads = Vec::with_capacity(intervals.len());
+    for &interval in intervals {
+        let cpu_before = process_cpu_time();
+        let started = Instant::now();
+        sampler.start(interval, 1)?;
+        std::thread::sleep(session);
+        sampler.stop()?;
+        let elapsed = started.elapsed();
+        let cpu = process_cpu_time()
+            .zip(cpu_before)
+            .map(|(after, before)| after.saturating_sub(before));
+
+        let samples = sampler.samples().map_or(0, Vec::len);
+        overheads.push(Overhead {
+            interval,
+            samples,
+            cpu_time_per_sample: cpu.map(|cpu| cpu / samples.max(1) as u32),
+            cpu_utilization: cpu.map(|cpu| cpu.as_secs_f64() / elapsed.as_secs_f64()),
+            average_power: sampler.profile()?.average_power,
+            extra_power: 0.0,
+            energy_per_sample: 0.0,
+        });
+    }
+
+    let baseline = overheads
+        .iter()
+        .max_by_key(|o| o.interval)
+        .map_or(0, |o| o.average_power) as f64;
+    for overhead in &mut overheads {
+        overhead.extra_power = overhead.average_power as f64 - baseline;
+        // mW * s == mJ
+        overhead.energy_per_sample = overhead.extra_power * overhead.interval.as_secs_f64();
+    }
+    Ok(overheads)
+}
+
+#[cfg(unix)]
+fn process_cpu_time() -> Option<Duration> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, &mut time) } != 0 {
+        return None;
+    }
+    Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+}
+
+#[cfg(not(unix))]
+fn process_cpu_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+
+    #[test]
+    fn test_measure_with_mock() {
+        let mut sampler =
+            StartStopSampler::with_backend(|| Ok(MockBackend::constant(1000.0, 0.0, 0.0)));
+        let intervals = [Duration::from_millis(20), Duration::from_millis(50)];
+        let overheads = measure_with(&mut sampler, &intervals, Duration::from_millis(200)).unwrap();
+
+        assert_eq!(overheads.len(), 2);
+        assert!(overheads[0].samples > overheads[1].samples);
+        // the mock draws the same power at any interval
+        assert!(overheads.iter().all(|o| o.extra_power == 0.0));
+        #[cfg(unix)]
+        assert!(overheads[0].cpu_utilization.is_some());
+    }
+}
@@ -0,0 +1,127 @@
+/// What the machine ran on when a sample was taken. Power management differs between
+/// AC and battery, so samples taken on either don't compare.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PowerSource {
+    pub on_battery: bool,
+    /// Charge of the battery in percent, `None` without a battery.
+    pub battery_percent: Option<u8>,
+}
+
+#[cfg(all(target_os = "macos", feature = "macos"))]
+mod sys {
+    use super::PowerSource;
+    use crate::cf_utils::{cfdict_get_val, get_cf_string};
+    use core_foundation::{
+        array::{CFArrayGetCount, CFArrayGetValueAtIndex, CFArrayRef},
+        base::{CFRelease, CFTypeRef},
+        dictionary::CFDictionaryRef,
+        number::{kCFNumberSInt32Type, CFNumberGetValue, CFNumberRef},
+        string::CFStringRef,
+    };
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPSCopyPowerSourcesInfo() -> CFTypeRef;
+        fn IOPSCopyPowerSourcesList(blob: CFTypeRef) -> CFArrayRef;
+        fn IOPSGetPowerSourceDescription(blob: CFTypeRef, source: CFTypeRef) -> CFDictionaryRef;
+        fn IOPSGetProvidingPowerSourceType(blob: CFTypeRef) -> CFStringRef;
+    }
+
+    fn number(description: CFDictionaryRef, key: &str) -> Option<i32> {
+        let value = cfdict_get_val(description, key)?;
+        let mut number = 0i32;
+        let ok = unsafe {
+            CFNumberGetValue(
+                value as CFNumberRef,
+                kCFNumberSInt32Type,
+                (&mut number as *mut i32).cast(),
+            )
+        };
+        ok.then_some(number)
+    }
+
+    /// Charge of the first battery among the power sources in `blob`.
+    fn battery_percent(blob: CFTypeRef) -> Option<u8> {
+        let sources = unsafe { IOPSCopyPowerSourcesList(blob) };
+        if sources.is_null() {
+            return None;
+        }
+        let percent = (0..unsafe { CFArrayGetCount(sources) }).find_map(|i| {
+            let source = unsafe { CFArrayGetValueAtIndex(sources, i) };
+            let description = unsafe { IOPSGetPowerSourceDescription(blob, source) };
+            if description.is_null() {
+                return None;
+            }
+            // keys of <IOKit/ps/IOPSKeys.h>
+            let current = number(description, "Current Capacity")?;
+            let max = number(description, "Max Capacity")?.max(1);
+            Some((current * 100 / max).clamp(0, 100) as u8)
+        });
+        unsafe { CFRelease(sources as _) };
+        percent
+    }
+
+    pub fn current() -> Option<PowerSource> {
+        let blob = unsafe { IOPSCopyPowerSourcesInfo() };
+        if blob.is_null() {
+            return None;
+        }
+        let providing = get_cf_string(|| unsafe { IOPSGetProvidingPowerSourceType(blob) });
+        let source = PowerSource {
+            on_battery: providing == "Battery Power",
+            battery_percent: battery_percent(blob),
+        };
+        unsafe { CFRelease(blob) };
+        Some(source)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::PowerSource;
+    use std::fs;
+
+    pub fn current() -> Option<PowerSource> {
+        let mut mains_online = None;
+        let mut battery = None;
+        for entry in fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+            let path = entry.path();
+            let read = |name: &str| fs::read_to_string(path.join(name)).ok();
+            match read("type").as_deref().map(str::trim) {
+                Some("Mains") => {
+                    let online = read("online").is_some_and(|v| v.trim() == "1");
+                    mains_online = Some(mains_online.unwrap_or(false) || online);
+                }
+                Some("Battery") if battery.is_none() => {
+                    let discharging = read("status").is_some_and(|v| v.trim() == "Discharging");
+                    let percent = read("capacity").and_then(|v| v.trim().parse().ok());
+                    battery = Some((discharging, percent));
+                }
+                _ => {}
+            }
+        }
+        if mains_online.is_none() && battery.is_none() {
+            return None;
+        }
+        // some laptops report no mains supply, only whether the battery discharges
+        let (discharging, battery_percent) = battery.unwrap_or((false, None));
+        Some(PowerSource {
+            on_battery: mains_online.map_or(discharging, |online| !online),
+            battery_percent,
+        })
+    }
+}
+
+#[cfg(not(any(all(target_os = "macos", feature = "macos"), target_os = "linux")))]
+mod sys {
+    pub fn current() -> Option<super::PowerSource> {
+        None
+    }
+}
+
+impl PowerSource {
+    /// The power source right now, `None` where it can't be read.
+    pub fn current() -> Option<Self> {
+        sys::current()
+    }
+}
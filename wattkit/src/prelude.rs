@@ -0,0 +1,7 @@
+//! The types and traits most programs need, `use wattkit::prelude::*;` brings them in.
+
+pub use crate::backend::PowerBackend;
+pub use crate::sampler::{
+    EnergySample, GuardSampler as Sampler, PowerProfile, SamplerError, Sampling, StartStopSampler,
+};
+pub use crate::{EnergyBudget, MockBackend, SamplerConfig, Units};
@@ -0,0 +1,127 @@
+//! What the current process can actually measure. Some macOS versions hand processes
+//! without elevated privileges channels that only ever read zero.
+
+use std::time::Duration;
+
+use crate::backend::ioreport::{unmapped_channels, GroupProbe, IOReportBackend};
+use crate::backend::{Capabilities, ChannelInfo, PowerBackend};
+use crate::sampler::SamplerError;
+
+/// How long [`capabilities`] reads the backend and each IOReport group.
+pub const PROBE_DURATION: Duration = Duration::from_millis(200);
+
+/// Units a backend has channels for against those that produced energy.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProbedCapabilities {
+    /// Units the backend has channels for, [`PowerBackend::capabilities`].
+    pub reported: Capabilities,
+    /// Units whose energy was nonzero while probing. The CPU always draws power, an idle
+    /// GPU or ANE reads zero with full access too.
+    pub measured: Capabilities,
+    /// Each IOReport group wattkit reads or may read, empty off macOS.
+    pub groups: Vec<GroupProbe>,
+    /// Channels of the backend none of the typed channels cover, see
+    /// [`unmapped_channels`].
+    pub unmapped: Vec<ChannelInfo>,
+}
+
+impl ProbedCapabilities {
+    /// Whether the CPU has channels that read zero, i.e. samples would silently report
+    /// no energy.
+    pub fn is_restricted(&self) -> bool {
+        self.reported.cpu && !self.measured.cpu
+    }
+}
+
+impl std::fmt::Display for ProbedCapabilities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unit = |reported: bool, measured: bool| match (reported, measured) {
+            (false, _) => "no channels",
+            (true, false) => "reads zero",
+            (true, true) => "measured",
+        };
+        writeln!(f, "CPU: {}", unit(self.reported.cpu, self.measured.cpu))?;
+        writeln!(f, "GPU: {}", unit(self.reported.gpu, self.measured.gpu))?;
+        write!(f, "ANE: {}", unit(self.reported.ane, self.measured.ane))?;
+        for group in &self.groups {
+            write!(f, "\n{}: ", group.group)?;
+            if group.subscribed {
+                write!(f, "{} of {} channels active", group.active, group.channels)?;
+            } else {
+                write!(f, "not available")?;
+            }
+        }
+        for channel in &self.unmapped {
+            write!(f, "\nUnmapped: {}", channel.name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Probes the default backend and, on macOS, every IOReport group wattkit reads, for
+/// [`PROBE_DURATION`] each.
+pub fn capabilities() -> Result<ProbedCapabilities, SamplerError> {
+    // before opening the backend, so its subscription is the one left pooled
+    let groups = IOReportBackend::probe_groups(PROBE_DURATION);
+    let mut probed = probe(&mut crate::backend::open_default()?, PROBE_DURATION)?;
+    probed.groups = groups;
+    Ok(probed)
+}
+
+/// Reads `backend` once over `duration` and compares the units it reports against those
+/// it measured.
+pub fn probe<B: PowerBackend>(
+    backend: &mut B,
+    duration: Duration,
+) -> Result<ProbedCapabilities, SamplerError> {
+    let sample = backend.sample(duration)?;
+    Ok(ProbedCapabilities {
+        reported: backend.capabilities(),
+        measured: Capabilities {
+            cpu: sample.cpu_energy > 0,
+            gpu: sample.gpu_energy > 0,
+            ane: sample.ane_energy > 0,
+        },
+        groups: Vec::new(),
+        unmapped: unmapped_channels(&backend.channels())
+            .into_iter()
+            .cloned()
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockBackend;
+
+    #[test]
+    fn test_probe() {
+        let mut backend = MockBackend::constant(5.0, 0.0, 0.0).realtime(false);
+        let probed = probe(&mut backend, Duration::from_millis(100)).unwrap();
+        assert_eq!(
+            probed.measured,
+            Capabilities {
+                cpu: true,
+                gpu: false,
+                ane: false
+            }
+        );
+        assert!(probed.reported.gpu);
+        assert!(!probed.is_restricted());
+
+        let mut backend = MockBackend::constant(0.0, 0.0, 0.0)
+            .realtime(false)
+            .capabilities(Capabilities {
+                cpu: true,
+                gpu: false,
+                ane: false,
+            });
+        let probed = probe(&mut backend, Duration::from_millis(100)).unwrap();
+        assert!(probed.is_restricted());
+        assert_eq!(
+            probed.to_string(),
+            "CPU: reads zero\nGPU: no channels\nANE: no channels"
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
+
+use crate::sampler::{EnergySample, PowerProfile, SamplerError, Sampling, StartStopSampler};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RunError {
+    #[error("Failed to run the command: {0}")]
+    Spawn(#[from] std::io::Error),
+    #[error(transparent)]
+    Sampler(#[from] SamplerError),
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use std::time::Duration;
+
+    extern "C" {
+        fn mach_timebase_info(info: *mut libc::mach_timebase_info) -> libc::c_int;
+    }
+
+    pub fn cpu_time(pid: u32) -> Option<Duration> {
+        let mut info: libc::proc_taskinfo = unsafe { std::mem::zeroed() };
+        let size = std::mem::size_of::<libc::proc_taskinfo>() as libc::c_int;
+        let written = unsafe {
+            libc::proc_pidinfo(
+                pid as libc::c_int,
+                libc::PROC_PIDTASKINFO,
+                0,
+                &mut info as *mut _ as *mut libc::c_void,
+                size,
+            )
+        };
+        if written != size {
+            return None;
+        }
+
+        // task times are reported in mach absolute time units
+        let mut timebase = libc::mach_timebase_info { numer: 0, denom: 0 };
+        if unsafe { mach_timebase_info(&mut timebase) } != 0 || timebase.denom == 0 {
+            return None;
+        }
+        let ticks = (info.pti_total_user + info.pti_total_system) as u128;
+        let nanos = ticks * timebase.numer as u128 / timebase.denom as u128;
+        Some(Duration::from_nanos(nanos as u64))
+    }
+
+    pub fn child_pids(pid: u32) -> Vec<u32> {
+        let mut pids = vec![0 as libc::pid_t; 1024];
+        let count = unsafe {
+            libc::proc_listchildpids(
+                pid as libc::pid_t,
+                pids.as_mut_ptr() as *mut libc::c_void,
+                (pids.len() * std::mem::size_of::<libc::pid_t>()) as libc::c_int,
+            )
+        };
+        pids.truncate(count.max(0) as usize);
+        pids.into_iter()
+            .filter(|pid| *pid > 0)
+            .map(|pid| pid as u32)
+            .collect()
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod sys {
+    use std::time::Duration;
+
+    pub fn cpu_time(_pid: u32) -> Option<Duration> {
+        None
+    }
+
+    pub fn child_pids(_pid: u32) -> Vec<u32> {
+        Vec::new()
+    }
+}
+
+/// Total user + system CPU time consumed by `pid` so far.
+pub fn cpu_time(pid: u32) -> Option<Duration> {
+    sys::cpu_time(pid)
+}
+
+/// `pid` followed by all of its (transitive) child processes.
+pub fn process_tree(pid: u32) -> Vec<u32> {
+    let mut tree = vec![pid];
+    let mut i = 0;
+    while i < tree.len() {
+        tree.extend(sys::child_pids(tree[i]));
+        i += 1;
+    }
+    tree
+}
+
+/// # ProcessAttribution
+///
+/// Splits the CPU energy of each sample across a process tree, proportionally
+/// to the CPU time each process consumed during the sample.
+#[derive(Debug)]
+pub struct ProcessAttribution {
+    root: u32,
+    previous: HashMap<u32, Duration>,
+    energy: HashMap<u32, f64>, //millijoules
+}
+
+impl ProcessAttribution {
+    pub fn new(root: u32) -> Self {
+        let mut attribution = ProcessAttribution {
+            root,
+            previous: HashMap::new(),
+            energy: HashMap::new(),
+        };
+        attribution.previous = attribution.cpu_times();
+        attribution
+    }
+
+    fn cpu_times(&self) -> HashMap<u32, Duration> {
+        process_tree(self.root)
+            .into_iter()
+            .filter_map(|pid| cpu_time(pid).map(|t| (pid, t)))
+            .collect()
+    }
+
+    pub fn record(&mut self, sample: &EnergySample) {
+        let current = self.cpu_times();
+        let deltas: Vec<(u32, Duration)> = current
+            .iter()
+            .map(|(pid, t)| {
+                let previous = self.previous.get(pid).copied().unwrap_or_default();
+                (*pid, t.saturating_sub(previous))
+            })
+            .collect();
+        self.previous = current;
+
+        let total: Duration = deltas.iter().map(|(_, d)| *d).sum();
+        if total.is_zero() {
+            return;
+        }
+        for (pid, delta) in deltas {
+            let share = delta.as_secs_f64() / total.as_secs_f64();
+            *self.energy.entry(pid).or_default() += sample.cpu_energy as f64 * share;
+        }
+    }
+
+    /// CPU energy in millijoules attributed to each process of the tree.
+    pub fn energy_by_pid(&self) -> &HashMap<u32, f64> {
+        &self.energy
+    }
+}
+
+/// CPU time of every terminated child process this process waited for.
+#[cfg(unix)]
+fn children_cpu_time() -> Option<Duration> {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) } != 0 {
+        return None;
+    }
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    Some(time(usage.ru_utime) + time(usage.ru_stime))
+}
+
+#[cfg(not(unix))]
+fn children_cpu_time() -> Option<Duration> {
+    None
+}
+
+/// Options of [`run_profiled_with`].
+#[derive(Clone, Copy, Debug)]
+pub struct RunOptions {
+    pub interval: Duration,
+    /// Attribute CPU energy to the child by its share of the host's busy CPU time, see
+    /// [`ProfiledRun::child_cpu_energy`].
+    pub attribute: bool,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            interval: Duration::from_millis(100),
+            attribute: false,
+        }
+    }
+}
+
+/// A command run to completion while sampling, see [`run_profiled_with`].
+#[derive(Debug)]
+pub struct ProfiledRun {
+    pub status: ExitStatus,
+    /// Energy of the whole host over the child's lifetime.
+    pub profile: PowerProfile,
+    /// User + system CPU time of the child and the descendants it waited for.
+    pub child_cpu_time: Option<Duration>,
+    /// Millijoules of the profile's CPU energy in proportion to the child's share of
+    /// the host's busy CPU time. `None` unless [`RunOptions::attribute`] is set or where
+    /// CPU times can't be read.
+    pub child_cpu_energy: Option<f64>,
+}
+
+impl ProfiledRun {
+    fn attribute(&mut self) {
+        let (Some(child), Some(utilization)) = (self.child_cpu_time, self.profile.cpu_utilization)
+        else {
+            return;
+        };
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        // utilization is across all cores
+        let busy = utilization * cores as f64 * self.profile.total_duration as f64 / 1000.0;
+        let share = if busy > 0.0 {
+            (child.as_secs_f64() / busy).min(1.0)
+        } else {
+            0.0
+        };
+        self.child_cpu_energy = Some(self.profile.total_cpu_energy as f64 * share);
+    }
+}
+
+/// Runs `command` to completion while sampling the default backend, and returns its
+/// exit status with the profile of its lifetime.
+pub fn run_profiled(command: &mut Command) -> Result<(ExitStatus, PowerProfile), RunError> {
+    let run = run_profiled_with(command, RunOptions::default())?;
+    Ok((run.status, run.profile))
+}
+
+/// [`run_profiled`] with `options`. Children of this process that terminate meanwhile
+/// count towards the child's CPU time.
+pub fn run_profiled_with(
+    command: &mut Command,
+    options: RunOptions,
+) -> Result<ProfiledRun, RunError> {
+    run_with_sampler(StartStopSampler::new(), command, options)
+}
+
+fn run_with_sampler<B: crate::PowerBackend + 'static>(
+    mut sampler: StartStopSampler<B>,
+    command: &mut Command,
+    options: RunOptions,
+) -> Result<ProfiledRun, RunError> {
+    let before = children_cpu_time();
+    sampler.start(options.interval, 1)?;
+    let status = command.status()?;
+    sampler.stop()?;
+    let child_cpu_time = children_cpu_time()
+        .zip(before)
+        .map(|(after, before)| after.saturating_sub(before));
+    let mut run = ProfiledRun {
+        status,
+        profile: sampler.profile()?,
+        child_cpu_time,
+        child_cpu_energy: None,
+    };
+    if options.attribute {
+        run.attribute();
+    }
+    Ok(run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockBackend;
+
+    #[test]
+    #[cfg_attr(not(unix), ignore = "requires a shell and getrusage")]
+    fn test_run_profiled() {
+        let sampler =
+            || StartStopSampler::with_backend(|| Ok(MockBackend::constant(1000.0, 0.0, 0.0)));
+        let options = RunOptions {
+            interval: Duration::from_millis(20),
+            attribute: true,
+        };
+        let mut command = Command::new("sh");
+        command.args([
+            "-c",
+            "i=0; while [ $i -lt 100000 ]; do i=$((i+1)); done; exit 3",
+        ]);
+        let run = run_with_sampler(sampler(), &mut command, options).unwrap();
+        assert_eq!(run.status.code(), Some(3));
+        assert!(run.profile.total_cpu_energy > 0);
+        assert!(run.child_cpu_time.unwrap() > Duration::ZERO);
+        if let Some(energy) = run.child_cpu_energy {
+            assert!((0.0..=run.profile.total_cpu_energy as f64).contains(&energy));
+        }
+
+        let mut missing = Command::new("/nonexistent/wattkit-test");
+        let run = run_with_sampler(sampler(), &mut missing, options);
+        assert!(matches!(run, Err(RunError::Spawn(_))));
+    }
+}
@@ -0,0 +1,251 @@
+//! Prometheus text exposition of profiles, e.g. for a `/metrics` endpoint or the
+//! node_exporter textfile collector.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::sampler::{DieEnergy, PowerProfile};
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown metric names {0:?}, expected \"wattkit\" or \"scaphandre\"")]
+pub struct UnknownMetricNames(pub String);
+
+/// Names and labels of the metrics [`PowerProfile::to_prometheus`] writes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MetricNames {
+    /// `wattkit_energy_joules_total` and `wattkit_power_watts` with a `unit` label of
+    /// "cpu", "gpu", "ane" or "package", plus `wattkit_peak_power_watts` and
+    /// `wattkit_duration_seconds`.
+    #[default]
+    Wattkit,
+    /// The names and labels of scaphandre's exporter, so its Grafana dashboards work
+    /// unchanged: `scaph_host_power_microwatts`, `scaph_host_energy_microjoules`,
+    /// `scaph_socket_power_microwatts` per die and `scaph_domain_power_microwatts` with
+    /// the CPU as the "core" domain, the GPU as "uncore" and the ANE as "ane".
+    Scaphandre,
+}
+
+impl FromStr for MetricNames {
+    type Err = UnknownMetricNames;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wattkit" => Ok(MetricNames::Wattkit),
+            "scaphandre" => Ok(MetricNames::Scaphandre),
+            _ => Err(UnknownMetricNames(s.to_string())),
+        }
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}");
+}
+
+/// Name, energy and average power of every unit the profile measured.
+fn units(profile: &PowerProfile) -> Vec<(&'static str, u128, u64)> {
+    let mut units = Vec::new();
+    if profile.cpu_available {
+        units.push(("cpu", profile.total_cpu_energy, profile.average_cpu_power));
+    }
+    if profile.gpu_available {
+        units.push(("gpu", profile.total_gpu_energy, profile.average_gpu_power));
+    }
+    if profile.ane_available {
+        units.push(("ane", profile.total_ane_energy, profile.average_ane_power));
+    }
+    if let (Some(energy), Some(power)) = (profile.package_energy, profile.average_package_power) {
+        units.push(("package", energy, power));
+    }
+    units
+}
+
+impl PowerProfile {
+    /// The profile in the Prometheus text format, named after `names`. Units the backend
+    /// doesn't measure are left out.
+    pub fn to_prometheus(&self, names: MetricNames) -> String {
+        match names {
+            MetricNames::Wattkit => self.wattkit_metrics(),
+            MetricNames::Scaphandre => self.scaphandre_metrics(),
+        }
+    }
+
+    fn wattkit_metrics(&self) -> String {
+        let units = units(self);
+        let mut out = String::new();
+        header(
+            &mut out,
+            "wattkit_energy_joules_total",
+            "counter",
+            "Energy used by each unit.",
+        );
+        for &(unit, energy, _) in &units {
+            let joules = energy as f64 / 1e3;
+            let _ = writeln!(
+                out,
+                "wattkit_energy_joules_total{{unit=\"{unit}\"}} {joules}"
+            );
+        }
+        header(
+            &mut out,
+            "wattkit_power_watts",
+            "gauge",
+            "Average power of each unit.",
+        );
+        for &(unit, _, power) in &units {
+            let watts = power as f64 / 1e3;
+            let _ = writeln!(out, "wattkit_power_watts{{unit=\"{unit}\"}} {watts}");
+        }
+        header(
+            &mut out,
+            "wattkit_peak_power_watts",
+            "gauge",
+            "Highest total power of any one sample.",
+        );
+        let _ = writeln!(
+            out,
+            "wattkit_peak_power_watts {}",
+            self.peak_power() as f64 / 1e3
+        );
+        header(
+            &mut out,
+            "wattkit_duration_seconds",
+            "gauge",
+            "Duration of the profile.",
+        );
+        let _ = writeln!(
+            out,
+            "wattkit_duration_seconds {}",
+            self.total_duration as f64 / 1e3
+        );
+        out
+    }
+
+    fn scaphandre_metrics(&self) -> String {
+        // mJ over ms is W, in microwatts
+        let microwatts = |energy: u128| match self.total_duration {
+            0 => 0,
+            duration => energy * 1_000_000 / duration as u128,
+        };
+        let host_energy = self.package_energy.unwrap_or(self.total_energy);
+        let host_power = self.average_package_power.unwrap_or(self.average_power);
+        let dies = match self.dies.as_slice() {
+            [] => vec![DieEnergy {
+                cpu: self.total_cpu_energy,
+                gpu: self.total_gpu_energy,
+                ane: self.total_ane_energy,
+            }],
+            dies => dies.to_vec(),
+        };
+
+        let mut out = String::new();
+        header(
+            &mut out,
+            "scaph_host_power_microwatts",
+            "gauge",
+            "Power measured on the host, in microwatts",
+        );
+        let _ = writeln!(out, "scaph_host_power_microwatts {}", host_power * 1000);
+        header(
+            &mut out,
+            "scaph_host_energy_microjoules",
+            "counter",
+            "Energy measured for the host, in microjoules",
+        );
+        let _ = writeln!(out, "scaph_host_energy_microjoules {}", host_energy * 1000);
+        header(
+            &mut out,
+            "scaph_socket_power_microwatts",
+            "gauge",
+            "Power measured on each socket, in microwatts",
+        );
+        for (socket, die) in dies.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "scaph_socket_power_microwatts{{socket_id=\"{socket}\"}} {}",
+                microwatts(die.cpu + die.gpu + die.ane)
+            );
+        }
+        header(
+            &mut out,
+            "scaph_domain_power_microwatts",
+            "gauge",
+            "Power measured on each domain of a socket, in microwatts",
+        );
+        for (socket, die) in dies.iter().enumerate() {
+            let domains = [
+                ("core", die.cpu, self.cpu_available),
+                ("uncore", die.gpu, self.gpu_available),
+                ("ane", die.ane, self.ane_available),
+            ];
+            for (domain, energy, _) in domains.into_iter().filter(|(_, _, available)| *available) {
+                let _ = writeln!(
+                    out,
+                    "scaph_domain_power_microwatts{{domain_name=\"{domain}\",socket_id=\"{socket}\"}} {}",
+                    microwatts(energy)
+                );
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::EnergySample;
+
+    #[test]
+    fn test_to_prometheus() {
+        // 4 W on the CPU and 1 W on the GPU for 100 ms, across two dies
+        let profile = PowerProfile::from(
+            [EnergySample {
+                cpu_energy: 400,
+                gpu_energy: 100,
+                duration: 100,
+                dies: vec![
+                    DieEnergy {
+                        cpu: 300,
+                        gpu: 100,
+                        ane: 0,
+                    },
+                    DieEnergy {
+                        cpu: 100,
+                        gpu: 0,
+                        ane: 0,
+                    },
+                ],
+                ..Default::default()
+            }]
+            .as_slice(),
+        );
+
+        let wattkit = profile.to_prometheus(MetricNames::default());
+        for line in [
+            "# TYPE wattkit_energy_joules_total counter\n",
+            "wattkit_energy_joules_total{unit=\"cpu\"} 0.4\n",
+            "wattkit_power_watts{unit=\"gpu\"} 1\n",
+            "wattkit_peak_power_watts 5\n",
+            "wattkit_duration_seconds 0.1\n",
+        ] {
+            assert!(wattkit.contains(line), "{line} missing from\n{wattkit}");
+        }
+
+        let scaphandre = profile.to_prometheus("scaphandre".parse().unwrap());
+        for line in [
+            "# TYPE scaph_host_power_microwatts gauge\n",
+            "scaph_host_power_microwatts 5000000\n",
+            "scaph_host_energy_microjoules 500000\n",
+            "scaph_socket_power_microwatts{socket_id=\"0\"} 4000000\n",
+            "scaph_socket_power_microwatts{socket_id=\"1\"} 1000000\n",
+            "scaph_domain_power_microwatts{domain_name=\"core\",socket_id=\"0\"} 3000000\n",
+            "scaph_domain_power_microwatts{domain_name=\"uncore\",socket_id=\"0\"} 1000000\n",
+        ] {
+            assert!(
+                scaphandre.contains(line),
+                "{line} missing from\n{scaphandre}"
+            );
+        }
+        assert!(!scaphandre.contains("wattkit_"));
+        assert!("statsd".parse::<MetricNames>().is_err());
+    }
+}
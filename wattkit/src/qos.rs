@@ -0,0 +1,48 @@
+/// Quality of service class of the sampling thread, see [`SamplerConfig::with_qos`].
+/// Lower classes keep sampling out of the way of the workload, higher ones keep its
+/// timing tight under load. Only has an effect on macOS.
+///
+/// [`SamplerConfig::with_qos`]: crate::SamplerConfig::with_qos
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QosClass {
+    UserInteractive,
+    UserInitiated,
+    /// What threads get unless told otherwise.
+    Default,
+    Utility,
+    Background,
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    extern "C" {
+        fn pthread_set_qos_class_self_np(class: u32, relative_priority: libc::c_int)
+            -> libc::c_int;
+    }
+
+    /// Best effort, a class the system refuses leaves the thread as it was.
+    pub fn set_current_thread(class: super::QosClass) {
+        use super::QosClass::*;
+        // values of `qos_class_t` in <sys/qos.h>
+        let class = match class {
+            UserInteractive => 0x21,
+            UserInitiated => 0x19,
+            Default => 0x15,
+            Utility => 0x11,
+            Background => 0x09,
+        };
+        unsafe { pthread_set_qos_class_self_np(class, 0) };
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod sys {
+    pub fn set_current_thread(_class: super::QosClass) {}
+}
+
+impl QosClass {
+    /// Applies the class to the calling thread.
+    pub(crate) fn apply(self) {
+        sys::set_current_thread(self);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::sampler::{DieEnergy, EnergySample, MemoryTraffic};
+
+/// A named span of a sampling session, in milliseconds since sampling started.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub name: String,
+    pub start: u64,
+    pub end: Option<u64>, //None while the region is still open
+}
+
+/// Identifies a region opened with `begin_region`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegionId(pub(crate) usize);
+
+impl RegionId {
+    /// Position of the region in `Sampling::regions`.
+    pub fn index(&self) -> usize {
+        self.0
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        RegionId(index)
+    }
+}
+
+impl Region {
+    /// The parts of `samples` inside any of `regions`, time covered by several
+    /// overlapping regions is only taken once.
+    pub fn clip_all<'a>(
+        samples: &[EnergySample],
+        regions: impl IntoIterator<Item = &'a Region>,
+    ) -> Vec<EnergySample> {
+        let mut regions: Vec<Region> = regions.into_iter().cloned().collect();
+        regions.sort_by_key(|r| r.start);
+        let mut merged: Vec<Region> = Vec::with_capacity(regions.len());
+        for region in regions {
+            match merged.last_mut() {
+                Some(last) if region.start <= last.end.unwrap_or(u64::MAX) => {
+                    // an open region stays open
+                    last.end = last.end.zip(region.end).map(|(a, b)| a.max(b));
+                }
+                _ => merged.push(region),
+            }
+        }
+        merged
+            .iter()
+            .flat_map(|region| samples.iter().filter_map(|s| region.clip(s)))
+            .collect()
+    }
+
+    /// The part of `sample` that overlaps this region, energy is prorated by the overlap.
+    /// An open region extends to the end of the session.
+    pub fn clip(&self, sample: &EnergySample) -> Option<EnergySample> {
+        let sample_end = sample.timestamp + sample.duration;
+        let start = self.start.max(sample.timestamp);
+        let end = self.end.unwrap_or(u64::MAX).min(sample_end);
+        if end <= start || sample.duration == 0 {
+            return None;
+        }
+
+        let overlap = end - start;
+        let scale = |energy: u128| energy * overlap as u128 / sample.duration as u128;
+        Some(EnergySample {
+            cpu_energy: scale(sample.cpu_energy),
+            gpu_energy: scale(sample.gpu_energy),
+            ane_energy: scale(sample.ane_energy),
+            display_energy: sample.display_energy.map(scale),
+            package_energy: sample.package_energy.map(scale),
+            timestamp: start,
+            wall_time: sample
+                .wall_time
+                .map(|wall| wall + std::time::Duration::from_millis(start - sample.timestamp)),
+            duration: overlap,
+            power_source: sample.power_source,
+            cpu_utilization: sample.cpu_utilization,
+            system_power: sample.system_power,
+            dc_in_power: sample.dc_in_power,
+            memory_traffic: sample.memory_traffic.map(|traffic| MemoryTraffic {
+                read: scale(traffic.read as u128) as u64,
+                write: scale(traffic.write as u128) as u64,
+            }),
+            thermal_pressure: sample.thermal_pressure,
+            cpu_residency: sample.cpu_residency,
+            extra: sample.extra.share(overlap, sample.duration),
+            dies: sample
+                .dies
+                .iter()
+                .map(|die| DieEnergy {
+                    cpu: scale(die.cpu),
+                    gpu: scale(die.gpu),
+                    ane: scale(die.ane),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Region bookkeeping shared by the samplers.
+#[derive(Debug, Default)]
+pub(crate) struct Regions(Vec<Region>);
+
+impl Regions {
+    pub(crate) fn begin(&mut self, name: &str, now: u64) -> RegionId {
+        self.0.push(Region {
+            name: name.to_string(),
+            start: now,
+            end: None,
+        });
+        RegionId(self.0.len() - 1)
+    }
+
+    pub(crate) fn end(&mut self, id: RegionId, now: u64) {
+        if let Some(region) = self.0.get_mut(id.0) {
+            region.end.get_or_insert(now);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub(crate) fn as_slice(&self) -> &[Region] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_prorates_energy() {
+        let sample = EnergySample {
+            cpu_energy: 100,
+            gpu_energy: 50,
+            ane_energy: 10,
+            timestamp: 100,
+            duration: 100,
+            dies: vec![DieEnergy {
+                cpu: 100,
+                gpu: 50,
+                ane: 10,
+            }],
+            ..Default::default()
+        };
+        let region = Region {
+            name: "half".to_string(),
+            start: 150,
+            end: Some(300),
+        };
+
+        let clipped = region.clip(&sample).unwrap();
+        assert_eq!(clipped.timestamp, 150);
+        assert_eq!(clipped.duration, 50);
+        assert_eq!(clipped.cpu_energy, 50);
+        assert_eq!(clipped.gpu_energy, 25);
+        assert_eq!(clipped.ane_energy, 5);
+        assert_eq!(
+            clipped.dies,
+            [DieEnergy {
+                cpu: 50,
+                gpu: 25,
+                ane: 5
+            }]
+        );
+
+        let outside = Region {
+            name: "outside".to_string(),
+            start: 200,
+            end: None,
+        };
+        assert!(outside.clip(&sample).is_none());
+    }
+}
@@ -0,0 +1,137 @@
+//! Markdown summaries of profiles, e.g. for CI to post as a pull request comment.
+
+use std::fmt::Write;
+
+use crate::sampler::PowerProfile;
+use crate::units::Units;
+
+#[derive(Clone, Copy)]
+enum Quantity {
+    /// mJ
+    Energy,
+    /// mW
+    Power,
+    /// ms
+    Duration,
+}
+
+/// Label, value and quantity of a table row.
+type Row = (&'static str, fn(&PowerProfile) -> f64, Quantity);
+
+impl PowerProfile {
+    /// A compact Markdown table of energy, average and peak power and duration, with a
+    /// column per value of `baseline` and the change from it when given. Warnings about
+    /// throttling or the power source changing follow the table. Energy and power are
+    /// in joules and watts, see [`PowerProfile::to_markdown_with`].
+    pub fn to_markdown(&self, baseline: Option<&PowerProfile>) -> String {
+        self.to_markdown_with(baseline, Units::SI)
+    }
+
+    /// [`PowerProfile::to_markdown`] with energy and power in `units`.
+    pub fn to_markdown_with(&self, baseline: Option<&PowerProfile>, units: Units) -> String {
+        let rows: [Row; 4] = [
+            ("Energy", |p| p.total_energy as f64, Quantity::Energy),
+            ("Average power", |p| p.average_power as f64, Quantity::Power),
+            ("Peak power", |p| p.peak_power() as f64, Quantity::Power),
+            ("Duration", |p| p.total_duration as f64, Quantity::Duration),
+        ];
+        let format = |value: f64, quantity| match quantity {
+            Quantity::Energy => units.energy.format(value),
+            Quantity::Power => units.power.format(value),
+            Quantity::Duration => format!("{:.2} s", value / 1000.0),
+        };
+        let mut out = String::new();
+        match baseline {
+            Some(_) => {
+                out.push_str("| | Value | Baseline | Change |\n|---|---:|---:|---:|\n");
+            }
+            None => out.push_str("| | Value |\n|---|---:|\n"),
+        }
+        for (name, value, quantity) in rows {
+            let current = value(self);
+            let _ = write!(out, "| {name} | {} |", format(current, quantity));
+            if let Some(baseline) = baseline {
+                let before = value(baseline);
+                let change = if before == 0.0 {
+                    "n/a".to_string()
+                } else {
+                    format!("{:+.1}%", (current - before) / before * 100.0)
+                };
+                let _ = write!(out, " {} | {change} |", format(before, quantity));
+            }
+            out.push('\n');
+        }
+        let profiles = [(Some(self), "this run"), (baseline, "the baseline")];
+        for (profile, which) in profiles {
+            let Some(profile) = profile else { continue };
+            if profile.throttled_duration > 0 {
+                let _ = write!(
+                    out,
+                    "\n> **Warning:** {which} was throttled for {} ms.\n",
+                    profile.throttled_duration
+                );
+            }
+            if profile.power_source_changed {
+                let _ = write!(
+                    out,
+                    "\n> **Warning:** the power source changed during {which}.\n"
+                );
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sampler::{EnergySample, PowerProfile};
+    use crate::units::Units;
+
+    #[test]
+    fn test_markdown_report() {
+        // 2 W then 6 W on the CPU for 100 ms each
+        let profile = |scale: u128| {
+            PowerProfile::from(
+                [200, 600]
+                    .map(|cpu_energy| EnergySample {
+                        cpu_energy: cpu_energy * scale,
+                        duration: 100,
+                        ..Default::default()
+                    })
+                    .as_slice(),
+            )
+        };
+        let (after, before) = (profile(1), profile(2));
+        assert_eq!(after.peak_power(), 6000);
+
+        assert_eq!(
+            after.to_markdown(None),
+            "| | Value |\n|---|---:|\n\
+             | Energy | 0.800 J |\n\
+             | Average power | 4.000 W |\n\
+             | Peak power | 6.000 W |\n\
+             | Duration | 0.20 s |\n"
+        );
+        let report = after.to_markdown(Some(&before));
+        assert!(
+            report.starts_with("| | Value | Baseline | Change |\n"),
+            "{report}"
+        );
+        assert!(
+            report.contains("| Energy | 0.800 J | 1.600 J | -50.0% |"),
+            "{report}"
+        );
+        assert!(
+            report.contains("| Duration | 0.20 s | 0.20 s | +0.0% |"),
+            "{report}"
+        );
+        assert!(!report.contains("Warning"), "{report}");
+
+        let milli = after.to_markdown_with(Some(&before), Units::default());
+        assert!(
+            milli.contains("| Energy | 800 mJ | 1600 mJ | -50.0% |"),
+            "{milli}"
+        );
+        assert!(milli.contains("| Peak power | 6000 mW |"), "{milli}");
+    }
+}
@@ -1,117 +1,984 @@
 use oneshot::channel as oneshot_channel;
 use oneshot::Sender as OneshotSender;
 use std::{
-    sync::mpsc::{channel, Receiver},
-    thread::JoinHandle,
+    collections::BTreeMap,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
-use crate::io_report::IOReportChannelRequest;
-use crate::io_report::IOReportSimpleGetIntegerValue;
-use crate::io_report::{EnergyUnit, IOReport, IOReportChannelGroup, IOReportChannelName};
+use crate::alert::SharedAlerts;
+use crate::backend::ioreport::{ChannelSet, IOReportBackend};
+use crate::backend::smc::SystemPowerMonitor;
+use crate::backend::{
+    open_default, BackendFactory, Capabilities, ChannelInfo, DefaultBackend, PowerBackend,
+};
+use crate::cadence::{Cadence, MetricRates};
+use crate::config::{SamplerConfig, DEFAULT_CONFIG};
+use crate::cpu_load::CpuLoad;
+use crate::disk_io::DiskCounters;
+use crate::net_io::NetworkCounters;
+use crate::power_source::PowerSource;
+use crate::qos::QosClass;
+use crate::region::{Region, RegionId, Regions};
+use crate::thermal::{ThermalMonitor, ThermalPressure};
+use crate::units::Units;
+use crate::worker;
 
 #[derive(thiserror::Error, Debug)]
 pub enum SamplerError {
     #[error("IOReportError: {0}")]
     IOReportError(#[from] crate::io_report::IOReportError),
+    #[error("HwmonError: {0}")]
+    HwmonError(#[from] crate::backend::hwmon::HwmonError),
+    #[error("PowerGadgetError: {0}")]
+    PowerGadgetError(#[from] crate::backend::power_gadget::PowerGadgetError),
+    #[error("SmcError: {0}")]
+    SmcError(#[from] crate::backend::smc::SmcError),
+    #[error("PowermetricsError: {0}")]
+    PowermetricsError(#[from] crate::backend::powermetrics::PowermetricsError),
+    #[error("CaptureError: {0}")]
+    CaptureError(#[from] crate::backend::capture::CaptureError),
     #[error("No samples available")]
     SamplesNotAvailable,
     #[error("Sampling in progress")]
     SamplingInProgress,
     #[error("No sampling currently in progress")]
     NoSamplingInProgress,
+    #[error("Sampling session belongs to parent process {0}")]
+    ForkedProcess(u32),
+    #[error("Sample interval of {0:?} is below the {MIN_SAMPLE_INTERVAL:?} minimum")]
+    IntervalTooShort(Duration),
+    #[error("Session {0:?} is already open")]
+    SessionAlreadyOpen(String),
+    #[error("No open session named {0:?}")]
+    UnknownSession(String),
+}
+
+/// Shortest supported interval between samples, below it the cost of a read dominates
+/// the interval.
+pub const MIN_SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+
+impl SamplerError {
+    /// Reads of counters that went backwards are discarded rather than ending the
+    /// session, returns the milliseconds such a read covered.
+    pub(crate) fn invalid_delta_duration(&self) -> Option<u64> {
+        use crate::backend::{hwmon::HwmonError, power_gadget::PowerGadgetError};
+        use crate::io_report::IOReportError;
+        match self {
+            SamplerError::IOReportError(IOReportError::InvalidDelta { duration, .. })
+            | SamplerError::HwmonError(HwmonError::InvalidDelta { duration, .. })
+            | SamplerError::PowerGadgetError(PowerGadgetError::InvalidDelta { duration }) => {
+                Some(*duration)
+            }
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct EnergySample {
-    cpu_energy: u128,
-    gpu_energy: u128,
-    ane_energy: u128,
-    duration: u64, //milliseconds
+    pub cpu_energy: u128,
+    pub gpu_energy: u128,
+    pub ane_energy: u128,
+    /// Energy of the display and its backlight, not part of the CPU, GPU and ANE totals.
+    /// `None` on machines that don't report it.
+    pub display_energy: Option<u128>,
+    /// Energy of the whole SoC: CPU, GPU and ANE plus DRAM, display and the other
+    /// channels IOReport has. powermetrics' "Combined Power" is only CPU, GPU and ANE,
+    /// like [`PowerProfile::total_energy`]. `None` for backends without the channels.
+    pub package_energy: Option<u128>,
+    pub timestamp: u64, //milliseconds since sampling started
+    pub duration: u64,  //milliseconds
+    /// Wall clock time at the start of the sample, to line traces up with logs. Read at
+    /// every read, while `timestamp` is monotonic and unaffected by clock adjustments.
+    /// `None` for samples that didn't come from a sampler.
+    pub wall_time: Option<SystemTime>,
+    /// AC or battery when the sample was taken, `None` where it can't be read.
+    pub power_source: Option<PowerSource>,
+    /// Fraction of the host's CPU time, over all cores, spent busy during the sample, to
+    /// tell power drawn by a busy machine from power drawn at low utilization. `None`
+    /// where it can't be read.
+    pub cpu_utilization: Option<f64>,
+    /// Power of the whole machine in milliwatts, the SMC's "PSTR" sensor averaged over
+    /// the read, a cross-check for the IOReport energies. `None` where it can't be read.
+    pub system_power: Option<f64>,
+    /// Power drawn through the DC input in milliwatts, the SMC's "PDTR" sensor, zero on
+    /// battery. `None` where it can't be read.
+    pub dc_in_power: Option<f64>,
+    /// Bytes moved to and from memory during the sample, only read by IOReport backends
+    /// opened with [`IOReportBackend::with_memory_bandwidth`].
+    ///
+    /// [`IOReportBackend::with_memory_bandwidth`]: crate::backend::ioreport::IOReportBackend::with_memory_bandwidth
+    pub memory_traffic: Option<MemoryTraffic>,
+    /// The system's thermal pressure at the end of the sample, only read on macOS.
+    pub thermal_pressure: Option<ThermalPressure>,
+    /// Performance state residency of the busiest CPU cluster, only read by IOReport
+    /// backends. Together with `thermal_pressure` it tells whether the sample was
+    /// throttled, see [`EnergySample::is_throttled`].
+    pub cpu_residency: Option<CpuResidency>,
+    /// What else the host did during the sample, to relate power to.
+    pub extra: ExtraMetrics,
+    /// Energy of each die of an M1 or M2 Ultra, the unit totals are their sums. Empty on
+    /// single-die chips and other backends.
+    pub dies: Vec<DieEnergy>,
+}
+
+/// CPU, GPU and ANE energy of one die of a multi-die SoC, in millijoules.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DieEnergy {
+    pub cpu: u128,
+    pub gpu: u128,
+    pub ane: u128,
+}
+
+impl DieEnergy {
+    pub fn total(&self) -> u128 {
+        self.cpu + self.gpu + self.ane
+    }
+}
+
+/// Bytes read from and written to memory by all agents of the SoC.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryTraffic {
+    pub read: u64,
+    pub write: u64,
+}
+
+/// Bytes read from and written to all disks of the host.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiskIo {
+    pub read: u64,
+    pub write: u64,
+}
+
+/// Performance state residency of the busiest CPU cluster over a sample, read from
+/// IOReport's "CPU Complex Performance States".
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CpuResidency {
+    /// Fraction of the sample the cluster wasn't idle or powered off.
+    pub active: f64,
+    /// Where the active time was spent, from 0 for all of it in the lowest performance
+    /// state to 1 for all of it in the highest.
+    pub performance_state: f64,
+}
+
+/// Bytes received and sent over all network interfaces of the host but loopback.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NetworkIo {
+    pub rx: u64,
+    pub tx: u64,
+}
+
+/// Host activity recorded alongside each sample, every metric is `None` where it can't
+/// be read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExtraMetrics {
+    /// To line power bumps up with e.g. checkpoint writes.
+    pub disk_io: Option<DiskIo>,
+    /// To relate power to the traffic of distributed training or serving.
+    pub network_io: Option<NetworkIo>,
+}
+
+impl ExtraMetrics {
+    /// The metrics of the `part` of a span of `whole` milliseconds, assuming steady
+    /// traffic throughout.
+    pub(crate) fn share(&self, part: u64, whole: u64) -> Self {
+        let share = |bytes: u64| (bytes as u128 * part as u128 / whole.max(1) as u128) as u64;
+        ExtraMetrics {
+            disk_io: self.disk_io.map(|io| DiskIo {
+                read: share(io.read),
+                write: share(io.write),
+            }),
+            network_io: self.network_io.map(|io| NetworkIo {
+                rx: share(io.rx),
+                tx: share(io.tx),
+            }),
+        }
+    }
+}
+
+impl EnergySample {
+    /// `amount` per second of the sample, zero for a sample without duration.
+    fn per_second(&self, amount: f64) -> f64 {
+        if self.duration == 0 {
+            return 0.0;
+        }
+        amount / (self.duration as f64 / 1000.0) //mJs-1 == mW
+    }
+
+    /// Average CPU power over the sample in milliwatts.
+    pub fn cpu_power(&self) -> f64 {
+        self.per_second(self.cpu_energy as f64)
+    }
+
+    /// Average GPU power over the sample in milliwatts.
+    pub fn gpu_power(&self) -> f64 {
+        self.per_second(self.gpu_energy as f64)
+    }
+
+    /// Average ANE power over the sample in milliwatts.
+    pub fn ane_power(&self) -> f64 {
+        self.per_second(self.ane_energy as f64)
+    }
+
+    /// Average package power over the sample in milliwatts, see
+    /// [`EnergySample::package_energy`].
+    pub fn package_power(&self) -> Option<f64> {
+        Some(self.per_second(self.package_energy? as f64))
+    }
+
+    /// Memory read plus write bandwidth over the sample in bytes per second.
+    pub fn memory_bandwidth(&self) -> Option<f64> {
+        let traffic = self.memory_traffic?;
+        Some(self.per_second((traffic.read + traffic.write) as f64))
+    }
+
+    /// Whether the SoC was throttling during the sample: under heavy thermal pressure, or
+    /// under moderate pressure with a busy CPU cluster held out of its top performance
+    /// states. Without residency moderate pressure counts, macOS caps clocks from there.
+    pub fn is_throttled(&self) -> bool {
+        match self.thermal_pressure {
+            None | Some(ThermalPressure::Nominal) => false,
+            Some(ThermalPressure::Moderate) => self.cpu_residency.is_none_or(|r| {
+                r.active >= THROTTLE_BUSY && r.performance_state < THROTTLE_PERFORMANCE_STATE
+            }),
+            Some(_) => true,
+        }
+    }
+
+    /// Whether no unit draws more than [`MAX_PLAUSIBLE_POWER`].
+    pub fn is_plausible(&self) -> bool {
+        [self.cpu_power(), self.gpu_power(), self.ane_power()]
+            .into_iter()
+            .all(|p| p <= MAX_PLAUSIBLE_POWER)
+    }
+}
+
+/// Fraction of a sample a CPU cluster has to be active for its performance states to
+/// show throttling, an idle cluster sits in low states anyway.
+const THROTTLE_BUSY: f64 = 0.5;
+
+/// Performance state, see [`CpuResidency::performance_state`], below which a busy
+/// cluster under moderate thermal pressure counts as throttled.
+const THROTTLE_PERFORMANCE_STATE: f64 = 0.85;
+
+/// Milliwatts above which a unit's reading is treated as a counter glitch, samples
+/// exceeding it are discarded.
+pub const MAX_PLAUSIBLE_POWER: f64 = 2_000_000.0;
+
+/// Bounds of an adaptive session, see [`StartStopSampler::start_adaptive`]. Reads start
+/// at `min_interval`, the interval doubles while power stays within `threshold` of the
+/// previous read and halves as soon as it moves further.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveRate {
+    pub min_interval: Duration,
+    pub max_interval: Duration,
+    /// Relative change in total power, 0.1 is 10%, that counts as a burst.
+    pub threshold: f64,
+}
+
+impl Default for AdaptiveRate {
+    fn default() -> Self {
+        AdaptiveRate {
+            min_interval: Duration::from_millis(100),
+            max_interval: Duration::from_secs(2),
+            threshold: 0.1,
+        }
+    }
+}
+
+impl AdaptiveRate {
+    /// Interval of the read after one at `interval` whose total power went from
+    /// `previous` to `current` milliwatts.
+    pub fn next_interval(&self, interval: Duration, previous: f64, current: f64) -> Duration {
+        let change = (current - previous).abs() / previous.max(1.0);
+        if change > self.threshold {
+            (interval / 2).max(self.min_interval)
+        } else {
+            interval
+                .saturating_mul(2)
+                .min(self.max_interval.max(self.min_interval))
+        }
+    }
+}
+
+/// How a session paces its reads.
+#[derive(Clone, Copy, Debug)]
+enum Schedule {
+    /// `num_samples` reads of `duration / num_samples` each.
+    Fixed {
+        duration: Duration,
+        num_samples: usize,
+    },
+    Adaptive(AdaptiveRate),
+}
+
+impl Schedule {
+    /// Requested interval between samples, `None` when it varies.
+    fn interval(&self) -> Option<Duration> {
+        match self {
+            Schedule::Fixed {
+                duration,
+                num_samples,
+            } => Some(
+                duration
+                    .checked_div(*num_samples as u32)
+                    .unwrap_or_default(),
+            ),
+            Schedule::Adaptive(_) => None,
+        }
+    }
+
+    /// `(duration, num_samples)` of the first read.
+    fn first_read(&self) -> Result<(Duration, usize), SamplerError> {
+        let read = match *self {
+            Schedule::Fixed {
+                duration,
+                num_samples,
+            } => (duration, num_samples),
+            Schedule::Adaptive(rate) => (rate.min_interval, 1),
+        };
+        let interval = read.0.checked_div(read.1 as u32).unwrap_or_default();
+        if interval < MIN_SAMPLE_INTERVAL {
+            return Err(SamplerError::IntervalTooShort(interval));
+        }
+        Ok(read)
+    }
+}
+
+/// Invoked from the sampling thread for every sample as soon as it is taken.
+pub type SampleCallback = Box<dyn FnMut(&EnergySample) + Send>;
+
+/// Start of each session left out of its profile, see [`SamplerConfig::discard_warmup`].
+/// The first read often includes activity from before the workload started.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Warmup {
+    #[default]
+    None,
+    /// The first samples.
+    Samples(usize),
+    /// Everything up to this long after sampling started, a sample straddling the end of
+    /// the warmup is prorated.
+    Duration(Duration),
+}
+
+impl From<usize> for Warmup {
+    fn from(samples: usize) -> Self {
+        Warmup::Samples(samples)
+    }
+}
+
+impl From<Duration> for Warmup {
+    fn from(duration: Duration) -> Self {
+        Warmup::Duration(duration)
+    }
+}
+
+impl Warmup {
+    /// `samples` without the warmup.
+    pub fn skip(&self, samples: &[EnergySample]) -> Vec<EnergySample> {
+        match *self {
+            Warmup::None => samples.to_vec(),
+            Warmup::Samples(n) => samples.iter().skip(n).cloned().collect(),
+            Warmup::Duration(duration) => {
+                let after = Region {
+                    name: String::new(),
+                    start: duration.as_millis() as u64,
+                    end: None,
+                };
+                samples.iter().filter_map(|s| after.clip(s)).collect()
+            }
+        }
+    }
+}
+
+/// Samples left out of a profile for being far from the rest of the session, see
+/// [`SamplerConfig::reject_outliers`], e.g. spikes from a background process.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutlierFilter {
+    #[default]
+    None,
+    /// Samples whose total power is more than `threshold` median absolute deviations,
+    /// scaled to estimate a standard deviation, from the median. 3.5 is a common choice.
+    Mad { threshold: f64 },
+}
+
+impl OutlierFilter {
+    /// `samples` without the outliers. Nothing is rejected while more than half of the
+    /// samples draw the same power, the spread can't be estimated then.
+    pub fn apply(&self, samples: Vec<EnergySample>) -> Vec<EnergySample> {
+        let OutlierFilter::Mad { threshold } = *self else {
+            return samples;
+        };
+        let power = |s: &EnergySample| s.cpu_power() + s.gpu_power() + s.ane_power();
+        let median = |mut values: Vec<f64>| {
+            values.sort_by(f64::total_cmp);
+            let mid = values.len() / 2;
+            match values.len() {
+                0 => 0.0,
+                n if n % 2 == 0 => (values[mid - 1] + values[mid]) / 2.0,
+                _ => values[mid],
+            }
+        };
+        let center = median(samples.iter().map(power).collect());
+        // 1.4826 scales the MAD of normally distributed data to its standard deviation
+        let spread = 1.4826 * median(samples.iter().map(|s| (power(s) - center).abs()).collect());
+        if spread == 0.0 {
+            return samples;
+        }
+        samples
+            .into_iter()
+            .filter(|s| (power(s) - center).abs() <= threshold * spread)
+            .collect()
+    }
+}
+
+/// Finds stretches of a session spent near its baseline power, see
+/// [`SamplerConfig::detect_idle`], so long interactive sessions can report the power
+/// drawn while actually working.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IdleDetection {
+    /// Watts above the baseline, the 10th percentile of the session's total power, a
+    /// sample may draw and still count as idle.
+    pub margin_w: f64,
+    /// Shortest run of idle samples reported as an idle period.
+    pub min_duration: Duration,
+    /// Whether idle periods are left out of the profile, rather than only annotated.
+    pub exclude: bool,
+}
+
+impl Default for IdleDetection {
+    fn default() -> Self {
+        IdleDetection {
+            margin_w: 0.5,
+            min_duration: Duration::from_secs(5),
+            exclude: false,
+        }
+    }
+}
+
+/// A run of samples near the baseline power, see [`IdleDetection`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdlePeriod {
+    /// Milliseconds since sampling started.
+    pub start: u64,
+    /// Milliseconds.
+    pub duration: u64,
+}
+
+impl IdlePeriod {
+    fn contains(&self, sample: &EnergySample) -> bool {
+        sample.timestamp >= self.start
+            && sample.timestamp + sample.duration <= self.start + self.duration
+    }
+}
+
+impl IdleDetection {
+    /// Idle periods of `samples`, in order.
+    pub fn periods(&self, samples: &[EnergySample]) -> Vec<IdlePeriod> {
+        let power = |s: &EnergySample| s.cpu_power() + s.gpu_power() + s.ane_power();
+        let mut powers: Vec<f64> = samples.iter().map(power).collect();
+        powers.sort_by(f64::total_cmp);
+        let Some(baseline) = powers.get(powers.len() / 10) else {
+            return Vec::new();
+        };
+        let threshold = baseline + self.margin_w * 1000.0;
+        let min_duration = self.min_duration.as_millis() as u64;
+
+        let mut periods = Vec::new();
+        let mut run: Option<IdlePeriod> = None;
+        for sample in samples {
+            if power(sample) <= threshold {
+                let end = sample.timestamp + sample.duration;
+                let period = run.get_or_insert(IdlePeriod {
+                    start: sample.timestamp,
+                    duration: 0,
+                });
+                period.duration = end - period.start;
+            } else if let Some(period) = run.take() {
+                periods.push(period);
+            }
+        }
+        periods.extend(run);
+        periods.retain(|p| p.duration >= min_duration);
+        periods
+    }
+}
+
+/// Profile of the samples of a session that make it into it.
+fn profile_of(
+    samples: &[EnergySample],
+    warmup: Warmup,
+    outliers: OutlierFilter,
+    idle: Option<IdleDetection>,
+) -> PowerProfile {
+    let samples = warmup.skip(samples);
+    let Some(idle) = idle else {
+        return PowerProfile::from(outliers.apply(samples));
+    };
+    let periods = idle.periods(&samples);
+    let active: Vec<EnergySample> = samples
+        .iter()
+        .filter(|s| !periods.iter().any(|p| p.contains(s)))
+        .cloned()
+        .collect();
+    let average_active_power = PowerProfile::from(outliers.apply(active.clone())).average_power;
+    let mut profile =
+        PowerProfile::from(outliers.apply(if idle.exclude { active } else { samples }));
+    profile.idle_duration = periods.iter().map(|p| p.duration).sum();
+    profile.idle_periods = periods;
+    profile.average_active_power = Some(average_active_power);
+    profile
+}
+
+/// Key/value pairs describing a session, e.g. git sha, model name or batch size.
+pub type Metadata = BTreeMap<String, String>;
+
+/// A stretch of a session the machine spent asleep. Reads spanning it are discarded
+/// rather than reported as one long sample.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Gap {
+    /// Milliseconds since sampling started, on the same clock as sample timestamps.
+    pub start: u64,
+    /// Milliseconds the machine was away.
+    pub duration: u64,
+}
+
+/// Unaccounted time beyond which a read is assumed to span a sleep.
+const SLEEP_THRESHOLD: Duration = Duration::from_secs(10);
+
+/// How long the machine was away during a read that took `awake` on the monotonic
+/// clock, `wall` on the system clock, when `requested` was asked for. The monotonic
+/// clock stops during sleep on macOS and Linux, on Windows the read just overruns.
+fn time_away(awake: Duration, wall: Duration, requested: Duration) -> Option<Duration> {
+    let away = wall
+        .saturating_sub(awake)
+        .max(awake.saturating_sub(requested));
+    (away > SLEEP_THRESHOLD).then_some(away)
+}
+
+/// Something that happened during a session, see [`StartStopSampler::events`].
+// samples are by far the most common event, boxing them would allocate for each
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug)]
+pub enum SamplerEvent {
+    /// A sample was taken and passed the plausibility check.
+    SampleReady(EnergySample),
+    /// A read failed or was implausible and got discarded. A `fatal` error ends the
+    /// session with the samples taken so far.
+    ChannelError { message: String, fatal: bool },
+    /// The machine slept, the backend was reopened after it.
+    SleepDetected(Gap),
+    /// The system's thermal pressure changed, only reported on macOS.
+    ThermalChange(ThermalPressure),
+    /// The session ended, the last event of every session.
+    Stopped,
+}
+
+/// Event streams handed out by a sampler, shared with its sampling threads.
+type SharedEvents = Arc<Mutex<Vec<Sender<SamplerEvent>>>>;
+
+/// Sends `event` to every stream, dropping the streams nobody receives from anymore.
+fn send_event(events: &SharedEvents, event: SamplerEvent) {
+    let mut senders = events.lock().unwrap_or_else(|e| e.into_inner());
+    senders.retain(|sender| sender.send(event.clone()).is_ok());
+}
+
+// like `SamplerEvent`, boxing samples would allocate for each
+#[allow(clippy::large_enum_variant)]
+enum SessionEvent {
+    Sample(EnergySample),
+    Gap(Gap),
+    Discarded,
+    Failed(SamplerError),
+}
+
+/// Everything a finished session produced.
+#[derive(Debug, Default)]
+struct Session {
+    samples: Vec<EnergySample>,
+    gaps: Vec<Gap>,
+    discarded: usize,
+    /// The error that ended the session early, if any.
+    error: Option<SamplerError>,
+}
+
+/// Events of a running session, received up to the last poll.
+#[derive(Debug)]
+struct Inbox {
+    receiver: Receiver<SessionEvent>,
+    session: Session,
 }
 
+impl Inbox {
+    fn record(&mut self, event: SessionEvent) {
+        match event {
+            SessionEvent::Sample(sample) => self.session.samples.push(sample),
+            SessionEvent::Gap(gap) => self.session.gaps.push(gap),
+            SessionEvent::Discarded => self.session.discarded += 1,
+            SessionEvent::Failed(e) => self.session.error = Some(e),
+        }
+    }
+
+    /// Records whatever the sampling thread has sent so far, without waiting.
+    fn poll(&mut self) {
+        while let Ok(event) = self.receiver.try_recv() {
+            self.record(event);
+        }
+    }
+
+    /// Records events until `done` holds for the session or the sampling thread ends.
+    fn wait_until(&mut self, done: impl Fn(&Session) -> bool) {
+        self.poll();
+        while !done(&self.session) {
+            match self.receiver.recv() {
+                Ok(event) => self.record(event),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// Owns the session on its sampling thread, see [`worker`]. The backend is opened on and
+/// never leaves that thread,
+/// so backends need not be `Send` while the samplers holding a manager are `Send + Sync`.
 #[derive(Debug)]
 struct SampleManager {
-    cancel_sender: OneshotSender<()>,
-    sample_receiver: Receiver<EnergySample>,
-    thread_handle: JoinHandle<()>,
+    // only used by `stop(self)`, the mutex just makes the channel end `Sync`
+    cancel_sender: Mutex<OneshotSender<()>>,
+    inbox: Mutex<Inbox>,
+    thread_handle: worker::Completion,
+    pid: u32,
 }
 
+/// What a backend reported about itself once opened.
+type BackendInfo = (Vec<ChannelInfo>, Capabilities);
+
 impl SampleManager {
-    fn new(duration: u64, num_samples: usize) -> Self {
+    fn new<B: PowerBackend + 'static>(
+        factory: BackendFactory<B>,
+        schedule: Schedule,
+        mut callback: Option<SampleCallback>,
+        alerts: SharedAlerts,
+        events: SharedEvents,
+        qos: Option<QosClass>,
+        rates: MetricRates,
+    ) -> Result<(Self, BackendInfo), SamplerError> {
+        let (mut duration, num_samples) = schedule.first_read()?;
+        alerts.lock().unwrap_or_else(|e| e.into_inner()).reset();
+        debug!("starting session, {schedule:?}");
+
         let (cancel_tx, cancel_rx) = oneshot_channel();
         let (sample_tx, sample_rx) = channel();
+        let (ready_tx, ready_rx) = channel();
 
-        let handle = std::thread::spawn(move || {
-            let requests = vec![IOReportChannelRequest::new(
-                IOReportChannelGroup::EnergyModel,
-                None as Option<IOReportChannelName>,
-            )];
-            let mut report = IOReport::new(requests).unwrap();
+        let mut session = move || {
+            let mut backend = match factory() {
+                Ok(backend) => {
+                    let _ = ready_tx.send(Ok((backend.channels(), backend.capabilities())));
+                    backend
+                }
+                Err(e) => {
+                    warn!("failed to open backend: {e}");
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+            let mut elapsed = 0;
+            // total power of the last sample, adaptive sessions pace themselves by it
+            let mut last_power = None;
+            let mut thermal = ThermalMonitor::new();
+            let mut cpu_load = CpuLoad::new();
+            let mut disk = DiskCounters::new();
+            let mut network = NetworkCounters::new();
+            let mut system_power = SystemPowerMonitor::new();
+            // slow groups keep their last value between reads
+            let mut thermal_cadence = Cadence::new(rates.thermal);
+            let mut cpu_load_cadence = Cadence::new(rates.cpu_load);
+            let mut io_cadence = Cadence::new(rates.io);
+            let mut system_power_cadence = Cadence::new(rates.system_power);
+            let mut power_source_cadence = Cadence::new(rates.power_source);
+            let (mut cpu_utilization, mut system, mut dc_in, mut power_source) =
+                (None, None, None, None);
 
-            loop {
+            'sampling: loop {
                 if cancel_rx.try_recv().is_ok() {
                     break;
                 }
 
-                let samples = report.get_samples(duration, num_samples);
-                for mut sample in samples {
-                    let duration = sample.duration();
-                    let mut energy_sample = EnergySample {
-                        duration,
-                        ..Default::default()
-                    };
-
-                    for entry in sample.iterator_mut() {
-                        match entry.group {
-                            IOReportChannelGroup::EnergyModel => {
-                                let u = EnergyUnit::from(entry.unit);
-                                let raw_joules = unsafe {
-                                    IOReportSimpleGetIntegerValue(entry.item, std::ptr::null_mut())
-                                } as u128;
-                                let milli_joules = match u {
-                                    EnergyUnit::NanoJoules => raw_joules / 1_000_000,
-                                    EnergyUnit::MicroJoules => raw_joules / 1_000,
-                                    EnergyUnit::MilliJoules => raw_joules,
-                                };
-
-                                match entry.channel_name {
-                                    IOReportChannelName::CPUEnergy => {
-                                        energy_sample.cpu_energy += milli_joules
-                                    }
-                                    IOReportChannelName::GPUEnergy => {
-                                        energy_sample.gpu_energy += milli_joules
-                                    }
-                                    IOReportChannelName::ANE => {
-                                        energy_sample.ane_energy += milli_joules
-                                    }
-                                    _ => {}
-                                };
+                let (awake, read_wall) = (Instant::now(), SystemTime::now());
+                // a failing read ends the session with the samples taken so far
+                let read = backend.get_samples(duration, num_samples);
+                if cpu_load_cadence.due(elapsed) {
+                    cpu_utilization = cpu_load.poll();
+                }
+                let extra = if io_cadence.due(elapsed) {
+                    ExtraMetrics {
+                        disk_io: disk.poll(),
+                        network_io: network.poll(),
+                    }
+                } else {
+                    ExtraMetrics::default()
+                };
+                if system_power_cadence.due(elapsed) {
+                    (system, dc_in) = system_power.poll();
+                }
+                let samples = match read {
+                    Ok(samples) => samples,
+                    Err(e) => match e.invalid_delta_duration() {
+                        Some(discarded) => {
+                            debug!("discarded {discarded} ms read: {e}");
+                            send_event(
+                                &events,
+                                SamplerEvent::ChannelError {
+                                    message: e.to_string(),
+                                    fatal: false,
+                                },
+                            );
+                            elapsed += discarded;
+                            if sample_tx.send(SessionEvent::Discarded).is_err() {
+                                break;
                             }
-                            _ => continue,
+                            continue;
+                        }
+                        None => {
+                            warn!("read failed, ending session: {e}");
+                            send_event(
+                                &events,
+                                SamplerEvent::ChannelError {
+                                    message: e.to_string(),
+                                    fatal: true,
+                                },
+                            );
+                            let _ = sample_tx.send(SessionEvent::Failed(e));
+                            break;
                         }
+                    },
+                };
+
+                if thermal_cadence.due(elapsed) {
+                    if let Some(pressure) = thermal.poll() {
+                        send_event(&events, SamplerEvent::ThermalChange(pressure));
                     }
-                    if sample_tx.send(energy_sample).is_err() {
+                }
+
+                let awake = awake.elapsed();
+                let wall = read_wall.elapsed().unwrap_or_default();
+                if let Some(away) = time_away(awake, wall, duration) {
+                    let gap = Gap {
+                        start: elapsed,
+                        duration: away.as_millis() as u64,
+                    };
+                    elapsed += awake.as_millis() as u64;
+                    info!("machine slept for {} ms, reopening backend", gap.duration);
+                    send_event(&events, SamplerEvent::SleepDetected(gap.clone()));
+                    if sample_tx.send(SessionEvent::Gap(gap)).is_err() {
                         break;
                     }
+                    // counters and subscriptions need not survive sleep, start over
+                    backend = match factory() {
+                        Ok(backend) => backend,
+                        Err(e) => {
+                            warn!("failed to reopen backend after sleep: {e}");
+                            send_event(
+                                &events,
+                                SamplerEvent::ChannelError {
+                                    message: e.to_string(),
+                                    fatal: true,
+                                },
+                            );
+                            let _ = sample_tx.send(SessionEvent::Failed(e));
+                            break;
+                        }
+                    };
+                    continue;
+                }
+
+                if power_source_cadence.due(elapsed) {
+                    power_source = PowerSource::current();
+                }
+                let read_duration = samples.iter().map(|s| s.duration).sum();
+                let read_start = elapsed;
+                for mut energy_sample in samples {
+                    energy_sample.timestamp = elapsed;
+                    energy_sample.wall_time =
+                        Some(read_wall + Duration::from_millis(elapsed - read_start));
+                    energy_sample.power_source = power_source;
+                    energy_sample.cpu_utilization = cpu_utilization;
+                    energy_sample.system_power = system;
+                    energy_sample.dc_in_power = dc_in;
+                    energy_sample.thermal_pressure = thermal.current();
+                    // the counters cover the whole read, each sample gets its share
+                    energy_sample.extra = extra.share(energy_sample.duration, read_duration);
+                    elapsed += energy_sample.duration;
+
+                    if !energy_sample.is_plausible() {
+                        debug!("discarded implausible sample {energy_sample:?}");
+                        send_event(
+                            &events,
+                            SamplerEvent::ChannelError {
+                                message: format!("implausible sample {energy_sample:?}"),
+                                fatal: false,
+                            },
+                        );
+                        if sample_tx.send(SessionEvent::Discarded).is_err() {
+                            break 'sampling;
+                        }
+                        continue;
+                    }
+                    if let Schedule::Adaptive(rate) = schedule {
+                        let power = energy_sample.cpu_power()
+                            + energy_sample.gpu_power()
+                            + energy_sample.ane_power();
+                        if let Some(previous) = last_power {
+                            duration = rate.next_interval(duration, previous, power);
+                        }
+                        last_power = Some(power);
+                    }
+                    alerts
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .check(&energy_sample);
+                    if let Some(callback) = callback.as_mut() {
+                        callback(&energy_sample);
+                    }
+                    send_event(&events, SamplerEvent::SampleReady(energy_sample.clone()));
+                    if sample_tx.send(SessionEvent::Sample(energy_sample)).is_err() {
+                        break 'sampling;
+                    }
                 }
             }
+            send_event(&events, SamplerEvent::Stopped);
+        };
+        let handle = worker::run(move || {
+            if let Some(qos) = qos {
+                qos.apply();
+            }
+            session();
+            // the thread outlives the session
+            if qos.is_some() {
+                QosClass::Default.apply();
+            }
         });
 
-        SampleManager {
-            cancel_sender: cancel_tx,
-            sample_receiver: sample_rx,
+        let info = match ready_rx.recv() {
+            Ok(Ok(info)) => info,
+            Ok(Err(e)) => {
+                handle.join();
+                return Err(e);
+            }
+            // the factory panicked
+            Err(_) => {
+                handle.join();
+                return Err(SamplerError::SamplesNotAvailable);
+            }
+        };
+
+        let manager = SampleManager {
+            cancel_sender: Mutex::new(cancel_tx),
+            inbox: Mutex::new(Inbox {
+                receiver: sample_rx,
+                session: Session {
+                    samples: Vec::with_capacity(128),
+                    ..Default::default()
+                },
+            }),
             thread_handle: handle,
-        }
+            pid: std::process::id(),
+        };
+        Ok((manager, info))
+    }
+
+    /// The sampling thread only exists in the process that spawned it, a forked
+    /// child must neither signal nor join it.
+    fn is_owned(&self) -> bool {
+        self.pid == std::process::id()
+    }
+
+    /// Runs `f` on the session as received once `done` holds for it, the session keeps
+    /// running. Waits for the sampling thread until then, or until it ends.
+    fn peek_when<T>(&self, done: impl Fn(&Session) -> bool, f: impl FnOnce(&Session) -> T) -> T {
+        let mut inbox = self.inbox.lock().unwrap_or_else(|e| e.into_inner());
+        inbox.wait_until(done);
+        f(&inbox.session)
     }
 
-    fn stop(self) -> Vec<EnergySample> {
-        let _ = self.cancel_sender.send(());
-        let mut samples = Vec::with_capacity(128);
-        while let Ok(sample) = self.sample_receiver.recv() {
-            samples.push(sample);
+    fn stop(self) -> Session {
+        let cancel_sender = self
+            .cancel_sender
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner());
+        let mut inbox = self.inbox.into_inner().unwrap_or_else(|e| e.into_inner());
+        let _ = cancel_sender.send(());
+        while let Ok(event) = inbox.receiver.recv() {
+            inbox.record(event);
         }
-        let _ = self.thread_handle.join();
-        samples
+        self.thread_handle.join();
+        debug!(
+            "stopped session, {} samples, {} discarded, {} gaps",
+            inbox.session.samples.len(),
+            inbox.session.discarded,
+            inbox.session.gaps.len()
+        );
+        inbox.session
+    }
+}
+
+/// Requested vs achieved interval between samples of a session in milliseconds, like
+/// [`EnergySample::duration`].
+/// Reads take time of their own, so achieved intervals run long, most visibly
+/// near [`MIN_SAMPLE_INTERVAL`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntervalAccuracy {
+    pub requested: u64,
+    pub mean: f64,
+    pub min: u64,
+    pub max: u64,
+    /// Mean deviation from `requested` relative to it, 0.05 is 5% off on average.
+    pub relative_error: f64,
+}
+
+impl IntervalAccuracy {
+    /// `None` without samples.
+    pub fn new(requested: Duration, samples: &[EnergySample]) -> Option<Self> {
+        let requested = requested.as_millis() as u64;
+        let durations = samples.iter().map(|s| s.duration);
+        let n = samples.len() as f64;
+        let deviation: f64 = durations
+            .clone()
+            .map(|d| (d as f64 - requested as f64).abs())
+            .sum();
+        Some(IntervalAccuracy {
+            requested,
+            mean: durations.clone().sum::<u64>() as f64 / n,
+            min: durations.clone().min()?,
+            max: durations.max()?,
+            relative_error: deviation / n / requested.max(1) as f64,
+        })
+    }
+}
+
+impl std::fmt::Display for IntervalAccuracy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Requested: {} ms, achieved: {:.1} ms mean ({}-{} ms), {:.1}% off",
+            self.requested,
+            self.mean,
+            self.min,
+            self.max,
+            self.relative_error * 100.0
+        )
+    }
+}
+
+fn with_capabilities(profile: PowerProfile, capabilities: Option<Capabilities>) -> PowerProfile {
+    match capabilities {
+        Some(capabilities) => profile.with_capabilities(capabilities),
+        None => profile,
     }
 }
 
@@ -122,14 +989,73 @@ pub trait Sampling {
 
     fn end_time(&self) -> Option<std::time::Instant>;
 
+    fn regions(&self) -> &[Region] {
+        &[]
+    }
+
+    /// The backend error that ended the last session before it was stopped.
+    fn error(&self) -> Option<&SamplerError> {
+        None
+    }
+
+    /// Periods of the last session the machine slept through.
+    fn gaps(&self) -> &[Gap] {
+        &[]
+    }
+
+    /// Requested sample interval of the last session, `None` for adaptive sessions.
+    fn interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// How closely the last session kept to its requested interval.
+    fn interval_accuracy(&self) -> Option<IntervalAccuracy> {
+        IntervalAccuracy::new(self.interval()?, self.samples()?)
+    }
+
+    /// Reads of the last session dropped for counters going backwards or
+    /// implausible power, see [`EnergySample::is_plausible`].
+    fn discarded_samples(&self) -> usize {
+        0
+    }
+
+    /// Raw channels of the backend used by the last session.
+    fn channels(&self) -> &[ChannelInfo] {
+        &[]
+    }
+
+    /// What the backend used by the last session can measure, `None` before any session.
+    fn capabilities(&self) -> Option<Capabilities> {
+        None
+    }
+
+    /// Options the profiles of this sampler are made with.
+    fn config(&self) -> &SamplerConfig {
+        &DEFAULT_CONFIG
+    }
+
+    /// Profile of the last finished session, [`SamplerError::SamplesNotAvailable`] before
+    /// the first one has been stopped and while the next one is running.
     fn profile(&self) -> Result<PowerProfile, SamplerError> {
         if let Some(samples) = self.samples() {
-            Ok(PowerProfile::from(samples))
+            let config = self.config();
+            let profile = profile_of(samples, config.warmup, config.outliers, config.idle)
+                .with_metadata(config.metadata.clone());
+            Ok(with_capabilities(profile, self.capabilities()))
         } else {
             Err(SamplerError::SamplesNotAvailable)
         }
     }
 
+    /// Profile of every region called `name`, combined, time they overlap counts once.
+    fn region_profile(&self, name: &str) -> Result<PowerProfile, SamplerError> {
+        let samples = self.samples().ok_or(SamplerError::SamplesNotAvailable)?;
+        let regions = self.regions().iter().filter(|r| r.name == name);
+        let profile = PowerProfile::for_regions(samples, regions)
+            .with_metadata(self.config().metadata.clone());
+        Ok(with_capabilities(profile, self.capabilities()))
+    }
+
     fn duration(&self) -> Option<u64> {
         if let (Some(start), Some(end)) = (self.start_time(), self.end_time()) {
             Some(end.duration_since(start).as_secs())
@@ -142,62 +1068,278 @@ pub trait Sampling {
 /// # Sampler
 ///
 /// The `Sampler` struct is used to sample the power consumption of the device.
-/// When sampling begins, the `Sampler` will `subscribe` to the underlying IOReport
-/// C API, and will begin to receive power samples.
+/// When sampling begins, the `Sampler` will `subscribe` to its [`PowerBackend`],
+/// IOReport on macOS by default, and will begin to receive power samples.
 ///
 /// These values are placed onto a queue, which can then be accessed by the user.
 ///
 /// ## Example
-/// ```rust
-/// use wattkit::*;
+/// ```no_run
+/// use std::time::Duration;
+/// use wattkit::prelude::*;
 ///
-/// let sampler = Sampler::new();
+/// let mut sampler = Sampler::new();
 /// {
 ///     // Start sampling
-///     let guard = sampler.subscribe(1000); //sample every 1000ms
+///     let _guard = sampler.subscribe(Duration::from_secs(1), 1)?; //sample every second
 ///
 ///     // Do some work
 ///     for x in 0..1000000 {
-///         let y = x * x;
+///         let _y = x * x;
 ///     }
 /// }
-/// let profile = sampler.power_profile();
-#[derive(Debug, Default)]
-pub struct GuardSampler {
+/// let profile = sampler.profile()?;
+/// # Ok::<(), SamplerError>(())
+/// ```
+pub struct GuardSampler<B = DefaultBackend> {
+    factory: BackendFactory<B>,
     start_time: Option<std::time::Instant>,
     end_time: Option<std::time::Instant>,
     samples: Option<Vec<EnergySample>>,
+    gaps: Vec<Gap>,
+    discarded: usize,
+    interval: Option<Duration>,
+    error: Option<SamplerError>,
+    regions: Regions,
+    channels: Vec<ChannelInfo>,
+    capabilities: Option<Capabilities>,
+    alerts: SharedAlerts,
+    events: SharedEvents,
+    config: SamplerConfig,
+}
+
+impl<B> std::fmt::Debug for GuardSampler<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GuardSampler")
+            .field("start_time", &self.start_time)
+            .field("end_time", &self.end_time)
+            .field("samples", &self.samples)
+            .field("gaps", &self.gaps)
+            .field("discarded", &self.discarded)
+            .field("interval", &self.interval)
+            .field("error", &self.error)
+            .field("regions", &self.regions)
+            .field("channels", &self.channels)
+            .field("capabilities", &self.capabilities)
+            .field("alerts", &self.alerts)
+            .field("events", &self.events)
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
 }
 
-pub struct SamplerGuard<'a> {
-    sampler: &'a mut GuardSampler,
+fn millis_since(start: Option<std::time::Instant>) -> u64 {
+    start.map_or(0, |start| start.elapsed().as_millis() as u64)
+}
+
+pub struct SamplerGuard<'a, B = DefaultBackend> {
+    sampler: &'a mut GuardSampler<B>,
     manager: Option<SampleManager>,
 }
 
-impl<'a> Drop for SamplerGuard<'a> {
+impl<B> Drop for SamplerGuard<'_, B> {
     fn drop(&mut self) {
         if let Some(manager) = self.manager.take() {
+            if !manager.is_owned() {
+                std::mem::forget(manager);
+                return;
+            }
             self.sampler.end_time = Some(std::time::Instant::now());
-            self.sampler.samples = Some(manager.stop());
+            let session = manager.stop();
+            self.sampler.samples = Some(session.samples);
+            self.sampler.gaps = session.gaps;
+            self.sampler.discarded = session.discarded;
+            self.sampler.error = session.error;
         }
     }
 }
 
+impl<B> SamplerGuard<'_, B> {
+    /// Marks the start of a named region, regions may nest and overlap.
+    pub fn begin_region(&mut self, name: &str) -> RegionId {
+        let now = millis_since(self.sampler.start_time);
+        self.sampler.regions.begin(name, now)
+    }
+
+    pub fn end_region(&mut self, id: RegionId) {
+        let now = millis_since(self.sampler.start_time);
+        self.sampler.regions.end(id, now);
+    }
+}
+
 impl GuardSampler {
     pub fn new() -> Self {
-        GuardSampler::default()
+        GuardSampler::with_backend(open_default)
+    }
+}
+
+impl GuardSampler<IOReportBackend> {
+    /// Samples IOReport subscribed to `channels` only, see [`ChannelSet`].
+    pub fn with_channels(channels: ChannelSet) -> Self {
+        GuardSampler::with_backend(move || Ok(IOReportBackend::with_channels(channels)?))
+    }
+}
+
+impl Default for GuardSampler {
+    fn default() -> Self {
+        GuardSampler::new()
+    }
+}
+
+impl<B: PowerBackend + 'static> GuardSampler<B> {
+    /// Samples a backend opened by `factory` each time a session starts.
+    pub fn with_backend<F>(factory: F) -> Self
+    where
+        F: Fn() -> Result<B, SamplerError> + Send + Sync + 'static,
+    {
+        GuardSampler {
+            factory: std::sync::Arc::new(factory),
+            start_time: None,
+            end_time: None,
+            samples: None,
+            gaps: Vec::new(),
+            discarded: 0,
+            interval: None,
+            error: None,
+            regions: Regions::default(),
+            channels: Vec::new(),
+            capabilities: None,
+            alerts: SharedAlerts::default(),
+            events: SharedEvents::default(),
+            config: SamplerConfig::default(),
+        }
+    }
+
+    /// Samples every `duration / num_samples` until the guard is dropped.
+    pub fn subscribe(
+        &mut self,
+        duration: Duration,
+        num_samples: usize,
+    ) -> Result<SamplerGuard<'_, B>, SamplerError> {
+        self.subscribe_inner(
+            Schedule::Fixed {
+                duration,
+                num_samples,
+            },
+            None,
+        )
+    }
+
+    /// Like [`GuardSampler::subscribe`], but `callback` receives each sample as it arrives.
+    pub fn subscribe_with_callback(
+        &mut self,
+        duration: Duration,
+        num_samples: usize,
+        callback: SampleCallback,
+    ) -> Result<SamplerGuard<'_, B>, SamplerError> {
+        self.subscribe_inner(
+            Schedule::Fixed {
+                duration,
+                num_samples,
+            },
+            Some(callback),
+        )
+    }
+
+    /// Calls `callback` from the sampling thread with the sample at which total power
+    /// rises above `watts`, again each time it rises above after falling back.
+    pub fn on_power_above(&mut self, watts: f64, callback: SampleCallback) {
+        self.alerts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .power_above(watts, callback);
     }
 
-    pub fn subscribe(&mut self, duration: u64, num_samples: usize) -> SamplerGuard {
+    /// Calls `callback` from the sampling thread, once per session, with the sample at
+    /// which the session's total energy exceeds `joules`.
+    pub fn on_energy_budget_exceeded(&mut self, joules: f64, callback: SampleCallback) {
+        self.alerts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .energy_budget(joules, callback);
+    }
+
+    /// Stream of the events of sessions from now on, the samples along with errors and
+    /// lifecycle changes. Every session ends with [`SamplerEvent::Stopped`], the stream
+    /// ends once the sampler is dropped.
+    pub fn events(&mut self) -> Receiver<SamplerEvent> {
+        let (sender, receiver) = channel();
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(sender);
+        receiver
+    }
+
+    /// Replaces the options of later sessions, see [`SamplerConfig`].
+    pub fn with_config(mut self, config: SamplerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Options of later sessions, e.g. `config_mut().warmup = Warmup::Samples(1)`.
+    pub fn config_mut(&mut self) -> &mut SamplerConfig {
+        &mut self.config
+    }
+
+    /// [`GuardSampler::subscribe`] with `duration` in milliseconds.
+    #[deprecated(note = "pass a `Duration` to `subscribe`")]
+    pub fn subscribe_millis(
+        &mut self,
+        duration: u64,
+        num_samples: usize,
+    ) -> Result<SamplerGuard<'_, B>, SamplerError> {
+        self.subscribe(Duration::from_millis(duration), num_samples)
+    }
+
+    /// Like [`GuardSampler::subscribe`], but the interval follows power within `rate`.
+    pub fn subscribe_adaptive(
+        &mut self,
+        rate: AdaptiveRate,
+    ) -> Result<SamplerGuard<'_, B>, SamplerError> {
+        self.subscribe_inner(Schedule::Adaptive(rate), None)
+    }
+
+    /// Like [`GuardSampler::subscribe_adaptive`], but `callback` receives each sample as it arrives.
+    pub fn subscribe_adaptive_with_callback(
+        &mut self,
+        rate: AdaptiveRate,
+        callback: SampleCallback,
+    ) -> Result<SamplerGuard<'_, B>, SamplerError> {
+        self.subscribe_inner(Schedule::Adaptive(rate), Some(callback))
+    }
+
+    fn subscribe_inner(
+        &mut self,
+        schedule: Schedule,
+        callback: Option<SampleCallback>,
+    ) -> Result<SamplerGuard<'_, B>, SamplerError> {
+        let (manager, (channels, capabilities)) = SampleManager::new(
+            self.factory.clone(),
+            schedule,
+            callback,
+            self.alerts.clone(),
+            self.events.clone(),
+            self.config.qos,
+            self.config.metric_rates,
+        )?;
+        self.channels = channels;
+        self.capabilities = Some(capabilities);
         self.start_time = Some(std::time::Instant::now());
-        SamplerGuard {
+        self.samples = None;
+        self.regions.clear();
+        self.gaps.clear();
+        self.discarded = 0;
+        self.interval = schedule.interval();
+        self.error = None;
+        Ok(SamplerGuard {
             sampler: self,
-            manager: Some(SampleManager::new(duration, num_samples)),
-        }
+            manager: Some(manager),
+        })
     }
 }
 
-impl Sampling for GuardSampler {
+impl<B> Sampling for GuardSampler<B> {
     fn samples(&self) -> Option<&Vec<EnergySample>> {
         self.samples.as_ref()
     }
@@ -209,49 +1351,356 @@ impl Sampling for GuardSampler {
     fn end_time(&self) -> Option<std::time::Instant> {
         self.end_time
     }
+
+    fn regions(&self) -> &[Region] {
+        self.regions.as_slice()
+    }
+
+    fn error(&self) -> Option<&SamplerError> {
+        self.error.as_ref()
+    }
+
+    fn gaps(&self) -> &[Gap] {
+        &self.gaps
+    }
+
+    fn discarded_samples(&self) -> usize {
+        self.discarded
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    fn channels(&self) -> &[ChannelInfo] {
+        &self.channels
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.capabilities
+    }
+
+    fn config(&self) -> &SamplerConfig {
+        &self.config
+    }
 }
 
 /// # StartStopSampler
 ///
 /// Exclusively for use with pyo3, use `Sampler` from Rust instead.
-#[derive(Debug, Default)]
-pub struct StartStopSampler {
+pub struct StartStopSampler<B = DefaultBackend> {
+    factory: BackendFactory<B>,
     samples: Option<Vec<EnergySample>>,
+    gaps: Vec<Gap>,
+    discarded: usize,
+    interval: Option<Duration>,
+    error: Option<SamplerError>,
     manager: Option<SampleManager>,
     start_time: Option<std::time::Instant>,
     end_time: Option<std::time::Instant>,
+    regions: Regions,
+    channels: Vec<ChannelInfo>,
+    capabilities: Option<Capabilities>,
+    alerts: SharedAlerts,
+    events: SharedEvents,
+    config: SamplerConfig,
+}
+
+impl<B> std::fmt::Debug for StartStopSampler<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StartStopSampler")
+            .field("samples", &self.samples)
+            .field("gaps", &self.gaps)
+            .field("discarded", &self.discarded)
+            .field("interval", &self.interval)
+            .field("error", &self.error)
+            .field("manager", &self.manager)
+            .field("start_time", &self.start_time)
+            .field("end_time", &self.end_time)
+            .field("regions", &self.regions)
+            .field("channels", &self.channels)
+            .field("capabilities", &self.capabilities)
+            .field("alerts", &self.alerts)
+            .field("events", &self.events)
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
 }
 
 impl StartStopSampler {
     pub fn new() -> Self {
-        StartStopSampler::default()
+        StartStopSampler::with_backend(open_default)
+    }
+}
+
+impl StartStopSampler<IOReportBackend> {
+    /// Samples IOReport subscribed to `channels` only, see [`ChannelSet`].
+    pub fn with_channels(channels: ChannelSet) -> Self {
+        StartStopSampler::with_backend(move || Ok(IOReportBackend::with_channels(channels)?))
+    }
+}
+
+impl Default for StartStopSampler {
+    fn default() -> Self {
+        StartStopSampler::new()
     }
+}
 
-    pub fn start(&mut self, duration: u64, num_samples: usize) -> Result<(), &'static str> {
+impl<B: PowerBackend + 'static> StartStopSampler<B> {
+    /// Samples a backend opened by `factory` each time a session starts.
+    pub fn with_backend<F>(factory: F) -> Self
+    where
+        F: Fn() -> Result<B, SamplerError> + Send + Sync + 'static,
+    {
+        StartStopSampler {
+            factory: std::sync::Arc::new(factory),
+            samples: None,
+            gaps: Vec::new(),
+            discarded: 0,
+            interval: None,
+            error: None,
+            manager: None,
+            start_time: None,
+            end_time: None,
+            regions: Regions::default(),
+            channels: Vec::new(),
+            capabilities: None,
+            alerts: SharedAlerts::default(),
+            events: SharedEvents::default(),
+            config: SamplerConfig::default(),
+        }
+    }
+
+    /// Samples every `duration / num_samples` until [`StartStopSampler::stop`].
+    pub fn start(&mut self, duration: Duration, num_samples: usize) -> Result<(), SamplerError> {
+        self.start_inner(
+            Schedule::Fixed {
+                duration,
+                num_samples,
+            },
+            None,
+        )
+    }
+
+    /// Like [`StartStopSampler::start`], but `callback` receives each sample as it arrives.
+    pub fn start_with_callback(
+        &mut self,
+        duration: Duration,
+        num_samples: usize,
+        callback: SampleCallback,
+    ) -> Result<(), SamplerError> {
+        self.start_inner(
+            Schedule::Fixed {
+                duration,
+                num_samples,
+            },
+            Some(callback),
+        )
+    }
+
+    /// [`StartStopSampler::start`] with `duration` in milliseconds.
+    #[deprecated(note = "pass a `Duration` to `start`")]
+    pub fn start_millis(&mut self, duration: u64, num_samples: usize) -> Result<(), SamplerError> {
+        self.start(Duration::from_millis(duration), num_samples)
+    }
+
+    /// Starts a session whose interval follows power within `rate`: fine while power
+    /// moves, coarse while it is stable, so long idle sessions stay small without
+    /// missing bursts.
+    pub fn start_adaptive(&mut self, rate: AdaptiveRate) -> Result<(), SamplerError> {
+        self.start_inner(Schedule::Adaptive(rate), None)
+    }
+
+    /// Like [`StartStopSampler::start_adaptive`], but `callback` receives each sample as it arrives.
+    pub fn start_adaptive_with_callback(
+        &mut self,
+        rate: AdaptiveRate,
+        callback: SampleCallback,
+    ) -> Result<(), SamplerError> {
+        self.start_inner(Schedule::Adaptive(rate), Some(callback))
+    }
+
+    fn start_inner(
+        &mut self,
+        schedule: Schedule,
+        callback: Option<SampleCallback>,
+    ) -> Result<(), SamplerError> {
         if self.manager.is_some() {
-            return Err("Sampling is already in progress");
+            return Err(SamplerError::SamplingInProgress);
         }
+        let (manager, (channels, capabilities)) = SampleManager::new(
+            self.factory.clone(),
+            schedule,
+            callback,
+            self.alerts.clone(),
+            self.events.clone(),
+            self.config.qos,
+            self.config.metric_rates,
+        )?;
+        self.manager = Some(manager);
+        self.channels = channels;
+        self.capabilities = Some(capabilities);
         self.start_time = Some(std::time::Instant::now());
-        self.manager = Some(SampleManager::new(duration, num_samples));
+        self.samples = None;
+        self.regions.clear();
+        self.gaps.clear();
+        self.discarded = 0;
+        self.interval = schedule.interval();
+        self.error = None;
         Ok(())
     }
+}
+
+impl<B> StartStopSampler<B> {
+    /// Calls `callback` from the sampling thread with the sample at which total power
+    /// rises above `watts`, again each time it rises above after falling back.
+    pub fn on_power_above(&mut self, watts: f64, callback: SampleCallback) {
+        self.alerts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .power_above(watts, callback);
+    }
 
-    pub fn stop(&mut self) -> Result<(), &'static str> {
+    /// Calls `callback` from the sampling thread, once per session, with the sample at
+    /// which the session's total energy exceeds `joules`.
+    pub fn on_energy_budget_exceeded(&mut self, joules: f64, callback: SampleCallback) {
+        self.alerts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .energy_budget(joules, callback);
+    }
+
+    /// Stream of the events of sessions from now on, the samples along with errors and
+    /// lifecycle changes. Every session ends with [`SamplerEvent::Stopped`], the stream
+    /// ends once the sampler is dropped.
+    pub fn events(&mut self) -> Receiver<SamplerEvent> {
+        let (sender, receiver) = channel();
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(sender);
+        receiver
+    }
+
+    /// Replaces the options of later sessions, see [`SamplerConfig`].
+    pub fn with_config(mut self, config: SamplerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Options of later sessions, e.g. `config_mut().warmup = Warmup::Samples(1)`.
+    pub fn config_mut(&mut self) -> &mut SamplerConfig {
+        &mut self.config
+    }
+
+    /// Marks the start of a named region, regions may nest and overlap.
+    pub fn begin_region(&mut self, name: &str) -> Result<RegionId, SamplerError> {
+        if self.manager.is_none() {
+            return Err(SamplerError::NoSamplingInProgress);
+        }
+        Ok(self.regions.begin(name, millis_since(self.start_time)))
+    }
+
+    pub fn end_region(&mut self, id: RegionId) {
+        let now = millis_since(self.start_time);
+        self.regions.end(id, now);
+    }
+
+    pub fn stop(&mut self) -> Result<(), SamplerError> {
         if let Some(core) = self.manager.take() {
+            if !core.is_owned() {
+                let pid = core.pid;
+                std::mem::forget(core);
+                return Err(SamplerError::ForkedProcess(pid));
+            }
             self.end_time = Some(std::time::Instant::now());
-            self.samples = Some(core.stop());
+            let session = core.stop();
+            self.samples = Some(session.samples);
+            self.gaps = session.gaps;
+            self.discarded = session.discarded;
+            self.error = session.error;
             Ok(())
         } else {
-            Err("No sampling in progress")
+            Err(SamplerError::NoSamplingInProgress)
         }
     }
 
     pub fn is_sampling(&self) -> bool {
         self.manager.is_some()
     }
+
+    /// Samples of the running session received so far, it keeps running.
+    pub fn samples_so_far(&self) -> Result<Vec<EnergySample>, SamplerError> {
+        self.peek(|session| session.samples.clone())
+    }
+
+    /// Profile of the samples of the running session received so far, for progress
+    /// reports during long sessions. The session keeps running.
+    pub fn profile_so_far(&self) -> Result<PowerProfile, SamplerError> {
+        self.peek(|session| {
+            let config = &self.config;
+            let profile = profile_of(
+                &session.samples,
+                config.warmup,
+                config.outliers,
+                config.idle,
+            )
+            .with_metadata(config.metadata.clone());
+            with_capabilities(profile, self.capabilities)
+        })
+    }
+
+    /// Profile of an ended region of the running session. Waits for the sample
+    /// covering the end of the region, at most one interval.
+    pub(crate) fn ended_region_profile(&self, id: RegionId) -> Result<PowerProfile, SamplerError> {
+        let region = self
+            .regions
+            .as_slice()
+            .get(id.0)
+            .ok_or(SamplerError::SamplesNotAvailable)?;
+        let end = region.end.ok_or(SamplerError::SamplesNotAvailable)?;
+        let covered = |session: &Session| {
+            session
+                .samples
+                .last()
+                .is_some_and(|s| s.timestamp + s.duration >= end)
+        };
+        self.peek_when(covered, |session| {
+            let profile = PowerProfile::for_regions(&session.samples, [region])
+                .with_metadata(self.config.metadata.clone());
+            with_capabilities(profile, self.capabilities)
+        })
+    }
+
+    fn peek<T>(&self, f: impl FnOnce(&Session) -> T) -> Result<T, SamplerError> {
+        self.peek_when(|_| true, f)
+    }
+
+    fn peek_when<T>(
+        &self,
+        done: impl Fn(&Session) -> bool,
+        f: impl FnOnce(&Session) -> T,
+    ) -> Result<T, SamplerError> {
+        let manager = self
+            .manager
+            .as_ref()
+            .ok_or(SamplerError::NoSamplingInProgress)?;
+        if !manager.is_owned() {
+            return Err(SamplerError::ForkedProcess(manager.pid));
+        }
+        Ok(manager.peek_when(done, f))
+    }
+}
+
+impl<B> Drop for StartStopSampler<B> {
+    fn drop(&mut self) {
+        // Never leave a detached sampling thread behind, e.g. when a session is
+        // interrupted before `stop` is reached.
+        let _ = self.stop();
+    }
 }
 
-impl Sampling for StartStopSampler {
+impl<B> Sampling for StartStopSampler<B> {
     fn samples(&self) -> Option<&Vec<EnergySample>> {
         self.samples.as_ref()
     }
@@ -263,9 +1712,41 @@ impl Sampling for StartStopSampler {
     fn end_time(&self) -> Option<std::time::Instant> {
         self.end_time
     }
+
+    fn regions(&self) -> &[Region] {
+        self.regions.as_slice()
+    }
+
+    fn error(&self) -> Option<&SamplerError> {
+        self.error.as_ref()
+    }
+
+    fn gaps(&self) -> &[Gap] {
+        &self.gaps
+    }
+
+    fn discarded_samples(&self) -> usize {
+        self.discarded
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    fn channels(&self) -> &[ChannelInfo] {
+        &self.channels
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.capabilities
+    }
+
+    fn config(&self) -> &SamplerConfig {
+        &self.config
+    }
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct PowerProfile {
     pub total_cpu_energy: u128,
     pub total_gpu_energy: u128,
@@ -276,38 +1757,262 @@ pub struct PowerProfile {
     pub total_energy: u128,
     pub average_power: u64,
     pub total_duration: u64,
+    /// Whether the backend has channels for the unit, the energy of a unit that is
+    /// not available is always zero rather than measured as zero.
+    pub cpu_available: bool,
+    pub gpu_available: bool,
+    pub ane_available: bool,
+    /// Highest single-sample power of each unit and when it occurred.
+    pub peak_cpu_power: PeakPower,
+    pub peak_gpu_power: PeakPower,
+    pub peak_ane_power: PeakPower,
+    /// Metadata of the session, see [`SamplerConfig::metadata`].
+    pub metadata: Metadata,
+    /// Whether the machine switched between AC and battery during the profiled samples,
+    /// such a profile doesn't compare with others.
+    pub power_source_changed: bool,
+    /// Average host CPU utilization over the samples that have it, see
+    /// [`EnergySample::cpu_utilization`].
+    pub cpu_utilization: Option<f64>,
+    /// Energy drawn by the display over the samples that have it, in millijoules, kept
+    /// out of `total_energy` to separate screen draw from compute draw.
+    pub display_energy: Option<u128>,
+    /// Energy of the whole SoC over the samples that have it, see
+    /// [`EnergySample::package_energy`].
+    pub package_energy: Option<u128>,
+    pub average_package_power: Option<u64>,
+    /// Average power of the whole machine and through the DC input in milliwatts over
+    /// the samples that have it, see [`EnergySample::system_power`].
+    pub average_system_power: Option<u64>,
+    pub average_dc_in_power: Option<u64>,
+    /// Energy of each die of an M1 or M2 Ultra over the samples, empty on single-die
+    /// chips. The unit totals are their sums.
+    pub dies: Vec<DieEnergy>,
+    /// Stretches spent near the baseline power, only found with idle detection on, see
+    /// [`SamplerConfig::detect_idle`].
+    pub idle_periods: Vec<IdlePeriod>,
+    pub idle_duration: u64,
+    /// Average power outside the idle periods, `None` without idle detection.
+    pub average_active_power: Option<u64>,
+    /// Runs of throttled samples, see [`EnergySample::is_throttled`]. Throttled runs
+    /// don't compare with cool ones.
+    pub throttled_periods: Vec<ThrottledPeriod>,
+    pub throttled_duration: u64,
+    /// Duration and per-unit power of every sample, kept for [`PowerProfile::duty_cycle`].
+    pub(crate) trace: Vec<(u64, [f64; 3])>,
+}
+
+/// Fraction of the profiled time, from 0 to 1, each unit spent above a power threshold.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DutyCycle {
+    pub cpu: f64,
+    pub gpu: f64,
+    pub ane: f64,
+}
+
+/// A run of throttled samples and the highest thermal pressure during it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ThrottledPeriod {
+    /// Milliseconds since sampling started.
+    pub start: u64,
+    /// Milliseconds.
+    pub duration: u64,
+    pub pressure: ThermalPressure,
+}
+
+/// Highest average power of any one sample, the resolution is the sample interval.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PeakPower {
+    /// Milliwatts.
+    pub power: u64,
+    /// Start of the sample in milliseconds since sampling started.
+    pub timestamp: u64,
+}
+
+impl PeakPower {
+    fn update(&mut self, power: f64, timestamp: u64) {
+        let power = f64::round(power) as u64;
+        if power > self.power {
+            *self = PeakPower { power, timestamp };
+        }
+    }
+}
+
+impl PowerProfile {
+    /// Marks the units `capabilities` lacks as unavailable.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.cpu_available = capabilities.cpu;
+        self.gpu_available = capabilities.gpu;
+        self.ane_available = capabilities.ane;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            cpu: self.cpu_available,
+            gpu: self.gpu_available,
+            ane: self.ane_available,
+        }
+    }
+
+    /// Fraction of the time each unit drew more than `threshold_w` watts, weighted by
+    /// sample duration. A bursty workload has a low duty cycle at a threshold close to
+    /// its peak power, a sustained one stays near 1.
+    pub fn duty_cycle(&self, threshold_w: f64) -> DutyCycle {
+        let threshold = threshold_w * 1000.0;
+        let mut above = [0u64; 3];
+        for (duration, powers) in &self.trace {
+            for (above, power) in above.iter_mut().zip(powers) {
+                if *power > threshold {
+                    *above += duration;
+                }
+            }
+        }
+        // an empty session is never above the threshold
+        let fraction = |above: u64| above as f64 / self.total_duration.max(1) as f64;
+        DutyCycle {
+            cpu: fraction(above[0]),
+            gpu: fraction(above[1]),
+            ane: fraction(above[2]),
+        }
+    }
+
+    /// Highest total power of any one sample in milliwatts, the unit peaks may fall in
+    /// different samples.
+    pub fn peak_power(&self) -> u64 {
+        let peak = self
+            .trace
+            .iter()
+            .map(|(_, powers)| powers.iter().sum::<f64>())
+            .fold(0., f64::max);
+        f64::round(peak) as u64
+    }
+
+    /// Profile of the parts of `samples` that fall inside any of `regions`. Time covered
+    /// by several overlapping regions counts once.
+    pub fn for_regions<'a>(
+        samples: &[EnergySample],
+        regions: impl IntoIterator<Item = &'a Region>,
+    ) -> PowerProfile {
+        PowerProfile::from(Region::clip_all(samples, regions))
+    }
 }
 
 impl<C: AsRef<[EnergySample]>> From<C> for PowerProfile {
     fn from(samples: C) -> Self {
-        let mut profile = PowerProfile::default();
+        // assumed available until a backend reports otherwise
+        let mut profile = PowerProfile {
+            cpu_available: true,
+            gpu_available: true,
+            ane_available: true,
+            ..Default::default()
+        };
         let samples = samples.as_ref();
 
         let mut average_cpu_power = 0.;
         let mut average_gpu_power = 0.;
         let mut average_ane_power = 0.;
         for s in samples.iter() {
-            let duration_secs = s.duration as f64 / 1000.0; //mJs-1 == mW
-
             profile.total_cpu_energy += s.cpu_energy;
             profile.total_gpu_energy += s.gpu_energy;
             profile.total_ane_energy += s.ane_energy;
-            average_cpu_power += s.cpu_energy as f64 / duration_secs;
-            average_gpu_power += s.gpu_energy as f64 / duration_secs;
-            average_ane_power += s.ane_energy as f64 / duration_secs;
+            average_cpu_power += s.cpu_power();
+            average_gpu_power += s.gpu_power();
+            average_ane_power += s.ane_power();
             profile.total_duration += s.duration;
+            profile.peak_cpu_power.update(s.cpu_power(), s.timestamp);
+            profile.peak_gpu_power.update(s.gpu_power(), s.timestamp);
+            profile.peak_ane_power.update(s.ane_power(), s.timestamp);
+            profile
+                .trace
+                .push((s.duration, [s.cpu_power(), s.gpu_power(), s.ane_power()]));
         }
 
-        let num_samples = samples.len() as f64;
+        // an empty session averages to zero
+        let num_samples = samples.len().max(1) as f64;
         profile.average_cpu_power = f64::round(average_cpu_power / num_samples) as u64;
         profile.average_gpu_power = f64::round(average_gpu_power / num_samples) as u64;
         profile.average_ane_power = f64::round(average_ane_power / num_samples) as u64;
 
         profile.total_energy =
             profile.total_cpu_energy + profile.total_gpu_energy + profile.total_ane_energy;
-        profile.average_power = profile.average_cpu_power
-            + profile.average_gpu_power
-            + profile.average_ane_power;
+        profile.average_power =
+            profile.average_cpu_power + profile.average_gpu_power + profile.average_ane_power;
+
+        let mut on_battery = samples
+            .iter()
+            .filter_map(|s| s.power_source.map(|source| source.on_battery));
+        profile.power_source_changed = on_battery
+            .next()
+            .is_some_and(|first| on_battery.any(|b| b != first));
+
+        let (busy, measured) = samples
+            .iter()
+            .filter_map(|s| Some((s.cpu_utilization?, s.duration as f64)))
+            .fold((0., 0.), |(busy, measured), (utilization, duration)| {
+                (busy + utilization * duration, measured + duration)
+            });
+        profile.cpu_utilization = (measured > 0.).then(|| busy / measured);
+
+        profile.display_energy = samples
+            .iter()
+            .filter_map(|s| s.display_energy)
+            .reduce(|total, energy| total + energy);
+
+        let package: Vec<(u128, f64)> = samples
+            .iter()
+            .filter_map(|s| Some((s.package_energy?, s.package_power()?)))
+            .collect();
+        if !package.is_empty() {
+            profile.package_energy = Some(package.iter().map(|(energy, _)| energy).sum());
+            let power: f64 = package.iter().map(|(_, power)| power).sum();
+            profile.average_package_power = Some(f64::round(power / package.len() as f64) as u64);
+        }
+
+        let mut throttled: Option<ThrottledPeriod> = None;
+        for sample in samples {
+            let pressure = sample.thermal_pressure.filter(|_| sample.is_throttled());
+            match (pressure, throttled.as_mut()) {
+                (Some(pressure), Some(period)) => {
+                    period.duration = sample.timestamp + sample.duration - period.start;
+                    period.pressure = period.pressure.max(pressure);
+                }
+                (Some(pressure), None) => {
+                    throttled = Some(ThrottledPeriod {
+                        start: sample.timestamp,
+                        duration: sample.duration,
+                        pressure,
+                    })
+                }
+                (None, _) => profile.throttled_periods.extend(throttled.take()),
+            }
+        }
+        profile.throttled_periods.extend(throttled);
+        profile.throttled_duration = profile.throttled_periods.iter().map(|p| p.duration).sum();
+
+        let average = |power: &dyn Fn(&EnergySample) -> Option<f64>| {
+            let powers: Vec<f64> = samples.iter().filter_map(power).collect();
+            (!powers.is_empty())
+                .then(|| f64::round(powers.iter().sum::<f64>() / powers.len() as f64) as u64)
+        };
+        profile.average_system_power = average(&|s| s.system_power);
+        profile.average_dc_in_power = average(&|s| s.dc_in_power);
+
+        for sample in samples {
+            if profile.dies.len() < sample.dies.len() {
+                profile.dies.resize(sample.dies.len(), DieEnergy::default());
+            }
+            for (total, die) in profile.dies.iter_mut().zip(&sample.dies) {
+                total.cpu += die.cpu;
+                total.gpu += die.gpu;
+                total.ane += die.ane;
+            }
+        }
 
         profile
     }
@@ -315,19 +2020,107 @@ impl<C: AsRef<[EnergySample]>> From<C> for PowerProfile {
 
 impl std::fmt::Display for PowerProfile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_units(f, Units::default())
+    }
+}
+
+impl PowerProfile {
+    /// The [`Display`](std::fmt::Display) of the profile, energy and power in `units`.
+    pub(crate) fn fmt_units(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        units: Units,
+    ) -> std::fmt::Result {
+        let e = |millijoules: u128| units.energy.format(millijoules as f64);
+        let p = |milliwatts: u64| units.power.format(milliwatts as f64);
+        let value = |available: bool, value: String| {
+            if available {
+                value
+            } else {
+                "n/a".to_string()
+            }
+        };
         write!(
             f,
-            "Total Energy: {} mJ\nTotal Power: {} mW\nTotal Duration: {} ms\nCPU Energy: {} mJ\nGPU Energy: {} mJ\nANE Energy: {} mJ\nCPU Power: {} mW\nGPU Power: {} mW\nANE Power: {} mW",
-            self.total_energy,
-            self.average_power,
+            "Total Energy: {}\nTotal Power: {}\nTotal Duration: {} ms\nCPU Energy: {}\nGPU Energy: {}\nANE Energy: {}\nCPU Power: {}\nGPU Power: {}\nANE Power: {}",
+            e(self.total_energy),
+            p(self.average_power),
             self.total_duration,
-            self.total_cpu_energy,
-            self.total_gpu_energy,
-            self.total_ane_energy,
-            self.average_cpu_power,
-            self.average_gpu_power,
-            self.average_ane_power
+            value(self.cpu_available, e(self.total_cpu_energy)),
+            value(self.gpu_available, e(self.total_gpu_energy)),
+            value(self.ane_available, e(self.total_ane_energy)),
+            value(self.cpu_available, p(self.average_cpu_power)),
+            value(self.gpu_available, p(self.average_gpu_power)),
+            value(self.ane_available, p(self.average_ane_power)),
+        )?;
+        let peak = |available: bool, peak: PeakPower| {
+            value(
+                available,
+                format!("{} at {} ms", p(peak.power), peak.timestamp),
+            )
+        };
+        write!(
+            f,
+            "\nCPU Peak: {}\nGPU Peak: {}\nANE Peak: {}",
+            peak(self.cpu_available, self.peak_cpu_power),
+            peak(self.gpu_available, self.peak_gpu_power),
+            peak(self.ane_available, self.peak_ane_power),
         )?;
+        if let Some(utilization) = self.cpu_utilization {
+            write!(f, "\nCPU Utilization: {:.1}%", utilization * 100.0)?;
+        }
+        if let Some(energy) = self.display_energy {
+            write!(f, "\nDisplay Energy: {}", e(energy))?;
+        }
+        if let (Some(energy), Some(power)) = (self.package_energy, self.average_package_power) {
+            write!(
+                f,
+                "\nPackage Energy: {}\nPackage Power: {}",
+                e(energy),
+                p(power)
+            )?;
+        }
+        if let Some(power) = self.average_system_power {
+            write!(f, "\nSystem Power: {}", p(power))?;
+        }
+        if let Some(power) = self.average_dc_in_power {
+            write!(f, "\nDC In Power: {}", p(power))?;
+        }
+        for (i, die) in self.dies.iter().enumerate() {
+            write!(
+                f,
+                "\nDie {i} Energy: {} (CPU {}, GPU {}, ANE {})",
+                e(die.total()),
+                e(die.cpu),
+                e(die.gpu),
+                e(die.ane)
+            )?;
+        }
+        if let Some(power) = self.average_active_power {
+            write!(
+                f,
+                "\nIdle: {} ms in {} periods\nActive Power: {}",
+                self.idle_duration,
+                self.idle_periods.len(),
+                p(power)
+            )?;
+        }
+        for (key, value) in &self.metadata {
+            write!(f, "\n{key}: {value}")?;
+        }
+        if self.throttled_duration > 0 {
+            write!(
+                f,
+                "\nWarning: throttled for {} ms, throttled runs don't compare with cool ones",
+                self.throttled_duration
+            )?;
+        }
+        if self.power_source_changed {
+            write!(
+                f,
+                "\nWarning: the power source changed during the session, AC and battery don't compare"
+            )?;
+        }
         Ok(())
     }
 }
@@ -335,12 +2128,665 @@ impl std::fmt::Display for PowerProfile {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::mock::MockBackend;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_interval_accuracy() {
+        let samples: Vec<EnergySample> = [10, 12, 14]
+            .into_iter()
+            .map(|duration| EnergySample {
+                duration,
+                ..Default::default()
+            })
+            .collect();
+        let accuracy = IntervalAccuracy::new(Duration::from_millis(10), &samples).unwrap();
+        assert_eq!((accuracy.min, accuracy.max), (10, 14));
+        assert_eq!(accuracy.mean, 12.0);
+        assert_eq!(accuracy.relative_error, 0.2);
+        assert_eq!(IntervalAccuracy::new(Duration::from_millis(10), &[]), None);
+    }
+
+    #[test]
+    fn test_interval_too_short() {
+        let mut sampler = StartStopSampler::with_backend(|| Ok(MockBackend::replay(vec![])));
+        let ms = Duration::from_millis;
+        assert!(matches!(
+            sampler.start(ms(50), 10),
+            Err(SamplerError::IntervalTooShort(d)) if d == ms(5)
+        ));
+        assert!(matches!(
+            sampler.start(ms(50), 0),
+            Err(SamplerError::IntervalTooShort(Duration::ZERO))
+        ));
+        assert!(!sampler.is_sampling());
+    }
+
+    #[test]
+    fn test_adaptive_next_interval() {
+        let ms = Duration::from_millis;
+        let rate = AdaptiveRate {
+            min_interval: ms(10),
+            max_interval: ms(80),
+            threshold: 0.1,
+        };
+        assert_eq!(rate.next_interval(ms(10), 1000.0, 1050.0), ms(20));
+        assert_eq!(rate.next_interval(ms(80), 1000.0, 1000.0), ms(80));
+        assert_eq!(rate.next_interval(ms(80), 1000.0, 2000.0), ms(40));
+        assert_eq!(rate.next_interval(ms(10), 2000.0, 1000.0), ms(10));
+    }
+
+    #[test]
+    fn test_adaptive_session_coarsens_when_stable() {
+        let mut sampler =
+            StartStopSampler::with_backend(|| Ok(MockBackend::constant(1000.0, 0.0, 0.0)));
+        sampler
+            .start_adaptive(AdaptiveRate {
+                min_interval: Duration::from_millis(10),
+                max_interval: Duration::from_millis(40),
+                threshold: 0.1,
+            })
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        sampler.stop().unwrap();
+
+        let durations: Vec<u64> = sampler
+            .samples()
+            .unwrap()
+            .iter()
+            .map(|s| s.duration)
+            .collect();
+        assert_eq!(durations[..4], [10, 10, 20, 40]);
+        assert!(durations[4..].iter().all(|d| *d == 40));
+        assert_eq!(sampler.interval_accuracy(), None);
+    }
+
+    #[test]
+    fn test_profile_empty_session() {
+        let mut sampler = StartStopSampler::with_backend(|| Ok(MockBackend::replay(vec![])));
+        sampler.start(Duration::from_millis(100), 1).unwrap();
+        sampler.stop().unwrap();
+
+        assert!(sampler.samples().unwrap().is_empty());
+        let profile = sampler.profile().unwrap();
+        assert_eq!(profile.total_energy, 0);
+        assert_eq!(profile.average_power, 0);
+        assert_eq!(profile.total_duration, 0);
+    }
+
+    #[test]
+    fn test_zero_duration_sample() {
+        let samples = [(100, 100), (50, 0)].map(|(cpu_energy, duration)| EnergySample {
+            cpu_energy,
+            package_energy: Some(cpu_energy),
+            duration,
+            ..Default::default()
+        });
+        assert_eq!(samples[1].cpu_power(), 0.0);
+        assert_eq!(samples[1].package_power(), Some(0.0));
+        let profile = PowerProfile::from(samples.as_slice());
+        assert_eq!(profile.total_energy, 150);
+        assert_eq!(profile.peak_cpu_power.power, 1000);
+        assert_eq!(profile.peak_power(), 1000);
+    }
 
     #[test]
+    fn test_overlapping_regions() {
+        let samples = [0, 100, 200, 300].map(|timestamp| EnergySample {
+            cpu_energy: 100,
+            timestamp,
+            duration: 100,
+            ..Default::default()
+        });
+        let region = |start, end| Region {
+            name: "step".to_string(),
+            start,
+            end,
+        };
+
+        let overlapping = [region(0, Some(200)), region(100, Some(300))];
+        let profile = PowerProfile::for_regions(&samples, &overlapping);
+        assert_eq!(profile.total_energy, 300);
+        assert_eq!(profile.total_duration, 300);
+
+        let apart = [region(200, Some(300)), region(0, Some(100))];
+        assert_eq!(
+            PowerProfile::for_regions(&samples, &apart).total_energy,
+            200
+        );
+
+        let open = [region(0, Some(200)), region(100, None)];
+        assert_eq!(PowerProfile::for_regions(&samples, &open).total_energy, 400);
+    }
+
+    #[test]
+    fn test_profile_in_progress() {
+        let mut sampler =
+            StartStopSampler::with_backend(|| Ok(MockBackend::constant(1000.0, 0.0, 0.0)));
+        assert!(matches!(
+            sampler.profile(),
+            Err(SamplerError::SamplesNotAvailable)
+        ));
+
+        sampler.start(Duration::from_millis(20), 1).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(matches!(
+            sampler.profile(),
+            Err(SamplerError::SamplesNotAvailable)
+        ));
+        sampler.stop().unwrap();
+        assert_eq!(sampler.profile().unwrap().average_cpu_power, 1000);
+
+        // the previous session's profile is gone once the next one starts
+        sampler.start(Duration::from_millis(20), 1).unwrap();
+        assert!(matches!(
+            sampler.profile(),
+            Err(SamplerError::SamplesNotAvailable)
+        ));
+        sampler.stop().unwrap();
+    }
+
+    #[test]
+    fn test_profile_so_far() {
+        let mut sampler =
+            StartStopSampler::with_backend(|| Ok(MockBackend::constant(1000.0, 0.0, 0.0)));
+        assert!(matches!(
+            sampler.profile_so_far(),
+            Err(SamplerError::NoSamplingInProgress)
+        ));
+
+        sampler.start(Duration::from_millis(20), 1).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        let early = sampler.profile_so_far().unwrap();
+        assert!(early.total_duration > 0);
+        assert_eq!(early.average_cpu_power, 1000);
+        std::thread::sleep(Duration::from_millis(100));
+        let later = sampler.profile_so_far().unwrap();
+        assert!(later.total_cpu_energy > early.total_cpu_energy);
+        assert!(sampler.is_sampling());
+
+        sampler.stop().unwrap();
+        // snapshots don't take samples away from the finished session
+        let profile = sampler.profile().unwrap();
+        assert!(profile.total_cpu_energy >= later.total_cpu_energy);
+        assert_eq!(
+            sampler.samples().unwrap()[0].timestamp,
+            0,
+            "samples received before a snapshot are kept"
+        );
+    }
+
+    #[test]
+    fn test_metadata() {
+        let config = SamplerConfig::default()
+            .with_metadata("model", "resnet50")
+            .with_metadata("batch_size", 32.to_string());
+        let mut sampler = StartStopSampler::with_backend(|| {
+            Ok(MockBackend::replay(vec![EnergySample {
+                cpu_energy: 10,
+                duration: 100,
+                ..Default::default()
+            }]))
+        })
+        .with_config(config);
+        sampler.start(Duration::from_millis(100), 1).unwrap();
+        let region = sampler.begin_region("all").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        sampler.end_region(region);
+        sampler.stop().unwrap();
+
+        let profile = sampler.profile().unwrap();
+        assert_eq!(profile.metadata["model"], "resnet50");
+        assert!(profile
+            .to_string()
+            .ends_with("batch_size: 32\nmodel: resnet50"));
+        assert_eq!(
+            sampler.region_profile("all").unwrap().metadata,
+            profile.metadata
+        );
+    }
+
+    #[test]
+    fn test_discard_warmup() {
+        let replay = || {
+            Ok(MockBackend::replay(
+                [100, 10, 10]
+                    .map(|cpu_energy| EnergySample {
+                        cpu_energy,
+                        duration: 100,
+                        ..Default::default()
+                    })
+                    .to_vec(),
+            ))
+        };
+        let mut sampler = StartStopSampler::with_backend(replay);
+        sampler.config_mut().warmup = Warmup::Samples(1);
+        sampler.start(Duration::from_millis(100), 1).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        sampler.stop().unwrap();
+        assert_eq!(sampler.profile().unwrap().total_cpu_energy, 20);
+        assert_eq!(sampler.samples().unwrap().len(), 3, "raw samples are kept");
+
+        // half of the second sample falls inside the warmup
+        let samples = sampler.samples().unwrap();
+        let rest = Warmup::from(Duration::from_millis(150)).skip(samples);
+        assert_eq!(PowerProfile::from(rest).total_cpu_energy, 15);
+    }
+
+    #[test]
+    fn test_reject_outliers() {
+        let samples = |energies: &[u128]| -> Vec<EnergySample> {
+            energies
+                .iter()
+                .map(|&cpu_energy| EnergySample {
+                    cpu_energy,
+                    duration: 100,
+                    ..Default::default()
+                })
+                .collect()
+        };
+        let spiky = samples(&[10, 11, 9, 10, 12, 500]);
+        let mut sampler =
+            StartStopSampler::with_backend(move || Ok(MockBackend::replay(spiky.clone())));
+        sampler.config_mut().outliers = OutlierFilter::Mad { threshold: 3.5 };
+        sampler.start(Duration::from_millis(100), 1).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        sampler.stop().unwrap();
+        assert_eq!(sampler.profile().unwrap().total_cpu_energy, 52);
+        assert_eq!(sampler.samples().unwrap().len(), 6, "raw samples are kept");
+
+        // no spread to measure outliers against
+        let flat = samples(&[10, 10, 10, 500]);
+        assert_eq!(OutlierFilter::Mad { threshold: 3.5 }.apply(flat).len(), 4);
+    }
+
+    #[test]
+    fn test_detect_idle() {
+        // 10 s idle at 1 W, 3 s busy at 20 W, 1 s idle, which is too short to count
+        let samples: Vec<EnergySample> = [1000; 10]
+            .into_iter()
+            .chain([20_000; 3])
+            .chain([1000])
+            .enumerate()
+            .map(|(i, cpu_energy)| EnergySample {
+                cpu_energy,
+                timestamp: i as u64 * 1000,
+                duration: 1000,
+                ..Default::default()
+            })
+            .collect();
+        let detection = IdleDetection::default();
+        assert_eq!(
+            detection.periods(&samples),
+            vec![IdlePeriod {
+                start: 0,
+                duration: 10_000
+            }]
+        );
+
+        let annotated = profile_of(&samples, Warmup::None, OutlierFilter::None, Some(detection));
+        assert_eq!(annotated.total_cpu_energy, 71_000);
+        assert_eq!(annotated.idle_duration, 10_000);
+        assert_eq!(annotated.average_active_power, Some(15_250));
+        assert!(annotated
+            .to_string()
+            .contains("Idle: 10000 ms in 1 periods\nActive Power: 15250 mW"));
+
+        let excluded = IdleDetection {
+            exclude: true,
+            ..detection
+        };
+        let active = profile_of(&samples, Warmup::None, OutlierFilter::None, Some(excluded));
+        assert_eq!(active.total_cpu_energy, 61_000);
+        assert_eq!(active.average_power, 15_250);
+        assert_eq!(PowerProfile::from(&samples).average_active_power, None);
+    }
+
+    #[test]
+    fn test_throttled() {
+        let busy = |performance_state| {
+            Some(CpuResidency {
+                active: 1.0,
+                performance_state,
+            })
+        };
+        let samples: Vec<EnergySample> = [
+            (ThermalPressure::Nominal, busy(0.5)),
+            // moderate pressure alone doesn't throttle a cluster at its top states
+            (ThermalPressure::Moderate, busy(1.0)),
+            (ThermalPressure::Moderate, busy(0.6)),
+            (ThermalPressure::Heavy, busy(1.0)),
+            (ThermalPressure::Nominal, None),
+            (ThermalPressure::Moderate, None),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (pressure, cpu_residency))| EnergySample {
+            cpu_energy: 100,
+            timestamp: i as u64 * 100,
+            duration: 100,
+            thermal_pressure: Some(pressure),
+            cpu_residency,
+            ..Default::default()
+        })
+        .collect();
+        let throttled: Vec<bool> = samples.iter().map(EnergySample::is_throttled).collect();
+        assert_eq!(throttled, [false, false, true, true, false, true]);
+
+        let profile = PowerProfile::from(&samples);
+        assert_eq!(
+            profile.throttled_periods,
+            [
+                ThrottledPeriod {
+                    start: 200,
+                    duration: 200,
+                    pressure: ThermalPressure::Heavy
+                },
+                ThrottledPeriod {
+                    start: 500,
+                    duration: 100,
+                    pressure: ThermalPressure::Moderate
+                }
+            ]
+        );
+        assert_eq!(profile.throttled_duration, 300);
+        assert!(profile.to_string().contains("throttled for 300 ms"));
+    }
+
+    #[test]
+    fn test_peak_power() {
+        let samples: Vec<EnergySample> = [(100, 0), (500, 100), (200, 200)]
+            .into_iter()
+            .map(|(cpu_energy, timestamp)| EnergySample {
+                cpu_energy,
+                gpu_energy: 10,
+                timestamp,
+                duration: 100,
+                ..Default::default()
+            })
+            .collect();
+        let profile = PowerProfile::from(&samples);
+        assert_eq!(
+            profile.peak_cpu_power,
+            PeakPower {
+                power: 5000,
+                timestamp: 100
+            }
+        );
+        // ties keep the first occurrence
+        assert_eq!(profile.peak_gpu_power.timestamp, 0);
+        assert_eq!(profile.peak_ane_power, PeakPower::default());
+    }
+
+    #[test]
+    fn test_power_source_changed() {
+        let source = |on_battery, battery_percent| {
+            Some(PowerSource {
+                on_battery,
+                battery_percent,
+            })
+        };
+        let profile = |sources: &[Option<PowerSource>]| {
+            let samples: Vec<EnergySample> = sources
+                .iter()
+                .map(|&power_source| EnergySample {
+                    duration: 100,
+                    power_source,
+                    ..Default::default()
+                })
+                .collect();
+            PowerProfile::from(samples)
+        };
+
+        // draining the battery is no change, unknown sources are ignored
+        let steady = profile(&[source(true, Some(80)), None, source(true, Some(79))]);
+        assert!(!steady.power_source_changed);
+        let unplugged = profile(&[source(false, Some(80)), source(true, Some(80))]);
+        assert!(unplugged.power_source_changed);
+        assert!(unplugged.to_string().contains("power source changed"));
+    }
+
+    #[test]
+    fn test_cpu_utilization() {
+        let samples: Vec<EnergySample> = [(Some(0.2), 100), (None, 100), (Some(0.8), 300)]
+            .into_iter()
+            .map(|(cpu_utilization, duration)| EnergySample {
+                duration,
+                cpu_utilization,
+                ..Default::default()
+            })
+            .collect();
+        // weighted by duration, samples without a reading are left out
+        let utilization = PowerProfile::from(&samples).cpu_utilization.unwrap();
+        assert!((utilization - 0.65).abs() < 1e-9);
+        assert_eq!(PowerProfile::from(&samples[1..2]).cpu_utilization, None);
+    }
+
+    #[test]
+    fn test_display_energy() {
+        let samples: Vec<EnergySample> = [Some(40), None, Some(60)]
+            .into_iter()
+            .map(|display_energy| EnergySample {
+                cpu_energy: 100,
+                display_energy,
+                duration: 100,
+                ..Default::default()
+            })
+            .collect();
+        let profile = PowerProfile::from(&samples);
+        assert_eq!(profile.display_energy, Some(100));
+        assert_eq!(profile.total_energy, 300);
+        assert!(profile.to_string().contains("Display Energy: 100 mJ"));
+        assert_eq!(PowerProfile::from(&samples[1..2]).display_energy, None);
+    }
+
+    #[test]
+    fn test_die_energy() {
+        let die = |cpu, gpu| DieEnergy { cpu, gpu, ane: 0 };
+        let samples: Vec<EnergySample> = (0..2)
+            .map(|_| EnergySample {
+                cpu_energy: 150,
+                gpu_energy: 30,
+                duration: 100,
+                dies: vec![die(100, 10), die(50, 20)],
+                ..Default::default()
+            })
+            .collect();
+        let profile = PowerProfile::from(&samples);
+        assert_eq!(profile.dies, [die(200, 20), die(100, 40)]);
+        assert_eq!(
+            profile.dies.iter().map(DieEnergy::total).sum::<u128>(),
+            profile.total_energy
+        );
+        assert!(profile
+            .to_string()
+            .contains("Die 1 Energy: 140 mJ (CPU 100 mJ, GPU 40 mJ, ANE 0 mJ)"));
+    }
+
+    #[test]
+    fn test_package_energy() {
+        let samples: Vec<EnergySample> = [(Some(300), 100), (None, 100), (Some(100), 200)]
+            .into_iter()
+            .map(|(package_energy, duration)| EnergySample {
+                cpu_energy: 50,
+                package_energy,
+                duration,
+                ..Default::default()
+            })
+            .collect();
+        // averaged like the unit powers, over the samples that have it
+        let profile = PowerProfile::from(&samples);
+        assert_eq!(profile.package_energy, Some(400));
+        assert_eq!(profile.average_package_power, Some(1750));
+        assert!(profile.to_string().contains("Package Power: 1750 mW"));
+        assert_eq!(PowerProfile::from(&samples[1..2]).package_energy, None);
+    }
+
+    #[test]
+    fn test_system_power() {
+        let samples: Vec<EnergySample> = [
+            (Some(12_000.0), Some(0.0)),
+            (None, None),
+            (Some(9_000.0), Some(0.0)),
+        ]
+        .into_iter()
+        .map(|(system_power, dc_in_power)| EnergySample {
+            system_power,
+            dc_in_power,
+            duration: 100,
+            ..Default::default()
+        })
+        .collect();
+        let profile = PowerProfile::from(&samples);
+        assert_eq!(profile.average_system_power, Some(10_500));
+        assert_eq!(profile.average_dc_in_power, Some(0));
+        assert!(profile.to_string().contains("System Power: 10500 mW"));
+        assert_eq!(
+            PowerProfile::from(&samples[1..2]).average_system_power,
+            None
+        );
+    }
+
+    #[test]
+    fn test_duty_cycle() {
+        // 1 W, 5 W and 3 W on the CPU, weighted by duration
+        let samples: Vec<EnergySample> = [(100, 100), (500, 100), (600, 200)]
+            .into_iter()
+            .map(|(cpu_energy, duration)| EnergySample {
+                cpu_energy,
+                gpu_energy: duration as u128,
+                duration,
+                ..Default::default()
+            })
+            .collect();
+        let profile = PowerProfile::from(&samples);
+        let duty = profile.duty_cycle(2.0);
+        assert_eq!(duty.cpu, 0.75);
+        assert_eq!(duty.gpu, 0.0);
+        assert_eq!(profile.duty_cycle(0.5).gpu, 1.0);
+        assert_eq!(
+            PowerProfile::default().duty_cycle(0.0),
+            DutyCycle::default()
+        );
+    }
+
+    #[test]
+    fn test_energy_budget_alert() {
+        let (tx, rx) = channel();
+        let mut sampler =
+            StartStopSampler::with_backend(|| Ok(MockBackend::constant(1000.0, 0.0, 0.0)));
+        // 1 W for 20 ms is 20 mJ per sample, the budget is used up by the third
+        sampler.on_energy_budget_exceeded(
+            0.05,
+            Box::new(move |s| {
+                let _ = tx.send(s.timestamp);
+            }),
+        );
+        sampler.start(Duration::from_millis(20), 1).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        sampler.stop().unwrap();
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [40]);
+    }
+
+    #[test]
+    fn test_events() {
+        let sample = |cpu_energy| EnergySample {
+            cpu_energy,
+            duration: 10,
+            ..Default::default()
+        };
+        // the middle sample draws 10 kW, the replay running out ends the session
+        let samples = [sample(10), sample(100_000), sample(10)];
+        let mut sampler =
+            GuardSampler::with_backend(move || Ok(MockBackend::replay(samples.clone())));
+        let events = sampler.events();
+        let guard = sampler.subscribe(Duration::from_millis(10), 1).unwrap();
+
+        // dropping the guard any earlier would cancel the session before it runs out
+        let mut kinds = Vec::new();
+        for event in events.iter() {
+            kinds.push(match event {
+                SamplerEvent::SampleReady(_) => "sample",
+                SamplerEvent::ChannelError { fatal: false, .. } => "discarded",
+                SamplerEvent::ChannelError { fatal: true, .. } => "failed",
+                SamplerEvent::SleepDetected(_) => "sleep",
+                SamplerEvent::ThermalChange(_) => continue,
+                SamplerEvent::Stopped => break,
+            });
+        }
+        kinds.push("stopped");
+        drop(guard);
+        assert_eq!(
+            kinds,
+            ["sample", "discarded", "sample", "failed", "stopped"]
+        );
+        assert_eq!(sampler.samples().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_wall_time() {
+        let sample = EnergySample {
+            cpu_energy: 10,
+            duration: 10,
+            ..Default::default()
+        };
+        let mut sampler =
+            GuardSampler::with_backend(move || Ok(MockBackend::replay(vec![sample.clone(); 4])));
+        let events = sampler.events();
+        let before = SystemTime::now();
+        let guard = sampler.subscribe(Duration::from_millis(20), 2).unwrap();
+        // the replay running out ends the session
+        let _ = events.iter().find(|e| matches!(e, SamplerEvent::Stopped));
+        drop(guard);
+
+        let samples = sampler.samples().unwrap();
+        let wall: Vec<SystemTime> = samples.iter().map(|s| s.wall_time.unwrap()).collect();
+        assert!(wall[0] >= before);
+        // within a read the wall clock follows the monotonic timestamps
+        assert_eq!(
+            wall[1].duration_since(wall[0]).unwrap(),
+            Duration::from_millis(samples[1].timestamp - samples[0].timestamp)
+        );
+    }
+
+    #[test]
+    fn test_time_away() {
+        let ms = Duration::from_millis;
+        // a short overrun is not sleep
+        assert_eq!(time_away(ms(1_200), ms(1_200), ms(1_000)), None);
+        // monotonic clock stopped while asleep
+        assert_eq!(
+            time_away(ms(1_000), ms(61_000), ms(1_000)),
+            Some(ms(60_000))
+        );
+        // monotonic clock kept running
+        assert_eq!(
+            time_away(ms(61_000), ms(61_000), ms(1_000)),
+            Some(ms(60_000))
+        );
+    }
+
+    #[test]
+    fn test_thread_safety() {
+        // independent of the backend, which stays on the sampling thread
+        assert_send_sync::<GuardSampler<*const ()>>();
+        assert_send_sync::<StartStopSampler<*const ()>>();
+        assert_send_sync::<EnergySample>();
+        assert_send_sync::<PowerProfile>();
+        assert_send_sync::<SamplerError>();
+    }
+
+    #[test]
+    #[cfg_attr(
+        not(all(target_os = "macos", feature = "macos")),
+        ignore = "requires IOReport"
+    )]
     fn test_guard_sampler() {
         let mut sampler = GuardSampler::new();
         {
-            let _guard = sampler.subscribe(100, 2);
+            let _guard = sampler.subscribe(Duration::from_millis(100), 2).unwrap();
             std::thread::sleep(std::time::Duration::from_secs(5));
         }
         let profile = sampler.profile().unwrap();
@@ -348,11 +2794,15 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(
+        not(all(target_os = "macos", feature = "macos")),
+        ignore = "requires IOReport"
+    )]
     fn test_start_stop_sampler() {
         let mut sampler = StartStopSampler::new();
 
         assert!(!sampler.is_sampling());
-        sampler.start(100, 2).unwrap();
+        sampler.start(Duration::from_millis(100), 2).unwrap();
         assert!(sampler.is_sampling());
 
         std::thread::sleep(std::time::Duration::from_secs(5));
@@ -362,4 +2812,34 @@ mod tests {
         let profile = sampler.profile().unwrap();
         println!("{}", profile);
     }
+
+    #[test]
+    #[cfg_attr(
+        not(all(target_os = "macos", feature = "macos")),
+        ignore = "requires IOReport"
+    )]
+    fn test_sample_callback() {
+        let (tx, rx) = channel();
+        let mut sampler = StartStopSampler::new();
+        sampler
+            .start_with_callback(
+                Duration::from_millis(100),
+                1,
+                Box::new(move |s| {
+                    let _ = tx.send(s.timestamp);
+                }),
+            )
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        sampler.stop().unwrap();
+
+        let live: Vec<u64> = rx.iter().collect();
+        let stored: Vec<u64> = sampler
+            .samples()
+            .unwrap()
+            .iter()
+            .map(|s| s.timestamp)
+            .collect();
+        assert_eq!(live, stored);
+    }
 }
@@ -0,0 +1,135 @@
+//! Named measurements sliced from one shared sampling thread.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::backend::{DefaultBackend, PowerBackend};
+use crate::region::RegionId;
+use crate::sampler::{PowerProfile, SamplerError, StartStopSampler};
+
+/// # SessionManager
+///
+/// Keeps one backend subscription sampling in the background while callers open and
+/// close any number of named, possibly overlapping, sessions. Each session's profile
+/// is sliced from the shared sample stream, energy of a sample straddling a session
+/// boundary is prorated like for regions.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use wattkit::SessionManager;
+///
+/// let mut sessions = SessionManager::new(Duration::from_millis(100)).unwrap();
+/// sessions.open("request-1").unwrap();
+/// sessions.open("request-2").unwrap();
+/// let first = sessions.close("request-1").unwrap();
+/// let second = sessions.close("request-2").unwrap();
+/// println!("{first}\n{second}");
+/// ```
+#[derive(Debug)]
+pub struct SessionManager<B = DefaultBackend> {
+    sampler: StartStopSampler<B>,
+    open: HashMap<String, RegionId>,
+}
+
+impl SessionManager {
+    /// Starts sampling the default backend every `interval`.
+    pub fn new(interval: Duration) -> Result<Self, SamplerError> {
+        SessionManager::with_sampler(StartStopSampler::new(), interval)
+    }
+}
+
+impl<B: PowerBackend + 'static> SessionManager<B> {
+    /// Starts `sampler` sampling every `interval`, it must not be sampling already.
+    pub fn with_sampler(
+        mut sampler: StartStopSampler<B>,
+        interval: Duration,
+    ) -> Result<Self, SamplerError> {
+        sampler.start(interval, 1)?;
+        Ok(SessionManager {
+            sampler,
+            open: HashMap::new(),
+        })
+    }
+}
+
+impl<B> SessionManager<B> {
+    /// Opens a session called `name`, names are unique among open sessions.
+    pub fn open(&mut self, name: &str) -> Result<(), SamplerError> {
+        if self.open.contains_key(name) {
+            return Err(SamplerError::SessionAlreadyOpen(name.to_string()));
+        }
+        let id = self.sampler.begin_region(name)?;
+        self.open.insert(name.to_string(), id);
+        Ok(())
+    }
+
+    /// Closes the session called `name` and returns its profile. Blocks until the
+    /// sample covering the end of the session arrives, at most one interval.
+    pub fn close(&mut self, name: &str) -> Result<PowerProfile, SamplerError> {
+        let id = self
+            .open
+            .remove(name)
+            .ok_or_else(|| SamplerError::UnknownSession(name.to_string()))?;
+        self.sampler.end_region(id);
+        self.sampler.ended_region_profile(id)
+    }
+
+    /// Attaches `key: value` to the profiles of sessions closed from now on.
+    pub fn set_metadata(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.sampler
+            .config_mut()
+            .metadata
+            .insert(key.into(), value.into());
+    }
+
+    pub fn is_open(&self, name: &str) -> bool {
+        self.open.contains_key(name)
+    }
+
+    /// Names of the open sessions, in no particular order.
+    pub fn open_sessions(&self) -> impl Iterator<Item = &str> {
+        self.open.keys().map(String::as_str)
+    }
+
+    /// Stops the sampling thread, sessions still open are discarded.
+    pub fn stop(mut self) -> Result<(), SamplerError> {
+        self.sampler.stop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+
+    #[test]
+    fn test_overlapping_sessions() {
+        let sampler =
+            StartStopSampler::with_backend(|| Ok(MockBackend::constant(1000.0, 0.0, 0.0)));
+        let mut sessions =
+            SessionManager::with_sampler(sampler, Duration::from_millis(20)).unwrap();
+
+        sessions.open("outer").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        sessions.open("inner").unwrap();
+        assert!(matches!(
+            sessions.open("inner"),
+            Err(SamplerError::SessionAlreadyOpen(_))
+        ));
+        std::thread::sleep(Duration::from_millis(50));
+        let inner = sessions.close("inner").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        let outer = sessions.close("outer").unwrap();
+
+        assert!(!sessions.is_open("outer"));
+        assert!(matches!(
+            sessions.close("outer"),
+            Err(SamplerError::UnknownSession(_))
+        ));
+        // 1 W is 1 mJ per ms, the whole of each session is covered
+        assert_eq!(inner.total_cpu_energy as u64, inner.total_duration);
+        assert!(inner.total_duration >= 50);
+        assert!(outer.total_duration >= inner.total_duration + 100);
+        sessions.stop().unwrap();
+    }
+}
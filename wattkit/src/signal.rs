@@ -0,0 +1,196 @@
+//! Sessions started and stopped from outside the process with `kill -USR1` and
+//! `kill -USR2`, for profiling a running process without changing its code.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::backend::PowerBackend;
+use crate::sampler::{Sampling, StartStopSampler};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SignalError {
+    #[error("Signal control is already installed")]
+    AlreadyInstalled,
+    #[error("Failed to install signal control: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Write end of the pipe the handler forwards signals into, -1 while not installed.
+static PIPE: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn forward(signal: libc::c_int) {
+    let fd = PIPE.load(Ordering::Relaxed);
+    if fd >= 0 {
+        // only async-signal-safe calls here, a full pipe drops the signal
+        let byte = signal as u8;
+        unsafe { libc::write(fd, &byte as *const u8 as *const libc::c_void, 1) };
+    }
+}
+
+/// # SignalControl
+///
+/// Starts a session on SIGUSR1 and stops it on SIGUSR2, writing the profile to a
+/// file, so `kill -USR1 <pid>` and `kill -USR2 <pid>` profile a running process. The
+/// handlers only forward the signals to a thread owning the sampler, which may take
+/// up to an interval to stop.
+///
+/// One may be installed at a time, dropping it restores the previous handlers.
+#[derive(Debug)]
+pub struct SignalControl {
+    read: libc::c_int,
+    write: libc::c_int,
+    previous: [libc::sigaction; 2],
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SignalControl {
+    /// Samples the default backend every `interval` between the signals, each profile
+    /// overwrites `path`.
+    pub fn install(path: impl Into<PathBuf>, interval: Duration) -> Result<Self, SignalError> {
+        SignalControl::with_sampler(StartStopSampler::new(), path, interval)
+    }
+
+    /// [`SignalControl::install`] with `sampler`, it must not be sampling already.
+    pub fn with_sampler<B: PowerBackend + 'static>(
+        mut sampler: StartStopSampler<B>,
+        path: impl Into<PathBuf>,
+        interval: Duration,
+    ) -> Result<Self, SignalError> {
+        let path = path.into();
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let [read, write] = fds;
+        // a signal arriving while the pipe is full must not block the handler
+        unsafe { libc::fcntl(write, libc::F_SETFL, libc::O_NONBLOCK) };
+        if PIPE
+            .compare_exchange(-1, write, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            unsafe {
+                libc::close(read);
+                libc::close(write);
+            }
+            return Err(SignalError::AlreadyInstalled);
+        }
+
+        let mut previous: [libc::sigaction; 2] = unsafe { std::mem::zeroed() };
+        for (signal, previous) in [libc::SIGUSR1, libc::SIGUSR2]
+            .into_iter()
+            .zip(&mut previous)
+        {
+            let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+            action.sa_sigaction = forward as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            unsafe { libc::sigemptyset(&mut action.sa_mask) };
+            if unsafe { libc::sigaction(signal, &action, previous) } != 0 {
+                let e = std::io::Error::last_os_error();
+                PIPE.store(-1, Ordering::SeqCst);
+                unsafe {
+                    libc::close(read);
+                    libc::close(write);
+                }
+                return Err(e.into());
+            }
+        }
+
+        let thread = std::thread::spawn(move || {
+            let mut byte = 0u8;
+            loop {
+                match unsafe { libc::read(read, &mut byte as *mut u8 as *mut libc::c_void, 1) } {
+                    1 => {}
+                    -1 if std::io::Error::last_os_error().kind()
+                        == std::io::ErrorKind::Interrupted =>
+                    {
+                        continue
+                    }
+                    // the write end closed
+                    _ => break,
+                }
+                match byte as libc::c_int {
+                    libc::SIGUSR1 if !sampler.is_sampling() => match sampler.start(interval, 1) {
+                        Ok(()) => info!("started sampling on SIGUSR1"),
+                        Err(e) => warn!("failed to start sampling on SIGUSR1: {e}"),
+                    },
+                    libc::SIGUSR2 if sampler.is_sampling() => {
+                        if let Err(e) = write_profile(&mut sampler, &path) {
+                            warn!("failed to write the profile to {}: {e}", path.display());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        Ok(SignalControl {
+            read,
+            write,
+            previous,
+            thread: Some(thread),
+        })
+    }
+}
+
+fn write_profile<B>(
+    sampler: &mut StartStopSampler<B>,
+    path: &std::path::Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    sampler.stop()?;
+    let profile = sampler.profile()?;
+    std::fs::write(path, format!("{profile}\n"))?;
+    info!("wrote the profile to {} on SIGUSR2", path.display());
+    Ok(())
+}
+
+impl Drop for SignalControl {
+    fn drop(&mut self) {
+        for (signal, previous) in [libc::SIGUSR1, libc::SIGUSR2].iter().zip(&self.previous) {
+            unsafe { libc::sigaction(*signal, previous, std::ptr::null_mut()) };
+        }
+        PIPE.store(-1, Ordering::SeqCst);
+        unsafe { libc::close(self.write) };
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        unsafe { libc::close(self.read) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockBackend;
+
+    #[test]
+    fn test_signal_control() {
+        let path = std::env::temp_dir().join(format!("wattkit-signal-{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let sampler =
+            || StartStopSampler::with_backend(|| Ok(MockBackend::constant(1000.0, 0.0, 0.0)));
+        let interval = Duration::from_millis(20);
+        let control = SignalControl::with_sampler(sampler(), &path, interval).unwrap();
+        assert!(matches!(
+            SignalControl::with_sampler(sampler(), &path, interval),
+            Err(SignalError::AlreadyInstalled)
+        ));
+
+        unsafe { libc::raise(libc::SIGUSR1) };
+        std::thread::sleep(Duration::from_millis(100));
+        unsafe { libc::raise(libc::SIGUSR2) };
+        let mut profile = String::new();
+        for _ in 0..100 {
+            profile = std::fs::read_to_string(&path).unwrap_or_default();
+            if !profile.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        drop(control);
+        let _ = std::fs::remove_file(&path);
+        assert!(profile.starts_with("Total Energy: "), "{profile}");
+        assert!(profile.contains("CPU Power: 1000 mW"), "{profile}");
+    }
+}
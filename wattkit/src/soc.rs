@@ -0,0 +1,194 @@
+/// The chip and machine samples are taken on, so results from different machines aren't
+/// compared as if they were the same. Every field is `None` where it can't be read.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SocInfo {
+    /// Chip or CPU model, e.g. "Apple M2 Pro".
+    pub name: Option<String>,
+    /// Hardware model, e.g. "Mac14,12".
+    pub model: Option<String>,
+    /// Logical CPUs available to the process.
+    pub cores: Option<u32>,
+    /// Performance and efficiency cores of Apple Silicon, only read on macOS.
+    pub performance_cores: Option<u32>,
+    pub efficiency_cores: Option<u32>,
+    /// Physical memory in bytes.
+    pub memory: Option<u64>,
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use super::SocInfo;
+
+    extern "C" {
+        fn sysctlbyname(
+            name: *const libc::c_char,
+            old: *mut libc::c_void,
+            old_size: *mut usize,
+            new: *mut libc::c_void,
+            new_size: usize,
+        ) -> libc::c_int;
+    }
+
+    fn string(name: &std::ffi::CStr) -> Option<String> {
+        let mut size = 0;
+        let status = unsafe {
+            sysctlbyname(
+                name.as_ptr(),
+                std::ptr::null_mut(),
+                &mut size,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        if status != 0 || size == 0 {
+            return None;
+        }
+        let mut buffer = vec![0u8; size];
+        let status = unsafe {
+            sysctlbyname(
+                name.as_ptr(),
+                buffer.as_mut_ptr().cast(),
+                &mut size,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        if status != 0 {
+            return None;
+        }
+        buffer.truncate(size);
+        let value = std::ffi::CStr::from_bytes_until_nul(&buffer).ok()?;
+        Some(value.to_string_lossy().trim().to_string()).filter(|s| !s.is_empty())
+    }
+
+    fn integer(name: &std::ffi::CStr) -> Option<u64> {
+        // sysctls are 32 or 64 bit, a zeroed 64 bit buffer reads either on little endian
+        let mut value: u64 = 0;
+        let mut size = std::mem::size_of::<u64>();
+        let status = unsafe {
+            sysctlbyname(
+                name.as_ptr(),
+                (&mut value as *mut u64).cast(),
+                &mut size,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        (status == 0).then_some(value)
+    }
+
+    pub fn detect() -> SocInfo {
+        SocInfo {
+            name: string(c"machdep.cpu.brand_string"),
+            model: string(c"hw.model"),
+            performance_cores: integer(c"hw.perflevel0.physicalcpu").map(|n| n as u32),
+            efficiency_cores: integer(c"hw.perflevel1.physicalcpu").map(|n| n as u32),
+            memory: integer(c"hw.memsize"),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::{field, SocInfo};
+
+    pub fn detect() -> SocInfo {
+        let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+        let meminfo = std::fs::read_to_string("/proc/meminfo").unwrap_or_default();
+        let model = std::fs::read_to_string("/sys/devices/virtual/dmi/id/product_name").ok();
+        SocInfo {
+            name: field(&cpuinfo, "model name").map(str::to_string),
+            model: model
+                .map(|m| m.trim().to_string())
+                .filter(|m| !m.is_empty()),
+            memory: field(&meminfo, "MemTotal")
+                .and_then(|kb| kb.trim_end_matches("kB").trim().parse::<u64>().ok())
+                .map(|kb| kb * 1024),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+mod sys {
+    use super::SocInfo;
+
+    pub fn detect() -> SocInfo {
+        SocInfo::default()
+    }
+}
+
+/// Value of the first `key: value` line of a /proc file.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn field<'a>(text: &'a str, key: &str) -> Option<&'a str> {
+    text.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        (name.trim() == key).then(|| value.trim())
+    })
+}
+
+impl SocInfo {
+    /// Reads the current machine's chip.
+    pub fn detect() -> Self {
+        SocInfo {
+            cores: std::thread::available_parallelism()
+                .ok()
+                .map(|n| n.get() as u32),
+            ..sys::detect()
+        }
+    }
+
+    /// Whether this is an Apple Silicon Mac, the only machines with ANE energy.
+    pub fn is_apple_silicon(&self) -> bool {
+        self.name.as_deref().is_some_and(|n| n.starts_with("Apple"))
+    }
+}
+
+impl std::fmt::Display for SocInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name.as_deref().unwrap_or("Unknown chip"))?;
+        if let Some(model) = &self.model {
+            write!(f, " ({model})")?;
+        }
+        match (self.performance_cores, self.efficiency_cores, self.cores) {
+            (Some(p), Some(e), _) => write!(f, ", {p}P+{e}E cores")?,
+            (_, _, Some(cores)) => write!(f, ", {cores} cores")?,
+            _ => {}
+        }
+        if let Some(memory) = self.memory {
+            write!(f, ", {} GB", memory / (1 << 30))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field() {
+        let cpuinfo = "processor\t: 0\nmodel name\t: AMD Ryzen 9 7950X\nflags\t\t: fpu vme\n";
+        assert_eq!(field(cpuinfo, "model name"), Some("AMD Ryzen 9 7950X"));
+        assert_eq!(field(cpuinfo, "model"), None);
+        assert_eq!(
+            field("MemTotal:       65536 kB", "MemTotal"),
+            Some("65536 kB")
+        );
+
+        let soc = SocInfo {
+            name: Some("Apple M2 Pro".into()),
+            model: Some("Mac14,12".into()),
+            cores: Some(12),
+            performance_cores: Some(8),
+            efficiency_cores: Some(4),
+            memory: Some(32 << 30),
+        };
+        assert!(soc.is_apple_silicon());
+        assert_eq!(
+            soc.to_string(),
+            "Apple M2 Pro (Mac14,12), 8P+4E cores, 32 GB"
+        );
+    }
+}
@@ -0,0 +1,389 @@
+//! Statistics across repeated runs of the same workload.
+
+use crate::sampler::{Metadata, PowerProfile};
+
+/// Mean, sample standard deviation and range of one quantity across runs.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Summary {
+    pub mean: f64,
+    /// Zero for fewer than two runs.
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    /// Summary of `values`, all zero when there are none.
+    pub fn new(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Summary::default();
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = if values.len() > 1 {
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        Summary {
+            mean,
+            stddev: variance.sqrt(),
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+
+    /// Standard deviation relative to the mean, 0.05 is 5%.
+    pub fn relative_stddev(&self) -> f64 {
+        if self.mean == 0.0 {
+            0.0
+        } else {
+            self.stddev / self.mean.abs()
+        }
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.1} ± {:.1} ({:.1}-{:.1})",
+            self.mean, self.stddev, self.min, self.max
+        )
+    }
+}
+
+/// Profiles of several runs summarized, see [`PowerProfile::merge`]. Energies are in
+/// millijoules, powers in milliwatts and durations in milliseconds, like [`PowerProfile`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AggregateProfile {
+    pub runs: usize,
+    pub total_energy: Summary,
+    pub total_cpu_energy: Summary,
+    pub total_gpu_energy: Summary,
+    pub total_ane_energy: Summary,
+    pub average_power: Summary,
+    pub total_duration: Summary,
+    /// Metadata entries every run agrees on.
+    pub metadata: Metadata,
+}
+
+impl PowerProfile {
+    /// Summarizes the profiles of repeated runs of one workload into a mean and standard
+    /// deviation per quantity.
+    pub fn merge(profiles: &[PowerProfile]) -> AggregateProfile {
+        let summary = |value: fn(&PowerProfile) -> f64| {
+            Summary::new(&profiles.iter().map(value).collect::<Vec<_>>())
+        };
+        let mut metadata = profiles
+            .first()
+            .map(|p| p.metadata.clone())
+            .unwrap_or_default();
+        metadata.retain(|key, value| profiles.iter().all(|p| p.metadata.get(key) == Some(value)));
+
+        AggregateProfile {
+            runs: profiles.len(),
+            total_energy: summary(|p| p.total_energy as f64),
+            total_cpu_energy: summary(|p| p.total_cpu_energy as f64),
+            total_gpu_energy: summary(|p| p.total_gpu_energy as f64),
+            total_ane_energy: summary(|p| p.total_ane_energy as f64),
+            average_power: summary(|p| p.average_power as f64),
+            total_duration: summary(|p| p.total_duration as f64),
+            metadata,
+        }
+    }
+}
+
+impl std::fmt::Display for AggregateProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Runs: {}\nTotal Energy: {} mJ\nTotal Power: {} mW\nTotal Duration: {} ms\nCPU Energy: {} mJ\nGPU Energy: {} mJ\nANE Energy: {} mJ",
+            self.runs,
+            self.total_energy,
+            self.average_power,
+            self.total_duration,
+            self.total_cpu_energy,
+            self.total_gpu_energy,
+            self.total_ane_energy,
+        )?;
+        for (key, value) in &self.metadata {
+            write!(f, "\n{key}: {value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether per-run energy differs between two sets of runs, see [`compare_runs`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Comparison {
+    /// Mean total energy per run in millijoules.
+    pub mean_a: f64,
+    pub mean_b: f64,
+    /// `mean_b - mean_a` relative to `mean_a`, -0.1 is 10% less energy.
+    pub relative_difference: f64,
+    /// Two-sided p-value of Welch's t-test, NaN with fewer than two runs on a side.
+    pub t_test_p: f64,
+    /// Two-sided p-value of the Mann-Whitney U test, normal approximation with tie and
+    /// continuity correction, NaN without runs on a side.
+    pub mann_whitney_p: f64,
+    pub alpha: f64,
+}
+
+impl Comparison {
+    /// Whether both tests reject equal energy at `alpha`. Requiring both keeps a few
+    /// outlier runs from either making or hiding a difference.
+    pub fn is_significant(&self) -> bool {
+        self.t_test_p < self.alpha && self.mann_whitney_p < self.alpha
+    }
+}
+
+impl std::fmt::Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Energy: {:.1} mJ -> {:.1} mJ ({:+.1}%), t-test p = {:.4}, Mann-Whitney p = {:.4}, {} at alpha = {}",
+            self.mean_a,
+            self.mean_b,
+            self.relative_difference * 100.0,
+            self.t_test_p,
+            self.mann_whitney_p,
+            if self.is_significant() {
+                "significant"
+            } else {
+                "not significant"
+            },
+            self.alpha,
+        )
+    }
+}
+
+/// Tests whether the total energy per run of `b` differs from that of `a` at
+/// significance level `alpha`, e.g. 0.05, before and after an optimization.
+pub fn compare_runs(a: &[PowerProfile], b: &[PowerProfile], alpha: f64) -> Comparison {
+    let energies = |runs: &[PowerProfile]| -> Vec<f64> {
+        runs.iter().map(|p| p.total_energy as f64).collect()
+    };
+    let (a, b) = (energies(a), energies(b));
+    let (mean_a, mean_b) = (Summary::new(&a).mean, Summary::new(&b).mean);
+    Comparison {
+        mean_a,
+        mean_b,
+        relative_difference: if mean_a == 0.0 {
+            0.0
+        } else {
+            (mean_b - mean_a) / mean_a
+        },
+        t_test_p: welch_t_test(&a, &b),
+        mann_whitney_p: mann_whitney_u(&a, &b),
+        alpha,
+    }
+}
+
+fn welch_t_test(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() < 2 || b.len() < 2 {
+        return f64::NAN;
+    }
+    let (sa, sb) = (Summary::new(a), Summary::new(b));
+    let va = sa.stddev.powi(2) / a.len() as f64;
+    let vb = sb.stddev.powi(2) / b.len() as f64;
+    let difference = sb.mean - sa.mean;
+    if va + vb == 0.0 {
+        // no spread at all, any difference is certain
+        return if difference == 0.0 { 1.0 } else { 0.0 };
+    }
+    let t = difference / (va + vb).sqrt();
+    let df =
+        (va + vb).powi(2) / (va.powi(2) / (a.len() - 1) as f64 + vb.powi(2) / (b.len() - 1) as f64);
+    incomplete_beta(df / 2.0, 0.5, df / (df + t * t))
+}
+
+fn mann_whitney_u(a: &[f64], b: &[f64]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return f64::NAN;
+    }
+    let mut all: Vec<(f64, bool)> = a
+        .iter()
+        .map(|&v| (v, true))
+        .chain(b.iter().map(|&v| (v, false)))
+        .collect();
+    all.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    // ranks start at 1, tied values share their average rank
+    let n = all.len() as f64;
+    let mut rank_sum_a = 0.0;
+    let mut ties = 0.0;
+    let mut i = 0;
+    while i < all.len() {
+        let j = i + all[i..].iter().take_while(|v| v.0 == all[i].0).count();
+        let rank = (i + j + 1) as f64 / 2.0;
+        rank_sum_a += rank * all[i..j].iter().filter(|v| v.1).count() as f64;
+        let t = (j - i) as f64;
+        ties += t.powi(3) - t;
+        i = j;
+    }
+
+    let (na, nb) = (a.len() as f64, b.len() as f64);
+    let u = rank_sum_a - na * (na + 1.0) / 2.0;
+    let mean = na * nb / 2.0;
+    let sigma = (na * nb / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)))).sqrt();
+    if sigma == 0.0 || sigma.is_nan() {
+        return 1.0;
+    }
+    let z = ((u - mean).abs() - 0.5).max(0.0) / sigma;
+    erfc(z / std::f64::consts::SQRT_2)
+}
+
+/// Regularized incomplete beta function `I_x(a, b)`.
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // the continued fraction converges quickly on this side of the mean
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Lentz's method for the continued fraction of the incomplete beta function.
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let clamp = |v: f64| if v.abs() < TINY { TINY } else { v };
+    let mut c = 1.0;
+    let mut d = 1.0 / clamp(1.0 - (a + b) * x / (a + 1.0));
+    let mut h = d;
+    for m in 1..300 {
+        let m = m as f64;
+        let even = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 / clamp(1.0 + even * d);
+        c = clamp(1.0 + even / c);
+        h *= d * c;
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 / clamp(1.0 + odd * d);
+        c = clamp(1.0 + odd / c);
+        let step = d * c;
+        h *= step;
+        if (step - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    h
+}
+
+/// Lanczos approximation of `ln Γ(x)` for `x > 0`.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000000000190015;
+    for (i, c) in COEFFICIENTS.iter().enumerate() {
+        series += c / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Complementary error function, fractional error below 1.2e-7.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = [
+        -1.26551223,
+        1.00002368,
+        0.37409196,
+        0.09678418,
+        -0.18628806,
+        0.27886807,
+        -1.13520398,
+        1.48851587,
+        -0.82215223,
+        0.17087277,
+    ]
+    .iter()
+    .rev()
+    .fold(0.0, |acc, c| c + t * acc);
+    let r = t * (-z * z + poly).exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::EnergySample;
+
+    fn profile(cpu_energy: u128, model: &str) -> PowerProfile {
+        let sample = EnergySample {
+            cpu_energy,
+            duration: 1000,
+            ..Default::default()
+        };
+        let mut metadata = Metadata::new();
+        metadata.insert("model".to_string(), model.to_string());
+        metadata.insert("run".to_string(), cpu_energy.to_string());
+        PowerProfile::from([sample]).with_metadata(metadata)
+    }
+
+    #[test]
+    fn test_merge() {
+        let runs = [profile(100, "a"), profile(200, "a"), profile(300, "a")];
+        let merged = PowerProfile::merge(&runs);
+        assert_eq!(merged.runs, 3);
+        assert_eq!(
+            merged.total_cpu_energy,
+            Summary {
+                mean: 200.0,
+                stddev: 100.0,
+                min: 100.0,
+                max: 300.0
+            }
+        );
+        assert_eq!(merged.total_duration.stddev, 0.0);
+        assert_eq!(merged.total_energy.relative_stddev(), 0.5);
+        // only what all runs share survives
+        assert_eq!(merged.metadata.keys().collect::<Vec<_>>(), ["model"]);
+
+        let single = PowerProfile::merge(&runs[..1]);
+        assert_eq!(single.total_energy.stddev, 0.0);
+        assert_eq!(PowerProfile::merge(&[]), AggregateProfile::default());
+    }
+
+    fn runs(energies: &[u128]) -> Vec<PowerProfile> {
+        energies.iter().map(|&e| profile(e, "a")).collect()
+    }
+
+    #[test]
+    fn test_compare_runs() {
+        // reference values from scipy.stats.ttest_ind(equal_var=False) and
+        // mannwhitneyu(method="asymptotic")
+        let comparison = compare_runs(&runs(&[1, 2, 3, 4, 5]), &runs(&[6, 7, 8, 9, 10]), 0.05);
+        assert!((comparison.t_test_p - 0.0010528).abs() < 1e-6);
+        assert!((comparison.mann_whitney_p - 0.0121862).abs() < 1e-5);
+        assert_eq!(comparison.relative_difference, 5.0 / 3.0);
+        assert!(comparison.is_significant());
+
+        let noise = compare_runs(&runs(&[10, 12, 11, 13]), &runs(&[11, 13, 10, 12]), 0.05);
+        assert!(noise.t_test_p > 0.9);
+        assert!(!noise.is_significant());
+
+        let single = compare_runs(&runs(&[10]), &runs(&[20]), 0.05);
+        assert!(single.t_test_p.is_nan());
+        assert!(!single.is_significant());
+    }
+}
@@ -0,0 +1,113 @@
+/// Thermal pressure the system reports, from none to throttling hard. Power drawn at
+/// higher levels is capped, so profiles taken under different pressure don't compare.
+/// Only available on macOS. Ordered from no to the most pressure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ThermalPressure {
+    Nominal,
+    Moderate,
+    Heavy,
+    Trapping,
+    Sleeping,
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use super::ThermalPressure;
+
+    extern "C" {
+        fn notify_register_check(name: *const libc::c_char, token: *mut libc::c_int) -> u32;
+        fn notify_get_state(token: libc::c_int, state: *mut u64) -> u32;
+        fn notify_cancel(token: libc::c_int) -> u32;
+    }
+
+    // kOSThermalNotificationPressureLevelName in <libkern/OSThermalNotification.h>
+    const PRESSURE_LEVEL: &std::ffi::CStr = c"com.apple.system.thermalpressurelevel";
+
+    /// Registration for the system's thermal pressure notifications.
+    #[derive(Debug)]
+    pub struct Watch(libc::c_int);
+
+    impl Watch {
+        pub fn new() -> Option<Self> {
+            let mut token = 0;
+            let status = unsafe { notify_register_check(PRESSURE_LEVEL.as_ptr(), &mut token) };
+            (status == 0).then_some(Watch(token))
+        }
+
+        pub fn pressure(&self) -> Option<ThermalPressure> {
+            let mut state = 0;
+            if unsafe { notify_get_state(self.0, &mut state) } != 0 {
+                return None;
+            }
+            // values of `OSThermalPressureLevel`
+            Some(match state {
+                0 => ThermalPressure::Nominal,
+                1 => ThermalPressure::Moderate,
+                2 => ThermalPressure::Heavy,
+                3 => ThermalPressure::Trapping,
+                _ => ThermalPressure::Sleeping,
+            })
+        }
+    }
+
+    impl Drop for Watch {
+        fn drop(&mut self) {
+            unsafe { notify_cancel(self.0) };
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod sys {
+    use super::ThermalPressure;
+
+    #[derive(Debug)]
+    pub enum Watch {}
+
+    impl Watch {
+        pub fn new() -> Option<Self> {
+            None
+        }
+
+        pub fn pressure(&self) -> Option<ThermalPressure> {
+            match *self {}
+        }
+    }
+}
+
+impl ThermalPressure {
+    /// The current thermal pressure, `None` where it can't be read.
+    pub fn current() -> Option<Self> {
+        sys::Watch::new()?.pressure()
+    }
+}
+
+/// Reports changes of the thermal pressure between polls.
+#[derive(Debug)]
+pub(crate) struct ThermalMonitor {
+    watch: Option<sys::Watch>,
+    last: Option<ThermalPressure>,
+}
+
+impl ThermalMonitor {
+    pub(crate) fn new() -> Self {
+        let watch = sys::Watch::new();
+        let last = watch.as_ref().and_then(sys::Watch::pressure);
+        ThermalMonitor { watch, last }
+    }
+
+    /// The pressure as of the last poll.
+    pub(crate) fn current(&self) -> Option<ThermalPressure> {
+        self.last
+    }
+
+    /// The new pressure if it changed since the last poll.
+    pub(crate) fn poll(&mut self) -> Option<ThermalPressure> {
+        let pressure = self.watch.as_ref()?.pressure()?;
+        if self.last == Some(pressure) {
+            return None;
+        }
+        self.last = Some(pressure);
+        Some(pressure)
+    }
+}
@@ -0,0 +1,399 @@
+//! Captures recorded only around power spikes or markers, see [`TriggeredSampler`].
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::backend::{open_default, BackendFactory, DefaultBackend, PowerBackend};
+use crate::sampler::{EnergySample, PowerProfile, SamplerError};
+use crate::worker;
+
+/// When a [`TriggeredSampler`] records and how much around each trigger.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TriggerOptions {
+    /// Interval of the monitoring, defaults to 100 ms.
+    pub interval: Duration,
+    /// Total power in watts a sample must exceed to trigger a capture, `None` leaves
+    /// only [`TriggeredSampler::fire`].
+    pub threshold: Option<f64>,
+    /// How far back before the trigger a capture reaches, defaults to one second.
+    pub pre_trigger: Duration,
+    /// How long a capture goes on after the last trigger, defaults to one second.
+    pub post_trigger: Duration,
+}
+
+impl Default for TriggerOptions {
+    fn default() -> Self {
+        TriggerOptions {
+            interval: Duration::from_millis(100),
+            threshold: None,
+            pre_trigger: Duration::from_secs(1),
+            post_trigger: Duration::from_secs(1),
+        }
+    }
+}
+
+/// What started a [`Capture`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum TriggerCause {
+    /// A sample drew more than the threshold, its total power in watts.
+    PowerAbove(f64),
+    /// A marker fired with [`TriggeredSampler::fire`].
+    Marker(String),
+}
+
+/// Samples around a trigger, from up to the pre-trigger window before it until the
+/// post-trigger window after the last trigger during the capture.
+#[derive(Clone, Debug)]
+pub struct Capture {
+    pub cause: TriggerCause,
+    /// Start of the sample the trigger came with, in milliseconds since monitoring started.
+    pub triggered_at: u64,
+    pub samples: Vec<EnergySample>,
+}
+
+impl Capture {
+    pub fn profile(&self) -> PowerProfile {
+        PowerProfile::from(&self.samples)
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    options: TriggerOptions,
+    /// Samples of the pre-trigger window while no capture is open.
+    ring: VecDeque<EnergySample>,
+    /// The open capture and the end of its post-trigger window.
+    current: Option<(Capture, u64)>,
+    captures: Vec<Capture>,
+    marker: Option<String>,
+    error: Option<SamplerError>,
+}
+
+impl State {
+    fn new(options: TriggerOptions) -> Self {
+        State {
+            options,
+            ring: VecDeque::new(),
+            current: None,
+            captures: Vec::new(),
+            marker: None,
+            error: None,
+        }
+    }
+
+    fn cause(&mut self, sample: &EnergySample) -> Option<TriggerCause> {
+        if let Some(marker) = self.marker.take() {
+            return Some(TriggerCause::Marker(marker));
+        }
+        let power = (sample.cpu_power() + sample.gpu_power() + sample.ane_power()) / 1000.0;
+        let threshold = self.options.threshold?;
+        (power > threshold).then_some(TriggerCause::PowerAbove(power))
+    }
+
+    fn record(&mut self, sample: EnergySample) {
+        let end = sample.timestamp + sample.duration;
+        let post_trigger = self.options.post_trigger.as_millis() as u64;
+        let cause = self.cause(&sample);
+        let (mut capture, mut until) = match (self.current.take(), cause) {
+            (Some((capture, until)), cause) => {
+                // another trigger keeps the capture going
+                (capture, cause.map_or(until, |_| end + post_trigger))
+            }
+            (None, Some(cause)) => {
+                let capture = Capture {
+                    cause,
+                    triggered_at: sample.timestamp,
+                    samples: self.ring.drain(..).collect(),
+                };
+                (capture, end + post_trigger)
+            }
+            (None, None) => {
+                self.ring.push_back(sample);
+                let keep_after = end.saturating_sub(self.options.pre_trigger.as_millis() as u64);
+                while self
+                    .ring
+                    .front()
+                    .is_some_and(|s| s.timestamp + s.duration <= keep_after)
+                {
+                    self.ring.pop_front();
+                }
+                return;
+            }
+        };
+        capture.samples.push(sample);
+        until = until.max(end);
+        if end >= until {
+            self.captures.push(capture);
+        } else {
+            self.current = Some((capture, until));
+        }
+    }
+
+    /// Ends the open capture early, when monitoring stops.
+    fn close(&mut self) {
+        self.captures
+            .extend(self.current.take().map(|(capture, _)| capture));
+        self.ring.clear();
+    }
+}
+
+#[derive(Debug)]
+struct Monitor {
+    cancel: Mutex<Sender<()>>, // the mutex just makes the channel end `Sync`
+    completion: worker::Completion,
+}
+
+/// # TriggeredSampler
+///
+/// Monitors power at a low cost and only keeps samples around triggers: a sample
+/// above a power threshold or a marker fired from code. Each capture reaches back into
+/// a small ring buffer of the samples before the trigger, so the run-up to an
+/// intermittent spike is recorded without recording everything else.
+///
+/// ```no_run
+/// use wattkit::{TriggerOptions, TriggeredSampler};
+///
+/// let mut sampler = TriggeredSampler::new(TriggerOptions {
+///     threshold: Some(20.0),
+///     ..Default::default()
+/// });
+/// sampler.start().unwrap();
+/// // ...
+/// for capture in sampler.stop().unwrap() {
+///     println!("{:?} at {} ms\n{}", capture.cause, capture.triggered_at, capture.profile());
+/// }
+/// ```
+pub struct TriggeredSampler<B = DefaultBackend> {
+    factory: BackendFactory<B>,
+    state: Arc<Mutex<State>>,
+    monitor: Option<Monitor>,
+}
+
+impl<B> std::fmt::Debug for TriggeredSampler<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TriggeredSampler")
+            .field("state", &self.state)
+            .field("monitor", &self.monitor)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TriggeredSampler {
+    pub fn new(options: TriggerOptions) -> Self {
+        TriggeredSampler::with_backend(open_default, options)
+    }
+}
+
+impl<B: PowerBackend + 'static> TriggeredSampler<B> {
+    /// Monitors a backend opened by `factory` each time monitoring starts.
+    pub fn with_backend<F>(factory: F, options: TriggerOptions) -> Self
+    where
+        F: Fn() -> Result<B, SamplerError> + Send + Sync + 'static,
+    {
+        TriggeredSampler {
+            factory: Arc::new(factory),
+            state: Arc::new(Mutex::new(State::new(options))),
+            monitor: None,
+        }
+    }
+
+    /// Starts monitoring, captures of a previous run not taken yet are dropped.
+    pub fn start(&mut self) -> Result<(), SamplerError> {
+        if self.monitor.is_some() {
+            return Err(SamplerError::SamplingInProgress);
+        }
+        let options = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            *state = State::new(state.options);
+            state.options
+        };
+        let (cancel_tx, cancel_rx) = channel();
+        let (ready_tx, ready_rx) = channel();
+        let (factory, state) = (self.factory.clone(), self.state.clone());
+        let completion = worker::run(move || {
+            let mut backend = match factory() {
+                Ok(backend) => backend,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+            let _ = ready_tx.send(Ok(()));
+            let mut elapsed = 0;
+            while cancel_rx.try_recv().is_err() {
+                let read_wall = SystemTime::now();
+                let mut sample = match backend.sample(options.interval) {
+                    Ok(sample) => sample,
+                    Err(e) => match e.invalid_delta_duration() {
+                        Some(discarded) => {
+                            elapsed += discarded;
+                            continue;
+                        }
+                        None => {
+                            warn!("read failed, ending triggered monitoring: {e}");
+                            state.lock().unwrap_or_else(|e| e.into_inner()).error = Some(e);
+                            break;
+                        }
+                    },
+                };
+                sample.timestamp = elapsed;
+                sample.wall_time = Some(read_wall);
+                elapsed += sample.duration;
+                if sample.is_plausible() {
+                    state
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .record(sample);
+                }
+            }
+        });
+        match ready_rx.recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                completion.join();
+                return Err(e);
+            }
+            // the factory panicked
+            Err(_) => {
+                completion.join();
+                return Err(SamplerError::SamplesNotAvailable);
+            }
+        }
+        self.monitor = Some(Monitor {
+            cancel: Mutex::new(cancel_tx),
+            completion,
+        });
+        Ok(())
+    }
+}
+
+impl<B> TriggeredSampler<B> {
+    /// Triggers a capture at the next sample, or extends the open one, labeled `marker`.
+    pub fn fire(&self, marker: impl Into<String>) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.marker = Some(marker.into());
+    }
+
+    /// The captures finished so far, they aren't returned again.
+    pub fn take_captures(&self) -> Vec<Capture> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        std::mem::take(&mut state.captures)
+    }
+
+    /// Stops monitoring, returns the captures not taken yet including the open one.
+    pub fn stop(&mut self) -> Result<Vec<Capture>, SamplerError> {
+        let monitor = self
+            .monitor
+            .take()
+            .ok_or(SamplerError::NoSamplingInProgress)?;
+        let _ = monitor
+            .cancel
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .send(());
+        monitor.completion.join();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.close();
+        Ok(std::mem::take(&mut state.captures))
+    }
+
+    pub fn is_sampling(&self) -> bool {
+        self.monitor.is_some()
+    }
+
+    /// The backend error that ended monitoring before it was stopped, returned once.
+    pub fn take_error(&self) -> Option<SamplerError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.error.take()
+    }
+}
+
+impl<B> Drop for TriggeredSampler<B> {
+    fn drop(&mut self) {
+        // never leave the monitoring thread running
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockBackend;
+
+    #[test]
+    fn test_power_trigger() {
+        // 1 W in 100 ms samples with a 5 W spike in the sixth
+        let samples: Vec<EnergySample> = (0..10)
+            .map(|i| EnergySample {
+                cpu_energy: if i == 5 { 500 } else { 100 },
+                duration: 100,
+                ..Default::default()
+            })
+            .collect();
+        let mut sampler = TriggeredSampler::with_backend(
+            move || Ok(MockBackend::replay(samples.clone())),
+            TriggerOptions {
+                threshold: Some(3.0),
+                pre_trigger: Duration::from_millis(200),
+                post_trigger: Duration::from_millis(200),
+                ..Default::default()
+            },
+        );
+        sampler.start().unwrap();
+        // the replay ends the monitoring once exhausted
+        let error = loop {
+            match sampler.take_error() {
+                Some(e) => break e,
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        };
+        assert!(matches!(error, SamplerError::SamplesNotAvailable));
+        let captures = sampler.stop().unwrap();
+
+        assert_eq!(captures.len(), 1);
+        let capture = &captures[0];
+        assert_eq!(capture.cause, TriggerCause::PowerAbove(5.0));
+        assert_eq!(capture.triggered_at, 500);
+        let starts: Vec<u64> = capture.samples.iter().map(|s| s.timestamp).collect();
+        assert_eq!(starts, [300, 400, 500, 600, 700]);
+        assert_eq!(capture.profile().total_energy, 900);
+    }
+
+    #[test]
+    fn test_marker_trigger() {
+        let mut sampler = TriggeredSampler::with_backend(
+            || Ok(MockBackend::constant(1000.0, 0.0, 0.0)),
+            TriggerOptions {
+                interval: Duration::from_millis(20),
+                pre_trigger: Duration::from_millis(40),
+                post_trigger: Duration::from_millis(40),
+                ..Default::default()
+            },
+        );
+        sampler.start().unwrap();
+        std::thread::sleep(Duration::from_millis(150));
+        sampler.fire("checkpoint");
+        std::thread::sleep(Duration::from_millis(200));
+        let mut captures = sampler.take_captures();
+        captures.extend(sampler.stop().unwrap());
+
+        assert_eq!(captures.len(), 1);
+        let capture = &captures[0];
+        assert_eq!(
+            capture.cause,
+            TriggerCause::Marker("checkpoint".to_string())
+        );
+        // at least two samples before the marker's and two after, how many fit in the
+        // pre-trigger window depends on timing
+        let at = capture
+            .samples
+            .iter()
+            .position(|s| s.timestamp == capture.triggered_at)
+            .expect("the marker's sample is captured");
+        assert!(at >= 2, "{:?}", capture.samples);
+        assert!(capture.samples.len() - at > 2, "{:?}", capture.samples);
+        assert!(!sampler.is_sampling());
+    }
+}
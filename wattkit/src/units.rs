@@ -0,0 +1,187 @@
+//! Units profiles are shown in, see [`Units`].
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::sampler::PowerProfile;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown unit {0:?}")]
+pub struct UnknownUnit(pub String);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EnergyUnit {
+    #[default]
+    Millijoules,
+    Joules,
+    WattHours,
+}
+
+impl EnergyUnit {
+    pub fn from_millijoules(self, millijoules: f64) -> f64 {
+        match self {
+            EnergyUnit::Millijoules => millijoules,
+            EnergyUnit::Joules => millijoules / 1e3,
+            EnergyUnit::WattHours => millijoules / 3.6e6,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            EnergyUnit::Millijoules => "mJ",
+            EnergyUnit::Joules => "J",
+            EnergyUnit::WattHours => "Wh",
+        }
+    }
+
+    /// `millijoules` in the unit with its symbol, whole millijoules and otherwise to
+    /// the precision of a millijoule.
+    pub(crate) fn format(self, millijoules: f64) -> String {
+        let value = self.from_millijoules(millijoules);
+        let symbol = self.symbol();
+        match self {
+            EnergyUnit::Millijoules => format!("{value:.0} {symbol}"),
+            EnergyUnit::Joules => format!("{value:.3} {symbol}"),
+            EnergyUnit::WattHours => format!("{value:.7} {symbol}"),
+        }
+    }
+}
+
+impl FromStr for EnergyUnit {
+    type Err = UnknownUnit;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mJ" => Ok(EnergyUnit::Millijoules),
+            "J" => Ok(EnergyUnit::Joules),
+            "Wh" => Ok(EnergyUnit::WattHours),
+            _ => Err(UnknownUnit(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PowerUnit {
+    #[default]
+    Milliwatts,
+    Watts,
+}
+
+impl PowerUnit {
+    pub fn from_milliwatts(self, milliwatts: f64) -> f64 {
+        match self {
+            PowerUnit::Milliwatts => milliwatts,
+            PowerUnit::Watts => milliwatts / 1e3,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            PowerUnit::Milliwatts => "mW",
+            PowerUnit::Watts => "W",
+        }
+    }
+
+    pub(crate) fn format(self, milliwatts: f64) -> String {
+        let value = self.from_milliwatts(milliwatts);
+        let symbol = self.symbol();
+        match self {
+            PowerUnit::Milliwatts => format!("{value:.0} {symbol}"),
+            PowerUnit::Watts => format!("{value:.3} {symbol}"),
+        }
+    }
+}
+
+impl FromStr for PowerUnit {
+    type Err = UnknownUnit;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mW" => Ok(PowerUnit::Milliwatts),
+            "W" => Ok(PowerUnit::Watts),
+            _ => Err(UnknownUnit(s.to_string())),
+        }
+    }
+}
+
+/// # Units
+///
+/// The units a profile is shown in. Profiles keep millijoules and milliwatts, the
+/// default, and convert when shown:
+///
+/// ```
+/// use wattkit::{PowerProfile, Units};
+///
+/// let profile = PowerProfile::default();
+/// println!("{}", profile.display(Units::SI));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Units {
+    pub energy: EnergyUnit,
+    pub power: PowerUnit,
+}
+
+impl Units {
+    /// Joules and watts.
+    pub const SI: Units = Units {
+        energy: EnergyUnit::Joules,
+        power: PowerUnit::Watts,
+    };
+}
+
+impl PowerProfile {
+    /// Shows the profile as its [`Display`] does, energy and power in `units`.
+    pub fn display(&self, units: Units) -> impl Display + '_ {
+        DisplayUnits {
+            profile: self,
+            units,
+        }
+    }
+}
+
+struct DisplayUnits<'a> {
+    profile: &'a PowerProfile,
+    units: Units,
+}
+
+impl Display for DisplayUnits<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.profile.fmt_units(f, self.units)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::EnergySample;
+
+    #[test]
+    fn test_units() {
+        let profile = PowerProfile::from(
+            [EnergySample {
+                cpu_energy: 7200,
+                duration: 1000,
+                ..Default::default()
+            }]
+            .as_slice(),
+        );
+        assert_eq!(
+            profile.display(Units::default()).to_string(),
+            profile.to_string()
+        );
+        let si = profile.display(Units::SI).to_string();
+        assert!(
+            si.starts_with("Total Energy: 7.200 J\nTotal Power: 7.200 W\n"),
+            "{si}"
+        );
+        let wh = Units {
+            energy: "Wh".parse().unwrap(),
+            ..Units::SI
+        };
+        assert!(profile
+            .display(wh)
+            .to_string()
+            .starts_with("Total Energy: 0.0020000 Wh"));
+        assert!("kWh".parse::<EnergyUnit>().is_err());
+    }
+}
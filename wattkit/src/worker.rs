@@ -0,0 +1,91 @@
+//! Sampling threads kept around between sessions, so starting and stopping hundreds of
+//! short sessions, e.g. one per unit test, doesn't spawn a thread for each.
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Most threads left waiting for a session, the rest exit once theirs ends.
+const MAX_IDLE: usize = 4;
+
+/// Senders to the threads waiting for a session, by the process that spawned them. A
+/// forked child has none of its parent's threads.
+static IDLE: Mutex<Vec<(u32, Sender<Job>)>> = Mutex::new(Vec::new());
+
+/// Signals when a job run with [`run`] ended, like a `JoinHandle` of its own thread.
+#[derive(Debug)]
+pub(crate) struct Completion(Mutex<Receiver<()>>); // the mutex just makes it `Sync`
+
+impl Completion {
+    /// Waits for the job to end, or to panic.
+    pub(crate) fn join(self) {
+        let _ = self
+            .0
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .recv();
+    }
+}
+
+/// Runs `job` on an idle sampling thread, or on a new one if none is idle.
+pub(crate) fn run(job: impl FnOnce() + Send + 'static) -> Completion {
+    let (done_tx, done_rx) = channel();
+    let job: Job = Box::new(move || {
+        job();
+        let _ = done_tx.send(());
+    });
+    let idle = {
+        let mut idle = IDLE.lock().unwrap_or_else(|e| e.into_inner());
+        idle.retain(|(pid, _)| *pid == std::process::id());
+        idle.pop()
+    };
+    match idle {
+        Some((_, worker)) => {
+            if let Err(e) = worker.send(job) {
+                spawn(e.0);
+            }
+        }
+        None => spawn(job),
+    }
+    Completion(Mutex::new(done_rx))
+}
+
+fn spawn(job: Job) {
+    let (sender, receiver) = channel::<Job>();
+    std::thread::spawn(move || {
+        let mut job = job;
+        loop {
+            job();
+            {
+                let mut idle = IDLE.lock().unwrap_or_else(|e| e.into_inner());
+                if idle.len() >= MAX_IDLE {
+                    return;
+                }
+                idle.push((std::process::id(), sender.clone()));
+            }
+            job = match receiver.recv() {
+                Ok(job) => job,
+                Err(_) => return,
+            };
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_waits_for_job() {
+        for _ in 0..MAX_IDLE + 2 {
+            let (tx, rx) = channel();
+            run(move || {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                tx.send(()).unwrap();
+            })
+            .join();
+            assert!(rx.try_recv().is_ok());
+        }
+    }
+}